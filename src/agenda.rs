@@ -0,0 +1,417 @@
+/// Open tasks collected from every note of the vault.
+pub struct Agenda {
+    items: crate::util::Query<Vec<AgendaItem>, std::io::ErrorKind>,
+    today: chrono::NaiveDate,
+}
+
+/// An open task and the note it comes from.
+#[derive(Debug, Clone)]
+pub struct AgendaItem {
+    pub path: std::path::PathBuf,
+    pub note: String,
+    pub line: usize,
+    /// The line as written in the note, to find it again when ticking it off.
+    source: String,
+    /// The text of the task, without its due date and priority.
+    text: String,
+    due: Option<chrono::NaiveDate>,
+    priority: Option<Priority>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Group {
+    Overdue,
+    Today,
+    Upcoming,
+    Undated,
+}
+
+impl std::fmt::Display for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Group::Overdue => "Overdue",
+                Group::Today => "Today",
+                Group::Upcoming => "Upcoming",
+                Group::Undated => "No due date",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Refresh,
+    Loaded(Result<Vec<AgendaItem>, std::io::ErrorKind>),
+    Tick(usize),
+    Ticked(Result<AgendaItem, std::io::ErrorKind>),
+    /// Opens the note of a task at its line.
+    Open(std::path::PathBuf, String, usize),
+}
+
+impl Agenda {
+    /// Collects the open tasks of `notes`, given as paths and display names.
+    pub fn new(notes: Vec<(std::path::PathBuf, String)>) -> (Self, iced::Task<Message>) {
+        (
+            Self {
+                items: crate::util::Query::Pending,
+                today: chrono::Local::now().date_naive(),
+            },
+            iced::Task::perform(collect(notes), Message::Loaded),
+        )
+    }
+
+    pub fn view(&self) -> iced::Element<'_, Message> {
+        let header = iced::widget::row![
+            iced::widget::text("Agenda").size(24),
+            iced::widget::horizontal_space(),
+            iced::widget::button("Refresh")
+                .style(crate::util::button_secondary)
+                .on_press(Message::Refresh),
+        ]
+        .align_y(iced::Alignment::Center);
+
+        let body: iced::Element<'_, Message> = match &self.items {
+            crate::util::Query::Pending => iced::widget::text("Loading...").into(),
+            crate::util::Query::Error(e) => {
+                iced::widget::text(format!("Failed to collect tasks: {:?}", e)).into()
+            }
+            crate::util::Query::Loaded(items) if items.is_empty() => {
+                iced::widget::text("No open tasks").into()
+            }
+            crate::util::Query::Loaded(items) => {
+                let mut groups = iced::widget::Column::new().spacing(16);
+                for group in [
+                    Group::Overdue,
+                    Group::Today,
+                    Group::Upcoming,
+                    Group::Undated,
+                ] {
+                    let tasks: Vec<_> = items
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, item)| self.group(item) == group)
+                        .collect();
+                    if tasks.is_empty() {
+                        continue;
+                    }
+                    groups = groups.push(
+                        iced::widget::column![iced::widget::text(format!(
+                            "{} ({})",
+                            group,
+                            tasks.len()
+                        ))
+                        .size(18)]
+                        .extend(tasks.into_iter().map(|(i, item)| self.item_view(i, item)))
+                        .spacing(6),
+                    );
+                }
+                iced::widget::scrollable(groups)
+                    .height(iced::Length::Fill)
+                    .into()
+            }
+        };
+
+        iced::widget::column![header, body]
+            .spacing(8)
+            .padding(8)
+            .height(iced::Length::Fill)
+            .into()
+    }
+
+    pub fn update(&mut self, message: Message) -> iced::Task<Message> {
+        match message {
+            // Handled by the notes, which know the vault.
+            Message::Refresh | Message::Open(..) => iced::Task::none(),
+            Message::Loaded(result) => {
+                self.items = match result {
+                    Ok(mut items) => {
+                        items.sort_by(|a, b| {
+                            a.due
+                                .is_none()
+                                .cmp(&b.due.is_none())
+                                .then(a.due.cmp(&b.due))
+                                .then(
+                                    a.priority
+                                        .unwrap_or(Priority::Medium)
+                                        .cmp(&b.priority.unwrap_or(Priority::Medium)),
+                                )
+                                .then_with(|| a.note.cmp(&b.note))
+                                .then(a.line.cmp(&b.line))
+                        });
+                        crate::util::Query::Loaded(items)
+                    }
+                    Err(e) => crate::util::Query::Error(e),
+                };
+                iced::Task::none()
+            }
+            Message::Tick(index) => match &self.items {
+                crate::util::Query::Loaded(items) => match items.get(index) {
+                    Some(item) => iced::Task::perform(tick(item.clone()), Message::Ticked),
+                    None => iced::Task::none(),
+                },
+                _ => iced::Task::none(),
+            },
+            Message::Ticked(Ok(ticked)) => {
+                if let crate::util::Query::Loaded(items) = &mut self.items {
+                    items.retain(|item| item.path != ticked.path || item.line != ticked.line);
+                }
+                iced::Task::none()
+            }
+            Message::Ticked(Err(e)) => {
+                eprintln!("Failed to tick the task off: {:?}", e);
+                iced::Task::done(Message::Refresh)
+            }
+        }
+    }
+
+    fn group(&self, item: &AgendaItem) -> Group {
+        match item.due {
+            None => Group::Undated,
+            Some(due) if due < self.today => Group::Overdue,
+            Some(due) if due == self.today => Group::Today,
+            Some(_) => Group::Upcoming,
+        }
+    }
+
+    fn item_view<'a>(&self, index: usize, item: &'a AgendaItem) -> iced::Element<'a, Message> {
+        let faded = |theme: &iced::Theme| iced::widget::text::Style {
+            color: Some(iced::Color {
+                a: 0.6,
+                ..theme.palette().text
+            }),
+        };
+
+        iced::widget::row![iced::widget::checkbox(item.text.as_str(), false)
+            .on_toggle(move |_| Message::Tick(index))
+            .width(iced::Length::Fill),]
+        .push_maybe(item.priority.map(|priority| {
+            iced::widget::text(match priority {
+                Priority::High => "high",
+                Priority::Medium => "medium",
+                Priority::Low => "low",
+            })
+            .size(12)
+            .style(move |theme: &iced::Theme| iced::widget::text::Style {
+                color: Some(match priority {
+                    Priority::High => theme.palette().danger,
+                    Priority::Medium => theme.palette().primary,
+                    Priority::Low => theme.palette().success,
+                }),
+            })
+        }))
+        .push_maybe(
+            item.due
+                .map(|due| iced::widget::text(due.format("%Y-%m-%d").to_string()).style(faded)),
+        )
+        .push(
+            iced::widget::button(iced::widget::text(item.note.as_str()).size(12))
+                .style(crate::util::button_no_bg)
+                .on_press(Message::Open(
+                    item.path.clone(),
+                    item.note.clone(),
+                    item.line,
+                )),
+        )
+        .spacing(8)
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+}
+
+/// Reads the open tasks of `notes`, skipping the ones that can't be read as text.
+async fn collect(
+    notes: Vec<(std::path::PathBuf, String)>,
+) -> Result<Vec<AgendaItem>, std::io::ErrorKind> {
+    let mut items = vec![];
+    for (path, note) in notes {
+        let contents = match crate::util::read_file(path.clone()).await {
+            Ok(contents) => contents,
+            Err(std::io::ErrorKind::InvalidData) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let mut fences = crate::folding::Fences::default();
+        for (line, source) in contents.lines().enumerate() {
+            let fenced = fences.read(source);
+            let Some(task) = crate::tasks::list_item(source) else {
+                continue;
+            };
+            if fenced || task.checked != Some(false) {
+                continue;
+            }
+            let (text, due, priority) = parse_task(&source[task.text..]);
+            items.push(AgendaItem {
+                path: path.clone(),
+                note: note.clone(),
+                line,
+                source: source.to_string(),
+                text,
+                due,
+                priority,
+            });
+        }
+    }
+    Ok(items)
+}
+
+/// Splits the due date (`📅 2026-10-20` or `due:2026-10-20`) and the priority (`⏫`, `🔼`,
+/// `🔽` or `priority:high`) out of the text of a task.
+fn parse_task(text: &str) -> (String, Option<chrono::NaiveDate>, Option<Priority>) {
+    let mut due = None;
+    let mut priority = None;
+    let mut words = vec![];
+
+    let mut tokens = text.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        let date = |value: &str| chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok();
+        match token {
+            "📅" => match tokens.peek().and_then(|value| date(value)) {
+                Some(date) => {
+                    due = Some(date);
+                    tokens.next();
+                }
+                None => words.push(token),
+            },
+            "🔺" | "⏫" => priority = Some(Priority::High),
+            "🔼" => priority = Some(Priority::Medium),
+            "🔽" | "⏬" => priority = Some(Priority::Low),
+            _ => {
+                if let Some(date) = token.strip_prefix("due:").and_then(date) {
+                    due = Some(date);
+                } else if let Some(value) = token.strip_prefix("priority:") {
+                    priority = match value {
+                        "high" => Some(Priority::High),
+                        "medium" => Some(Priority::Medium),
+                        "low" => Some(Priority::Low),
+                        _ => {
+                            words.push(token);
+                            priority
+                        }
+                    };
+                } else {
+                    words.push(token);
+                }
+            }
+        }
+    }
+
+    (words.join(" "), due, priority)
+}
+
+/// Ticks `item` off in its note. The task is looked up by its text if the note changed since
+/// the agenda was collected.
+async fn tick(item: AgendaItem) -> Result<AgendaItem, std::io::ErrorKind> {
+    let contents = crate::util::read_file(item.path.clone()).await?;
+    let mut lines: Vec<String> = contents.split('\n').map(str::to_string).collect();
+
+    let matches = |line: &String| line.trim_end_matches('\r') == item.source;
+    let index = if lines.get(item.line).is_some_and(matches) {
+        item.line
+    } else {
+        lines
+            .iter()
+            .position(matches)
+            .ok_or(std::io::ErrorKind::NotFound)?
+    };
+    let mark = crate::tasks::list_item(&lines[index])
+        .filter(|task| task.checked == Some(false))
+        .ok_or(std::io::ErrorKind::NotFound)?
+        .mark;
+    lines[index].replace_range(mark..mark + 1, "x");
+
+    crate::util::write_file(item.path.clone(), lines.join("\n")).await?;
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> Option<chrono::NaiveDate> {
+        chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()
+    }
+
+    #[test]
+    fn parse_dates_and_priorities() {
+        assert_eq!(
+            parse_task("Pay rent 📅 2026-11-01 ⏫"),
+            ("Pay rent".into(), date("2026-11-01"), Some(Priority::High))
+        );
+        assert_eq!(
+            parse_task("due:2026-02-03 call priority:low"),
+            ("call".into(), date("2026-02-03"), Some(Priority::Low))
+        );
+        assert_eq!(
+            parse_task("🔼 write 📅 soon"),
+            ("write 📅 soon".into(), None, Some(Priority::Medium))
+        );
+    }
+
+    #[test]
+    fn parse_keeps_what_isnt_a_date_or_priority() {
+        assert_eq!(
+            parse_task("due:tomorrow priority:urgent"),
+            ("due:tomorrow priority:urgent".into(), None, None)
+        );
+        assert_eq!(
+            parse_task("📅 2026-13-01"),
+            ("📅 2026-13-01".into(), None, None)
+        );
+        assert_eq!(parse_task(""), (String::new(), None, None));
+    }
+
+    fn tick_in(contents: &str, line: usize, source: &str) -> Result<String, std::io::ErrorKind> {
+        let path = std::env::temp_dir().join(format!(
+            "darkstone-agenda-{}-{}.md",
+            std::process::id(),
+            line
+        ));
+        std::fs::write(&path, contents).unwrap();
+        let item = AgendaItem {
+            path: path.clone(),
+            note: "note".into(),
+            line,
+            source: source.into(),
+            text: String::new(),
+            due: None,
+            priority: None,
+        };
+        let result = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(tick(item));
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        result.map(|_| contents)
+    }
+
+    #[test]
+    fn tick_at_its_line() {
+        assert_eq!(
+            tick_in("# Tasks\r\n- [ ] a\r\n- [ ] b", 2, "- [ ] b").unwrap(),
+            "# Tasks\r\n- [ ] a\r\n- [x] b"
+        );
+    }
+
+    #[test]
+    fn tick_finds_moved_tasks() {
+        assert_eq!(
+            tick_in("new line\n- [ ] a\n- [ ] b", 1, "- [ ] b").unwrap(),
+            "new line\n- [ ] a\n- [x] b"
+        );
+        assert_eq!(
+            tick_in("- [x] b", 3, "- [ ] b"),
+            Err(std::io::ErrorKind::NotFound)
+        );
+    }
+}
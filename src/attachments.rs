@@ -0,0 +1,138 @@
+/// Whether the file at `path` is an image, by its extension.
+pub fn is_image(path: &std::path::Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    matches!(
+        extension.as_deref(),
+        Some("png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp")
+    )
+}
+
+/// Stores `bytes` as a new file in the attachments `folder` of the note at `note`, naming it
+/// after `stem` and the current time. Returns the path of the file relative to the note.
+pub async fn store(
+    note: std::path::PathBuf,
+    folder: std::path::PathBuf,
+    stem: String,
+    extension: String,
+    bytes: Vec<u8>,
+) -> Result<String, std::io::ErrorKind> {
+    let directory = note
+        .parent()
+        .unwrap_or(std::path::Path::new(""))
+        .join(&folder);
+    tokio::fs::create_dir_all(&directory)
+        .await
+        .map_err(|e| e.kind())?;
+
+    let name = format!(
+        "{}-{}",
+        stem.replace(char::is_whitespace, "-"),
+        chrono::Local::now().format("%Y%m%d%H%M%S")
+    );
+    let mut file_name = format!("{}.{}", name, extension);
+    let mut count = 1;
+    while tokio::fs::try_exists(directory.join(&file_name))
+        .await
+        .unwrap_or(false)
+    {
+        file_name = format!("{}-{}.{}", name, count, extension);
+        count += 1;
+    }
+    tokio::fs::write(directory.join(&file_name), bytes)
+        .await
+        .map_err(|e| e.kind())?;
+
+    let relative = folder.join(file_name);
+    Ok(relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Copies the image at `source` into the attachments `folder` of the note at `note`.
+pub async fn copy(
+    note: std::path::PathBuf,
+    folder: std::path::PathBuf,
+    source: std::path::PathBuf,
+) -> Result<String, std::io::ErrorKind> {
+    let bytes = tokio::fs::read(&source).await.map_err(|e| e.kind())?;
+    let stem = source
+        .file_stem()
+        .map_or("image".into(), |stem| stem.to_string_lossy().to_string());
+    let extension = source.extension().map_or("png".into(), |extension| {
+        extension.to_string_lossy().to_lowercase()
+    });
+    store(note, folder, stem, extension, bytes).await
+}
+
+/// The Markdown showing the image at `path`, relative to the note.
+pub fn link(path: &str) -> String {
+    if path.contains(char::is_whitespace) {
+        format!("![](<{}>)", path)
+    } else {
+        format!("![]({})", path)
+    }
+}
+
+/// The file of the image at `source`, a URL or a path relative to the note at `note`, if it's
+/// a local file.
+pub fn resolve(note: &std::path::Path, source: &str) -> Option<std::path::PathBuf> {
+    let directory = std::path::absolute(note.parent()?).ok()?;
+    let url = url::Url::from_directory_path(directory)
+        .ok()?
+        .join(source)
+        .ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+    url.to_file_path().ok()
+}
+
+/// The PNG image on the clipboard, if there's one. The clipboard of the app only reads text,
+/// so this asks the tools of the system.
+pub async fn clipboard_image() -> Option<Vec<u8>> {
+    tokio::task::spawn_blocking(|| {
+        let output = if cfg!(target_os = "macos") {
+            std::process::Command::new("osascript")
+                .args(["-e", "the clipboard as «class PNGf»"])
+                .output()
+        } else if cfg!(target_os = "windows") {
+            std::process::Command::new("powershell")
+                .args([
+                    "-NoProfile",
+                    "-Command",
+                    "$image = Get-Clipboard -Format Image; if ($image) { \
+                     $stream = New-Object System.IO.MemoryStream; \
+                     $image.Save($stream, [System.Drawing.Imaging.ImageFormat]::Png); \
+                     [Console]::OpenStandardOutput().Write($stream.ToArray(), 0, $stream.Length) }",
+                ])
+                .output()
+        } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            std::process::Command::new("wl-paste")
+                .args(["--no-newline", "--type", "image/png"])
+                .output()
+        } else {
+            std::process::Command::new("xclip")
+                .args(["-selection", "clipboard", "-target", "image/png", "-out"])
+                .output()
+        }
+        .ok()
+        .filter(|output| output.status.success())?;
+
+        // AppleScript prints the data as `«data PNGf89504E47...»`.
+        let bytes = match output.stdout.strip_prefix("«data PNGf".as_bytes()) {
+            Some(hex) => hex
+                .chunks(2)
+                .map_while(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+                .collect(),
+            None => output.stdout,
+        };
+        bytes.starts_with(b"\x89PNG").then_some(bytes)
+    })
+    .await
+    .ok()
+    .flatten()
+}
@@ -1,13 +1,158 @@
 #[derive(Debug, Clone)]
 pub struct Configuration {
     pub notes_path: std::path::PathBuf,
+    /// Folder holding note templates, relative to `notes_path`.
+    pub templates_path: std::path::PathBuf,
+    /// Extension given to new notes, without the leading dot.
+    pub default_extension: String,
+    /// Globs of the files shown in the note tree. Empty means every file.
+    pub include: Vec<String>,
+    /// Globs of the files and folders hidden from the note tree.
+    pub exclude: Vec<String>,
+    /// How the editor and the preview are laid out in split mode.
+    pub split_orientation: SplitOrientation,
+    /// Whether code blocks in the preview show line numbers.
+    pub code_line_numbers: bool,
+    /// Whether the undo history of notes is kept across sessions.
+    pub persist_undo: bool,
+    /// Folder where pasted and dropped images are stored, relative to the note.
+    pub attachments_path: std::path::PathBuf,
+    /// Folder notes are exported to as HTML, in the same folders as under `notes_path`.
+    pub export_path: std::path::PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitOrientation {
+    /// Editor on the left, preview on the right.
+    Horizontal,
+    /// Editor on top, preview below.
+    Vertical,
+}
+
+impl Configuration {
+    /// Parses the `key: value` lines of the config file. Unknown keys are ignored and missing
+    /// ones keep their default value.
+    pub fn parse(contents: &str) -> Self {
+        let mut config = Self::default();
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "notes_path" => config.notes_path = std::path::PathBuf::from(value),
+                "templates_path" => config.templates_path = std::path::PathBuf::from(value),
+                "default_extension" => {
+                    config.default_extension = value.trim_start_matches('.').to_string()
+                }
+                "include" => config.include = parse_list(value),
+                "exclude" => config.exclude = parse_list(value),
+                "split_orientation" => {
+                    config.split_orientation = match value {
+                        "vertical" => SplitOrientation::Vertical,
+                        _ => SplitOrientation::Horizontal,
+                    }
+                }
+                "code_line_numbers" => config.code_line_numbers = value == "true",
+                "persist_undo" => config.persist_undo = value == "true",
+                "attachments_path" => config.attachments_path = std::path::PathBuf::from(value),
+                "export_path" => config.export_path = std::path::PathBuf::from(value),
+                _ => {}
+            }
+        }
+        config
+    }
+
+    pub fn serialize(&self) -> String {
+        format!(
+            "notes_path: {}\ntemplates_path: {}\ndefault_extension: {}\ninclude: {}\nexclude: {}\nsplit_orientation: {}\ncode_line_numbers: {}\npersist_undo: {}\nattachments_path: {}\nexport_path: {}\n",
+            self.notes_path.to_string_lossy(),
+            self.templates_path.to_string_lossy(),
+            self.default_extension,
+            self.include.join(", "),
+            self.exclude.join(", "),
+            match self.split_orientation {
+                SplitOrientation::Horizontal => "horizontal",
+                SplitOrientation::Vertical => "vertical",
+            },
+            self.code_line_numbers,
+            self.persist_undo,
+            self.attachments_path.to_string_lossy(),
+            self.export_path.to_string_lossy()
+        )
+    }
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Self {
             notes_path: std::path::PathBuf::from(std::env::var("HOME").unwrap())
-                .join("{}/.darkstone/notes"),
+                .join(".darkstone/notes"),
+            templates_path: std::path::PathBuf::from("templates"),
+            default_extension: "md".to_string(),
+            include: vec![],
+            exclude: vec![".*".to_string(), ".git".to_string()],
+            split_orientation: SplitOrientation::Horizontal,
+            code_line_numbers: false,
+            persist_undo: false,
+            attachments_path: std::path::PathBuf::from("attachments"),
+            export_path: std::path::PathBuf::from(std::env::var("HOME").unwrap())
+                .join(".darkstone/export"),
         }
     }
 }
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialized_config_parses_back() {
+        let config = Configuration {
+            notes_path: "/notes".into(),
+            templates_path: "my templates".into(),
+            default_extension: "txt".into(),
+            include: vec!["*.md".into(), "*.png".into()],
+            exclude: vec![],
+            split_orientation: SplitOrientation::Vertical,
+            code_line_numbers: true,
+            persist_undo: true,
+            attachments_path: "files".into(),
+            export_path: "/export".into(),
+        };
+        let parsed = Configuration::parse(&config.serialize());
+        assert_eq!(parsed.serialize(), config.serialize());
+        assert_eq!(parsed.include, config.include);
+        assert!(parsed.exclude.is_empty());
+        assert_eq!(parsed.split_orientation, SplitOrientation::Vertical);
+        assert!(parsed.code_line_numbers && parsed.persist_undo);
+    }
+
+    #[test]
+    fn parse_keeps_defaults_for_missing_keys() {
+        let default = Configuration::default();
+        let config = Configuration::parse(
+            "default_extension: .markdown\nunknown: x\nsplit_orientation: sideways\nno colon",
+        );
+        assert_eq!(config.default_extension, "markdown");
+        assert_eq!(config.notes_path, default.notes_path);
+        assert_eq!(config.exclude, default.exclude);
+        assert_eq!(config.split_orientation, SplitOrientation::Horizontal);
+        assert!(!config.persist_undo);
+    }
+
+    #[test]
+    fn lists_skip_empty_items() {
+        assert_eq!(parse_list(" a , ,b,"), ["a", "b"]);
+        assert!(parse_list("").is_empty());
+    }
+}
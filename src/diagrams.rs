@@ -0,0 +1,2000 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+/// A diagram as drawn, or why it couldn't be.
+type Diagram = Result<iced::widget::svg::Handle, String>;
+
+/// The diagrams drawn so far, by their language and code, so that blocks parsed again on each
+/// edit aren't laid out again.
+static CACHE: LazyLock<Mutex<HashMap<(String, String), Diagram>>> = LazyLock::new(Default::default);
+/// A flowchart edge, from after the node it starts at: an arrow like `-->`, `-.->` or `==>`,
+/// with its text either in the arrow (`-- yes -->`) or after it (`-->|yes|`).
+static EDGE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r"^\s*(<)?(?:(?:--|==|-\.)\s*([^-=.>|\s][^>|]*?)\s*)?(-{2,}|={2,}|-?\.+-)(>|[xo]\s)?(?:\|([^|]*)\|)?",
+    )
+    .unwrap()
+});
+/// A message of a sequence diagram: `Alice->>Bob: Hello`.
+static MESSAGE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"^(.+?)\s*(-->>|->>|--x|-x|--\)|-\)|-->|->)\s*[+-]?\s*(.+?)\s*:(.*)$")
+        .unwrap()
+});
+
+/// Fonts are named, as the SVG renderer maps `sans-serif` to Arial alone.
+const FONT_FAMILY: &str =
+    "Segoe UI, Helvetica Neue, Helvetica, Arial, Noto Sans, DejaVu Sans, Liberation Sans, sans-serif";
+const FONT_SIZE: f32 = 14.0;
+const LINE_HEIGHT: f32 = FONT_SIZE * 1.3;
+
+/// Whether code blocks in `language` are drawn as diagrams.
+pub fn is_diagram(language: &str) -> bool {
+    matches!(language, "mermaid" | "dot" | "graphviz")
+}
+
+/// Draws the diagram of `code`, a Mermaid flowchart or sequence diagram, or a Graphviz graph.
+/// Errors say why it can't be read.
+pub fn render(language: &str, code: &str) -> Result<iced::widget::svg::Handle, String> {
+    let key = (language.to_string(), code.to_string());
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(diagram) = cache.get(&key) {
+        return diagram.clone();
+    }
+    let diagram =
+        svg(language, code).map(|svg| iced::widget::svg::Handle::from_memory(svg.into_bytes()));
+    // Old versions of edited diagrams pile up otherwise.
+    if cache.len() >= 64 {
+        cache.clear();
+    }
+    cache.insert(key, diagram.clone());
+    diagram
+}
+
+/// The SVG of the diagram written in `code`.
+pub fn svg(language: &str, code: &str) -> Result<String, String> {
+    match language {
+        "mermaid" => mermaid(code),
+        _ => dot(code).map(|graph| draw_graph(&graph)),
+    }
+}
+
+fn mermaid(code: &str) -> Result<String, String> {
+    let mut lines = code
+        .lines()
+        .map(|line| line.split("%%").next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty());
+    let header = lines.next().ok_or("Empty diagram")?;
+    let mut words = header.split_whitespace();
+    match words.next() {
+        Some("graph" | "flowchart") => {
+            let direction = match words.next() {
+                None | Some("TD" | "TB") => Direction::Down,
+                Some("BT") => Direction::Up,
+                Some("LR") => Direction::Right,
+                Some("RL") => Direction::Left,
+                Some(direction) => return Err(format!("Unknown direction {}", direction)),
+            };
+            flowchart(direction, lines).map(|graph| draw_graph(&graph))
+        }
+        Some("sequenceDiagram") => sequence(lines).map(|sequence| draw_sequence(&sequence)),
+        Some(kind) => Err(format!("Unsupported diagram type {}", kind)),
+        None => Err("Empty diagram".into()),
+    }
+}
+
+/// Nodes joined by edges, laid out in ranks.
+struct Graph {
+    direction: Direction,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Down,
+    Up,
+    Right,
+    Left,
+}
+
+struct Node {
+    id: String,
+    label: String,
+    shape: Shape,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Rectangle,
+    Rounded,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    Ellipse,
+    Diamond,
+    Hexagon,
+    /// The asymmetric shape of `>text]`.
+    Flag,
+    /// Text alone.
+    Plain,
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    label: String,
+    line: Line,
+    head: bool,
+    tail: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Line {
+    Solid,
+    Dotted,
+    Thick,
+}
+
+impl Graph {
+    /// The index of the node `id`, added if it's new.
+    fn node(&mut self, id: &str) -> usize {
+        match self.nodes.iter().position(|node| node.id == id) {
+            Some(i) => i,
+            None => {
+                self.nodes.push(Node {
+                    id: id.to_string(),
+                    label: id.to_string(),
+                    shape: Shape::Rectangle,
+                });
+                self.nodes.len() - 1
+            }
+        }
+    }
+}
+
+fn flowchart<'a>(
+    direction: Direction,
+    lines: impl Iterator<Item = &'a str>,
+) -> Result<Graph, String> {
+    let mut graph = Graph {
+        direction,
+        nodes: vec![],
+        edges: vec![],
+    };
+    let statements = lines.flat_map(|line| line.split(';').map(str::trim));
+    for statement in statements {
+        let keyword = statement.split_whitespace().next().unwrap_or("");
+        // Styles and subgraphs aren't drawn, the nodes in subgraphs are.
+        if statement.is_empty()
+            || matches!(
+                keyword,
+                "classDef"
+                    | "class"
+                    | "style"
+                    | "linkStyle"
+                    | "click"
+                    | "subgraph"
+                    | "end"
+                    | "direction"
+            )
+        {
+            continue;
+        }
+
+        let mut rest = statement;
+        let mut previous = flowchart_nodes(&mut graph, &mut rest)?;
+        while !rest.trim().is_empty() {
+            let edge = EDGE
+                .captures(rest)
+                .ok_or_else(|| format!("Can't read {}", rest.trim()))?;
+            let body = &edge[3];
+            let label = edge
+                .get(2)
+                .or(edge.get(5))
+                .map_or("", |label| label.as_str());
+            let head = edge.get(4).map(|head| head.as_str().trim());
+            rest = &rest[edge[0].len()..];
+
+            let next = flowchart_nodes(&mut graph, &mut rest)?;
+            for from in &previous {
+                for to in &next {
+                    graph.edges.push(Edge {
+                        from: *from,
+                        to: *to,
+                        label: label_text(label.trim().trim_matches('"')),
+                        line: if body.contains('.') {
+                            Line::Dotted
+                        } else if body.contains('=') {
+                            Line::Thick
+                        } else {
+                            Line::Solid
+                        },
+                        head: head.is_some(),
+                        tail: edge.get(1).is_some(),
+                    });
+                }
+            }
+            previous = next;
+        }
+    }
+    Ok(graph)
+}
+
+/// Reads the nodes at the start of `rest`, joined by `&`.
+fn flowchart_nodes(graph: &mut Graph, rest: &mut &str) -> Result<Vec<usize>, String> {
+    let mut nodes = vec![flowchart_node(graph, rest)?];
+    while let Some(after) = rest.trim_start().strip_prefix('&') {
+        *rest = after;
+        nodes.push(flowchart_node(graph, rest)?);
+    }
+    Ok(nodes)
+}
+
+/// Reads a node at the start of `rest`, with its shape and text when given.
+fn flowchart_node(graph: &mut Graph, rest: &mut &str) -> Result<usize, String> {
+    let trimmed = rest.trim_start();
+    let length = trimmed
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(trimmed.len());
+    if length == 0 {
+        return Err(format!("Missing node at {}", trimmed));
+    }
+    let id = &trimmed[..length];
+    let node = graph.node(id);
+    *rest = &trimmed[length..];
+
+    const SHAPES: [(&str, &str, Shape); 12] = [
+        ("(((", ")))", Shape::Circle),
+        ("([", "])", Shape::Stadium),
+        ("[[", "]]", Shape::Subroutine),
+        ("[(", ")]", Shape::Cylinder),
+        ("((", "))", Shape::Circle),
+        ("{{", "}}", Shape::Hexagon),
+        ("[/", "]", Shape::Rectangle),
+        ("[\\", "]", Shape::Rectangle),
+        ("[", "]", Shape::Rectangle),
+        ("(", ")", Shape::Rounded),
+        ("{", "}", Shape::Diamond),
+        (">", "]", Shape::Flag),
+    ];
+    if let Some((open, close, shape)) = SHAPES.iter().find(|(open, ..)| rest.starts_with(open)) {
+        let text = &rest[open.len()..];
+        // Quoted text may hold the closing brackets.
+        let end = match text.strip_prefix('"') {
+            Some(quoted) => quoted
+                .find('"')
+                .and_then(|quote| text[quote + 2..].find(close).map(|end| quote + 2 + end)),
+            None => text.find(close),
+        }
+        .ok_or_else(|| format!("Missing {} after {}", close, id))?;
+        let label = text[..end]
+            .trim()
+            .trim_matches(['/', '\\'])
+            .trim_matches('"');
+        graph.nodes[node].label = label_text(label);
+        graph.nodes[node].shape = *shape;
+        *rest = &text[end + close.len()..];
+    }
+    // Classes given with `:::` aren't drawn.
+    if let Some(class) = rest.strip_prefix(":::") {
+        let end = class
+            .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '-')
+            .unwrap_or(class.len());
+        *rest = &class[end..];
+    }
+    Ok(node)
+}
+
+/// The text of a label, with `<br>` tags as line breaks.
+fn label_text(label: &str) -> String {
+    label
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n")
+}
+
+/// A sequence diagram: participants and what they send each other, in order.
+struct Sequence {
+    participants: Vec<Participant>,
+    events: Vec<Event>,
+    numbered: bool,
+}
+
+struct Participant {
+    id: String,
+    label: String,
+    actor: bool,
+}
+
+enum Event {
+    Message {
+        from: usize,
+        to: usize,
+        text: String,
+        dashed: bool,
+        head: Head,
+    },
+    Note {
+        first: usize,
+        last: usize,
+        side: Side,
+        text: String,
+    },
+    /// The start of a frame like `loop` or `alt`, with its label.
+    Open(String, String),
+    /// A new part of the current frame, like `else`.
+    Divider(String),
+    Close,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Head {
+    None,
+    Filled,
+    Open,
+    Cross,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+    Over,
+}
+
+impl Sequence {
+    /// The index of the participant `id`, added if it's new.
+    fn participant(&mut self, id: &str) -> usize {
+        match self.participants.iter().position(|p| p.id == id) {
+            Some(i) => i,
+            None => {
+                self.participants.push(Participant {
+                    id: id.to_string(),
+                    label: id.to_string(),
+                    actor: false,
+                });
+                self.participants.len() - 1
+            }
+        }
+    }
+}
+
+fn sequence<'a>(lines: impl Iterator<Item = &'a str>) -> Result<Sequence, String> {
+    let mut sequence = Sequence {
+        participants: vec![],
+        events: vec![],
+        numbered: false,
+    };
+    let mut depth = 0;
+    for line in lines {
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match keyword {
+            "autonumber" => sequence.numbered = true,
+            "activate" | "deactivate" | "title" | "box" => {}
+            "participant" | "actor" => {
+                let (id, label) = rest.split_once(" as ").unwrap_or((rest, rest));
+                let i = sequence.participant(id.trim());
+                sequence.participants[i].label = label_text(label.trim());
+                sequence.participants[i].actor = keyword == "actor";
+            }
+            "loop" | "alt" | "opt" | "par" | "critical" | "break" | "rect" => {
+                depth += 1;
+                let label = if keyword == "rect" { "" } else { rest };
+                sequence
+                    .events
+                    .push(Event::Open(keyword.into(), label_text(label)));
+            }
+            "else" | "and" | "option" if depth > 0 => {
+                sequence.events.push(Event::Divider(label_text(rest)));
+            }
+            "end" if depth > 0 => {
+                depth -= 1;
+                sequence.events.push(Event::Close);
+            }
+            _ if keyword.eq_ignore_ascii_case("note") => {
+                let (place, text) = rest
+                    .split_once(':')
+                    .ok_or_else(|| format!("Missing : in {}", line))?;
+                let (side, who) = if let Some(who) = place.strip_prefix("left of") {
+                    (Side::Left, who)
+                } else if let Some(who) = place.strip_prefix("right of") {
+                    (Side::Right, who)
+                } else if let Some(who) = place.strip_prefix("over") {
+                    (Side::Over, who)
+                } else {
+                    return Err(format!("Can't read {}", line));
+                };
+                let (first, last) = who.split_once(',').unwrap_or((who, who));
+                let first = sequence.participant(first.trim());
+                let last = sequence.participant(last.trim());
+                sequence.events.push(Event::Note {
+                    first: first.min(last),
+                    last: first.max(last),
+                    side,
+                    text: label_text(text.trim()),
+                });
+            }
+            _ => {
+                let message = MESSAGE
+                    .captures(line)
+                    .ok_or_else(|| format!("Can't read {}", line))?;
+                let from = sequence.participant(message[1].trim());
+                let to = sequence.participant(message[3].trim());
+                let arrow = &message[2];
+                sequence.events.push(Event::Message {
+                    from,
+                    to,
+                    text: label_text(message[4].trim()),
+                    dashed: arrow.starts_with("--"),
+                    head: match arrow.trim_start_matches('-') {
+                        ">>" => Head::Filled,
+                        ")" => Head::Open,
+                        "x" => Head::Cross,
+                        _ => Head::None,
+                    },
+                });
+            }
+        }
+    }
+    if depth > 0 {
+        return Err("Missing end".into());
+    }
+    Ok(sequence)
+}
+
+/// Reads a Graphviz graph.
+fn dot(code: &str) -> Result<Graph, String> {
+    let tokens = dot_tokens(code)?;
+    let mut tokens = tokens.iter().map(String::as_str).peekable();
+
+    if tokens.peek() == Some(&"strict") {
+        tokens.next();
+    }
+    let directed = match tokens.next() {
+        Some("digraph") => true,
+        Some("graph") => false,
+        _ => return Err("Expected graph or digraph".into()),
+    };
+    if tokens.peek() != Some(&"{") {
+        tokens.next();
+    }
+    if tokens.next() != Some("{") {
+        return Err("Missing {".into());
+    }
+
+    let mut graph = Graph {
+        direction: Direction::Down,
+        nodes: vec![],
+        edges: vec![],
+    };
+    let mut parser = DotParser {
+        graph: &mut graph,
+        directed,
+        node_defaults: vec![],
+        edge_defaults: vec![],
+    };
+    parser.statements(&mut tokens)?;
+    if tokens.next().is_some() {
+        return Err("Unexpected text after }".into());
+    }
+    Ok(graph)
+}
+
+/// Cuts Graphviz code into identifiers, strings and symbols. Strings keep their quotes, so
+/// that `"{"` isn't read as a brace.
+fn dot_tokens(code: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut chars = code.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '#' if code[..i].ends_with('\n') || i == 0 => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            '/' if chars.next_if(|(_, c)| *c == '/').is_some() => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            '/' if chars.next_if(|(_, c)| *c == '*').is_some() => {
+                let end = code[i + 2..].find("*/").ok_or("Unclosed comment")?;
+                while chars.next_if(|(j, _)| *j < i + 2 + end + 2).is_some() {}
+            }
+            '"' => {
+                let mut text = String::from('"');
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n' | 'l' | 'r')) => text.push('\n'),
+                            Some((_, c)) => text.push(c),
+                            None => return Err("Unclosed string".into()),
+                        },
+                        Some((_, '"')) => break,
+                        Some((_, c)) => text.push(c),
+                        None => return Err("Unclosed string".into()),
+                    }
+                }
+                tokens.push(text);
+            }
+            // HTML labels are shown as their text.
+            '<' => {
+                let mut depth = 1;
+                let mut text = String::from('"');
+                let mut tag = false;
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '<' => {
+                            depth += 1;
+                            tag = true;
+                        }
+                        '>' => {
+                            depth -= 1;
+                            tag = false;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        c if !tag => text.push(c),
+                        _ => {}
+                    }
+                }
+                tokens.push(text);
+            }
+            '-' if chars.next_if(|(_, c)| matches!(c, '>' | '-')).is_some() => {
+                tokens.push(code[i..i + 2].to_string());
+            }
+            '{' | '}' | '[' | ']' | '=' | ';' | ',' | ':' => tokens.push(c.to_string()),
+            c if c.is_alphanumeric() || matches!(c, '_' | '.' | '-') => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, c)) =
+                    chars.next_if(|(_, c)| c.is_alphanumeric() || matches!(c, '_' | '.'))
+                {
+                    end = j + c.len_utf8();
+                }
+                tokens.push(code[i..end].to_string());
+            }
+            c => return Err(format!("Unexpected {}", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct DotParser<'a> {
+    graph: &'a mut Graph,
+    directed: bool,
+    node_defaults: Vec<(String, String)>,
+    edge_defaults: Vec<(String, String)>,
+}
+
+impl DotParser<'_> {
+    /// Reads statements up to the closing brace, returning the nodes they name.
+    fn statements<'a>(
+        &mut self,
+        tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+    ) -> Result<Vec<usize>, String> {
+        let mut named = vec![];
+        loop {
+            let token = tokens.next().ok_or("Missing }")?;
+            match token {
+                "}" => return Ok(named),
+                ";" | "," => {}
+                "graph" | "node" | "edge" => {
+                    let attributes = attributes(tokens)?;
+                    match token {
+                        "node" => self.node_defaults.extend(attributes),
+                        "edge" => self.edge_defaults.extend(attributes),
+                        _ => self.graph_attributes(&attributes),
+                    }
+                }
+                _ => {
+                    if tokens.next_if(|token| *token == "=").is_some() {
+                        let value = tokens.next().ok_or("Missing value")?;
+                        self.graph_attributes(&[(unquote(token), unquote(value))]);
+                        continue;
+                    }
+                    let mut chain = vec![self.operand(token, tokens)?];
+                    while matches!(tokens.peek(), Some(&"->" | &"--")) {
+                        tokens.next();
+                        let token = tokens.next().ok_or("Missing node after edge")?;
+                        chain.push(self.operand(token, tokens)?);
+                    }
+                    let attributes = if tokens.peek() == Some(&"[") {
+                        attributes(tokens)?
+                    } else {
+                        vec![]
+                    };
+                    if chain.len() == 1 {
+                        for node in &chain[0] {
+                            self.node_attributes(*node, &attributes);
+                        }
+                    } else {
+                        for pair in chain.windows(2) {
+                            for from in &pair[0] {
+                                for to in &pair[1] {
+                                    self.edge(*from, *to, &attributes);
+                                }
+                            }
+                        }
+                    }
+                    named.extend(chain.into_iter().flatten());
+                }
+            }
+        }
+    }
+
+    /// Reads a node or a subgraph, returning its nodes.
+    fn operand<'a>(
+        &mut self,
+        token: &'a str,
+        tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+    ) -> Result<Vec<usize>, String> {
+        if token == "subgraph" {
+            // Its name isn't drawn.
+            tokens.next_if(|token| *token != "{");
+            if tokens.next() != Some("{") {
+                return Err("Missing { after subgraph".into());
+            }
+            return self.statements(tokens);
+        }
+        if token == "{" {
+            return self.statements(tokens);
+        }
+        if matches!(token, "[" | "]" | "=" | "->" | "--") {
+            return Err(format!("Unexpected {}", token));
+        }
+        // Ports aren't drawn.
+        if tokens.next_if(|token| *token == ":").is_some() {
+            tokens.next();
+            if tokens.next_if(|token| *token == ":").is_some() {
+                tokens.next();
+            }
+        }
+        let id = unquote(token);
+        let is_new = !self.graph.nodes.iter().any(|node| node.id == id);
+        let node = self.graph.node(&id);
+        if is_new {
+            self.graph.nodes[node].shape = Shape::Ellipse;
+            let defaults = self.node_defaults.clone();
+            self.node_attributes(node, &defaults);
+        }
+        Ok(vec![node])
+    }
+
+    fn graph_attributes(&mut self, attributes: &[(String, String)]) {
+        for (name, value) in attributes {
+            if name == "rankdir" {
+                self.graph.direction = match value.as_str() {
+                    "LR" => Direction::Right,
+                    "RL" => Direction::Left,
+                    "BT" => Direction::Up,
+                    _ => Direction::Down,
+                };
+            }
+        }
+    }
+
+    fn node_attributes(&mut self, node: usize, attributes: &[(String, String)]) {
+        let node = &mut self.graph.nodes[node];
+        for (name, value) in attributes {
+            match name.as_str() {
+                "label" => node.label = value.clone(),
+                "shape" => {
+                    node.shape = match value.as_str() {
+                        "box" | "rect" | "rectangle" | "square" | "record" | "note" | "tab"
+                        | "folder" | "component" => Shape::Rectangle,
+                        "Mrecord" => Shape::Rounded,
+                        "circle" | "doublecircle" | "point" => Shape::Circle,
+                        "diamond" => Shape::Diamond,
+                        "hexagon" => Shape::Hexagon,
+                        "cylinder" => Shape::Cylinder,
+                        "plaintext" | "plain" | "none" => Shape::Plain,
+                        _ => Shape::Ellipse,
+                    }
+                }
+                "style" if value.contains("rounded") && node.shape == Shape::Rectangle => {
+                    node.shape = Shape::Rounded
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn edge(&mut self, from: usize, to: usize, attributes: &[(String, String)]) {
+        let mut edge = Edge {
+            from,
+            to,
+            label: String::new(),
+            line: Line::Solid,
+            head: self.directed,
+            tail: false,
+        };
+        for (name, value) in self.edge_defaults.iter().chain(attributes) {
+            match name.as_str() {
+                "label" => edge.label = value.clone(),
+                "style" if value == "dashed" || value == "dotted" => edge.line = Line::Dotted,
+                "style" if value == "bold" => edge.line = Line::Thick,
+                "dir" => {
+                    (edge.head, edge.tail) = match value.as_str() {
+                        "none" => (false, false),
+                        "back" => (false, true),
+                        "both" => (true, true),
+                        _ => (true, false),
+                    }
+                }
+                "arrowhead" if value == "none" => edge.head = false,
+                _ => {}
+            }
+        }
+        self.graph.edges.push(edge);
+    }
+}
+
+/// Reads `[name=value, ...]` lists, one after another.
+fn attributes<'a>(
+    tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+) -> Result<Vec<(String, String)>, String> {
+    let mut attributes = vec![];
+    while tokens.next_if(|token| *token == "[").is_some() {
+        loop {
+            match tokens.next().ok_or("Missing ]")? {
+                "]" => break,
+                "," | ";" => {}
+                name => {
+                    if tokens.next() != Some("=") {
+                        return Err(format!("Missing = after {}", name));
+                    }
+                    let value = tokens.next().ok_or("Missing value")?;
+                    attributes.push((unquote(name), unquote(value)));
+                }
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+fn unquote(token: &str) -> String {
+    token.strip_prefix('"').unwrap_or(token).to_string()
+}
+
+/// An SVG document being drawn, growing to fit what's drawn in it.
+struct Svg {
+    body: String,
+    min: iced::Point,
+    max: iced::Point,
+}
+
+impl Svg {
+    fn new() -> Self {
+        Self {
+            body: String::new(),
+            min: iced::Point::new(f32::MAX, f32::MAX),
+            max: iced::Point::new(f32::MIN, f32::MIN),
+        }
+    }
+
+    fn fit(&mut self, x: f32, y: f32) {
+        self.min = iced::Point::new(self.min.x.min(x), self.min.y.min(y));
+        self.max = iced::Point::new(self.max.x.max(x), self.max.y.max(y));
+    }
+
+    fn element(&mut self, element: String, points: &[iced::Point]) {
+        for point in points {
+            self.fit(point.x, point.y);
+        }
+        self.body.push_str(&element);
+        self.body.push('\n');
+    }
+
+    fn rectangle(&mut self, bounds: iced::Rectangle, radius: f32, style: &str) {
+        self.element(
+            format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{}" {}/>"#,
+                bounds.x, bounds.y, bounds.width, bounds.height, radius, style
+            ),
+            &[
+                bounds.position(),
+                bounds.position() + iced::Vector::from(bounds.size()),
+            ],
+        );
+    }
+
+    fn polygon(&mut self, points: &[iced::Point], style: &str) {
+        let list: Vec<String> = points
+            .iter()
+            .map(|point| format!("{},{}", point.x, point.y))
+            .collect();
+        self.element(
+            format!(r#"<polygon points="{}" {}/>"#, list.join(" "), style),
+            points,
+        );
+    }
+
+    fn path(&mut self, data: String, points: &[iced::Point], style: &str) {
+        self.element(format!(r#"<path d="{}" {}/>"#, data, style), points);
+    }
+
+    fn line(&mut self, from: iced::Point, to: iced::Point, style: &str) {
+        self.element(
+            format!(
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {}/>"#,
+                from.x, from.y, to.x, to.y, style
+            ),
+            &[from, to],
+        );
+    }
+
+    /// Draws lines of `text` centered on `center`, or starting from it when `start` is set.
+    fn text(&mut self, text: &str, center: iced::Point, start: bool, color: &str) {
+        let lines: Vec<&str> = text.lines().collect();
+        let top = center.y - lines.len() as f32 * LINE_HEIGHT / 2.0;
+        for (i, line) in lines.iter().enumerate() {
+            let width = text_width(line);
+            let left = if start {
+                center.x
+            } else {
+                center.x - width / 2.0
+            };
+            let y = top + i as f32 * LINE_HEIGHT;
+            self.element(
+                format!(
+                    r#"<text x="{}" y="{}" font-family="{}" font-size="{}" fill="{}" text-anchor="{}">{}</text>"#,
+                    center.x,
+                    y + LINE_HEIGHT / 2.0 + FONT_SIZE * 0.35,
+                    FONT_FAMILY,
+                    FONT_SIZE,
+                    color,
+                    if start { "start" } else { "middle" },
+                    crate::util::escape(line)
+                ),
+                &[
+                    iced::Point::new(left, y),
+                    iced::Point::new(left + width, y + LINE_HEIGHT),
+                ],
+            );
+        }
+    }
+
+    /// Adds what's drawn in `other` over what's drawn in this one.
+    fn extend(&mut self, other: Svg) {
+        self.body.push_str(&other.body);
+        if other.min.x <= other.max.x {
+            self.fit(other.min.x, other.min.y);
+            self.fit(other.max.x, other.max.y);
+        }
+    }
+
+    fn finish(self) -> String {
+        let margin = 8.0;
+        let (min, max) = if self.min.x <= self.max.x {
+            (self.min, self.max)
+        } else {
+            (iced::Point::ORIGIN, iced::Point::ORIGIN)
+        };
+        let width = max.x - min.x + margin * 2.0;
+        let height = max.y - min.y + margin * 2.0;
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">
+{}</svg>"#,
+            width.ceil(),
+            height.ceil(),
+            min.x - margin,
+            min.y - margin,
+            width,
+            height,
+            self.body
+        )
+    }
+}
+
+/// About how wide `text` is in a sans-serif font, as the SVG is laid out before it's drawn.
+fn text_width(text: &str) -> f32 {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'l' | 'j' | 't' | 'f' | 'r' | 'I' | '.' | ',' | ':' | ';' | '\'' | '|' | '!'
+            | ' ' => 0.32,
+            'm' | 'w' | 'M' | 'W' => 0.85,
+            c if c.is_uppercase() || c.is_ascii_digit() => 0.64,
+            c if c as u32 >= 0x2E80 => 1.0,
+            _ => 0.54,
+        })
+        .sum::<f32>()
+        * FONT_SIZE
+}
+
+fn text_size(text: &str) -> iced::Size {
+    iced::Size::new(
+        text.lines().map(text_width).fold(0.0, f32::max),
+        text.lines().count().max(1) as f32 * LINE_HEIGHT,
+    )
+}
+
+/// The colors of diagrams, from the theme of the app.
+struct Colors {
+    text: String,
+    line: String,
+    fill: String,
+    border: String,
+    background: String,
+    note: String,
+}
+
+impl Colors {
+    fn new() -> Self {
+        let hex = |color: iced::Color| {
+            let [r, g, b, _] = color.into_rgba8();
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        };
+        let palette = crate::util::THEME.extended_palette();
+        Self {
+            text: hex(palette.background.base.text),
+            line: hex(palette.background.strong.color),
+            fill: hex(palette.background.weak.color),
+            border: hex(palette.primary.base.color),
+            background: hex(palette.background.base.color),
+            note: hex(palette.secondary.weak.color),
+        }
+    }
+}
+
+/// The size of `node`, fitting its label in its shape.
+fn node_size(node: &Node) -> iced::Size {
+    let text = text_size(&node.label);
+    let (width, height) = (text.width + 32.0, text.height + 20.0);
+    let (width, height) = match node.shape {
+        Shape::Circle => {
+            let diameter = text.width.max(text.height) + 28.0;
+            (diameter, diameter)
+        }
+        Shape::Diamond => (
+            text.width + text.height * 2.0 + 24.0,
+            text.height * 2.0 + 24.0,
+        ),
+        Shape::Ellipse => (text.width * 1.3 + 28.0, text.height * 1.4 + 14.0),
+        Shape::Hexagon => (width + 20.0, height),
+        Shape::Cylinder => (width, height + 16.0),
+        Shape::Flag => (width + 12.0, height),
+        Shape::Plain => (text.width + 8.0, text.height + 4.0),
+        _ => (width, height),
+    };
+    iced::Size::new(width.max(40.0), height)
+}
+
+/// Where the nodes of a graph go, and the points their edges go through.
+struct Placement {
+    centers: Vec<iced::Point>,
+    sizes: Vec<iced::Size>,
+    routes: Vec<Vec<iced::Point>>,
+}
+
+/// Lays `graph` out in ranks following its edges: cycles are broken by turning edges around,
+/// ranks are the longest paths to the nodes, edges longer than a rank go through hidden
+/// nodes, and nodes are ordered in their rank to cut crossings then pulled toward their
+/// neighbours.
+fn layout(graph: &Graph) -> Placement {
+    let count = graph.nodes.len();
+    let horizontal = matches!(graph.direction, Direction::Left | Direction::Right);
+    let sizes: Vec<iced::Size> = graph.nodes.iter().map(node_size).collect();
+    // Across ranks, and along the edges from one rank to the next.
+    let across = |size: iced::Size| if horizontal { size.height } else { size.width };
+    let along = |size: iced::Size| if horizontal { size.width } else { size.height };
+
+    let mut outgoing = vec![vec![]; count];
+    for (i, edge) in graph.edges.iter().enumerate() {
+        if edge.from != edge.to {
+            outgoing[edge.from].push(i);
+        }
+    }
+    let mut reversed = vec![false; graph.edges.len()];
+    let mut state = vec![0; count];
+    for node in 0..count {
+        if state[node] == 0 {
+            break_cycles(node, graph, &outgoing, &mut state, &mut reversed);
+        }
+    }
+    let ends = |i: usize| {
+        let edge = &graph.edges[i];
+        if reversed[i] {
+            (edge.to, edge.from)
+        } else {
+            (edge.from, edge.to)
+        }
+    };
+    let edges: Vec<usize> = (0..graph.edges.len())
+        .filter(|i| graph.edges[*i].from != graph.edges[*i].to)
+        .collect();
+
+    // Ranks, in topological order.
+    let mut incoming = vec![0; count];
+    for i in &edges {
+        incoming[ends(*i).1] += 1;
+    }
+    let mut queue: std::collections::VecDeque<usize> =
+        (0..count).filter(|node| incoming[*node] == 0).collect();
+    let mut ranks = vec![0; count];
+    while let Some(node) = queue.pop_front() {
+        for i in &edges {
+            let (from, to) = ends(*i);
+            if from == node {
+                ranks[to] = ranks[to].max(ranks[node] + 1);
+                incoming[to] -= 1;
+                if incoming[to] == 0 {
+                    queue.push_back(to);
+                }
+            }
+        }
+    }
+
+    // Vertices are the nodes then hidden nodes on long edges, with their width across ranks.
+    let mut widths: Vec<f32> = sizes.iter().map(|size| across(*size)).collect();
+    let mut vertex_ranks = ranks.clone();
+    let mut chains: Vec<Vec<usize>> = vec![vec![]; graph.edges.len()];
+    let mut links = vec![];
+    for i in &edges {
+        let (from, to) = ends(*i);
+        let mut chain = vec![from];
+        for rank in ranks[from] + 1..ranks[to] {
+            widths.push(if graph.edges[*i].label.is_empty() {
+                8.0
+            } else {
+                across(text_size(&graph.edges[*i].label))
+            });
+            vertex_ranks.push(rank);
+            chain.push(widths.len() - 1);
+        }
+        chain.push(to);
+        for pair in chain.windows(2) {
+            links.push((pair[0], pair[1]));
+        }
+        chains[*i] = chain;
+    }
+    let vertices = widths.len();
+    let mut above = vec![vec![]; vertices];
+    let mut below = vec![vec![]; vertices];
+    for (upper, lower) in &links {
+        below[*upper].push(*lower);
+        above[*lower].push(*upper);
+    }
+
+    let rank_count = vertex_ranks.iter().max().map_or(0, |rank| rank + 1);
+    let mut layers = vec![vec![]; rank_count];
+    for (vertex, rank) in vertex_ranks.iter().enumerate() {
+        layers[*rank].push(vertex);
+    }
+    let mut order = vec![0.0; vertices];
+    let number = |layers: &[Vec<usize>], order: &mut [f32]| {
+        for layer in layers {
+            for (i, vertex) in layer.iter().enumerate() {
+                order[*vertex] = i as f32;
+            }
+        }
+    };
+    number(&layers, &mut order);
+    for sweep in 0..8 {
+        let down = sweep % 2 == 0;
+        let ranks: Vec<usize> = if down {
+            (1..rank_count).collect()
+        } else {
+            (0..rank_count.saturating_sub(1)).rev().collect()
+        };
+        for rank in ranks {
+            let neighbours = if down { &above } else { &below };
+            let barycenter = |vertex: usize| {
+                let neighbours = &neighbours[vertex];
+                if neighbours.is_empty() {
+                    order[vertex]
+                } else {
+                    neighbours.iter().map(|n| order[*n]).sum::<f32>() / neighbours.len() as f32
+                }
+            };
+            let mut keyed: Vec<(f32, usize)> =
+                layers[rank].iter().map(|v| (barycenter(*v), *v)).collect();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            layers[rank] = keyed.into_iter().map(|(_, vertex)| vertex).collect();
+            for (i, vertex) in layers[rank].iter().enumerate() {
+                order[*vertex] = i as f32;
+            }
+        }
+    }
+
+    // Positions across ranks: packed, then pulled toward the neighbours of each vertex while
+    // keeping them apart.
+    let gap = |a: usize, b: usize| {
+        let space = if a < count && b < count { 32.0 } else { 16.0 };
+        (widths[a] + widths[b]) / 2.0 + space
+    };
+    let mut position = vec![0.0; vertices];
+    for layer in &layers {
+        let mut x = 0.0;
+        for (i, vertex) in layer.iter().enumerate() {
+            if i > 0 {
+                x += gap(layer[i - 1], *vertex);
+            }
+            position[*vertex] = x;
+        }
+        for vertex in layer {
+            position[*vertex] -= x / 2.0;
+        }
+    }
+    for round in 0..8 {
+        let down = round % 2 == 0;
+        let neighbours = if down { &above } else { &below };
+        let ranks: Vec<usize> = if down {
+            (0..rank_count).collect()
+        } else {
+            (0..rank_count).rev().collect()
+        };
+        for rank in ranks {
+            let layer = &layers[rank];
+            let wanted: Vec<f32> = layer
+                .iter()
+                .map(|vertex| {
+                    let neighbours = &neighbours[*vertex];
+                    if neighbours.is_empty() {
+                        position[*vertex]
+                    } else {
+                        neighbours.iter().map(|n| position[*n]).sum::<f32>()
+                            / neighbours.len() as f32
+                    }
+                })
+                .collect();
+            // Pushed right from the left, and left from the right, then averaged.
+            let mut left = wanted.clone();
+            for i in 1..layer.len() {
+                left[i] = left[i].max(left[i - 1] + gap(layer[i - 1], layer[i]));
+            }
+            let mut right = wanted;
+            for i in (0..layer.len().saturating_sub(1)).rev() {
+                right[i] = right[i].min(right[i + 1] - gap(layer[i], layer[i + 1]));
+            }
+            for (i, vertex) in layer.iter().enumerate() {
+                position[*vertex] = (left[i] + right[i]) / 2.0;
+            }
+        }
+    }
+
+    // Positions along the edges, leaving room for the labels between ranks.
+    let mut depths = vec![0.0; rank_count];
+    let mut gaps = vec![48.0; rank_count];
+    for (node, size) in sizes.iter().enumerate() {
+        depths[ranks[node]] = f32::max(depths[ranks[node]], along(*size));
+    }
+    for i in &edges {
+        let (from, _) = ends(*i);
+        if !graph.edges[*i].label.is_empty() {
+            let label = along(text_size(&graph.edges[*i].label));
+            gaps[ranks[from]] = f32::max(gaps[ranks[from]], label + 32.0);
+        }
+    }
+    let mut rank_positions = vec![0.0; rank_count];
+    let mut y = 0.0;
+    for rank in 0..rank_count {
+        rank_positions[rank] = y + depths[rank] / 2.0;
+        y += depths[rank] + gaps[rank];
+    }
+
+    let point = |vertex: usize| {
+        let (x, y) = (position[vertex], rank_positions[vertex_ranks[vertex]]);
+        match graph.direction {
+            Direction::Down => iced::Point::new(x, y),
+            Direction::Up => iced::Point::new(x, -y),
+            Direction::Right => iced::Point::new(y, x),
+            Direction::Left => iced::Point::new(-y, x),
+        }
+    };
+    let centers: Vec<iced::Point> = (0..count).map(point).collect();
+    let routes = graph
+        .edges
+        .iter()
+        .enumerate()
+        .map(|(i, edge)| {
+            if edge.from == edge.to {
+                // A loop on the side of the node.
+                let center = centers[edge.from];
+                let size = sizes[edge.from];
+                let start = iced::Point::new(center.x + size.width / 2.0, center.y - 6.0);
+                let end = iced::Point::new(center.x + size.width / 2.0, center.y + 6.0);
+                return vec![
+                    start,
+                    start + iced::Vector::new(30.0, -24.0),
+                    end + iced::Vector::new(30.0, 24.0),
+                    end,
+                ];
+            }
+            let mut points: Vec<iced::Point> = chains[i].iter().map(|v| point(*v)).collect();
+            if reversed[i] {
+                points.reverse();
+            }
+            let last = points.len() - 1;
+            points[0] = border(
+                &graph.nodes[edge.from],
+                centers[edge.from],
+                sizes[edge.from],
+                points[1],
+            );
+            points[last] = border(
+                &graph.nodes[edge.to],
+                centers[edge.to],
+                sizes[edge.to],
+                points[last - 1],
+            );
+            points
+        })
+        .collect();
+    Placement {
+        centers,
+        sizes,
+        routes,
+    }
+}
+
+/// Marks the edges that close a cycle, found depth first from `node`, to be turned around.
+fn break_cycles(
+    node: usize,
+    graph: &Graph,
+    outgoing: &[Vec<usize>],
+    state: &mut [u8],
+    reversed: &mut [bool],
+) {
+    state[node] = 1;
+    for edge in &outgoing[node] {
+        let to = graph.edges[*edge].to;
+        match state[to] {
+            0 => break_cycles(to, graph, outgoing, state, reversed),
+            1 => reversed[*edge] = true,
+            _ => {}
+        }
+    }
+    state[node] = 2;
+}
+
+/// Where the line from the center of `node` toward `toward` leaves its shape.
+fn border(node: &Node, center: iced::Point, size: iced::Size, toward: iced::Point) -> iced::Point {
+    let (dx, dy) = (toward.x - center.x, toward.y - center.y);
+    let (w, h) = (size.width / 2.0, size.height / 2.0);
+    if dx == 0.0 && dy == 0.0 {
+        return center;
+    }
+    let t = match node.shape {
+        Shape::Circle | Shape::Ellipse => 1.0 / ((dx / w).powi(2) + (dy / h).powi(2)).sqrt(),
+        Shape::Diamond => 1.0 / (dx.abs() / w + dy.abs() / h),
+        _ => f32::min(
+            if dx == 0.0 { f32::MAX } else { w / dx.abs() },
+            if dy == 0.0 { f32::MAX } else { h / dy.abs() },
+        ),
+    };
+    let t = t.min(1.0);
+    iced::Point::new(center.x + dx * t, center.y + dy * t)
+}
+
+fn draw_graph(graph: &Graph) -> String {
+    let colors = Colors::new();
+    let placement = layout(graph);
+    let mut svg = Svg::new();
+
+    for (edge, route) in graph.edges.iter().zip(&placement.routes) {
+        let style = format!(
+            r#"fill="none" stroke="{}" stroke-width="{}"{}"#,
+            colors.line,
+            if edge.line == Line::Thick { 3.0 } else { 1.5 },
+            if edge.line == Line::Dotted {
+                r#" stroke-dasharray="5 4""#
+            } else {
+                ""
+            }
+        );
+        let data = if edge.from == edge.to {
+            format!(
+                "M {} {} C {} {} {} {} {} {}",
+                route[0].x,
+                route[0].y,
+                route[1].x,
+                route[1].y,
+                route[2].x,
+                route[2].y,
+                route[3].x,
+                route[3].y
+            )
+        } else {
+            smooth(route)
+        };
+        svg.path(data, route, &style);
+        let last = route.len() - 1;
+        if edge.head {
+            svg.polygon(
+                &arrow(route[last - 1], route[last]),
+                &format!(r#"fill="{}""#, colors.line),
+            );
+        }
+        if edge.tail {
+            svg.polygon(
+                &arrow(route[1], route[0]),
+                &format!(r#"fill="{}""#, colors.line),
+            );
+        }
+    }
+    for (edge, route) in graph.edges.iter().zip(&placement.routes) {
+        if edge.label.is_empty() {
+            continue;
+        }
+        let middle = if edge.from == edge.to {
+            iced::Point::new(
+                route[1].x + text_size(&edge.label).width / 2.0,
+                (route[0].y + route[3].y) / 2.0,
+            )
+        } else {
+            halfway(route)
+        };
+        let size = text_size(&edge.label);
+        svg.rectangle(
+            iced::Rectangle::new(
+                iced::Point::new(
+                    middle.x - size.width / 2.0 - 4.0,
+                    middle.y - size.height / 2.0,
+                ),
+                iced::Size::new(size.width + 8.0, size.height),
+            ),
+            3.0,
+            &format!(r#"fill="{}""#, colors.background),
+        );
+        svg.text(&edge.label, middle, false, &colors.text);
+    }
+
+    for (i, node) in graph.nodes.iter().enumerate() {
+        let center = placement.centers[i];
+        let size = placement.sizes[i];
+        let bounds = iced::Rectangle::new(
+            iced::Point::new(center.x - size.width / 2.0, center.y - size.height / 2.0),
+            size,
+        );
+        draw_shape(&mut svg, node.shape, bounds, &colors);
+        svg.text(&node.label, center, false, &colors.text);
+    }
+    svg.finish()
+}
+
+fn draw_shape(svg: &mut Svg, shape: Shape, bounds: iced::Rectangle, colors: &Colors) {
+    let style = format!(
+        r#"fill="{}" stroke="{}" stroke-width="1.5""#,
+        colors.fill, colors.border
+    );
+    let (x, y, w, h) = (bounds.x, bounds.y, bounds.width, bounds.height);
+    let point = iced::Point::new;
+    match shape {
+        Shape::Rectangle => svg.rectangle(bounds, 0.0, &style),
+        Shape::Rounded => svg.rectangle(bounds, 8.0, &style),
+        Shape::Stadium => svg.rectangle(bounds, h / 2.0, &style),
+        Shape::Subroutine => {
+            svg.rectangle(bounds, 0.0, &style);
+            let stroke = format!(r#"stroke="{}" stroke-width="1.5""#, colors.border);
+            svg.line(point(x + 8.0, y), point(x + 8.0, y + h), &stroke);
+            svg.line(point(x + w - 8.0, y), point(x + w - 8.0, y + h), &stroke);
+        }
+        Shape::Cylinder => {
+            let ry = 8.0;
+            svg.path(
+                format!(
+                    "M {x} {top} A {rx} {ry} 0 0 1 {right} {top} L {right} {bottom} A {rx} {ry} 0 0 1 {x} {bottom} Z",
+                    x = x,
+                    top = y + ry,
+                    right = x + w,
+                    bottom = y + h - ry,
+                    rx = w / 2.0,
+                    ry = ry
+                ),
+                &[point(x, y), point(x + w, y + h)],
+                &style,
+            );
+            svg.path(
+                format!(
+                    "M {} {} A {} {} 0 0 0 {} {}",
+                    x,
+                    y + ry,
+                    w / 2.0,
+                    ry,
+                    x + w,
+                    y + ry
+                ),
+                &[],
+                &format!(
+                    r#"fill="none" stroke="{}" stroke-width="1.5""#,
+                    colors.border
+                ),
+            );
+        }
+        Shape::Circle => svg.element(
+            format!(
+                r#"<circle cx="{}" cy="{}" r="{}" {}/>"#,
+                x + w / 2.0,
+                y + h / 2.0,
+                w / 2.0,
+                style
+            ),
+            &[point(x, y), point(x + w, y + h)],
+        ),
+        Shape::Ellipse => svg.element(
+            format!(
+                r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" {}/>"#,
+                x + w / 2.0,
+                y + h / 2.0,
+                w / 2.0,
+                h / 2.0,
+                style
+            ),
+            &[point(x, y), point(x + w, y + h)],
+        ),
+        Shape::Diamond => svg.polygon(
+            &[
+                point(x + w / 2.0, y),
+                point(x + w, y + h / 2.0),
+                point(x + w / 2.0, y + h),
+                point(x, y + h / 2.0),
+            ],
+            &style,
+        ),
+        Shape::Hexagon => svg.polygon(
+            &[
+                point(x + 12.0, y),
+                point(x + w - 12.0, y),
+                point(x + w, y + h / 2.0),
+                point(x + w - 12.0, y + h),
+                point(x + 12.0, y + h),
+                point(x, y + h / 2.0),
+            ],
+            &style,
+        ),
+        Shape::Flag => svg.polygon(
+            &[
+                point(x, y),
+                point(x + w, y),
+                point(x + w, y + h),
+                point(x, y + h),
+                point(x + 12.0, y + h / 2.0),
+            ],
+            &style,
+        ),
+        Shape::Plain => {}
+    }
+}
+
+/// A path through `points`, rounded at the points in between.
+fn smooth(points: &[iced::Point]) -> String {
+    let mut data = format!("M {} {}", points[0].x, points[0].y);
+    for i in 1..points.len() - 1 {
+        let middle =
+            |a: iced::Point, b: iced::Point| iced::Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+        let (before, after) = (
+            middle(points[i - 1], points[i]),
+            middle(points[i], points[i + 1]),
+        );
+        data.push_str(&format!(
+            " L {} {} Q {} {} {} {}",
+            before.x, before.y, points[i].x, points[i].y, after.x, after.y
+        ));
+    }
+    let last = points[points.len() - 1];
+    data.push_str(&format!(" L {} {}", last.x, last.y));
+    data
+}
+
+/// The point halfway along `points`.
+fn halfway(points: &[iced::Point]) -> iced::Point {
+    let total: f32 = points
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum();
+    let mut left = total / 2.0;
+    for pair in points.windows(2) {
+        let length = pair[0].distance(pair[1]);
+        if length >= left && length > 0.0 {
+            let t = left / length;
+            return iced::Point::new(
+                pair[0].x + (pair[1].x - pair[0].x) * t,
+                pair[0].y + (pair[1].y - pair[0].y) * t,
+            );
+        }
+        left -= length;
+    }
+    points[0]
+}
+
+/// An arrowhead pointing at `tip`, coming from `from`.
+fn arrow(from: iced::Point, tip: iced::Point) -> [iced::Point; 3] {
+    let length = from.distance(tip).max(0.001);
+    let (dx, dy) = ((tip.x - from.x) / length, (tip.y - from.y) / length);
+    let base = iced::Point::new(tip.x - dx * 10.0, tip.y - dy * 10.0);
+    [
+        tip,
+        iced::Point::new(base.x - dy * 5.0, base.y + dx * 5.0),
+        iced::Point::new(base.x + dy * 5.0, base.y - dx * 5.0),
+    ]
+}
+
+/// A frame of a sequence diagram being drawn, with how far what's in it goes.
+struct Frame {
+    kind: String,
+    label: String,
+    top: f32,
+    dividers: Vec<(f32, String)>,
+    left: f32,
+    right: f32,
+}
+
+fn draw_sequence(sequence: &Sequence) -> String {
+    let colors = Colors::new();
+    let count = sequence.participants.len();
+    let box_height = 36.0;
+    let widths: Vec<f32> = sequence
+        .participants
+        .iter()
+        .map(|p| (text_size(&p.label).width + 24.0).max(64.0))
+        .collect();
+
+    // Gaps between the lifelines, widened for the messages and notes between them.
+    let mut gaps: Vec<f32> = (1..count)
+        .map(|i| (widths[i - 1] + widths[i]) / 2.0 + 24.0)
+        .collect();
+    let mut widen = |first: usize, last: usize, width: f32| {
+        if first >= last || last > gaps.len() {
+            return;
+        }
+        let have: f32 = gaps[first..last].iter().sum();
+        if have < width {
+            gaps[last - 1] += width - have;
+        }
+    };
+    for event in &sequence.events {
+        match event {
+            Event::Message { from, to, text, .. } => {
+                let width = text_size(text).width + 32.0;
+                if from == to {
+                    widen(*from, from + 1, width + 24.0);
+                } else {
+                    widen(*from.min(to), *from.max(to), width);
+                }
+            }
+            Event::Note {
+                first,
+                last,
+                side,
+                text,
+            } => {
+                let width = text_size(text).width + 40.0;
+                match side {
+                    Side::Right => widen(*last, last + 1, width),
+                    Side::Left if *first > 0 => widen(first - 1, *first, width),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut centers = vec![widths.first().map_or(0.0, |width| width / 2.0)];
+    for gap in &gaps {
+        centers.push(centers[centers.len() - 1] + gap);
+    }
+
+    let mut back = Svg::new();
+    let mut front = Svg::new();
+    let mut frames: Vec<Frame> = vec![];
+    let line = format!(r#"fill="none" stroke="{}" stroke-width="1.5""#, colors.line);
+    let mut y = box_height + 24.0;
+    let mut number = 0;
+    for event in &sequence.events {
+        let (left, right) = match event {
+            Event::Message {
+                from,
+                to,
+                text,
+                dashed,
+                head,
+            } => {
+                number += 1;
+                let text = if sequence.numbered {
+                    format!("{}. {}", number, text)
+                } else {
+                    text.clone()
+                };
+                let size = text_size(&text);
+                let (start, end) = (centers[*from], centers[*to]);
+                let style = if *dashed {
+                    format!(r#"{} stroke-dasharray="5 4""#, line)
+                } else {
+                    line.clone()
+                };
+                let extent = if from == to {
+                    front.text(
+                        &text,
+                        iced::Point::new(start + 8.0, y + size.height / 2.0),
+                        true,
+                        &colors.text,
+                    );
+                    y += size.height + 4.0;
+                    let route = [
+                        iced::Point::new(start, y),
+                        iced::Point::new(start + 36.0, y),
+                        iced::Point::new(start + 36.0, y + 24.0),
+                        iced::Point::new(start, y + 24.0),
+                    ];
+                    front.path(smooth(&route), &route, &style);
+                    message_head(&mut front, route[2], route[3], *head, &colors);
+                    y += 24.0;
+                    (start, start + 8.0 + size.width.max(36.0))
+                } else {
+                    let middle = (start + end) / 2.0;
+                    front.text(
+                        &text,
+                        iced::Point::new(middle, y + size.height / 2.0),
+                        false,
+                        &colors.text,
+                    );
+                    y += size.height + 4.0;
+                    let (from, to) = (iced::Point::new(start, y), iced::Point::new(end, y));
+                    front.line(from, to, &style);
+                    message_head(&mut front, from, to, *head, &colors);
+                    (start.min(end), start.max(end))
+                };
+                y += 16.0;
+                extent
+            }
+            Event::Note {
+                first,
+                last,
+                side,
+                text,
+            } => {
+                let size = text_size(text);
+                let width = size.width + 20.0;
+                let (x, width) = match side {
+                    Side::Left => (centers[*first] - 12.0 - width, width),
+                    Side::Right => (centers[*last] + 12.0, width),
+                    Side::Over => {
+                        let (left, right) = (centers[*first] - 24.0, centers[*last] + 24.0);
+                        let width = width.max(right - left);
+                        ((left + right - width) / 2.0, width)
+                    }
+                };
+                let bounds = iced::Rectangle::new(
+                    iced::Point::new(x, y),
+                    iced::Size::new(width, size.height + 12.0),
+                );
+                front.rectangle(
+                    bounds,
+                    2.0,
+                    &format!(r#"fill="{}" stroke="{}""#, colors.note, colors.line),
+                );
+                front.text(text, bounds.center(), false, &colors.text);
+                y += bounds.height + 16.0;
+                (x, x + width)
+            }
+            Event::Open(kind, label) => {
+                frames.push(Frame {
+                    kind: kind.clone(),
+                    label: label.clone(),
+                    top: y,
+                    dividers: vec![],
+                    left: f32::MAX,
+                    right: f32::MIN,
+                });
+                y += LINE_HEIGHT + 16.0;
+                continue;
+            }
+            Event::Divider(label) => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.dividers.push((y, label.clone()));
+                }
+                y += LINE_HEIGHT + 16.0;
+                continue;
+            }
+            Event::Close => {
+                let Some(frame) = frames.pop() else {
+                    continue;
+                };
+                let (left, right) = if frame.left <= frame.right {
+                    (frame.left - 16.0, frame.right + 16.0)
+                } else {
+                    (centers[0] - 16.0, centers[count - 1] + 16.0)
+                };
+                let bounds = iced::Rectangle::new(
+                    iced::Point::new(left, frame.top),
+                    iced::Size::new(right - left, y - frame.top),
+                );
+                back.rectangle(bounds, 0.0, &line);
+                if frame.kind != "rect" {
+                    let tab = text_width(&frame.kind) + 16.0;
+                    back.polygon(
+                        &[
+                            bounds.position(),
+                            iced::Point::new(left + tab, frame.top),
+                            iced::Point::new(left + tab, frame.top + LINE_HEIGHT),
+                            iced::Point::new(left + tab - 6.0, frame.top + LINE_HEIGHT + 6.0),
+                            iced::Point::new(left, frame.top + LINE_HEIGHT + 6.0),
+                        ],
+                        &format!(
+                            r#"fill="{}" stroke="{}" stroke-width="1.5""#,
+                            colors.fill, colors.line
+                        ),
+                    );
+                    back.text(
+                        &frame.kind,
+                        iced::Point::new(left + tab / 2.0, frame.top + LINE_HEIGHT / 2.0 + 3.0),
+                        false,
+                        &colors.text,
+                    );
+                    back.text(
+                        &format!("[{}]", frame.label),
+                        iced::Point::new(left + tab + 8.0, frame.top + LINE_HEIGHT / 2.0 + 3.0),
+                        true,
+                        &colors.text,
+                    );
+                }
+                for (top, label) in &frame.dividers {
+                    back.line(
+                        iced::Point::new(left, *top),
+                        iced::Point::new(right, *top),
+                        &format!(r#"{} stroke-dasharray="5 4""#, line),
+                    );
+                    if !label.is_empty() {
+                        back.text(
+                            &format!("[{}]", label),
+                            iced::Point::new((left + right) / 2.0, top + LINE_HEIGHT / 2.0 + 4.0),
+                            false,
+                            &colors.text,
+                        );
+                    }
+                }
+                y += 12.0;
+                (left, right)
+            }
+        };
+        for frame in &mut frames {
+            frame.left = frame.left.min(left);
+            frame.right = frame.right.max(right);
+        }
+    }
+
+    let bottom = y;
+    for (i, participant) in sequence.participants.iter().enumerate() {
+        back.line(
+            iced::Point::new(centers[i], box_height),
+            iced::Point::new(centers[i], bottom),
+            &format!(r#"stroke="{}" stroke-width="1""#, colors.line),
+        );
+        for top in [0.0, bottom] {
+            let bounds = iced::Rectangle::new(
+                iced::Point::new(centers[i] - widths[i] / 2.0, top),
+                iced::Size::new(widths[i], box_height),
+            );
+            if participant.actor {
+                draw_actor(&mut back, bounds, &colors);
+                back.text(
+                    &participant.label,
+                    iced::Point::new(centers[i], top + box_height + LINE_HEIGHT / 2.0),
+                    false,
+                    &colors.text,
+                );
+            } else {
+                back.rectangle(
+                    bounds,
+                    3.0,
+                    &format!(
+                        r#"fill="{}" stroke="{}" stroke-width="1.5""#,
+                        colors.fill, colors.border
+                    ),
+                );
+                back.text(&participant.label, bounds.center(), false, &colors.text);
+            }
+        }
+    }
+    back.extend(front);
+    back.finish()
+}
+
+fn message_head(svg: &mut Svg, from: iced::Point, to: iced::Point, head: Head, colors: &Colors) {
+    match head {
+        Head::None => {}
+        Head::Filled => svg.polygon(&arrow(from, to), &format!(r#"fill="{}""#, colors.line)),
+        Head::Open => {
+            let [tip, a, b] = arrow(from, to);
+            svg.path(
+                format!("M {} {} L {} {} L {} {}", a.x, a.y, tip.x, tip.y, b.x, b.y),
+                &[a, tip, b],
+                &format!(r#"fill="none" stroke="{}" stroke-width="1.5""#, colors.line),
+            );
+        }
+        Head::Cross => {
+            let stroke = format!(r#"stroke="{}" stroke-width="2""#, colors.line);
+            let offset = if to.x >= from.x { -8.0 } else { 8.0 };
+            let center = iced::Point::new(to.x + offset, to.y);
+            for (dx, dy) in [(5.0, 5.0), (5.0, -5.0)] {
+                svg.line(
+                    iced::Point::new(center.x - dx, center.y - dy),
+                    iced::Point::new(center.x + dx, center.y + dy),
+                    &stroke,
+                );
+            }
+        }
+    }
+}
+
+/// A stick figure, for the participants that are actors.
+fn draw_actor(svg: &mut Svg, bounds: iced::Rectangle, colors: &Colors) {
+    let x = bounds.center_x();
+    let y = bounds.y;
+    let stroke = format!(
+        r#"fill="none" stroke="{}" stroke-width="1.5""#,
+        colors.border
+    );
+    svg.element(
+        format!(r#"<circle cx="{}" cy="{}" r="6" {}/>"#, x, y + 6.0, stroke),
+        &[
+            iced::Point::new(x - 6.0, y),
+            iced::Point::new(x + 6.0, y + 12.0),
+        ],
+    );
+    let point = iced::Point::new;
+    svg.line(point(x, y + 12.0), point(x, y + 26.0), &stroke);
+    svg.line(
+        point(x - 10.0, y + 17.0),
+        point(x + 10.0, y + 17.0),
+        &stroke,
+    );
+    svg.line(point(x, y + 26.0), point(x - 8.0, y + 36.0), &stroke);
+    svg.line(point(x, y + 26.0), point(x + 8.0, y + 36.0), &stroke);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flowchart_of(code: &str) -> Graph {
+        let lines = code.lines().map(str::trim).filter(|line| !line.is_empty());
+        flowchart(Direction::Down, lines).unwrap()
+    }
+
+    /// The labels of the nodes of `graph`.
+    fn labels(graph: &Graph) -> Vec<&str> {
+        graph.nodes.iter().map(|node| node.label.as_str()).collect()
+    }
+
+    /// The edges of `graph`, by the ids of their nodes, with their labels.
+    fn edges(graph: &Graph) -> Vec<(&str, &str, &str)> {
+        graph
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    graph.nodes[edge.from].id.as_str(),
+                    graph.nodes[edge.to].id.as_str(),
+                    edge.label.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flowchart_nodes_and_edges() {
+        let graph = flowchart_of(
+            "A[Start] --> B{\"Ok?\"} -->|yes| C((End))\nB -. no .-> A; D & E === F\nstyle A fill:#f9f",
+        );
+        assert_eq!(labels(&graph), ["Start", "Ok?", "End", "D", "E", "F"]);
+        let shapes: Vec<Shape> = graph.nodes.iter().map(|node| node.shape).collect();
+        assert!(shapes[..3] == [Shape::Rectangle, Shape::Diamond, Shape::Circle]);
+        assert_eq!(
+            edges(&graph),
+            [
+                ("A", "B", ""),
+                ("B", "C", "yes"),
+                ("B", "A", "no"),
+                ("D", "F", ""),
+                ("E", "F", "")
+            ]
+        );
+        assert!(graph.edges[2].line == Line::Dotted && graph.edges[2].head);
+        assert!(graph.edges[3].line == Line::Thick && !graph.edges[3].head);
+    }
+
+    #[test]
+    fn flowchart_labels() {
+        let graph = flowchart_of("A[\"a [b]\"] -- text --> B>flag]:::class\nC[one<br>two]");
+        assert_eq!(labels(&graph), ["a [b]", "flag", "one\ntwo"]);
+        assert!(graph.nodes[1].shape == Shape::Flag);
+        assert_eq!(edges(&graph), [("A", "B", "text")]);
+    }
+
+    #[test]
+    fn mermaid_errors() {
+        assert_eq!(mermaid("%% nothing").unwrap_err(), "Empty diagram");
+        assert_eq!(
+            mermaid("pie\n").unwrap_err(),
+            "Unsupported diagram type pie"
+        );
+        assert_eq!(mermaid("graph XY").unwrap_err(), "Unknown direction XY");
+        assert_eq!(mermaid("graph\nA[a").unwrap_err(), "Missing ] after A");
+        assert_eq!(mermaid("graph\nA ~~ B").unwrap_err(), "Can't read ~~ B");
+    }
+
+    #[test]
+    fn sequences() {
+        let code = "participant A as Alice\nactor B\nA->>B: Hello\nloop Every day\nB-->>A: Hi\nend\nNote over A,B: Both";
+        let diagram = sequence(code.lines()).unwrap();
+        let participants: Vec<(&str, &str, bool)> = diagram
+            .participants
+            .iter()
+            .map(|p| (p.id.as_str(), p.label.as_str(), p.actor))
+            .collect();
+        assert_eq!(participants, [("A", "Alice", false), ("B", "B", true)]);
+        assert_eq!(diagram.events.len(), 5);
+        assert!(matches!(
+            &diagram.events[2],
+            Event::Message { from: 1, to: 0, text, dashed: true, head: Head::Filled }
+                if text == "Hi"
+        ));
+        assert!(matches!(
+            &diagram.events[4],
+            Event::Note {
+                first: 0,
+                last: 1,
+                side: Side::Over,
+                ..
+            }
+        ));
+
+        assert_eq!(
+            sequence("loop\nA->B: x".lines()).err().unwrap(),
+            "Missing end"
+        );
+        assert_eq!(
+            sequence("Note A: x".lines()).err().unwrap(),
+            "Can't read Note A: x"
+        );
+        assert_eq!(
+            sequence("A => B".lines()).err().unwrap(),
+            "Can't read A => B"
+        );
+    }
+
+    #[test]
+    fn dot_graphs() {
+        let graph = dot(
+            "# comment\nstrict digraph G {\n  rankdir=LR\n  node [shape=box]\n  a -> {b c} [label=\"x}\"]; // edge\n  subgraph s { d [label=<<b>D</b>>] }\n  /* c */ c -- d\n}",
+        )
+        .unwrap();
+        assert!(graph.direction == Direction::Right);
+        assert_eq!(labels(&graph), ["a", "b", "c", "D"]);
+        assert!(graph
+            .nodes
+            .iter()
+            .all(|node| node.shape == Shape::Rectangle));
+        assert_eq!(
+            edges(&graph),
+            [("a", "b", "x}"), ("a", "c", "x}"), ("c", "d", "")]
+        );
+        assert!(graph.edges.iter().all(|edge| edge.head));
+    }
+
+    #[test]
+    fn dot_errors() {
+        assert_eq!(dot("{}").err().unwrap(), "Expected graph or digraph");
+        assert_eq!(dot("graph { a").err().unwrap(), "Missing }");
+        assert_eq!(dot("graph { a [b] }").err().unwrap(), "Missing = after b");
+        assert_eq!(
+            dot("graph { a [label=\"b] }").err().unwrap(),
+            "Unclosed string"
+        );
+        assert_eq!(dot("graph { } x").err().unwrap(), "Unexpected text after }");
+    }
+
+    #[test]
+    fn diagrams_are_drawn() {
+        for (language, code) in [
+            ("mermaid", "graph LR\nA --> B"),
+            ("mermaid", "sequenceDiagram\nA->>B: x"),
+            ("dot", "graph { a -- b }"),
+        ] {
+            let svg = svg(language, code).unwrap();
+            assert!(svg.starts_with("<svg"), "{}", svg);
+        }
+        assert!(render("dot", "nothing").is_err());
+    }
+}
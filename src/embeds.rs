@@ -0,0 +1,190 @@
+use std::sync::LazyLock;
+
+/// A line embedding another note, `![[Note]]`, or a section of it, `![[Note#Section]]`, with
+/// an optional `|alias` after it.
+static EMBED: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"^\s*!\[\[([^\]|#]+)(?:#([^\]|]+))?(?:\|[^\]]*)?\]\]\s*$").unwrap()
+});
+
+/// How many notes deep embedded notes are shown, each within the one before.
+pub const DEPTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Embed {
+    /// The path of the note from the notes folder, or the name of its file, with or without
+    /// its extension.
+    pub name: String,
+    /// The heading of the section to show, rather than the whole note.
+    pub section: Option<String>,
+}
+
+impl std::fmt::Display for Embed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.section {
+            Some(section) => write!(f, "{} › {}", self.name, section),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Reads `line` as an embed, if that's all it holds.
+pub fn embed(line: &str) -> Option<Embed> {
+    let captures = EMBED.captures(line)?;
+    Some(Embed {
+        name: captures[1].trim().to_string(),
+        section: captures
+            .get(2)
+            .map(|section| section.as_str().trim().to_string()),
+    })
+}
+
+/// The section of `text` under the heading `section`, with the heading, up to the next heading
+/// of the same level or above. Headings match by text, ignoring case, or by anchor.
+pub fn section(text: &str, section: &str) -> Option<String> {
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let mut fences = crate::folding::Fences::default();
+    let start = lines.iter().position(|line| {
+        if fences.read(line) || crate::folding::heading_level(line).is_none() {
+            return false;
+        }
+        let (heading, id) = match crate::extended::heading_id(line) {
+            Some((heading, id)) => (heading, Some(id)),
+            None => (line.as_str(), None),
+        };
+        let heading = heading.trim().trim_start_matches('#').trim();
+        heading.to_lowercase() == section.to_lowercase()
+            || id == Some(section)
+            || crate::extended::slug(heading) == section
+    })?;
+    let end = crate::folding::range(&lines, start, |_| false).unwrap_or(start);
+    Some(lines[start..=end].join("\n"))
+}
+
+/// Finds the note `name` in the notes folder at `root` and reads it, with its path.
+pub async fn load(
+    root: std::path::PathBuf,
+    name: String,
+    extension: String,
+) -> Result<(std::path::PathBuf, String), String> {
+    let with_extension = format!("{}.{}", name, extension);
+    let mut direct = None;
+    for path in [root.join(&name), root.join(&with_extension)] {
+        if is(&path, std::fs::Metadata::is_file).await {
+            direct = Some(path);
+            break;
+        }
+    }
+    let path = match direct {
+        Some(path) => path,
+        None => find(root, name.to_lowercase(), with_extension.to_lowercase())
+            .await
+            .ok_or_else(|| format!("No note named {}", name))?,
+    };
+    let text = crate::util::read_file(path.clone())
+        .await
+        .map_err(|e| format!("Can't read {}: {:?}", name, e))?;
+    Ok((path, text))
+}
+
+/// The first file under `directory` named `name` or `with_extension`, in lowercase. Hidden
+/// folders are skipped.
+async fn find(
+    directory: std::path::PathBuf,
+    name: String,
+    with_extension: String,
+) -> Option<std::path::PathBuf> {
+    let mut entries = tokio::fs::read_dir(&directory).await.ok()?;
+    let mut directories = vec![];
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_lowercase();
+        if is(&path, std::fs::Metadata::is_dir).await {
+            if !file_name.starts_with('.') {
+                directories.push(path);
+            }
+        } else if file_name == name || file_name == with_extension {
+            return Some(path);
+        }
+    }
+    for child in directories {
+        if let Some(path) = Box::pin(find(child, name.clone(), with_extension.clone())).await {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Whether there's something at `path` and it's `kind`, following links.
+async fn is(path: &std::path::Path, kind: fn(&std::fs::Metadata) -> bool) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| kind(&metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_take_a_whole_line() {
+        assert_eq!(
+            embed("  ![[Folder/Note#Some Section|alias]] "),
+            Some(Embed {
+                name: "Folder/Note".into(),
+                section: Some("Some Section".into()),
+            })
+        );
+        assert_eq!(embed("![[Note]]").unwrap().section, None);
+        assert_eq!(embed("see ![[Note]]"), None);
+        assert_eq!(embed("[[Note]]"), None);
+    }
+
+    #[test]
+    fn sections_end_at_a_heading_as_high() {
+        let text = "# Note\n\n## One\ntext\n### Deeper\nmore\n## Two\nlast";
+        assert_eq!(
+            section(text, "one").as_deref(),
+            Some("## One\ntext\n### Deeper\nmore")
+        );
+        assert_eq!(section(text, "Two").as_deref(), Some("## Two\nlast"));
+        assert_eq!(section(text, "Missing"), None);
+    }
+
+    #[test]
+    fn sections_match_anchors_and_skip_code() {
+        let text = "```\n## Code\n```\n## Some Heading {#custom}\nbody\n## Code\nreal";
+        assert_eq!(
+            section(text, "custom").as_deref(),
+            Some("## Some Heading {#custom}\nbody")
+        );
+        assert_eq!(
+            section(text, "some-heading").as_deref(),
+            Some("## Some Heading {#custom}\nbody")
+        );
+        assert_eq!(section(text, "code").as_deref(), Some("## Code\nreal"));
+    }
+
+    #[test]
+    fn notes_are_found_under_the_root() {
+        let root = std::env::temp_dir().join(format!("darkstone-embeds-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::create_dir_all(root.join(".hidden")).unwrap();
+        std::fs::write(root.join("sub/Deep.md"), "deep").unwrap();
+        std::fs::write(root.join(".hidden/Secret.md"), "secret").unwrap();
+        std::fs::write(root.join("Top.md"), "top").unwrap();
+
+        let load = |name: &str| {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(load(root.clone(), name.into(), "md".into()))
+        };
+        let top = load("Top");
+        let deep = load("deep");
+        let hidden = load("Secret");
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(top, Ok((root.join("Top.md"), "top".into())));
+        assert_eq!(deep, Ok((root.join("sub/Deep.md"), "deep".into())));
+        assert!(hidden.is_err());
+    }
+}
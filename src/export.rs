@@ -0,0 +1,971 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// A link to another note, `[[Note]]`, to a section of it, `[[Note#Section]]`, or to a
+/// section of this note, `[[#Section]]`, with an optional `|text` to show.
+static WIKI_LINK: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(!?)\[\[([^\]|#]*)(?:#([^\]|]+))?(?:\|([^\]]+))?\]\]").unwrap()
+});
+
+/// Where an image goes in the text given to the Markdown parser, as
+/// [`crate::preview::placeholder`] gives where formulas go.
+const IMAGE: char = '\u{E008}';
+/// The size of text in the pages, in pixels, which formulas are drawn for.
+const TEXT_SIZE: f32 = 16.0;
+
+/// Exports the note at `path` to its page in the export folder, and returns the page.
+/// `notes` are all the notes, with their names, which links and embeds can lead to.
+pub async fn export_note(
+    notes: Vec<(PathBuf, String)>,
+    path: PathBuf,
+    config: crate::config::Configuration,
+) -> Result<PathBuf, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut exporter = Exporter::new(notes, &config);
+        exporter.written = HashSet::from([path.clone()]);
+        exporter.export(&path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Exports the notes under `folder`, and a page listing them which is returned.
+pub async fn export_folder(
+    notes: Vec<(PathBuf, String)>,
+    folder: PathBuf,
+    config: crate::config::Configuration,
+) -> Result<PathBuf, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut exporter = Exporter::new(notes, &config);
+        exporter.written = exporter
+            .notes
+            .iter()
+            .map(|(path, _)| path)
+            .filter(|path| path.starts_with(&folder))
+            .cloned()
+            .collect();
+        // A note named `index` keeps its page.
+        let mut index = exporter.page(&folder.join("index"));
+        let mut n = 1;
+        while exporter
+            .written
+            .iter()
+            .any(|path| exporter.page(path) == index)
+        {
+            n += 1;
+            index = exporter.page(&folder.join(format!("index-{}", n)));
+        }
+        let folder_page = index
+            .parent()
+            .unwrap_or(&exporter.export_path)
+            .to_path_buf();
+        let mut list = String::new();
+        for (path, name) in exporter
+            .notes
+            .iter()
+            .filter(|(path, _)| exporter.written.contains(path))
+        {
+            // A note that can't be read is left out rather than failing the others.
+            match exporter.export(path) {
+                Ok(page) => list.push_str(&format!(
+                    r#"<li><a href="{}">{}</a></li>"#,
+                    relative(&folder_page, &page),
+                    crate::util::escape(title(Path::new(name)))
+                )),
+                Err(error) => eprintln!("Failed to export {:?}: {}", path, error),
+            }
+        }
+        let name = folder
+            .file_name()
+            .map_or("Notes".into(), |name| name.to_string_lossy().to_string());
+        let body = format!(
+            "<h1>{}</h1>\n<ul class=\"index\">{}</ul>",
+            crate::util::escape(&name),
+            list
+        );
+        write(&index, &name, "", &body)?;
+        Ok(index)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// What the pages of an export share.
+pub struct Exporter {
+    notes_path: PathBuf,
+    /// The folder whose notes' pages go straight in `export_path`.
+    pub root: PathBuf,
+    pub export_path: PathBuf,
+    extension: String,
+    pub notes: Vec<(PathBuf, String)>,
+    /// The notes whose pages are written, which links to notes lead to. Links to the other
+    /// notes are made like links to other files.
+    pub written: HashSet<PathBuf>,
+    /// Whether links to other files lead to them on this computer, which pages published
+    /// elsewhere can't do.
+    pub file_links: bool,
+}
+
+impl Exporter {
+    pub fn new(notes: Vec<(PathBuf, String)>, config: &crate::config::Configuration) -> Self {
+        Self {
+            notes_path: config.notes_path.clone(),
+            root: config.notes_path.clone(),
+            export_path: config.export_path.clone(),
+            extension: config.default_extension.clone(),
+            written: notes.iter().map(|(path, _)| path.clone()).collect(),
+            notes,
+            file_links: true,
+        }
+    }
+
+    /// Writes the page of the note at `path`.
+    fn export(&self, path: &Path) -> Result<PathBuf, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{:?}", e.kind()))?;
+        let page = self.page(path);
+        let (body, _) = self.body(path, &text);
+        write(&page, title(path), "", &body)?;
+        Ok(page)
+    }
+
+    /// The HTML of the page of the note at `path`, holding `text`, under its table of
+    /// contents, and the notes it links to.
+    pub fn body(&self, path: &Path, text: &str) -> (String, Vec<PathBuf>) {
+        let page = self.page(path);
+        let mut writer = Writer {
+            exporter: self,
+            folder: page.parent().unwrap_or(&self.export_path).to_path_buf(),
+            shown: vec![(path.to_path_buf(), None)],
+            embeds: 0,
+            links: vec![],
+        };
+        let (body, outline) = writer.note(path, text);
+        let toc = if outline.len() > 1 {
+            let entries: String = outline
+                .iter()
+                .map(|heading| {
+                    format!(
+                        r##"<li class="toc-{}"><a href="#{}">{}</a></li>"##,
+                        heading.level,
+                        crate::util::escape(&heading.anchor),
+                        crate::util::escape(&heading.text)
+                    )
+                })
+                .collect();
+            format!("<nav class=\"toc\"><ul>{}</ul></nav>\n", entries)
+        } else {
+            String::new()
+        };
+        (format!("{}{}", toc, body), writer.links)
+    }
+
+    /// Where the page of the note at `path` goes: under the export folder, in the folders it's
+    /// in under the root folder.
+    pub fn page(&self, path: &Path) -> PathBuf {
+        let relative = path
+            .strip_prefix(&self.root)
+            .ok()
+            .or(path.file_name().map(Path::new))
+            .unwrap_or(path);
+        self.export_path.join(relative).with_extension("html")
+    }
+
+    /// The note called `name` in a link or an embed: its path from the notes folder, or the
+    /// name of its file, with or without the extension.
+    fn find(&self, name: &str) -> Option<&Path> {
+        let with_extension = format!("{}.{}", name, self.extension);
+        let direct = [
+            self.notes_path.join(name),
+            self.notes_path.join(&with_extension),
+        ];
+        let (name, with_extension) = (name.to_lowercase(), with_extension.to_lowercase());
+        self.notes
+            .iter()
+            .find(|(path, _)| direct.contains(path))
+            .or_else(|| {
+                self.notes.iter().find(|(path, _)| {
+                    path.file_name().is_some_and(|file| {
+                        let file = file.to_string_lossy().to_lowercase();
+                        file == name || file == with_extension
+                    })
+                })
+            })
+            .map(|(path, _)| path.as_path())
+    }
+
+    fn is_written(&self, path: &Path) -> bool {
+        self.written.contains(path)
+    }
+}
+
+/// Writes the HTML of a note for its page, with the notes embedded in it.
+struct Writer<'a> {
+    exporter: &'a Exporter,
+    /// The folder of the page, which links are relative to.
+    folder: PathBuf,
+    /// The notes being written, with their section: the page's note, then the ones
+    /// embedded in it.
+    shown: Vec<(PathBuf, Option<String>)>,
+    /// How many notes were embedded, to keep the ids of their footnotes apart.
+    embeds: usize,
+    /// The notes the page's note links to or embeds.
+    links: Vec<PathBuf>,
+}
+
+/// A note being written.
+struct Note<'a> {
+    path: &'a Path,
+    /// Put before the ids of its footnotes.
+    prefix: String,
+    /// The ids of its footnotes, in the order they're numbered.
+    footnotes: Vec<String>,
+    /// The footnotes whose first reference was written.
+    referenced: HashSet<String>,
+    /// The formulas of the Markdown being written, by the index of their placeholders.
+    formulas: Vec<String>,
+    /// Its images, in the order of their placeholders.
+    images: VecDeque<String>,
+    /// The id of the footnote reference being read.
+    reference: Option<String>,
+    /// Its link reference definitions, for the links of every block.
+    definitions: String,
+}
+
+impl Writer<'_> {
+    /// The HTML of the note at `path`, holding `text`, and its headings.
+    fn note(&mut self, path: &Path, text: &str) -> (String, Vec<crate::preview::Heading>) {
+        let mut preview = crate::preview::Preview::new(path);
+        preview.rebuild(&iced::widget::text_editor::Content::with_text(text));
+        let outline = preview.outline();
+        let prefix = if self.shown.len() > 1 {
+            self.embeds += 1;
+            format!("embed-{}-", self.embeds)
+        } else {
+            String::new()
+        };
+        let mut note = Note {
+            path,
+            prefix,
+            footnotes: preview
+                .footnotes()
+                .iter()
+                .map(|footnote| footnote.id.clone())
+                .collect(),
+            referenced: HashSet::new(),
+            formulas: vec![],
+            images: VecDeque::new(),
+            reference: None,
+            definitions: preview.definitions().to_string(),
+        };
+
+        let mut html = String::new();
+        let mut footnotes = std::collections::HashMap::new();
+        for (lines, source, rendered) in preview.sources() {
+            // Headings are given the anchors of the outline, as in the preview.
+            let mut anchors: VecDeque<String> = outline
+                .iter()
+                .filter(|heading| lines.contains(&heading.line))
+                .map(|heading| heading.anchor.clone())
+                .collect();
+            match rendered {
+                crate::preview::Rendered::Callout(_) => {
+                    if let Some(callout) = crate::extended::callout(source) {
+                        let kind = callout.kind.name().to_lowercase();
+                        html.push_str(&format!(
+                            "<div class=\"callout callout-{}\"><p class=\"callout-title\">{} {}</p>\n{}</div>\n",
+                            kind,
+                            callout.kind.icon(),
+                            crate::util::escape(&callout.title),
+                            self.markdown(&callout.body, &mut note, &mut VecDeque::new())
+                        ));
+                    }
+                }
+                crate::preview::Rendered::Definitions(_) => {
+                    html.push_str("<dl>\n");
+                    for definition in crate::extended::definitions(source).unwrap_or_default() {
+                        let term = self.markdown(&definition.term, &mut note, &mut VecDeque::new());
+                        html.push_str(&format!("<dt>{}</dt>\n", unwrap_paragraph(&term)));
+                        for detail in &definition.details {
+                            let detail = self.markdown(detail, &mut note, &mut VecDeque::new());
+                            html.push_str(&format!("<dd>{}</dd>\n", unwrap_paragraph(&detail)));
+                        }
+                    }
+                    html.push_str("</dl>\n");
+                }
+                // Footnotes are gathered at the end.
+                crate::preview::Rendered::Footnotes(_) => {
+                    for footnote in crate::extended::footnotes(source).unwrap_or_default() {
+                        footnotes.entry(footnote.id).or_insert(footnote.text);
+                    }
+                }
+                crate::preview::Rendered::Segments(_) => {
+                    let mut run = String::new();
+                    let mut blocks = crate::folding::Code::default();
+                    for line in source.lines() {
+                        let code = blocks.read(line);
+                        match crate::embeds::embed(line).filter(|_| !code) {
+                            Some(embed) => {
+                                html.push_str(&self.markdown(&run, &mut note, &mut anchors));
+                                run.clear();
+                                html.push_str(&self.embed(&embed));
+                            }
+                            None => {
+                                run.push_str(line);
+                                run.push('\n');
+                            }
+                        }
+                    }
+                    html.push_str(&self.markdown(&run, &mut note, &mut anchors));
+                }
+                _ => html.push_str(&self.markdown(source, &mut note, &mut anchors)),
+            }
+        }
+
+        if !note.footnotes.is_empty() {
+            html.push_str("<section class=\"footnotes\">\n<ol>\n");
+            for (i, id) in note.footnotes.clone().iter().enumerate() {
+                let text = footnotes.get(id).map_or("", String::as_str);
+                let mut text = self.markdown(text, &mut note, &mut VecDeque::new());
+                if preview.is_referenced(id) {
+                    let back = format!(
+                        r##" <a href="#{}fnref-{}" class="back">↩</a>"##,
+                        note.prefix,
+                        i + 1
+                    );
+                    match text.rfind("</p>") {
+                        Some(end) => text.insert_str(end, &back),
+                        None => text.push_str(&back),
+                    }
+                }
+                html.push_str(&format!(
+                    "<li id=\"{}fn-{}\">{}</li>\n",
+                    note.prefix,
+                    i + 1,
+                    text
+                ));
+            }
+            html.push_str("</ol>\n</section>\n");
+        }
+        (html, outline)
+    }
+
+    /// A note embedded in the one being written, framed, under its name which links to its
+    /// page. A note embedded in itself, or nested too deep, only shows its name.
+    fn embed(&mut self, embed: &crate::embeds::Embed) -> String {
+        let name = crate::util::escape(&embed.to_string());
+        let Some(path) = self.exporter.find(&embed.name) else {
+            return format!(
+                "<div class=\"embed\"><div class=\"embed-title\">{}</div><p class=\"error\">No note named {}</p></div>\n",
+                name,
+                crate::util::escape(&embed.name)
+            );
+        };
+        let note = (path.to_path_buf(), embed.section.clone());
+        self.link_to([path.to_path_buf()]);
+        let body = if self.shown.contains(&note) {
+            "<p class=\"faint\">Embedded in itself</p>".to_string()
+        } else if self.shown.len() > crate::embeds::DEPTH {
+            "<p class=\"faint\">Nested too deep</p>".to_string()
+        } else {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Can't read {}: {:?}", embed.name, e.kind()))
+                .and_then(|text| match &embed.section {
+                    Some(section) => crate::embeds::section(&text, section)
+                        .ok_or_else(|| format!("No section {} in {}", section, embed.name)),
+                    None => Ok(text),
+                });
+            match text {
+                Ok(text) => {
+                    self.shown.push(note);
+                    let (body, _) = self.note(path, &text);
+                    self.shown.pop();
+                    body
+                }
+                Err(error) => format!("<p class=\"error\">{}</p>", crate::util::escape(&error)),
+            }
+        };
+        format!(
+            "<div class=\"embed\"><div class=\"embed-title\"><a href=\"{}\">{}</a></div>\n{}</div>\n",
+            self.href(path, embed.section.as_deref()),
+            name,
+            body
+        )
+    }
+
+    /// The HTML of the Markdown `source` of `note`, giving its headings the `anchors`, in
+    /// order.
+    fn markdown(
+        &mut self,
+        source: &str,
+        note: &mut Note,
+        anchors: &mut VecDeque<String>,
+    ) -> String {
+        use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+
+        if source.trim().is_empty() {
+            return String::new();
+        }
+        // Images and wiki links are rewritten line by line, outside code.
+        let mut text = String::new();
+        let mut code = crate::folding::Code::default();
+        for line in source.lines() {
+            if code.read(line) {
+                text.push_str(line);
+            } else {
+                text.push_str(&self.wiki_links(&self.images(line, note)));
+            }
+            text.push('\n');
+        }
+        // Then formulas are taken out and the extended syntax rewritten, as in the preview.
+        let (text, formulas) = crate::preview::take_formulas(&text);
+        note.formulas = formulas
+            .into_iter()
+            .map(|(formula, display)| match crate::math::parse(formula) {
+                Ok(node) => {
+                    let svg = crate::math::svg(&node, TEXT_SIZE, display);
+                    if display {
+                        format!("<span class=\"math-display\">{}</span>", svg)
+                    } else {
+                        svg
+                    }
+                }
+                Err(error) => {
+                    let delimiter = if display { "$$" } else { "$" };
+                    format!(
+                        "<code class=\"error\" title=\"{}\">{}</code>",
+                        crate::util::escape(&error),
+                        crate::util::escape(&format!("{0}{1}{0}", delimiter, formula))
+                    )
+                }
+            })
+            .collect();
+        let mut text = crate::extended::inline(&text).unwrap_or(text);
+        text.push('\n');
+        text.push_str(&note.definitions);
+
+        let mut options = pulldown_cmark::Options::empty();
+        options.insert(pulldown_cmark::Options::ENABLE_TABLES);
+        options.insert(pulldown_cmark::Options::ENABLE_STRIKETHROUGH);
+        options.insert(pulldown_cmark::Options::ENABLE_TASKLISTS);
+        options.insert(pulldown_cmark::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+        let mut events = vec![];
+        let mut code: Option<(String, String)> = None;
+        let mut front_matter = false;
+        for event in pulldown_cmark::Parser::new_ext(&text, options) {
+            match (event, &mut code) {
+                // Front matter is left out of the page.
+                (Event::Start(Tag::MetadataBlock(_)), _) => front_matter = true,
+                (Event::End(TagEnd::MetadataBlock(_)), _) => front_matter = false,
+                _ if front_matter => {}
+                (Event::Start(Tag::CodeBlock(kind)), _) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(info) => info
+                            .split(|c: char| c.is_whitespace() || c == ',')
+                            .next()
+                            .unwrap_or("")
+                            .to_string(),
+                        CodeBlockKind::Indented => String::new(),
+                    };
+                    code = Some((language, String::new()));
+                }
+                (Event::Text(text), Some((_, code))) => code.push_str(&text),
+                (Event::End(TagEnd::CodeBlock), _) => {
+                    if let Some((language, code)) = code.take() {
+                        events.push(Event::Html(
+                            format!("{}\n", code_block(&language, &code)).into(),
+                        ));
+                    }
+                }
+                (
+                    Event::Start(Tag::Heading {
+                        level,
+                        classes,
+                        attrs,
+                        ..
+                    }),
+                    _,
+                ) => events.push(Event::Start(Tag::Heading {
+                    level,
+                    id: anchors.pop_front().map(Into::into),
+                    classes,
+                    attrs,
+                })),
+                (
+                    Event::Start(Tag::Link {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    }),
+                    _,
+                ) => events.push(Event::Start(Tag::Link {
+                    link_type,
+                    dest_url: self.link(&dest_url, note.path).into(),
+                    title,
+                    id,
+                })),
+                (Event::Text(text), None) => {
+                    events.push(Event::InlineHtml(self.text(&text, note).into()))
+                }
+                (event, _) => events.push(event),
+            }
+        }
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, events.into_iter());
+        html
+    }
+
+    /// The HTML of text read by the Markdown parser, with its placeholders and the markers of
+    /// its extended syntax replaced.
+    fn text(&self, text: &str, note: &mut Note) -> String {
+        use crate::extended::{FOOTNOTE_REFERENCE, MARK, SUBSCRIPT, SUPERSCRIPT};
+
+        let mut html = String::new();
+        let mut plain = String::new();
+        for c in text.chars() {
+            let tag = match c {
+                c if note.reference.is_some() && c != FOOTNOTE_REFERENCE.1 => {
+                    note.reference.iter_mut().for_each(|id| id.push(c));
+                    continue;
+                }
+                c if c == MARK.0 => "<mark>".to_string(),
+                c if c == MARK.1 => "</mark>".to_string(),
+                c if c == SUPERSCRIPT.0 => "<sup>".to_string(),
+                c if c == SUPERSCRIPT.1 => "</sup>".to_string(),
+                c if c == SUBSCRIPT.0 => "<sub>".to_string(),
+                c if c == SUBSCRIPT.1 => "</sub>".to_string(),
+                c if c == FOOTNOTE_REFERENCE.0 => {
+                    note.reference = Some(String::new());
+                    String::new()
+                }
+                c if c == FOOTNOTE_REFERENCE.1 => {
+                    let id = note.reference.take().unwrap_or_default();
+                    match note.footnotes.iter().position(|footnote| *footnote == id) {
+                        Some(i) => {
+                            // The first reference is the one footnotes link back to.
+                            let anchor = if note.referenced.insert(id) {
+                                format!(" id=\"{}fnref-{}\"", note.prefix, i + 1)
+                            } else {
+                                String::new()
+                            };
+                            format!(
+                                "<sup class=\"footnote-ref\"><a href=\"#{}fn-{}\"{}>{}</a></sup>",
+                                note.prefix,
+                                i + 1,
+                                anchor,
+                                i + 1
+                            )
+                        }
+                        None => crate::util::escape(&format!("[^{}]", id)),
+                    }
+                }
+                IMAGE => note.images.pop_front().unwrap_or_default(),
+                c if crate::preview::formula(c).is_some() => crate::preview::formula(c)
+                    .and_then(|index| note.formulas.get(index).cloned())
+                    .unwrap_or_default(),
+                c => {
+                    plain.push(c);
+                    continue;
+                }
+            };
+            html.push_str(&crate::util::escape(&std::mem::take(&mut plain)));
+            html.push_str(&tag);
+        }
+        html.push_str(&crate::util::escape(&plain));
+        html
+    }
+
+    /// `line` with its images taken out for [`IMAGE`] placeholders, their tags kept in
+    /// `note`. Local images are inlined.
+    fn images(&self, line: &str, note: &mut Note) -> String {
+        let mut rewritten = String::new();
+        let mut end = 0;
+        for (range, image) in crate::preview::images(line) {
+            rewritten.push_str(&line[end..range.start]);
+            rewritten.push(IMAGE);
+            end = range.end;
+
+            let source = match crate::attachments::resolve(note.path, &image.source) {
+                Some(file) => match std::fs::read(&file) {
+                    Ok(bytes) => format!("data:{};base64,{}", mime(&file), {
+                        use base64::Engine;
+                        base64::engine::general_purpose::STANDARD.encode(bytes)
+                    }),
+                    Err(_) => {
+                        note.images.push_back(format!(
+                            "<span class=\"error\">{}</span>",
+                            crate::util::escape(&image.source)
+                        ));
+                        continue;
+                    }
+                },
+                None => image.source.clone(),
+            };
+            let size = |name: &str, size: Option<f32>| {
+                size.map_or(String::new(), |size| format!(" {}=\"{}\"", name, size))
+            };
+            note.images.push_back(format!(
+                "<img src=\"{}\" alt=\"{}\"{}{}>",
+                crate::util::escape(&source),
+                crate::util::escape(&image.alt),
+                size("width", image.width),
+                size("height", image.height)
+            ));
+        }
+        rewritten.push_str(&line[end..]);
+        rewritten
+    }
+
+    /// `line` with its wiki links made Markdown links to the pages of their notes. Links to
+    /// notes without a page lead to their files, if links to files are made, and links to
+    /// notes that can't be found are left as text.
+    fn wiki_links(&mut self, line: &str) -> String {
+        // Code spans are left as they are.
+        let mut rewritten = String::new();
+        let mut links = vec![];
+        for (i, part) in line.split('`').enumerate() {
+            if i > 0 {
+                rewritten.push('`');
+            }
+            if i % 2 == 1 {
+                rewritten.push_str(part);
+                continue;
+            }
+            rewritten.push_str(&WIKI_LINK.replace_all(part, |link: &regex::Captures| {
+                let name = link[2].trim();
+                let section = link.get(3).map(|section| section.as_str().trim());
+                let text = link.get(4).map_or(
+                    match section {
+                        Some(section) if name.is_empty() => section,
+                        _ => name,
+                    },
+                    |text| text.as_str().trim(),
+                );
+                if !link[1].is_empty() {
+                    return link[0].to_string();
+                }
+                let href = if name.is_empty() {
+                    format!("#{}", crate::extended::slug(section.unwrap_or("")))
+                } else {
+                    match self.exporter.find(name) {
+                        Some(path) if self.exporter.is_written(path) => {
+                            links.push(path.to_path_buf());
+                            format!("page:{}", self.href(path, section))
+                        }
+                        Some(path) if self.exporter.file_links => {
+                            match url::Url::from_file_path(path) {
+                                Ok(url) => url.to_string(),
+                                Err(()) => return text.to_string(),
+                            }
+                        }
+                        _ => return text.to_string(),
+                    }
+                };
+                format!("[{}](<{}>)", text, href)
+            }));
+        }
+        self.link_to(links);
+        rewritten
+    }
+
+    /// Where a link of the note at `path` leads on its page: links to notes whose pages are
+    /// written lead to their pages, links to other files to the files themselves.
+    fn link(&mut self, destination: &str, path: &Path) -> String {
+        if let Some(anchor) = destination.strip_prefix("anchor:") {
+            return format!("#{}", anchor);
+        }
+        if let Some(page) = destination.strip_prefix("page:") {
+            return page.to_string();
+        }
+        if destination.starts_with('#') || url::Url::parse(destination).is_ok() {
+            return destination.to_string();
+        }
+        let (file, section) = match destination.split_once('#') {
+            Some((file, section)) => (file, Some(section)),
+            None => (destination, None),
+        };
+        let Some(file) = crate::attachments::resolve(path, file) else {
+            return destination.to_string();
+        };
+        if self.exporter.is_written(&file) {
+            let page = relative(&self.folder, &self.exporter.page(&file));
+            self.link_to([file]);
+            match section {
+                Some(section) => format!("{}#{}", page, section),
+                None => page,
+            }
+        } else if self.exporter.file_links {
+            url::Url::from_file_path(&file).map_or(destination.to_string(), |url| url.to_string())
+        } else {
+            destination.to_string()
+        }
+    }
+
+    /// Keeps the notes the page's own note links to. Those of embedded notes don't count.
+    fn link_to(&mut self, notes: impl IntoIterator<Item = PathBuf>) {
+        if self.shown.len() == 1 {
+            self.links.extend(notes);
+        }
+    }
+
+    /// The link to the page of the note at `path`, at the heading `section`.
+    fn href(&self, path: &Path, section: Option<&str>) -> String {
+        let page = relative(&self.folder, &self.exporter.page(path));
+        match section {
+            Some(section) => format!("{}#{}", page, crate::extended::slug(section)),
+            None => page,
+        }
+    }
+}
+
+/// A code block, highlighted like in the preview, or drawn if it's a diagram.
+fn code_block(language: &str, code: &str) -> String {
+    let code = code.strip_suffix('\n').unwrap_or(code);
+    if crate::diagrams::is_diagram(language) {
+        match crate::diagrams::svg(language, code) {
+            Ok(svg) => return format!("<div class=\"diagram\">{}</div>", svg),
+            Err(error) => {
+                return format!(
+                    "{}<p class=\"error\">{}</p>",
+                    code_block("", code),
+                    crate::util::escape(&error)
+                )
+            }
+        }
+    }
+    let mut html = String::from("<pre><code>");
+    for (line, tokens) in code
+        .lines()
+        .zip(crate::highlighter::highlight_code(language, code))
+    {
+        for (range, token) in tokens {
+            let text = crate::util::escape(line.get(range).unwrap_or(""));
+            match crate::highlighter::token_color(token, &crate::util::THEME) {
+                Some(color) => html.push_str(&format!(
+                    "<span style=\"color: {}\">{}</span>",
+                    css(color),
+                    text
+                )),
+                None => html.push_str(&text),
+            }
+        }
+        html.push('\n');
+    }
+    html.push_str("</code></pre>");
+    html
+}
+
+/// `html` without the paragraph around it, for a term or definition that's only a line.
+fn unwrap_paragraph(html: &str) -> &str {
+    let trimmed = html.trim();
+    match trimmed
+        .strip_prefix("<p>")
+        .and_then(|inner| inner.strip_suffix("</p>"))
+    {
+        Some(inner) if !inner.contains("<p>") => inner,
+        _ => trimmed,
+    }
+}
+
+/// The name of the note at `path`, without its extension.
+pub fn title(path: &Path) -> &str {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("Note")
+}
+
+/// The URL of `to` relative to the `from` folder.
+pub fn relative(from: &Path, to: &Path) -> String {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|part| {
+        part.as_os_str()
+            .to_string_lossy()
+            .replace('%', "%25")
+            .replace(' ', "%20")
+            .replace('#', "%23")
+            .replace('?', "%3F")
+    }));
+    parts.join("/")
+}
+
+fn mime(file: &Path) -> &'static str {
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("bmp") => "image/bmp",
+        Some("svg") => "image/svg+xml",
+        _ => "image/png",
+    }
+}
+
+fn css(color: iced::Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+    format!("rgba({}, {}, {}, {:.2})", r, g, b, color.a)
+}
+
+/// Writes a page with `title` around `body`, styled after the theme of the app, with `aside`
+/// in a sidebar unless it's empty.
+pub fn write(page: &Path, title: &str, aside: &str, body: &str) -> Result<(), String> {
+    let palette = crate::util::THEME.extended_palette();
+    let mut style = format!(
+        "body {{ margin: 0; background: {background}; color: {text}; font: 16px/1.6 system-ui, -apple-system, \"Segoe UI\", Helvetica, Arial, sans-serif; }}
+main {{ max-width: 48rem; margin: 0 auto; padding: 2rem 1.5rem 4rem; }}
+a {{ color: {primary}; }}
+h1, h2, h3, h4, h5, h6 {{ line-height: 1.25; margin: 1.5em 0 0.5em; }}
+code, pre {{ font-family: ui-monospace, Menlo, Consolas, \"DejaVu Sans Mono\", monospace; font-size: 0.9em; }}
+code {{ background: {weak}; padding: 0.1em 0.3em; border-radius: 3px; }}
+pre {{ background: {weak}; padding: 0.75rem 1rem; border-radius: 4px; overflow-x: auto; }}
+pre code {{ background: none; padding: 0; }}
+blockquote {{ margin: 1em 0; padding: 0 1em; border-left: 3px solid {strong}; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid {strong}; padding: 0.3em 0.6em; }}
+hr {{ border: none; border-top: 1px solid {strong}; }}
+img {{ max-width: 100%; }}
+mark {{ background: {highlight}; color: inherit; border-radius: 2px; }}
+li > input[type=checkbox] {{ margin-right: 0.5em; }}
+dt {{ font-weight: bold; }}
+dd {{ margin: 0.25em 0 0.75em 1.5em; }}
+.toc {{ border: 1px solid {strong}; border-radius: 4px; padding: 0.5rem 1rem; margin-bottom: 2rem; }}
+.toc ul {{ list-style: none; margin: 0; padding: 0; }}
+.toc-2 {{ padding-left: 1em; }} .toc-3 {{ padding-left: 2em; }} .toc-4 {{ padding-left: 3em; }}
+.toc-5 {{ padding-left: 4em; }} .toc-6 {{ padding-left: 5em; }}
+.callout {{ border: 1px solid; border-radius: 4px; padding: 0.25rem 1rem; margin: 1em 0; }}
+.callout-title {{ font-weight: bold; }}
+.embed {{ border: 1px solid {strong}; border-radius: 4px; padding: 0.5rem 1rem; margin: 1em 0; }}
+.embed-title {{ font-size: 0.85em; }}
+.footnotes {{ border-top: 1px solid {strong}; margin-top: 2rem; font-size: 0.9em; }}
+.footnote-ref a, .back {{ text-decoration: none; }}
+.diagram, .math-display {{ display: block; text-align: center; margin: 1em 0; overflow-x: auto; }}
+.error {{ color: {danger}; }}
+.faint {{ opacity: 0.6; }}
+.site {{ display: flex; }}
+.site main {{ flex: 1; min-width: 0; }}
+aside {{ width: 16rem; flex-shrink: 0; box-sizing: border-box; height: 100vh; position: sticky; top: 0; overflow-y: auto; padding: 1.5rem 1rem; border-right: 1px solid {strong}; font-size: 0.9em; }}
+aside ul {{ list-style: none; margin: 0; padding-left: 1em; }}
+aside > nav > ul {{ padding-left: 0; }}
+aside a {{ text-decoration: none; }}
+aside .current {{ font-weight: bold; }}
+aside .folder {{ opacity: 0.7; }}
+aside input {{ width: 100%; box-sizing: border-box; margin-bottom: 1rem; padding: 0.3em 0.5em; background: {weak}; color: inherit; border: 1px solid {strong}; border-radius: 4px; }}
+.tags a {{ background: {weak}; border-radius: 3px; padding: 0.1em 0.4em; margin-right: 0.3em; text-decoration: none; font-size: 0.9em; }}
+.backlinks {{ border-top: 1px solid {strong}; margin-top: 2rem; font-size: 0.9em; }}
+",
+        background = css(palette.background.base.color),
+        text = css(palette.background.base.text),
+        primary = css(palette.primary.base.color),
+        weak = css(palette.background.weak.color),
+        strong = css(palette.background.strong.color),
+        highlight = css(crate::extended::highlight()),
+        danger = css(palette.danger.base.color),
+    );
+    for kind in [
+        crate::extended::Kind::Note,
+        crate::extended::Kind::Tip,
+        crate::extended::Kind::Important,
+        crate::extended::Kind::Warning,
+        crate::extended::Kind::Caution,
+    ] {
+        let color = kind.color();
+        style.push_str(&format!(
+            ".callout-{name} {{ border-color: {color}; background: {background}; }}\n.callout-{name} .callout-title {{ color: {color}; }}\n",
+            name = kind.name().to_lowercase(),
+            color = css(color),
+            background = css(iced::Color { a: 0.1, ..color }),
+        ));
+    }
+    let body = if aside.is_empty() {
+        format!("<body>\n<main>\n{}</main>\n", body)
+    } else {
+        format!(
+            "<body class=\"site\">\n<aside>\n{}</aside>\n<main>\n{}</main>\n",
+            aside, body
+        )
+    };
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n{}</body>\n</html>\n",
+        crate::util::escape(title),
+        style,
+        body
+    );
+    if let Some(parent) = page.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("{:?}", e.kind()))?;
+    }
+    std::fs::write(page, html).map_err(|e| format!("{:?}", e.kind()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(notes: &Path, export: &Path) -> crate::config::Configuration {
+        crate::config::Configuration {
+            notes_path: notes.to_path_buf(),
+            export_path: export.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    fn named(notes: &Path, names: &[&str]) -> Vec<(PathBuf, String)> {
+        names
+            .iter()
+            .map(|name| (notes.join(name), name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn links_lead_to_written_pages_only() {
+        let notes = &std::env::temp_dir().join("notes");
+        let mut exporter = Exporter::new(
+            named(notes, &["Note.md", "Other.md"]),
+            &config(notes, &std::env::temp_dir().join("export")),
+        );
+        exporter.written = HashSet::from([notes.join("Note.md")]);
+        let (body, links) = exporter.body(
+            &notes.join("Note.md"),
+            "[[Note#Top]] [[Other]] [other](Other.md) [[Missing]]",
+        );
+        assert!(body.contains(r##"href="Note.html#top""##), "{body}");
+        let file = url::Url::from_file_path(notes.join("Other.md")).unwrap();
+        assert!(body.contains(&format!(r#"href="{}""#, file)), "{body}");
+        assert!(!body.contains("Other.html"), "{body}");
+        assert!(body.contains("Missing"), "{body}");
+        assert_eq!(links, [notes.join("Note.md")]);
+
+        exporter.file_links = false;
+        let (body, _) = exporter.body(&notes.join("Note.md"), "[[Other]]");
+        assert_eq!(body, "<p>Other</p>\n");
+    }
+
+    #[test]
+    fn folder_index_keeps_clear_of_notes() {
+        let root = std::env::temp_dir().join(format!("darkstone-export-{}", std::process::id()));
+        let (notes, export) = (root.join("notes"), root.join("export"));
+        std::fs::create_dir_all(notes.join("folder")).unwrap();
+        std::fs::write(notes.join("folder/index.md"), "# Home").unwrap();
+        std::fs::write(notes.join("Outside.md"), "# Outside").unwrap();
+
+        let index = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(export_folder(
+                named(&notes, &["folder/index.md", "Outside.md"]),
+                notes.join("folder"),
+                config(&notes, &export),
+            ));
+        let home = std::fs::read_to_string(export.join("folder/index.html"));
+        let outside = export.join("Outside.html").exists();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(index, Ok(export.join("folder/index-2.html")));
+        assert!(home.unwrap().contains("Home"));
+        assert!(!outside);
+    }
+}
@@ -0,0 +1,558 @@
+use std::sync::LazyLock;
+
+/// A footnote reference, `[^id]`, or the start of a footnote, `[^id]:`.
+static FOOTNOTE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\[\^([^\]\s]+)\](:)?").unwrap());
+/// A link reference definition, `[id]: url`, which isn't a footnote.
+static LINK_DEFINITION: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^ {0,3}\[[^\]^][^\]]*\]:\s*\S").unwrap());
+/// An autolink, `<scheme:...>`, or an email address between `<>`.
+static AUTOLINK: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"^<(?:[A-Za-z][A-Za-z0-9+.-]{1,31}:[^\s<>]*|[^\s<>@]+@[^\s<>@]+)>").unwrap()
+});
+
+/// Private-use characters around highlighted text, in text given to the Markdown parser,
+/// which leaves them be.
+pub const MARK: (char, char) = ('\u{E000}', '\u{E001}');
+pub const SUPERSCRIPT: (char, char) = ('\u{E002}', '\u{E003}');
+pub const SUBSCRIPT: (char, char) = ('\u{E004}', '\u{E005}');
+/// Around the id of a footnote reference.
+pub const FOOTNOTE_REFERENCE: (char, char) = ('\u{E006}', '\u{E007}');
+
+/// The background of highlighted text.
+pub fn highlight() -> iced::Color {
+    iced::Color::from_rgba8(0xe0, 0xaf, 0x68, 0.35)
+}
+
+/// Rewrites the syntax Markdown doesn't know in `source` for the characters above:
+/// `==highlight==`, `^superscript^`, `~subscript~` and `[^footnote]` references. Links to
+/// headings, `(#anchor)`, are made `anchor:` URLs and `{#id}` is taken off headings. Code is
+/// left as it is. Returns nothing when there's nothing to rewrite.
+pub fn inline(source: &str) -> Option<String> {
+    if !source.contains(['=', '^', '~', '#', '[']) {
+        return None;
+    }
+    let mut rewritten = String::with_capacity(source.len());
+    let mut fences = crate::folding::Fences::default();
+    for (i, line) in source.split('\n').enumerate() {
+        if i > 0 {
+            rewritten.push('\n');
+        }
+        if fences.read(line) || is_link_definition(line) {
+            rewritten.push_str(line);
+            continue;
+        }
+        let line = match heading_id(line) {
+            Some((text, _)) => text,
+            None => line,
+        };
+        rewrite(line, &mut rewritten);
+    }
+    (rewritten != source).then_some(rewritten)
+}
+
+fn rewrite(text: &str, rewritten: &mut String) {
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let delimited = |delimiter: &str| {
+            let inner = &rest[delimiter.len()..];
+            let end = inner.find(delimiter)?;
+            let content = &inner[..end];
+            (!content.is_empty()
+                && !content.starts_with(char::is_whitespace)
+                && !content.ends_with(char::is_whitespace))
+            .then_some(content)
+        };
+        match c {
+            '\\' => {
+                let length = rest[1..].chars().next().map_or(1, |c| 1 + c.len_utf8());
+                rewritten.push_str(&rest[..length]);
+                rest = &rest[length..];
+            }
+            '`' => {
+                let ticks = rest.chars().take_while(|c| *c == '`').count();
+                let end = rest[ticks..]
+                    .find(&rest[..ticks])
+                    .map_or(ticks, |end| ticks + end + ticks);
+                rewritten.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            '=' if rest.starts_with("==") => match delimited("==") {
+                Some(content) => {
+                    rewritten.push(MARK.0);
+                    rewrite(content, rewritten);
+                    rewritten.push(MARK.1);
+                    rest = &rest[content.len() + 4..];
+                }
+                None => {
+                    rewritten.push_str("==");
+                    rest = &rest[2..];
+                }
+            },
+            '^' | '~' if !rest.starts_with("~~") => {
+                let delimiter = &rest[..1];
+                // Paths like `~/notes~old` aren't subscripts.
+                match delimited(delimiter).filter(|content| {
+                    let path = c == '~' && content.contains('/');
+                    !path
+                        && !content.contains(char::is_whitespace)
+                        && !rest[content.len() + 2..].starts_with('~')
+                }) {
+                    Some(content) => {
+                        let (open, close) = if c == '^' { SUPERSCRIPT } else { SUBSCRIPT };
+                        rewritten.push(open);
+                        rewritten.push_str(content);
+                        rewritten.push(close);
+                        rest = &rest[content.len() + 2..];
+                    }
+                    None => {
+                        rewritten.push(c);
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            '[' if rest.starts_with("[^") => match FOOTNOTE.captures(rest) {
+                Some(footnote) if footnote.get(0).is_some_and(|m| m.start() == 0) => {
+                    let reference = footnote.get(2).is_none();
+                    if reference {
+                        rewritten.push(FOOTNOTE_REFERENCE.0);
+                        rewritten.push_str(&footnote[1]);
+                        rewritten.push(FOOTNOTE_REFERENCE.1);
+                    } else {
+                        rewritten.push_str(&footnote[0]);
+                    }
+                    rest = &rest[footnote[0].len()..];
+                }
+                _ => {
+                    rewritten.push('[');
+                    rest = &rest[1..];
+                }
+            },
+            // Link destinations are left as they are, but for links to headings.
+            ']' if rest.starts_with("](") => {
+                let length = destination(&rest[2..]).unwrap_or(0);
+                let destination = &rest[2..2 + length];
+                rewritten.push_str("](");
+                if let Some(anchor) = destination.strip_prefix('#') {
+                    rewritten.push_str("anchor:");
+                    rewritten.push_str(anchor);
+                } else {
+                    rewritten.push_str(destination);
+                }
+                rest = &rest[2 + length..];
+            }
+            '<' if autolink(rest).is_some() => {
+                let length = autolink(rest).unwrap_or(1);
+                rewritten.push_str(&rest[..length]);
+                rest = &rest[length..];
+            }
+            '~' => {
+                rewritten.push_str("~~");
+                rest = &rest[2..];
+            }
+            c => {
+                rewritten.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+}
+
+/// Whether `line` is a link reference definition, `[id]: url`.
+pub fn is_link_definition(line: &str) -> bool {
+    LINK_DEFINITION.is_match(line)
+}
+
+/// The length of the autolink starting `text`, if it starts with one.
+pub fn autolink(text: &str) -> Option<usize> {
+    AUTOLINK.find(text).map(|autolink| autolink.end())
+}
+
+/// The length of the destination of a link starting `text`, after its `](`, up to its
+/// closing parenthesis, with the ones it holds balanced. A destination that isn't closed
+/// isn't one.
+pub fn destination(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'(' => depth += 1,
+            b')' if depth == 0 => return Some(i + 1),
+            b')' => depth -= 1,
+            b'\n' if bytes.get(i + 1) == Some(&b'\n') => return None,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The text of a heading `line` without its `{#id}`, and the id.
+pub fn heading_id(line: &str) -> Option<(&str, &str)> {
+    if !line.trim_start().starts_with('#') {
+        return None;
+    }
+    let trimmed = line.trim_end();
+    let start = trimmed.rfind("{#")?;
+    let id = trimmed[start + 2..].strip_suffix('}')?;
+    (!id.is_empty() && !id.contains(char::is_whitespace))
+        .then_some((trimmed[..start].trim_end(), id))
+}
+
+/// The anchor of a heading with `text`, as GitHub makes them: lowercase, with spaces as
+/// dashes and without punctuation.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The ids of the footnotes referenced in `source`, in order.
+pub fn references(source: &str) -> impl Iterator<Item = &str> {
+    FOOTNOTE.captures_iter(source).filter_map(|footnote| {
+        let start = footnote.get(0)?.start();
+        let definition =
+            footnote.get(2).is_some() && (start == 0 || source[..start].ends_with('\n'));
+        (!definition)
+            .then(|| footnote.get(1))
+            .flatten()
+            .map(|id| id.as_str())
+    })
+}
+
+/// The kinds of callouts, `> [!NOTE]`, with the names Obsidian also uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl Kind {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_lowercase().as_str() {
+            "note" | "info" | "abstract" | "summary" | "todo" | "example" | "quote" => Self::Note,
+            "tip" | "hint" | "success" | "check" | "done" | "question" | "help" => Self::Tip,
+            "important" => Self::Important,
+            "warning" | "attention" => Self::Warning,
+            "caution" | "danger" | "error" | "failure" | "fail" | "bug" => Self::Caution,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Note => "Note",
+            Self::Tip => "Tip",
+            Self::Important => "Important",
+            Self::Warning => "Warning",
+            Self::Caution => "Caution",
+        }
+    }
+
+    /// The color callouts of the kind are framed in.
+    pub fn color(self) -> iced::Color {
+        let palette = crate::util::THEME.palette();
+        match self {
+            Self::Note => palette.primary,
+            Self::Tip => palette.success,
+            Self::Important => iced::Color::from_rgb8(0xbb, 0x9a, 0xf7),
+            Self::Warning => iced::Color::from_rgb8(0xe0, 0xaf, 0x68),
+            Self::Caution => palette.danger,
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Note => "ℹ",
+            Self::Tip => "💡",
+            Self::Important => "❗",
+            Self::Warning => "⚠",
+            Self::Caution => "⛔",
+        }
+    }
+}
+
+/// A quote starting with `[!KIND]`, shown as a callout.
+pub struct Callout {
+    pub kind: Kind,
+    pub title: String,
+    /// The Markdown of the quote, without its `>`.
+    pub body: String,
+}
+
+/// Reads `source` as a callout, if that's all it holds.
+pub fn callout(source: &str) -> Option<Callout> {
+    let lines: Vec<&str> = source.trim_end().lines().collect();
+    let quoted: Vec<&str> = lines
+        .iter()
+        .map(|line| {
+            let line = line.trim_start().strip_prefix('>')?;
+            Some(line.strip_prefix(' ').unwrap_or(line))
+        })
+        .collect::<Option<_>>()?;
+    let (first, body) = quoted.split_first()?;
+    let kind = first.trim_start().strip_prefix("[!")?;
+    let (kind, title) = kind.split_once(']')?;
+    let kind = Kind::parse(kind)?;
+    // Obsidian marks foldable callouts with `+` or `-`.
+    let title = title.trim_start_matches(['+', '-']).trim();
+    Some(Callout {
+        kind,
+        title: if title.is_empty() {
+            kind.name().to_string()
+        } else {
+            title.to_string()
+        },
+        body: body.join("\n"),
+    })
+}
+
+/// A term and its definitions.
+pub struct Definition {
+    pub term: String,
+    pub details: Vec<String>,
+}
+
+/// Reads `source` as a definition list, if that's all it holds: terms on a line each,
+/// followed by their definitions starting with `: `, which go on in indented lines.
+pub fn definitions(source: &str) -> Option<Vec<Definition>> {
+    let mut definitions: Vec<Definition> = vec![];
+    for line in source.trim_end().lines() {
+        if let Some(detail) = line.strip_prefix(": ").or(line.strip_prefix(":\t")) {
+            definitions
+                .last_mut()?
+                .details
+                .push(detail.trim().to_string());
+        } else if line.starts_with([' ', '\t']) && !line.trim().is_empty() {
+            let detail = definitions.last_mut()?.details.last_mut()?;
+            detail.push('\n');
+            detail.push_str(line.trim());
+        } else if line.trim().is_empty()
+            || line.starts_with(['#', '>', '-', '*', '+', '|', '`', '~', '<', '[', '!'])
+            || definitions
+                .last()
+                .is_some_and(|last| last.details.is_empty())
+        {
+            return None;
+        } else {
+            definitions.push(Definition {
+                term: line.trim().to_string(),
+                details: vec![],
+            });
+        }
+    }
+    (!definitions.is_empty() && definitions.iter().all(|d| !d.details.is_empty()))
+        .then_some(definitions)
+}
+
+/// The text of a footnote, `[^id]: text`.
+pub struct Footnote {
+    pub id: String,
+    pub text: String,
+}
+
+/// Reads `source` as footnotes, if that's all it holds. Their text goes on in indented
+/// lines.
+pub fn footnotes(source: &str) -> Option<Vec<Footnote>> {
+    let mut footnotes: Vec<Footnote> = vec![];
+    for line in source.trim_end().lines() {
+        let start = FOOTNOTE
+            .captures(line)
+            .filter(|footnote| footnote.get(2).is_some() && line.starts_with("[^"));
+        if let Some(start) = start {
+            footnotes.push(Footnote {
+                id: start[1].to_string(),
+                text: line[start[0].len()..].trim().to_string(),
+            });
+        } else if line.starts_with([' ', '\t']) || line.trim().is_empty() {
+            let footnote = footnotes.last_mut()?;
+            footnote.text.push('\n');
+            footnote.text.push_str(line.trim());
+        } else {
+            return None;
+        }
+    }
+    (!footnotes.is_empty()).then_some(footnotes)
+}
+
+/// `text` in superscript characters, if they all have one.
+pub fn superscript(text: &str) -> Option<String> {
+    text.chars()
+        .map(|c| {
+            Some(match c {
+                '0' => '⁰',
+                '1' => '¹',
+                '2' => '²',
+                '3' => '³',
+                '4'..='9' => char::from_u32(0x2074 + (c as u32 - '4' as u32))?,
+                '+' => '⁺',
+                '-' => '⁻',
+                '=' => '⁼',
+                '(' => '⁽',
+                ')' => '⁾',
+                'a' => 'ᵃ',
+                'b' => 'ᵇ',
+                'c' => 'ᶜ',
+                'd' => 'ᵈ',
+                'e' => 'ᵉ',
+                'f' => 'ᶠ',
+                'g' => 'ᵍ',
+                'h' => 'ʰ',
+                'i' => 'ⁱ',
+                'j' => 'ʲ',
+                'k' => 'ᵏ',
+                'l' => 'ˡ',
+                'm' => 'ᵐ',
+                'n' => 'ⁿ',
+                'o' => 'ᵒ',
+                'p' => 'ᵖ',
+                'r' => 'ʳ',
+                's' => 'ˢ',
+                't' => 'ᵗ',
+                'u' => 'ᵘ',
+                'v' => 'ᵛ',
+                'w' => 'ʷ',
+                'x' => 'ˣ',
+                'y' => 'ʸ',
+                'z' => 'ᶻ',
+                _ => return None,
+            })
+        })
+        .collect()
+}
+
+/// `text` in subscript characters, if they all have one.
+pub fn subscript(text: &str) -> Option<String> {
+    text.chars()
+        .map(|c| {
+            Some(match c {
+                '0'..='9' => char::from_u32(0x2080 + (c as u32 - '0' as u32))?,
+                '+' => '₊',
+                '-' => '₋',
+                '=' => '₌',
+                '(' => '₍',
+                ')' => '₎',
+                'a' => 'ₐ',
+                'e' => 'ₑ',
+                'h' => 'ₕ',
+                'i' => 'ᵢ',
+                'j' => 'ⱼ',
+                'k' => 'ₖ',
+                'l' => 'ₗ',
+                'm' => 'ₘ',
+                'n' => 'ₙ',
+                'o' => 'ₒ',
+                'p' => 'ₚ',
+                'r' => 'ᵣ',
+                's' => 'ₛ',
+                't' => 'ₜ',
+                'u' => 'ᵤ',
+                'v' => 'ᵥ',
+                'x' => 'ₓ',
+                _ => return None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marked(text: &str, (open, close): (char, char)) -> String {
+        format!("{open}{text}{close}")
+    }
+
+    #[test]
+    fn inline_rewrites_extended_syntax() {
+        assert_eq!(inline("plain text"), None);
+        assert_eq!(
+            inline("H~2~O, x^2^ and ==this==[^1]").unwrap(),
+            format!(
+                "H{}O, x{} and {}{}",
+                marked("2", SUBSCRIPT),
+                marked("2", SUPERSCRIPT),
+                marked("this", MARK),
+                marked("1", FOOTNOTE_REFERENCE),
+            )
+        );
+        assert_eq!(inline("~~struck~~"), None);
+        assert_eq!(inline("[up](#top)").unwrap(), "[up](anchor:top)");
+        assert_eq!(inline("# Title {#top}").unwrap(), "# Title");
+    }
+
+    #[test]
+    fn inline_leaves_code_and_links_alone() {
+        let untouched = |source: &str| assert_eq!(inline(source), None, "{source}");
+        untouched("[home](https://host/~alice/~tmp)");
+        untouched("[sum](a^b^c(d)^e^)");
+        untouched("<https://host/~alice/~tmp>");
+        untouched("see ~/notes~old");
+        untouched("`x^2^` and ``==y==``");
+        untouched("```\nH~2~O\n```");
+        untouched("[id]: https://host/~a~");
+        untouched("[^1]: a footnote");
+    }
+
+    #[test]
+    fn callouts_have_a_kind_a_title_and_a_body() {
+        let callout = callout("> [!warning] Careful\n> - [ ] a task\n>\n> more").unwrap();
+        assert_eq!(callout.kind, Kind::Warning);
+        assert_eq!(callout.title, "Careful");
+        assert_eq!(callout.body, "- [ ] a task\n\nmore");
+
+        let folded = super::callout("> [!TIP]-\n> hidden").unwrap();
+        assert_eq!(folded.kind, Kind::Tip);
+        assert_eq!(folded.title, "Tip");
+        assert!(super::callout("> [!unknown] x").is_none());
+        assert!(super::callout("> [!note]\nnot quoted").is_none());
+    }
+
+    #[test]
+    fn footnotes_go_on_in_indented_lines() {
+        let footnotes = footnotes("[^1]: one\n[^note]: two\n    lines\n").unwrap();
+        assert_eq!(footnotes.len(), 2);
+        assert_eq!(footnotes[0].id, "1");
+        assert_eq!(footnotes[0].text, "one");
+        assert_eq!(footnotes[1].id, "note");
+        assert_eq!(footnotes[1].text, "two\nlines");
+        assert!(super::footnotes("[^1]: one\ntext").is_none());
+        assert!(super::footnotes("text [^1]").is_none());
+    }
+
+    #[test]
+    fn references_skip_definitions() {
+        let source = "a[^1] b[^2]\n[^1]: c[^3]";
+        assert_eq!(references(source).collect::<Vec<_>>(), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn headings_and_slugs() {
+        assert_eq!(heading_id("## Title {#id}"), Some(("## Title", "id")));
+        assert_eq!(heading_id("## Title"), None);
+        assert_eq!(heading_id("text {#id}"), None);
+        assert_eq!(slug(" Hello, World! "), "hello-world");
+        assert_eq!(slug("snake_case-ok"), "snake_case-ok");
+    }
+
+    #[test]
+    fn destinations_and_autolinks() {
+        assert_eq!(destination("a(b)c) rest"), Some(6));
+        assert_eq!(destination("a\\)b) rest"), Some(5));
+        assert_eq!(destination("open\n\n)"), None);
+        assert_eq!(autolink("<https://host> rest"), Some(14));
+        assert_eq!(autolink("<me@host>"), Some(9));
+        assert_eq!(autolink("<b>"), None);
+    }
+}
@@ -0,0 +1,222 @@
+/// What the find bar of a note looks for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub text: String,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Whether `text` is a regular expression, and replacements may refer to its capture
+    /// groups with `$1` or `${name}`.
+    pub regex: bool,
+}
+
+impl Query {
+    /// The regular expression matching the query, or `None` when there is nothing to find.
+    pub fn regex(&self) -> Option<Result<regex::Regex, regex::Error>> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let pattern = if self.regex {
+            self.text.clone()
+        } else {
+            regex::escape(&self.text)
+        };
+        let pattern = if self.whole_word {
+            format!(r"\b(?:{})\b", pattern)
+        } else {
+            pattern
+        };
+        Some(
+            regex::RegexBuilder::new(&pattern)
+                .case_insensitive(!self.case_sensitive)
+                .multi_line(true)
+                .build(),
+        )
+    }
+
+    /// The text replacing the match `captures`, with capture groups expanded in regex mode.
+    pub fn replacement(&self, captures: &regex::Captures, replace: &str) -> String {
+        if self.regex {
+            let mut replacement = String::new();
+            captures.expand(replace, &mut replacement);
+            replacement
+        } else {
+            replace.to_string()
+        }
+    }
+
+    /// Replaces every match of `regex` in `text`, a line at a time like [`captures`].
+    pub fn replace_all(&self, regex: &regex::Regex, text: &str, replace: &str) -> String {
+        text.split('\n')
+            .map(|line| {
+                if self.regex {
+                    regex.replace_all(line, replace)
+                } else {
+                    regex.replace_all(line, regex::NoExpand(replace))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The non-empty matches of `regex` in `text`, with the offset of the line each is on. Matches
+/// are looked for a line at a time, as the editor highlights them, so none spans lines.
+pub fn captures<'a>(
+    regex: &'a regex::Regex,
+    text: &'a str,
+) -> impl Iterator<Item = (usize, regex::Captures<'a>)> + 'a {
+    text.split('\n')
+        .scan(0, |start, line| {
+            let offset = *start;
+            *start += line.len() + 1;
+            Some((offset, line))
+        })
+        .flat_map(move |(offset, line)| {
+            regex
+                .captures_iter(line)
+                .filter(|captures| captures.get(0).is_some_and(|found| !found.is_empty()))
+                .map(move |captures| (offset, captures))
+        })
+}
+
+/// The byte ranges of the non-empty matches of `regex` in a single `line`.
+pub fn line_matches<'a>(
+    regex: &'a regex::Regex,
+    line: &'a str,
+) -> impl Iterator<Item = std::ops::Range<usize>> + 'a {
+    regex
+        .find_iter(line)
+        .filter(|found| !found.is_empty())
+        .map(|found| found.range())
+}
+
+/// The matches of `regex` in `text`, as found by [`captures`], each with its line and byte
+/// columns.
+pub fn matches(regex: &regex::Regex, text: &str) -> Vec<(usize, std::ops::Range<usize>)> {
+    text.split('\n')
+        .enumerate()
+        .flat_map(|(i, line)| line_matches(regex, line).map(move |found| (i, found)))
+        .collect()
+}
+
+/// The text of `content`, without the trailing newline added by
+/// [`iced::widget::text_editor::Content::text`], so that it can be pasted back as is.
+pub fn text(content: &iced::widget::text_editor::Content) -> String {
+    let mut text = String::new();
+    for (i, line) in content.lines().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        text.push_str(&line);
+    }
+    text
+}
+
+/// The line and byte column of `offset` in `text`.
+pub fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let column = offset - before.rfind('\n').map_or(0, |i| i + 1);
+    (line, column)
+}
+
+/// The byte offset in `text` of the given line and byte column.
+pub fn offset(text: &str, line: usize, column: usize) -> usize {
+    let start = if line == 0 {
+        0
+    } else {
+        text.match_indices('\n')
+            .nth(line - 1)
+            .map_or(text.len(), |(i, _)| i + 1)
+    };
+    (start + column).min(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(text: &str) -> Query {
+        Query {
+            text: text.into(),
+            ..Query::default()
+        }
+    }
+
+    #[test]
+    fn plain_queries_are_escaped() {
+        assert!(query("").regex().is_none());
+        let regex = query("a.b").regex().unwrap().unwrap();
+        assert_eq!(matches(&regex, "a.b axb A.B"), [(0, 0..3), (0, 8..11)]);
+    }
+
+    #[test]
+    fn options() {
+        let regex = Query {
+            case_sensitive: true,
+            whole_word: true,
+            ..query("cat")
+        }
+        .regex()
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            matches(&regex, "cat Cat cats cat"),
+            [(0, 0..3), (0, 13..16)]
+        );
+        assert!(Query {
+            regex: true,
+            ..query("(")
+        }
+        .regex()
+        .unwrap()
+        .is_err());
+    }
+
+    #[test]
+    fn matches_stay_on_their_line() {
+        let regex = Query {
+            regex: true,
+            ..query(r"^\w+\s*")
+        }
+        .regex()
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            matches(&regex, "one\ntwo  \n\nthree"),
+            [(0, 0..3), (1, 0..5), (3, 0..5)]
+        );
+        assert!(matches(&regex, "").is_empty());
+    }
+
+    #[test]
+    fn replacements() {
+        let plain = query("$1");
+        let regex = plain.regex().unwrap().unwrap();
+        assert_eq!(
+            plain.replace_all(&regex, "a $1\n$1", "${x}"),
+            "a ${x}\n${x}"
+        );
+
+        let groups = Query {
+            regex: true,
+            ..query(r"(\w+)@(\w+)$")
+        };
+        let regex = groups.regex().unwrap().unwrap();
+        assert_eq!(groups.replace_all(&regex, "a@b\nc@d", "$2@$1"), "b@a\nd@c");
+        let captures = regex.captures("x@y").unwrap();
+        assert_eq!(groups.replacement(&captures, "$2-$1"), "y-x");
+        assert_eq!(plain.replacement(&captures, "$2-$1"), "$2-$1");
+    }
+
+    #[test]
+    fn positions_and_offsets() {
+        let text = "ab\ncdé\n\nf";
+        assert_eq!(position(text, 0), (0, 0));
+        assert_eq!(position(text, 5), (1, 2));
+        assert_eq!(position(text, 9), (3, 0));
+        assert_eq!(offset(text, 1, 2), 5);
+        assert_eq!(offset(text, 3, 0), 9);
+        assert_eq!(offset(text, 7, 0), text.len());
+    }
+}
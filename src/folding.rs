@@ -0,0 +1,566 @@
+/// The sections of a note folded away in the editor.
+///
+/// The editor can't hide lines, so folded lines are taken out of its content and kept here
+/// until unfolded. Lines are counted in the editor's content unless said otherwise; the
+/// lines of the whole note are found with [`Folds::note_line`] and [`Folds::text`].
+#[derive(Debug, Default)]
+pub struct Folds {
+    /// Sorted by line.
+    folds: Vec<Fold>,
+}
+
+#[derive(Debug)]
+struct Fold {
+    /// The line left visible, a heading, a list item or the opening line of a fence.
+    line: usize,
+    /// The lines hidden after it, with the folds inside it unfolded.
+    hidden: Vec<String>,
+    /// The folds inside it, by line from the first hidden line and with how many lines they
+    /// hide, to fold again once it's unfolded.
+    inner: Vec<(usize, usize)>,
+}
+
+impl Folds {
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    /// The lines hiding a fold, in order.
+    pub fn lines(&self) -> Vec<usize> {
+        self.folds.iter().map(|fold| fold.line).collect()
+    }
+
+    pub fn is_folded(&self, line: usize) -> bool {
+        self.folds
+            .binary_search_by_key(&line, |fold| fold.line)
+            .is_ok()
+    }
+
+    /// The line of the note shown at `line`.
+    pub fn note_line(&self, line: usize) -> usize {
+        line + self
+            .folds
+            .iter()
+            .take_while(|fold| fold.line < line)
+            .map(|fold| fold.hidden.len())
+            .sum::<usize>()
+    }
+
+    /// The line showing `line` of the note, or `None` if it's folded away.
+    pub fn editor_line(&self, line: usize) -> Option<usize> {
+        let mut hidden = 0;
+        for fold in &self.folds {
+            let start = fold.line + hidden;
+            if line <= start {
+                break;
+            }
+            if line <= start + fold.hidden.len() {
+                return None;
+            }
+            hidden += fold.hidden.len();
+        }
+        Some(line - hidden)
+    }
+
+    /// Where `line` of the note is: shown at a line of the editor, or folded away.
+    pub fn line(&self, line: usize) -> Result<usize, &str> {
+        let mut hidden = 0;
+        for fold in &self.folds {
+            let start = fold.line + hidden;
+            if line <= start {
+                break;
+            }
+            if line <= start + fold.hidden.len() {
+                return Err(&fold.hidden[line - start - 1]);
+            }
+            hidden += fold.hidden.len();
+        }
+        Ok(line - hidden)
+    }
+
+    /// How many lines are folded away.
+    pub fn hidden(&self) -> usize {
+        self.folds.iter().map(|fold| fold.hidden.len()).sum()
+    }
+
+    /// The text of the whole note, given the `lines` left in the editor.
+    pub fn text(&self, lines: impl Iterator<Item = impl std::ops::Deref<Target = str>>) -> String {
+        let mut text = String::new();
+        let mut folds = self.folds.iter().peekable();
+        for (i, line) in lines.enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(&line);
+            if let Some(fold) = folds.next_if(|fold| fold.line == i) {
+                for hidden in &fold.hidden {
+                    text.push('\n');
+                    text.push_str(hidden);
+                }
+            }
+        }
+        text
+    }
+
+    /// Folds the section starting at `line`, if there's one and it isn't folded yet. The
+    /// folds inside it are kept to fold them again once it's unfolded.
+    pub fn fold(&mut self, lines: &mut Vec<String>, line: usize) -> bool {
+        if self.is_folded(line) {
+            return false;
+        }
+        let Some(end) = range(lines, line, |line| self.is_folded(line)) else {
+            return false;
+        };
+        self.hide(lines, line, end);
+        true
+    }
+
+    /// Hides the lines after `line` up to `end`.
+    fn hide(&mut self, lines: &mut Vec<String>, line: usize, end: usize) {
+        let first = self.folds.partition_point(|fold| fold.line <= line);
+        let last = self.folds.partition_point(|fold| fold.line <= end);
+        let mut inside = self.folds.drain(first..last).peekable();
+        let mut hidden = vec![];
+        let mut inner = vec![];
+        for (i, text) in lines.drain(line + 1..=end).enumerate() {
+            hidden.push(text);
+            if let Some(fold) = inside.next_if(|fold| fold.line == line + 1 + i) {
+                inner.push((hidden.len() - 1, fold.hidden.len()));
+                inner.extend(
+                    fold.inner
+                        .iter()
+                        .map(|(offset, length)| (hidden.len() + offset, *length)),
+                );
+                hidden.extend(fold.hidden);
+            }
+        }
+        drop(inside);
+
+        for fold in &mut self.folds[first..] {
+            fold.line -= end - line;
+        }
+        self.folds.insert(
+            first,
+            Fold {
+                line,
+                hidden,
+                inner,
+            },
+        );
+    }
+
+    /// Unfolds the fold at `line`, folding the ones inside it again as they were.
+    pub fn unfold(&mut self, lines: &mut Vec<String>, line: usize) -> bool {
+        let Ok(index) = self.folds.binary_search_by_key(&line, |fold| fold.line) else {
+            return false;
+        };
+        let fold = self.folds.remove(index);
+        let count = fold.hidden.len();
+        lines.splice(line + 1..line + 1, fold.hidden);
+        for fold in &mut self.folds[index..] {
+            fold.line += count;
+        }
+        // The last ones first, so that the lines before them don't move. The lines of the
+        // ones folded again inside a fold no longer count towards its end.
+        let mut folded: Vec<(usize, usize)> = vec![];
+        for &(offset, length) in fold.inner.iter().rev() {
+            let inside = |other: &(usize, usize)| offset < other.0 && other.0 <= offset + length;
+            let hidden: usize = folded
+                .iter()
+                .filter(|other| inside(other))
+                .map(|other| other.1)
+                .sum();
+            folded.retain(|other| !inside(other));
+            folded.push((offset, length));
+            self.hide(
+                lines,
+                line + 1 + offset,
+                line + 1 + offset + length - hidden,
+            );
+        }
+        true
+    }
+
+    /// Folds every section, the last ones first so that sections fold into the ones holding
+    /// them.
+    pub fn fold_all(&mut self, lines: &mut Vec<String>) {
+        for line in (0..lines.len()).rev() {
+            self.fold(lines, line);
+        }
+    }
+
+    pub fn unfold_all(&mut self, lines: &mut Vec<String>) {
+        while let Some(fold) = self.folds.pop() {
+            lines.splice(fold.line + 1..fold.line + 1, fold.hidden);
+        }
+    }
+
+    /// The lines of the note where the folds start, nested ones included, to fold them again
+    /// with [`Folds::restore`].
+    pub fn note_lines(&self) -> Vec<usize> {
+        let mut lines = vec![];
+        let mut hidden = 0;
+        for fold in &self.folds {
+            let start = fold.line + hidden;
+            lines.push(start);
+            lines.extend(fold.inner.iter().map(|(offset, _)| start + 1 + offset));
+            hidden += fold.hidden.len();
+        }
+        lines
+    }
+
+    /// Folds the sections starting at the given lines of the note, in a note with no folds,
+    /// except the ones that would hide `visible`.
+    pub fn restore(&mut self, lines: &mut Vec<String>, note_lines: &[usize], visible: usize) {
+        let mut note_lines = note_lines.to_vec();
+        note_lines.sort_unstable();
+        note_lines.dedup();
+        // The last ones first, so the lines of the note and of the editor match up to them.
+        for line in note_lines.into_iter().rev() {
+            let cursor = self.editor_line(visible).unwrap_or(visible);
+            let end = range(lines, line, |line| self.is_folded(line));
+            if end.is_some_and(|end| line < cursor && cursor <= end) {
+                continue;
+            }
+            self.fold(lines, line);
+        }
+    }
+
+    /// Moves the folds after `line` by `delta` lines, after an edit.
+    pub fn shift(&mut self, line: usize, delta: isize) {
+        for fold in &mut self.folds {
+            if fold.line > line {
+                fold.line = fold.line.saturating_add_signed(delta);
+            }
+        }
+    }
+}
+
+/// The last line of the section starting at `line`: the lines under a heading up to the next
+/// heading of the same level or above, the lines indented under a list item, or the rest of a
+/// fenced block. Lines for which `folded` returns `true` hold a whole folded section.
+pub fn range(lines: &[String], line: usize, folded: impl Fn(usize) -> bool) -> Option<usize> {
+    let mut fences = Fences::default();
+    for (i, text) in lines.iter().enumerate().take(line) {
+        if !folded(i) {
+            fences.read(text);
+        }
+    }
+    section_end(lines, line, fences, &folded)
+}
+
+/// Which of `lines` start a section that [`range`] finds, reading the fences once.
+pub fn starts(lines: &[String], folded: impl Fn(usize) -> bool) -> Vec<bool> {
+    let mut fences = Fences::default();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            let start = section_end(lines, i, fences, &folded).is_some();
+            if !folded(i) {
+                fences.read(text);
+            }
+            start
+        })
+        .collect()
+}
+
+/// The end of the section starting at `line`, given the `fences` of the lines before it.
+fn section_end(
+    lines: &[String],
+    line: usize,
+    mut fences: Fences,
+    folded: &impl Fn(usize) -> bool,
+) -> Option<usize> {
+    let start = lines.get(line)?;
+    // Nothing folds inside a fenced block, and its closing line doesn't start one.
+    if fences.is_open() {
+        return None;
+    }
+
+    let end = if let Some(level) = heading_level(start) {
+        let mut end = line;
+        for (i, text) in lines.iter().enumerate().skip(line + 1) {
+            let code = if folded(i) {
+                fences.is_open()
+            } else {
+                fences.read(text)
+            };
+            if !code && heading_level(text).is_some_and(|other| other <= level) {
+                break;
+            }
+            end = i;
+        }
+        end
+    } else if fences.read(start) {
+        lines
+            .iter()
+            .enumerate()
+            .skip(line + 1)
+            .find(|(_, text)| {
+                fences.read(text);
+                !fences.is_open()
+            })?
+            .0
+    } else {
+        let indent = crate::tasks::list_item(start)?.indent;
+        let mut end = line;
+        for (i, text) in lines.iter().enumerate().skip(line + 1) {
+            if text.trim().is_empty() {
+                continue;
+            }
+            if text.len() - text.trim_start().len() <= indent {
+                break;
+            }
+            end = i;
+        }
+        end
+    };
+
+    // Blank lines at the end stay visible, between the fold and what follows.
+    let end = (line..=end)
+        .rev()
+        .find(|i| *i == line || !lines[*i].trim().is_empty())
+        .unwrap_or(line);
+    (end > line).then_some(end)
+}
+
+/// Tells which lines of a note are in fenced blocks, reading it a line at a time. A block is
+/// closed by a fence of the same character at least as long as the one opening it, so a
+/// ```` block can hold ``` lines.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Fences {
+    open: Option<(char, usize)>,
+}
+
+impl Fences {
+    /// Reads `line`, returning whether it's part of a fenced block, its fences included.
+    pub fn read(&mut self, line: &str) -> bool {
+        match (self.open, fence(line)) {
+            (Some((open, length)), Some((c, count, rest)))
+                if c == open && count >= length && rest.trim().is_empty() =>
+            {
+                self.open = None;
+                true
+            }
+            (Some(_), _) => true,
+            (None, Some((c, count, rest))) if c == '~' || !rest.contains('`') => {
+                self.open = Some((c, count));
+                true
+            }
+            (None, _) => false,
+        }
+    }
+
+    /// Whether the lines read so far left a fenced block open.
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+}
+
+/// Tells which lines of a note are code, in fenced blocks or indented by four spaces,
+/// reading it a line at a time. Indented lines only start a code block after a blank line
+/// outside a list, since they otherwise continue a paragraph or a list item.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Code {
+    fences: Fences,
+    /// Whether the last line was indented code.
+    indented: bool,
+    /// Whether the last line was text that an indented line continues.
+    paragraph: bool,
+    /// Whether the lines read are in a list.
+    list: bool,
+}
+
+impl Code {
+    /// Reads `line`, returning whether it's code.
+    pub fn read(&mut self, line: &str) -> bool {
+        if self.fences.read(line) {
+            self.paragraph = false;
+            return true;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            self.paragraph = false;
+            return self.indented;
+        }
+        let width: usize = line[..line.len() - trimmed.len()]
+            .chars()
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+        self.indented = width >= 4 && (self.indented || !self.paragraph && !self.list);
+        if self.indented {
+            return true;
+        }
+        if width < 4 {
+            let item = crate::tasks::list_item(line).is_some();
+            self.list = item || (self.list && (self.paragraph || width > 0));
+        }
+        self.paragraph = heading_level(line).is_none();
+        false
+    }
+}
+
+/// The character and length of the fence starting `line`, with what follows it.
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() >= 4 {
+        return None;
+    }
+    let c = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let count = trimmed.chars().take_while(|other| *other == c).count();
+    (count >= 3).then(|| (c, count, &trimmed[count..]))
+}
+
+/// The level of the `#` heading on `line`.
+pub fn heading_level(line: &str) -> Option<usize> {
+    if line.len() - line.trim_start().len() >= 4 {
+        return None;
+    }
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    ((1..=6).contains(&level)
+        && trimmed[level..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace))
+    .then_some(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str =
+        "# A\ntext\n## B\n- item\n  more\n```\n# not a heading\n```\n\n## C\nend\n# D";
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split('\n').map(String::from).collect()
+    }
+
+    #[test]
+    fn ranges() {
+        let lines = lines(NOTE);
+        let unfolded = |_| false;
+        assert_eq!(range(&lines, 0, unfolded), Some(10));
+        assert_eq!(range(&lines, 2, unfolded), Some(7));
+        assert_eq!(range(&lines, 3, unfolded), Some(4));
+        assert_eq!(range(&lines, 5, unfolded), Some(7));
+        assert_eq!(range(&lines, 6, unfolded), None);
+        assert_eq!(range(&lines, 7, unfolded), None);
+        assert_eq!(range(&lines, 11, unfolded), None);
+    }
+
+    #[test]
+    fn starts_match_ranges() {
+        let mut lines = lines(NOTE);
+        let mut folds = Folds::default();
+        folds.fold(&mut lines, 5);
+        let starts = starts(&lines, |line| folds.is_folded(line));
+        for (line, start) in starts.into_iter().enumerate() {
+            let end = range(&lines, line, |line| folds.is_folded(line));
+            assert_eq!(start, end.is_some(), "line {}", line);
+        }
+    }
+
+    #[test]
+    fn nested_folds_round_trip() {
+        let mut lines = lines(NOTE);
+        let mut folds = Folds::default();
+        assert!(folds.fold(&mut lines, 5));
+        assert!(folds.fold(&mut lines, 3));
+        assert!(folds.fold(&mut lines, 2));
+        assert!(!folds.fold(&mut lines, 2));
+        assert_eq!(lines, ["# A", "text", "## B", "", "## C", "end", "# D"]);
+        assert_eq!(folds.text(lines.iter().map(String::as_str)), NOTE);
+        assert_eq!(folds.note_lines(), [2, 3, 5]);
+        assert_eq!(folds.note_line(4), 9);
+        assert_eq!(folds.editor_line(9), Some(4));
+        assert_eq!(folds.editor_line(6), None);
+        // Every line of the note is found, shown or folded away.
+        let note: Vec<String> = (0..NOTE.lines().count())
+            .map(|line| match folds.line(line) {
+                Ok(line) => lines[line].clone(),
+                Err(hidden) => hidden.to_string(),
+            })
+            .collect();
+        assert_eq!(note.join("\n"), NOTE);
+
+        // The folds inside come back as they were.
+        assert!(folds.unfold(&mut lines, 2));
+        assert_eq!(folds.lines(), [3, 4]);
+        assert_eq!(
+            lines,
+            ["# A", "text", "## B", "- item", "```", "", "## C", "end", "# D"]
+        );
+        assert_eq!(folds.text(lines.iter().map(String::as_str)), NOTE);
+
+        assert!(folds.fold(&mut lines, 0));
+        assert_eq!(lines, ["# A", "# D"]);
+        assert_eq!(folds.text(lines.iter().map(String::as_str)), NOTE);
+        assert!(folds.unfold(&mut lines, 0));
+        assert_eq!(folds.lines(), [3, 4]);
+
+        folds.unfold_all(&mut lines);
+        assert!(folds.is_empty());
+        assert_eq!(lines.join("\n"), NOTE);
+    }
+
+    #[test]
+    fn fold_all_and_restore() {
+        let mut lines = lines(NOTE);
+        let mut folds = Folds::default();
+        folds.fold_all(&mut lines);
+        assert_eq!(lines, ["# A", "# D"]);
+        assert_eq!(folds.text(lines.iter().map(String::as_str)), NOTE);
+        let note_lines = folds.note_lines();
+
+        folds.unfold_all(&mut lines);
+        assert_eq!(lines.join("\n"), NOTE);
+        // The sections holding the line to keep visible stay open.
+        folds.restore(&mut lines, &note_lines, 4);
+        assert_eq!(
+            lines,
+            ["# A", "text", "## B", "- item", "  more", "```", "", "## C", "# D"]
+        );
+        assert_eq!(folds.text(lines.iter().map(String::as_str)), NOTE);
+    }
+
+    #[test]
+    fn indented_code() {
+        let mut code = Code::default();
+        let read: Vec<bool> = [
+            "    code",
+            "text",
+            "    more text",
+            "",
+            "    code",
+            "",
+            "\tcode",
+            "- item",
+            "",
+            "    item text",
+            "```",
+            "    fenced",
+            "```",
+        ]
+        .into_iter()
+        .map(|line| code.read(line))
+        .collect();
+        assert_eq!(
+            read,
+            [true, false, false, false, true, true, true, false, false, false, true, true, true]
+        );
+    }
+
+    #[test]
+    fn fences() {
+        let mut fences = Fences::default();
+        let read: Vec<bool> = ["a", "````", "```", "````", "b"]
+            .into_iter()
+            .map(|line| fences.read(line))
+            .collect();
+        assert_eq!(read, [false, true, true, true, false]);
+        assert!(!fences.is_open());
+    }
+}
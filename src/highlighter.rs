@@ -0,0 +1,498 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxSet};
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_nonewlines);
+
+/// Scope prefixes of the languages inside fenced blocks, most specific first.
+static TOKENS: LazyLock<Vec<(Scope, Token)>> = LazyLock::new(|| {
+    [
+        ("comment", Token::Comment),
+        ("string", Token::String),
+        ("constant.numeric", Token::Constant),
+        ("constant.language", Token::Constant),
+        ("entity.name.function", Token::Function),
+        ("support.function", Token::Function),
+        ("entity.name", Token::Type),
+        ("support.type", Token::Type),
+        ("keyword", Token::Keyword),
+        ("storage", Token::Keyword),
+    ]
+    .into_iter()
+    .map(|(scope, token)| (Scope::new(scope).unwrap(), token))
+    .collect()
+});
+
+/// What a part of the markdown source is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Heading,
+    Emphasis,
+    Strong,
+    Code,
+    Link,
+    Url,
+    /// List markers, task boxes, thematic breaks and block quote markers.
+    Marker,
+    Quote,
+    /// The opening and closing lines of a fenced block, and front matter.
+    Fence,
+    /// A token of the language of a fenced block.
+    Token(Token),
+    /// A match of the find bar.
+    Match,
+    /// A line hiding a folded section.
+    Folded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Plain,
+    Comment,
+    String,
+    Constant,
+    Keyword,
+    Function,
+    Type,
+}
+
+/// Highlights the markdown source of a note, with the contents of fenced blocks highlighted
+/// for their language.
+pub struct Markdown {
+    /// The state at the start of each line up to the current one.
+    states: Vec<State>,
+    current_line: usize,
+    search: Option<regex::Regex>,
+    folded: Vec<usize>,
+}
+
+/// What the editor highlights besides the markdown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    /// The query of the find bar, whose matches are highlighted.
+    pub query: crate::find::Query,
+    /// The lines hiding a folded section, in order.
+    pub folded: Vec<usize>,
+}
+
+#[derive(Clone)]
+enum State {
+    Text,
+    FrontMatter,
+    Fence {
+        marker: String,
+        code: Option<Box<(ParseState, ScopeStack)>>,
+    },
+}
+
+impl iced::advanced::text::Highlighter for Markdown {
+    type Settings = Settings;
+    type Highlight = Highlight;
+    type Iterator<'a> = std::vec::IntoIter<(Range<usize>, Highlight)>;
+
+    fn new(settings: &Self::Settings) -> Self {
+        Self {
+            states: vec![State::Text],
+            current_line: 0,
+            search: settings.query.regex().and_then(Result::ok),
+            folded: settings.folded.clone(),
+        }
+    }
+
+    fn update(&mut self, new_settings: &Self::Settings) {
+        self.search = new_settings.query.regex().and_then(Result::ok);
+        self.folded = new_settings.folded.clone();
+        self.change_line(0);
+    }
+
+    fn change_line(&mut self, line: usize) {
+        self.current_line = line.min(self.states.len() - 1);
+        self.states.truncate(self.current_line + 1);
+    }
+
+    fn highlight_line(&mut self, line: &str) -> Self::Iterator<'_> {
+        let mut state = self.states[self.current_line].clone();
+        let mut spans = highlight_line(&mut state, line, self.current_line);
+        if self.folded.binary_search(&self.current_line).is_ok() {
+            // The section is folded whole, so the lines after it start as it did.
+            state = self.states[self.current_line].clone();
+            spans.push((0..line.len(), Highlight::Folded));
+        }
+        if let Some(search) = &self.search {
+            // Later spans take over the earlier ones.
+            spans.extend(
+                search
+                    .find_iter(line)
+                    .filter(|found| !found.is_empty())
+                    .map(|found| (found.range(), Highlight::Match)),
+            );
+        }
+        self.states.push(state);
+        self.current_line += 1;
+        spans.into_iter()
+    }
+
+    fn current_line(&self) -> usize {
+        self.current_line
+    }
+}
+
+/// How a [`Highlight`] looks in `theme`.
+pub fn to_format(
+    highlight: &Highlight,
+    theme: &iced::Theme,
+) -> iced::advanced::text::highlighter::Format<iced::Font> {
+    let palette = theme.extended_palette();
+    let faded = |alpha| iced::Color {
+        a: alpha,
+        ..palette.background.base.text
+    };
+    let bold = iced::Font {
+        weight: iced::font::Weight::Bold,
+        ..iced::Font::DEFAULT
+    };
+    let italic = iced::Font {
+        style: iced::font::Style::Italic,
+        ..iced::Font::DEFAULT
+    };
+
+    let (color, font) = match highlight {
+        Highlight::Heading => (Some(palette.primary.strong.color), Some(bold)),
+        Highlight::Emphasis => (None, Some(italic)),
+        Highlight::Strong => (None, Some(bold)),
+        Highlight::Code => (
+            Some(palette.success.base.color),
+            Some(iced::Font::MONOSPACE),
+        ),
+        Highlight::Link => (Some(palette.primary.base.color), None),
+        Highlight::Url => (Some(faded(0.6)), None),
+        Highlight::Marker => (Some(palette.primary.base.color), None),
+        Highlight::Quote => (Some(faded(0.75)), Some(italic)),
+        Highlight::Fence => (Some(faded(0.6)), Some(iced::Font::MONOSPACE)),
+        Highlight::Token(token) => (token_color(*token, theme), Some(iced::Font::MONOSPACE)),
+        Highlight::Match => (Some(palette.danger.base.color), Some(bold)),
+        Highlight::Folded => (Some(palette.secondary.strong.color), Some(italic)),
+    };
+
+    iced::advanced::text::highlighter::Format { color, font }
+}
+
+/// The color of a [`Token`] in `theme`, or `None` for the text color.
+pub fn token_color(token: Token, theme: &iced::Theme) -> Option<iced::Color> {
+    let palette = theme.extended_palette();
+    match token {
+        Token::Plain => None,
+        Token::Comment => Some(iced::Color {
+            a: 0.5,
+            ..palette.background.base.text
+        }),
+        Token::String => Some(palette.success.base.color),
+        Token::Constant => Some(palette.danger.base.color),
+        Token::Keyword => Some(palette.primary.base.color),
+        Token::Function => Some(palette.primary.strong.color),
+        Token::Type => Some(palette.success.strong.color),
+    }
+}
+
+fn highlight_line(state: &mut State, line: &str, index: usize) -> Vec<(Range<usize>, Highlight)> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+
+    match state {
+        State::FrontMatter => {
+            if index > 0 && matches!(line.trim_end(), "---" | "...") {
+                *state = State::Text;
+            }
+            vec![(0..line.len(), Highlight::Fence)]
+        }
+        State::Fence { marker, code } => {
+            if trimmed.starts_with(marker.as_str())
+                && trimmed
+                    .trim_start_matches(marker.chars().next().unwrap())
+                    .trim()
+                    .is_empty()
+            {
+                *state = State::Text;
+                return vec![(0..line.len(), Highlight::Fence)];
+            }
+            match code {
+                Some(code) => tokenize(&mut code.0, &mut code.1, line)
+                    .into_iter()
+                    .map(|(range, token)| (range, Highlight::Token(token)))
+                    .collect(),
+                None => vec![(0..line.len(), Highlight::Token(Token::Plain))],
+            }
+        }
+        State::Text => {
+            if index == 0 && line.trim_end() == "---" {
+                *state = State::FrontMatter;
+                return vec![(0..line.len(), Highlight::Fence)];
+            }
+            if indent < 4 && (trimmed.starts_with("```") || trimmed.starts_with("~~~")) {
+                let fence = trimmed.chars().next().unwrap();
+                let marker: String = trimmed.chars().take_while(|c| *c == fence).collect();
+                let language = trimmed[marker.len()..]
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or("");
+                let code = SYNTAXES
+                    .find_syntax_by_token(language)
+                    .map(|syntax| Box::new((ParseState::new(syntax), ScopeStack::new())));
+                *state = State::Fence { marker, code };
+                return vec![(0..line.len(), Highlight::Fence)];
+            }
+            highlight_text(line)
+        }
+    }
+}
+
+/// Splits each line of `code` into tokens of `language`, or into a single plain token when
+/// the language is unknown.
+pub fn highlight_code(language: &str, code: &str) -> Vec<Vec<(Range<usize>, Token)>> {
+    let Some(syntax) = SYNTAXES.find_syntax_by_token(language) else {
+        return code
+            .lines()
+            .map(|line| vec![(0..line.len(), Token::Plain)])
+            .collect();
+    };
+
+    let mut parser = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    code.lines()
+        .map(|line| tokenize(&mut parser, &mut stack, line))
+        .collect()
+}
+
+fn tokenize(
+    parser: &mut ParseState,
+    stack: &mut ScopeStack,
+    line: &str,
+) -> Vec<(Range<usize>, Token)> {
+    let Ok(ops) = parser.parse_line(line, &SYNTAXES) else {
+        return vec![(0..line.len(), Token::Plain)];
+    };
+
+    let mut tokens = vec![];
+    for (range, op) in syntect::easy::ScopeRangeIterator::new(&ops, line) {
+        if stack.apply(op).is_err() {
+            break;
+        }
+        if !range.is_empty() {
+            tokens.push((range, classify(stack)));
+        }
+    }
+    tokens
+}
+
+fn classify(stack: &ScopeStack) -> Token {
+    stack
+        .as_slice()
+        .iter()
+        .rev()
+        .find_map(|scope| {
+            TOKENS
+                .iter()
+                .find(|(prefix, _)| prefix.is_prefix_of(*scope))
+                .map(|(_, token)| *token)
+        })
+        .unwrap_or(Token::Plain)
+}
+
+/// Highlights a line outside of fenced blocks.
+fn highlight_text(line: &str) -> Vec<(Range<usize>, Highlight)> {
+    let trimmed = line.trim_start();
+    let mut start = line.len() - trimmed.len();
+    let mut spans = vec![];
+
+    let hashes = trimmed.bytes().take_while(|b| *b == b'#').count();
+    if (1..=6).contains(&hashes)
+        && trimmed[hashes..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+    {
+        return vec![(0..line.len(), Highlight::Heading)];
+    }
+
+    let compact: String = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.len() >= 3
+        && matches!(compact.as_bytes()[0], b'-' | b'*' | b'_')
+        && compact.bytes().all(|b| b == compact.as_bytes()[0])
+    {
+        return vec![(0..line.len(), Highlight::Marker)];
+    }
+
+    let mut quoted = false;
+    while line[start..].starts_with('>') {
+        spans.push((start..start + 1, Highlight::Marker));
+        start += 1;
+        start += line[start..].len() - line[start..].trim_start().len();
+        quoted = true;
+    }
+
+    if let Some(marker) = list_marker(&line[start..]) {
+        spans.push((start..start + marker, Highlight::Marker));
+        start += marker;
+    }
+
+    highlight_inline(line, start, quoted, &mut spans);
+    spans
+}
+
+/// The length of the list marker at the start of `text`, with the task box if any.
+fn list_marker(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let length = match bytes.first()? {
+        b'-' | b'*' | b'+' => 1,
+        b'0'..=b'9' => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+            match bytes.get(digits) {
+                Some(b'.' | b')') if digits <= 9 => digits + 1,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    if !matches!(bytes.get(length), Some(b' ' | b'\t') | None) {
+        return None;
+    }
+
+    let rest = &text[length..];
+    let task = rest.trim_start();
+    let offset = length + rest.len() - task.len();
+    if ["[ ]", "[x]", "[X]"]
+        .iter()
+        .any(|box_| task.starts_with(box_))
+    {
+        Some(offset + 3)
+    } else {
+        Some(length)
+    }
+}
+
+/// Highlights code spans, emphasis and links in `line` from `start`. Text outside of them is
+/// left plain, or highlighted as [`Highlight::Quote`] if `quoted`.
+fn highlight_inline(
+    line: &str,
+    start: usize,
+    quoted: bool,
+    spans: &mut Vec<(Range<usize>, Highlight)>,
+) {
+    let bytes = line.as_bytes();
+    let mut plain = start;
+    let mut i = start;
+
+    let push = |spans: &mut Vec<_>, plain: &mut usize, range: Range<usize>, highlight| {
+        if quoted && *plain < range.start {
+            spans.push((*plain..range.start, Highlight::Quote));
+        }
+        *plain = range.end;
+        spans.push((range, highlight));
+    };
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'`' => {
+                let run = run_length(bytes, i, b'`');
+                match find_run(bytes, i + run, b'`', run) {
+                    Some(end) => {
+                        push(spans, &mut plain, i..end + run, Highlight::Code);
+                        i = end + run;
+                    }
+                    None => i += run,
+                }
+            }
+            delimiter @ (b'*' | b'_') => {
+                let run = run_length(bytes, i, delimiter).min(3);
+                let opens = bytes.get(i + run).is_some_and(|b| !b.is_ascii_whitespace())
+                    && (delimiter == b'*'
+                        || !line[..i]
+                            .chars()
+                            .next_back()
+                            .is_some_and(char::is_alphanumeric));
+                match find_run(bytes, i + run, delimiter, run).filter(|_| opens) {
+                    Some(end) if end > i + run => {
+                        let highlight = if run == 1 {
+                            Highlight::Emphasis
+                        } else {
+                            Highlight::Strong
+                        };
+                        push(spans, &mut plain, i..end + run, highlight);
+                        i = end + run;
+                    }
+                    _ => i += run,
+                }
+            }
+            b'[' | b'!' => {
+                let open = if bytes[i] == b'!' { i + 1 } else { i };
+                match link(bytes, open) {
+                    Some((text_end, url_end)) if bytes.get(open) == Some(&b'[') => {
+                        push(spans, &mut plain, i..text_end, Highlight::Link);
+                        push(spans, &mut plain, text_end..url_end, Highlight::Url);
+                        i = url_end;
+                    }
+                    _ => i += 1,
+                }
+            }
+            b'<' => match line[i..].find('>') {
+                Some(end) if line[i + 1..].starts_with("http") => {
+                    push(spans, &mut plain, i..i + end + 1, Highlight::Link);
+                    i += end + 1;
+                }
+                _ => i += 1,
+            },
+            _ => i += 1,
+        }
+    }
+
+    if quoted && plain < line.len() {
+        spans.push((plain..line.len(), Highlight::Quote));
+    }
+}
+
+fn run_length(bytes: &[u8], start: usize, byte: u8) -> usize {
+    bytes[start..].iter().take_while(|b| **b == byte).count()
+}
+
+/// Finds the next run of exactly `length` times `byte` from `start`.
+fn find_run(bytes: &[u8], start: usize, byte: u8, length: usize) -> Option<usize> {
+    let mut i = start;
+    while i < bytes.len() {
+        if bytes[i] == byte {
+            let run = run_length(bytes, i, byte);
+            if run == length {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Finds the end of the `[text]` and of the `(url)` of a link opening at `open`.
+fn link(bytes: &[u8], open: usize) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut i = open;
+    let text_end = loop {
+        match bytes.get(i)? {
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    };
+    if bytes.get(text_end) != Some(&b'(') {
+        return None;
+    }
+    let close = bytes[text_end..].iter().position(|b| *b == b')')?;
+    Some((text_end, text_end + close + 1))
+}
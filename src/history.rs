@@ -0,0 +1,327 @@
+/// The undo and redo stacks of a note, kept as snapshots of its text.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// The kind of the last edit and when it was made, while the edits that follow may join
+    /// its undo step.
+    group: Option<(Kind, std::time::Instant)>,
+    /// Hash of the text the history leads to once the note is left, so that it is dropped if
+    /// the note changed in the meantime. `None` fits any text.
+    hash: Option<u64>,
+}
+
+/// The text of a note and where its cursor was.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub text: String,
+    pub cursor: (usize, usize),
+}
+
+/// What an edit does, for grouping typing into undo steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Typing,
+    Spacing,
+    Deleting,
+    /// Enter, paste and edits made on behalf of the user, each in its own step.
+    Other,
+}
+
+/// Edits are no longer grouped after this pause.
+const PAUSE: std::time::Duration = std::time::Duration::from_secs(1);
+/// How many steps can be undone.
+const LIMIT: usize = 100;
+/// How many bytes of snapshots are kept, the oldest steps being dropped past it.
+const SIZE: usize = 8 << 20;
+/// How many bytes of snapshots are kept on disk, the newest steps first, since the history is
+/// written on every save.
+const STORED: usize = 1 << 20;
+
+impl Kind {
+    pub fn of(action: &iced::widget::text_editor::Action) -> Self {
+        use iced::widget::text_editor::{Action, Edit};
+
+        match action {
+            Action::Edit(Edit::Insert(c)) if c.is_whitespace() => Kind::Spacing,
+            Action::Edit(Edit::Insert(_)) => Kind::Typing,
+            Action::Edit(Edit::Backspace | Edit::Delete) => Kind::Deleting,
+            _ => Kind::Other,
+        }
+    }
+
+    /// Whether an edit of kind `next` joins the step of this one: a word and the spaces after
+    /// it go together.
+    fn joins(self, next: Kind) -> bool {
+        matches!(
+            (self, next),
+            (Kind::Typing, Kind::Typing | Kind::Spacing)
+                | (Kind::Spacing, Kind::Spacing)
+                | (Kind::Deleting, Kind::Deleting)
+        )
+    }
+}
+
+impl History {
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty() && self.redo.is_empty()
+    }
+
+    /// Records the `snapshot` of the note before an edit of `kind`, unless the edit joins the
+    /// current step.
+    pub fn record(&mut self, kind: Kind, snapshot: impl FnOnce() -> Snapshot) {
+        let now = std::time::Instant::now();
+        let joined = self
+            .group
+            .is_some_and(|(last, at)| last.joins(kind) && now - at < PAUSE);
+        self.group = (kind != Kind::Other).then_some((kind, now));
+        if joined {
+            return;
+        }
+
+        self.undo.push(snapshot());
+        self.redo.clear();
+        let mut size: usize = self.undo.iter().map(|snapshot| snapshot.text.len()).sum();
+        while self.undo.len() > LIMIT || (self.undo.len() > 1 && size > SIZE) {
+            size -= self.undo.remove(0).text.len();
+        }
+    }
+
+    /// Ends the current step, when the cursor moves away.
+    pub fn seal(&mut self) {
+        self.group = None;
+    }
+
+    /// The text to go back to, with the `current` one kept to redo.
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let snapshot = self.undo.pop()?;
+        self.redo.push(current);
+        self.group = None;
+        Some(snapshot)
+    }
+
+    /// The text to go forward to, with the `current` one kept to undo.
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let snapshot = self.redo.pop()?;
+        self.undo.push(current);
+        self.group = None;
+        Some(snapshot)
+    }
+
+    /// Marks the history as leading to `text`, as saved, when leaving or saving the note.
+    pub fn leave(&mut self, text: &str) {
+        self.hash = Some(hash(text));
+        self.group = None;
+    }
+
+    /// Records the `current` text before the note is read again after a change on disk, so
+    /// that the change can be undone.
+    pub fn reload(&mut self, current: Snapshot) {
+        self.record(Kind::Other, || current);
+        self.hash = None;
+    }
+
+    /// Whether the history leads to `text`, as saved.
+    pub fn fits(&self, text: &str) -> bool {
+        self.hash.is_none_or(|h| h == hash(text))
+    }
+
+    /// Writes the history of the note at `path`, with the newest steps of each stack that fit
+    /// in [`STORED`] bytes.
+    fn serialize(&self, path: &std::path::Path) -> String {
+        let mut serialized = format!(
+            "darkstone-history 2\n{}\n{}\n",
+            path.display(),
+            self.hash.unwrap_or(0)
+        );
+        let mut budget = STORED;
+        let mut newest = |snapshots: &[Snapshot]| {
+            let kept = snapshots
+                .iter()
+                .rev()
+                .take_while(|snapshot| match budget.checked_sub(snapshot.text.len()) {
+                    Some(rest) => {
+                        budget = rest;
+                        true
+                    }
+                    None => false,
+                })
+                .count();
+            snapshots.len() - kept
+        };
+        let undo = &self.undo[newest(&self.undo)..];
+        let redo = &self.redo[newest(&self.redo)..];
+        for (stack, snapshots) in [("undo", undo), ("redo", redo)] {
+            for snapshot in snapshots {
+                serialized.push_str(&format!(
+                    "{} {} {} {}\n{}\n",
+                    stack,
+                    snapshot.cursor.0,
+                    snapshot.cursor.1,
+                    snapshot.text.len(),
+                    snapshot.text
+                ));
+            }
+        }
+        serialized
+    }
+
+    /// Reads the history of the note at `path`, if `contents` is one and is for that note.
+    fn parse(mut contents: &str, path: &std::path::Path) -> Option<Self> {
+        let mut line = || {
+            let (line, rest) = contents.split_once('\n')?;
+            contents = rest;
+            Some(line)
+        };
+        if line()? != "darkstone-history 2" || line()? != path.display().to_string() {
+            return None;
+        }
+        let mut history = Self {
+            hash: Some(line()?.parse().ok()?),
+            ..Self::default()
+        };
+
+        while !contents.is_empty() {
+            let (header, rest) = contents.split_once('\n')?;
+            let mut fields = header.split(' ');
+            let stack = match fields.next()? {
+                "undo" => &mut history.undo,
+                "redo" => &mut history.redo,
+                _ => return None,
+            };
+            let line = fields.next()?.parse().ok()?;
+            let column = fields.next()?.parse().ok()?;
+            let length: usize = fields.next()?.parse().ok()?;
+            stack.push(Snapshot {
+                text: rest.get(..length)?.to_string(),
+                cursor: (line, column),
+            });
+            contents = rest.get(length..)?.strip_prefix('\n')?;
+        }
+        Some(history)
+    }
+}
+
+/// The 64-bit FNV-1a hash of `text`, used to check a history still fits its note and to name
+/// its file. Unlike the standard library's hasher, it's the same for every build.
+fn hash(text: impl AsRef<[u8]>) -> u64 {
+    text.as_ref()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Where the history of the note at `path` is kept across sessions. The file also holds the
+/// path, in case another note's path has the same hash.
+fn file(path: &std::path::Path) -> std::path::PathBuf {
+    std::path::PathBuf::from(std::env::var("HOME").unwrap())
+        .join(".config/darkstone/history")
+        .join(format!(
+            "{:016x}",
+            hash(path.as_os_str().as_encoded_bytes())
+        ))
+}
+
+/// Reads the history kept for the note at `path`, if there's a valid one.
+pub async fn load(path: std::path::PathBuf) -> Option<History> {
+    let contents = tokio::fs::read_to_string(file(&path)).await.ok()?;
+    History::parse(&contents, &path)
+}
+
+pub async fn store(path: std::path::PathBuf, history: History) {
+    let file = file(&path);
+    let write = async {
+        if let Some(parent) = file.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&file, history.serialize(&path)).await
+    };
+    if let Err(e) = write.await {
+        eprintln!("Failed to save the history of {:?}: {}", path, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(text: &str, cursor: (usize, usize)) -> Snapshot {
+        Snapshot {
+            text: text.into(),
+            cursor,
+        }
+    }
+
+    fn texts(snapshots: &[Snapshot]) -> Vec<&str> {
+        snapshots
+            .iter()
+            .map(|snapshot| snapshot.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash("foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::path::Path::new("/notes/a note.md");
+        let mut history = History::default();
+        history.record(Kind::Other, || snapshot("", (0, 0)));
+        history.record(Kind::Other, || snapshot("one\ntwo\n", (1, 3)));
+        history.undo(snapshot("one\ntwo\nthree", (2, 5)));
+        history.leave("one\ntwo\n");
+
+        let parsed = History::parse(&history.serialize(path), path).unwrap();
+        assert_eq!(texts(&parsed.undo), [""]);
+        assert_eq!(texts(&parsed.redo), ["one\ntwo\nthree"]);
+        assert_eq!(parsed.redo[0].cursor, (2, 5));
+        assert!(parsed.fits("one\ntwo\n"));
+        assert!(!parsed.fits("one\ntwo"));
+    }
+
+    #[test]
+    fn parse_rejects_other_notes_and_damaged_files() {
+        let path = std::path::Path::new("/notes/a.md");
+        let mut history = History::default();
+        history.record(Kind::Other, || snapshot("text", (0, 4)));
+        let serialized = history.serialize(path);
+
+        assert!(History::parse(&serialized, std::path::Path::new("/notes/b.md")).is_none());
+        assert!(History::parse(&serialized[..serialized.len() - 2], path).is_none());
+        assert!(History::parse("darkstone-history\n0\n", path).is_none());
+        assert!(History::parse(&serialized.replace("undo", "none"), path).is_none());
+    }
+
+    #[test]
+    fn stored_history_keeps_the_newest_steps() {
+        let path = std::path::Path::new("/notes/a.md");
+        let big = "x".repeat(STORED / 2);
+        let mut history = History::default();
+        for text in ["a", &big, &big, "b"] {
+            history.record(Kind::Other, || snapshot(text, (0, 0)));
+        }
+        let parsed = History::parse(&history.serialize(path), path).unwrap();
+        assert_eq!(parsed.undo.len(), 2);
+        assert_eq!(parsed.undo[1].text, "b");
+    }
+
+    #[test]
+    fn records_are_grouped_and_capped() {
+        let mut history = History::default();
+        history.record(Kind::Typing, || snapshot("a", (0, 0)));
+        history.record(Kind::Spacing, || snapshot("b", (0, 0)));
+        history.record(Kind::Deleting, || snapshot("c", (0, 0)));
+        assert_eq!(texts(&history.undo), ["a", "c"]);
+
+        for i in 0..LIMIT + 10 {
+            history.record(Kind::Other, || snapshot(&i.to_string(), (0, 0)));
+        }
+        assert_eq!(history.undo.len(), LIMIT);
+        assert_eq!(history.undo[0].text, "10");
+    }
+}
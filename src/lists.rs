@@ -0,0 +1,281 @@
+/// What Enter does on a line of a list or a quote.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Enter {
+    /// Breaks the line and starts the next one with this prefix.
+    Continue(String),
+    /// Ends the list or the quote, replacing the empty item with this line.
+    End(String),
+}
+
+/// What Enter does at `column` of `line`, if it's in a list item or a quote and after its
+/// marker.
+pub fn enter(lines: &[String], line: usize, column: usize) -> Option<Enter> {
+    let text = &lines[line];
+    let quote = quote_prefix(text);
+    let rest = &text[quote..];
+    let item = crate::tasks::list_item(rest);
+    let prefix = quote + item.as_ref().map_or(0, |item| item.text);
+    if (quote == 0 && item.is_none()) || column < prefix {
+        return None;
+    }
+
+    if text[prefix..].trim().is_empty() {
+        // An empty item ends the list but stays in the quote, an empty quote line ends it.
+        return Some(Enter::End(match item {
+            Some(_) => text[..quote].to_string(),
+            None => String::new(),
+        }));
+    }
+
+    let mut next = text[..quote].to_string();
+    if let Some(item) = item {
+        let marker = rest.trim_start();
+        next.push_str(&rest[..rest.len() - marker.len()]);
+        match ordered(rest) {
+            Some((indent, digits, number)) => {
+                // Lists numbered `1.` all along stay so.
+                let lazy = quote == 0
+                    && previous(lines, line, indent)
+                        .and_then(|sibling| ordered(sibling))
+                        .is_some_and(|(_, _, other)| other == number);
+                next.push_str(&(if lazy { number } else { number + 1 }).to_string());
+                next.push_str(&rest[digits.end..digits.end + 1]);
+            }
+            None => next.push_str(&marker[..1]),
+        }
+        next.push(' ');
+        if item.checked.is_some() {
+            next.push_str("[ ] ");
+        }
+    }
+    Some(Enter::Continue(next))
+}
+
+/// How many spaces to put before the list item on `line` to nest it in the item before it,
+/// if there's one at the same level.
+pub fn indent(lines: &[String], line: usize) -> Option<usize> {
+    let item = crate::tasks::list_item(&lines[line])?;
+    let text = previous(lines, line, item.indent)?;
+    let sibling = crate::tasks::list_item(text)?;
+    let content = match sibling.checked {
+        Some(_) => sibling.mark - 1,
+        None => sibling.text,
+    };
+    Some(content - leading(text))
+}
+
+/// How many bytes of indentation to take from the list item on `line` to put it at the level
+/// of its parent, if it's nested.
+pub fn outdent(lines: &[String], line: usize) -> Option<usize> {
+    let item = crate::tasks::list_item(&lines[line])?;
+    let parent = lines[..line]
+        .iter()
+        .rev()
+        .find(|text| !text.trim().is_empty() && width(text) < item.indent)
+        .filter(|text| crate::tasks::list_item(text).is_some())
+        .map_or(0, |text| leading(text));
+    let outdent = leading(&lines[line]).saturating_sub(parent);
+    (outdent > 0).then_some(outdent)
+}
+
+/// The numbers to change, as a line, a byte range and the new number, so that the ordered list
+/// holding the item on `line` counts up from its first item. With `restart`, a list starting
+/// at `line` counts from 1.
+pub fn renumber(
+    lines: &[String],
+    line: usize,
+    restart: bool,
+) -> Vec<(usize, std::ops::Range<usize>, String)> {
+    let Some((indent, _, _)) = ordered(&lines[line]) else {
+        return vec![];
+    };
+    // Blank lines and the lines nested in the items don't end the list.
+    let sibling = |text: &String| {
+        if text.trim().is_empty() || width(text) > indent {
+            Some(None)
+        } else {
+            ordered(text)
+                .filter(|(other, _, _)| *other == indent)
+                .map(Some)
+        }
+    };
+
+    let mut start = line;
+    for (i, text) in lines[..line].iter().enumerate().rev() {
+        match sibling(text) {
+            Some(Some(_)) => start = i,
+            Some(None) => {}
+            None => break,
+        }
+    }
+    let mut number = match ordered(&lines[start]) {
+        Some(_) if restart && start == line => 1,
+        Some((_, _, number)) => number,
+        None => return vec![],
+    };
+
+    let mut changes = vec![];
+    let mut numbers = vec![];
+    for (i, text) in lines.iter().enumerate().skip(start) {
+        match sibling(text) {
+            Some(Some((_, digits, current))) => {
+                numbers.push(current);
+                if current != number {
+                    changes.push((i, digits, number.to_string()));
+                }
+                number += 1;
+            }
+            Some(None) => {}
+            None => break,
+        }
+    }
+    // Lists numbered `1.` all along stay so.
+    if numbers.len() > 1 && numbers.iter().all(|other| *other == numbers[0]) {
+        return vec![];
+    }
+    changes
+}
+
+/// Whether `line` is inside a fenced block, where lists are just text.
+pub fn in_code(lines: &[String], line: usize) -> bool {
+    let mut fences = crate::folding::Fences::default();
+    for text in &lines[..line] {
+        fences.read(text);
+    }
+    fences.is_open()
+}
+
+/// The line before `line` at the same `indent`, skipping blank lines and the lines nested
+/// deeper, if there's one before anything less indented.
+fn previous(lines: &[String], line: usize, indent: usize) -> Option<&String> {
+    lines[..line]
+        .iter()
+        .rev()
+        .find(|text| !text.trim().is_empty() && width(text) <= indent)
+        .filter(|text| width(text) == indent)
+}
+
+/// The indentation, the byte range of the number and the number of an ordered list item.
+fn ordered(line: &str) -> Option<(usize, std::ops::Range<usize>, usize)> {
+    let item = crate::tasks::list_item(line)?;
+    let start = leading(line);
+    let digits = line[start..].bytes().take_while(u8::is_ascii_digit).count();
+    let number = line[start..start + digits].parse().ok()?;
+    Some((item.indent, start..start + digits, number))
+}
+
+/// The byte length of the `>` markers of a quote starting `line`.
+fn quote_prefix(line: &str) -> usize {
+    let mut end = 0;
+    loop {
+        let rest = &line[end..];
+        let trimmed = rest.trim_start_matches(' ');
+        let Some(after) = trimmed.strip_prefix('>') else {
+            return end;
+        };
+        if rest.len() - trimmed.len() > 3 {
+            return end;
+        }
+        end = line.len() - after.strip_prefix(' ').unwrap_or(after).len();
+    }
+}
+
+fn leading(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The width of the indentation of `line`, with tabs counting as four spaces.
+fn width(line: &str) -> usize {
+    line[..leading(line)]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    fn enter_at_end(text: &str) -> Option<Enter> {
+        let lines = note(text);
+        let line = lines.len() - 1;
+        enter(&lines, line, lines[line].len())
+    }
+
+    #[test]
+    fn enter_continues_items() {
+        let next = |text| match enter_at_end(text) {
+            Some(Enter::Continue(next)) => next,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(next("- a"), "- ");
+        assert_eq!(next("  * [x] done"), "  * [ ] ");
+        assert_eq!(next("9) a"), "10) ");
+        assert_eq!(next("> quoted"), "> ");
+        assert_eq!(next("> > 2. a"), "> > 3. ");
+        // Lists numbered `1.` all along stay so.
+        assert_eq!(next("1. a\n1. b"), "1. ");
+        assert_eq!(next("1. a\n  - b\n2. c"), "3. ");
+    }
+
+    #[test]
+    fn enter_ends_empty_items() {
+        assert_eq!(enter_at_end("- a\n- "), Some(Enter::End(String::new())));
+        assert_eq!(enter_at_end("> - [ ] "), Some(Enter::End("> ".into())));
+        assert_eq!(enter_at_end("> a\n>"), Some(Enter::End(String::new())));
+        assert_eq!(enter_at_end("text"), None);
+        // Before the marker, Enter just breaks the line.
+        assert_eq!(enter(&note("- a"), 0, 1), None);
+    }
+
+    #[test]
+    fn indent_and_outdent() {
+        let lines = note("- a\n- b\n  1. c\n  2. [ ] d\n     - e");
+        assert_eq!(indent(&lines, 0), None);
+        assert_eq!(indent(&lines, 1), Some(2));
+        assert_eq!(indent(&lines, 3), Some(3));
+        assert_eq!(outdent(&lines, 1), None);
+        assert_eq!(outdent(&lines, 2), Some(2));
+        assert_eq!(outdent(&lines, 4), Some(3));
+    }
+
+    #[test]
+    fn renumber_counts_up_from_the_first_item() {
+        let lines = note("3. a\n   - nested\n\n3. b\n9. c\ntext\n1. d");
+        assert_eq!(
+            renumber(&lines, 3, false),
+            [(3, 0..1, "4".into()), (4, 0..1, "5".into())]
+        );
+        assert_eq!(renumber(&lines, 6, false), []);
+        assert_eq!(renumber(&lines, 1, false), []);
+        assert_eq!(
+            renumber(&lines, 0, true),
+            [
+                (0, 0..1, "1".into()),
+                (3, 0..1, "2".into()),
+                (4, 0..1, "3".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn renumber_keeps_lists_of_ones() {
+        let lines = note("1. a\n1. b\n1. c");
+        assert_eq!(renumber(&lines, 1, false), []);
+        let lines = note("1. a\n1. b\n  1. c\n  5. d");
+        assert_eq!(renumber(&lines, 0, false), []);
+        assert_eq!(renumber(&lines, 3, false), [(3, 2..3, "2".into())]);
+    }
+
+    #[test]
+    fn lists_in_code_are_text() {
+        let lines = note("- a\n```\n- b\n```\n- c");
+        assert!(!in_code(&lines, 0));
+        assert!(in_code(&lines, 2));
+        assert!(!in_code(&lines, 4));
+    }
+}
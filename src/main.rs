@@ -2,10 +2,29 @@ use std::path::PathBuf;
 
 use iced::futures::TryFutureExt;
 
+mod agenda;
+mod attachments;
 mod config;
+mod diagrams;
+mod embeds;
+mod export;
+mod extended;
+mod find;
+mod folding;
+mod highlighter;
+mod history;
+mod lists;
+mod math;
 mod note_editor;
 mod notes;
+mod preview;
+mod site;
+mod sorting;
+mod tables;
+mod tasks;
+mod templates;
 mod util;
+mod vault_search;
 
 struct Darkstone {
     data: util::Query<DarkstoneData, std::io::ErrorKind>,
@@ -20,6 +39,7 @@ struct DarkstoneData {
 enum Message {
     LoadConfig,
     LoadedConfig(Result<config::Configuration, std::io::ErrorKind>),
+    #[allow(dead_code)]
     SaveConfig,
     SavedConfig(Result<(), std::io::ErrorKind>),
     Notes(notes::Message),
@@ -55,10 +75,10 @@ impl Darkstone {
                 iced::Task::none()
             }
             Message::LoadedConfig(Ok(config)) => {
-                let (notes, notes_task) = notes::Notes::new(config.notes_path.clone());
+                let (notes, notes_task) = notes::Notes::new(config.clone());
                 self.data = util::Query::Loaded(DarkstoneData {
                     config: config.clone(),
-                    notes: notes,
+                    notes,
                 });
                 notes_task.map(Message::Notes)
             }
@@ -70,9 +90,7 @@ impl Darkstone {
                 util::Query::Loaded(DarkstoneData {
                     ref mut notes,
                     config: _,
-                }) => {
-                    return notes.update(message).map(Message::Notes);
-                }
+                }) => notes.update(message).map(Message::Notes),
                 _ => iced::Task::none(),
             },
         }
@@ -80,7 +98,7 @@ impl Darkstone {
     fn view(&self) -> iced::Element<'_, Message> {
         match self.data {
             util::Query::Pending => iced::widget::Text::new("Loading...").into(),
-            util::Query::Loaded(ref data) => data.notes.view().map(Message::Notes).into(),
+            util::Query::Loaded(ref data) => data.notes.view().map(Message::Notes),
             util::Query::Error(ref e) => iced::widget::Text::new(format!("{:?}", e)).into(),
         }
     }
@@ -98,12 +116,7 @@ async fn load_config(
     path: std::path::PathBuf,
 ) -> Result<config::Configuration, std::io::ErrorKind> {
     match crate::util::read_file(path).await {
-        Ok(file) => {
-            let config = config::Configuration {
-                notes_path: PathBuf::from(file),
-            };
-            Ok(config)
-        }
+        Ok(file) => Ok(config::Configuration::parse(&file)),
         Err(std::io::ErrorKind::NotFound) => {
             let config = crate::config::Configuration::default();
             save_config(config.clone()).await?;
@@ -114,18 +127,60 @@ async fn load_config(
 }
 
 async fn save_config(config: config::Configuration) -> Result<(), std::io::ErrorKind> {
-    let config_file = format!("notes_path: {}", config.notes_path.to_string_lossy());
+    let config_file = config.serialize();
     let path =
-        std::path::PathBuf::from(std::env::var("HOME").unwrap()).join("/.config/darkstone/config");
+        std::path::PathBuf::from(std::env::var("HOME").unwrap()).join(".config/darkstone/config");
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .map_err(|e| e.kind())
+            .await?;
+    }
     tokio::fs::write(path, config_file)
         .map_err(|e| e.kind())
         .await?;
     Ok(())
 }
 
+/// Publishes the notes of a folder as a website, without opening the app:
+/// `darkstone publish <folder> [<output>]`. The folder is relative to the notes folder, and
+/// the site goes to `site` in the export folder by default.
+async fn publish(args: Vec<String>) -> Result<String, String> {
+    let [folder, rest @ ..] = args.as_slice() else {
+        return Err("Usage: darkstone publish <folder> [<output>]".into());
+    };
+    let config =
+        load_config(PathBuf::from(std::env::var("HOME").unwrap()).join(".config/darkstone/config"))
+            .await
+            .map_err(|e| format!("Can't load the config: {:?}", e))?;
+    let folder = config.notes_path.join(folder);
+    let output = match rest.first() {
+        Some(output) => PathBuf::from(output),
+        None => config.export_path.join("site"),
+    };
+    let tree = notes::site_tree(folder.clone(), config.clone())
+        .await
+        .map_err(|e| format!("Can't read {:?}: {:?}", folder, e))?;
+    let published = site::publish(tree, output.clone(), config).await?;
+    Ok(format!("Published {} notes to {:?}", published, output))
+}
+
 pub fn main() -> iced::Result {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("publish") {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("Failed to start the runtime");
+        match runtime.block_on(publish(args.collect())) {
+            Ok(published) => println!("{}", published),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
     iced::application("Darkstone", Darkstone::update, Darkstone::view)
-        .theme(|_| iced::Theme::TokyoNightStorm)
+        .theme(|_| util::THEME)
         .subscription(Darkstone::subscription)
         .settings(iced::Settings {
             fonts: vec![include_bytes!("../assets/darkstone-icons.ttf")
@@ -0,0 +1,1578 @@
+/// A formula, read from the part of LaTeX math notes use.
+#[derive(Debug)]
+pub enum Node {
+    /// Some text, spaced from its neighbours by its class.
+    Symbol(String, Style, Class),
+    Row(Vec<Node>),
+    /// A numerator over a denominator, with a bar between them or not.
+    Fraction(Box<Node>, Box<Node>, bool),
+    /// A base with a subscript and a superscript.
+    Scripts(Box<Node>, Option<Box<Node>>, Option<Box<Node>>),
+    /// A radicand with a degree.
+    Root(Option<Box<Node>>, Box<Node>),
+    /// What's between two delimiters, sized to it. Empty delimiters aren't shown.
+    Delimited(String, Box<Node>, String),
+    /// The rows of cells of a matrix, between delimiters.
+    Matrix(Vec<Vec<Node>>, String, String, Columns),
+    Accent(Box<Node>, Accent),
+    /// A space, in ems.
+    Space(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Italic,
+    Upright,
+    Bold,
+}
+
+/// What a symbol is, for the spacing around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Ordinary,
+    Binary,
+    Relation,
+    Punctuation,
+    /// A named function like `\sin`.
+    Function,
+    /// A named function with limits under it in display math, like `\lim`.
+    Limits,
+    /// A big operator with limits, like `\sum`.
+    Large,
+    Integral,
+}
+
+/// How the cells of a matrix line up in their column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Columns {
+    Center,
+    Left,
+    /// Right then left, around the `&` of `aligned`.
+    Aligned,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Accent {
+    Hat,
+    Tilde,
+    Dot,
+    Dots,
+    Vector,
+    Overline,
+    Underline,
+}
+
+/// The byte ranges of the formulas of a Markdown `source`, and whether each is display math
+/// (between `$$`) or inline (between `$`). Code, link destinations, autolinks and link
+/// reference definitions are skipped.
+///
+/// An inline formula doesn't start with a space nor end with one, and isn't followed by a
+/// digit, so that prices aren't read as math.
+pub fn find(source: &str) -> Vec<(std::ops::Range<usize>, bool)> {
+    let mut formulas = vec![];
+    let bytes = source.as_bytes();
+    let mut code = crate::folding::Code::default();
+    // The lines from `line` on are yet to be read, for what's skipped whole.
+    let mut line = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        while line <= i && line < bytes.len() {
+            let end = source[line..]
+                .find('\n')
+                .map_or(source.len(), |end| line + end);
+            let text = &source[line..end];
+            let skipped = code.read(text) || crate::extended::is_link_definition(text);
+            line = end + 1;
+            if skipped {
+                i = i.max(line);
+            }
+        }
+        if i >= bytes.len() {
+            break;
+        }
+        match bytes[i] {
+            // An escaped line break is still one.
+            b'\\' if bytes.get(i + 1) != Some(&b'\n') => i += 2,
+            b'`' => {
+                let ticks = bytes[i..].iter().take_while(|b| **b == b'`').count();
+                let marker = &source[i..i + ticks];
+                i = source[i + ticks..]
+                    .find(marker)
+                    .map_or(i + ticks, |end| i + ticks + end + ticks);
+            }
+            b']' if bytes.get(i + 1) == Some(&b'(') => {
+                i += 2 + crate::extended::destination(&source[i + 2..]).unwrap_or(0)
+            }
+            b'<' => i += crate::extended::autolink(&source[i..]).unwrap_or(1),
+            b'$' if bytes.get(i + 1) == Some(&b'$') => match source[i + 2..].find("$$") {
+                Some(end) if !source[i + 2..i + 2 + end].trim().is_empty() => {
+                    formulas.push((i..i + end + 4, true));
+                    i += end + 4;
+                }
+                _ => i += 2,
+            },
+            b'$' => {
+                let starts = bytes.get(i + 1).is_some_and(|b| !b.is_ascii_whitespace());
+                let end = starts
+                    .then(|| {
+                        let mut j = i + 1;
+                        while j < bytes.len() {
+                            match bytes[j] {
+                                b'\\' => j += 1,
+                                // A `$` that can't close it, like in `$5 and $10`, means
+                                // the first one wasn't math either.
+                                b'$' => {
+                                    return (!bytes[j - 1].is_ascii_whitespace()
+                                        && !bytes.get(j + 1).is_some_and(u8::is_ascii_digit))
+                                    .then_some(j);
+                                }
+                                // Inline math doesn't cross paragraphs.
+                                b'\n' if bytes.get(j + 1) == Some(&b'\n') => return None,
+                                _ => {}
+                            }
+                            j += 1;
+                        }
+                        None
+                    })
+                    .flatten();
+                match end {
+                    Some(end) => {
+                        formulas.push((i..end + 1, false));
+                        i = end + 1;
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    formulas
+}
+
+/// Reads a formula, without its `$` delimiters. Errors say what's wrong in it.
+pub fn parse(source: &str) -> Result<Node, String> {
+    let mut parser = Parser {
+        source,
+        position: 0,
+    };
+    let (node, stop) = parser.row()?;
+    match stop {
+        Stop::End => Ok(node),
+        Stop::Brace => Err("Unexpected }".into()),
+        Stop::Right => Err("\\right without \\left".into()),
+        Stop::Cell | Stop::Line => Err("& or \\\\ outside of a matrix".into()),
+        Stop::Environment(name) => Err(format!("\\end{{{}}} without \\begin", name)),
+    }
+}
+
+/// What ends a row of nodes.
+enum Stop {
+    End,
+    Brace,
+    Right,
+    /// `&`, between the cells of a matrix.
+    Cell,
+    /// `\\`, between the rows of a matrix.
+    Line,
+    Environment(String),
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// The nodes up to the end of the group or of the cell they're in.
+    fn row(&mut self) -> Result<(Node, Stop), String> {
+        let mut nodes = vec![];
+        loop {
+            self.skip_spaces();
+            let Some(c) = self.peek() else {
+                return Ok((Node::Row(nodes), Stop::End));
+            };
+            match c {
+                '}' => {
+                    self.position += 1;
+                    return Ok((Node::Row(nodes), Stop::Brace));
+                }
+                '&' => {
+                    self.position += 1;
+                    return Ok((Node::Row(nodes), Stop::Cell));
+                }
+                '^' | '_' | '\'' => {
+                    self.position += 1;
+                    let script = match c {
+                        '\'' => Node::Symbol("′".into(), Style::Upright, Class::Ordinary),
+                        _ => self.argument(c)?,
+                    };
+                    let base = nodes.pop().unwrap_or(Node::Row(vec![]));
+                    nodes.push(attach(base, c == '_', script)?);
+                }
+                '\\' => {
+                    let start = self.position;
+                    match self.command_name().as_str() {
+                        "\\" => return Ok((Node::Row(nodes), Stop::Line)),
+                        "right" => return Ok((Node::Row(nodes), Stop::Right)),
+                        "end" => {
+                            let name = self.group_text()?;
+                            return Ok((Node::Row(nodes), Stop::Environment(name)));
+                        }
+                        _ => {
+                            self.position = start;
+                            nodes.push(self.atom()?);
+                        }
+                    }
+                }
+                _ => nodes.push(self.atom()?),
+            }
+        }
+    }
+
+    /// A group, a command or a symbol.
+    fn atom(&mut self) -> Result<Node, String> {
+        self.skip_spaces();
+        let Some(c) = self.peek() else {
+            return Err("Unexpected end".into());
+        };
+        if c == '{' {
+            return self.group();
+        }
+        if c == '\\' {
+            return self.command();
+        }
+        self.position += c.len_utf8();
+        Ok(if c.is_ascii_digit() {
+            let rest = &self.source[self.position..];
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            self.position += digits;
+            let number = format!("{}{}", c, &rest[..digits]);
+            Node::Symbol(number, Style::Upright, Class::Ordinary)
+        } else if c.is_alphabetic() {
+            Node::Symbol(c.to_string(), Style::Italic, Class::Ordinary)
+        } else {
+            let (text, class) = match c {
+                '+' => ("+", Class::Binary),
+                '-' => ("−", Class::Binary),
+                '*' => ("∗", Class::Binary),
+                '=' => ("=", Class::Relation),
+                '<' => ("<", Class::Relation),
+                '>' => (">", Class::Relation),
+                ':' => (":", Class::Relation),
+                ',' | ';' => (if c == ',' { "," } else { ";" }, Class::Punctuation),
+                '~' => return Ok(Node::Space(0.33)),
+                _ => return Ok(Node::Symbol(c.to_string(), Style::Upright, Class::Ordinary)),
+            };
+            Node::Symbol(text.into(), Style::Upright, class)
+        })
+    }
+
+    /// A group in braces.
+    fn group(&mut self) -> Result<Node, String> {
+        self.position += 1;
+        match self.row()? {
+            (node, Stop::Brace) => Ok(node),
+            (_, Stop::End) => Err("Missing }".into()),
+            (_, Stop::Right) => Err("\\right without \\left".into()),
+            (_, Stop::Cell | Stop::Line) => Err("& or \\\\ outside of a matrix".into()),
+            (_, Stop::Environment(name)) => Err(format!("\\end{{{}}} without \\begin", name)),
+        }
+    }
+
+    /// The argument of a command or of a script: a group, a command or a single character.
+    fn argument(&mut self, of: impl std::fmt::Display) -> Result<Node, String> {
+        self.skip_spaces();
+        match self.peek() {
+            None | Some('}' | '&' | '^' | '_') => Err(format!("Missing argument after {}", of)),
+            // A number is read one digit at a time here, like LaTeX does.
+            Some(c) if c.is_ascii_digit() => {
+                self.position += 1;
+                Ok(Node::Symbol(c.to_string(), Style::Upright, Class::Ordinary))
+            }
+            Some(_) => self.atom(),
+        }
+    }
+
+    /// The name of the command at the position, after its backslash: letters, or a single
+    /// character.
+    fn command_name(&mut self) -> String {
+        self.position += 1;
+        let rest = &self.source[self.position..];
+        let length = match rest.find(|c: char| !c.is_ascii_alphabetic()) {
+            Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+            Some(length) => length,
+            None => rest.len(),
+        };
+        self.position += length;
+        rest[..length].to_string()
+    }
+
+    /// The raw text of a group in braces.
+    fn group_text(&mut self) -> Result<String, String> {
+        self.skip_spaces();
+        if self.peek() != Some('{') {
+            return Err("Missing {".into());
+        }
+        let start = self.position + 1;
+        let mut depth = 0;
+        for (i, c) in self.source[self.position..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.position += i + 1;
+                        return Ok(self.source[start..self.position - 1].to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err("Missing }".into())
+    }
+
+    fn command(&mut self) -> Result<Node, String> {
+        let name = self.command_name();
+        let command = format!("\\{}", name);
+        Ok(match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => Node::Fraction(
+                Box::new(self.argument(&command)?),
+                Box::new(self.argument(&command)?),
+                true,
+            ),
+            "binom" => Node::Delimited(
+                "(".into(),
+                Box::new(Node::Fraction(
+                    Box::new(self.argument(&command)?),
+                    Box::new(self.argument(&command)?),
+                    false,
+                )),
+                ")".into(),
+            ),
+            "sqrt" => {
+                self.skip_spaces();
+                let degree = if self.peek() == Some('[') {
+                    let rest = &self.source[self.position + 1..];
+                    let end = rest.find(']').ok_or("Missing ]")?;
+                    let degree = parse(&rest[..end])?;
+                    self.position += end + 2;
+                    Some(Box::new(degree))
+                } else {
+                    None
+                };
+                Node::Root(degree, Box::new(self.argument(&command)?))
+            }
+            "left" => {
+                let left = self.delimiter(&command)?;
+                let (inner, stop) = self.row()?;
+                if !matches!(stop, Stop::Right) {
+                    return Err("\\left without \\right".into());
+                }
+                let right = self.delimiter("\\right")?;
+                Node::Delimited(left, Box::new(inner), right)
+            }
+            "middle" => Node::Symbol(self.delimiter(&command)?, Style::Upright, Class::Relation),
+            "begin" => self.environment()?,
+            "text" | "textrm" | "mathrm" | "mbox" | "textsf" | "mathsf" | "texttt" | "mathtt" => {
+                Node::Symbol(self.group_text()?, Style::Upright, Class::Ordinary)
+            }
+            "textit" | "mathit" => Node::Symbol(self.group_text()?, Style::Italic, Class::Ordinary),
+            "textbf" | "mathbf" | "boldsymbol" | "bm" => {
+                Node::Symbol(self.group_text()?, Style::Bold, Class::Ordinary)
+            }
+            "operatorname" => Node::Symbol(self.group_text()?, Style::Upright, Class::Function),
+            "mathbb" => Node::Symbol(
+                self.group_text()?.chars().map(double_struck).collect(),
+                Style::Upright,
+                Class::Ordinary,
+            ),
+            "mathcal" | "mathscr" => Node::Symbol(
+                self.group_text()?.chars().map(script).collect(),
+                Style::Upright,
+                Class::Ordinary,
+            ),
+            "hat" | "widehat" => Node::Accent(Box::new(self.argument(&command)?), Accent::Hat),
+            "tilde" | "widetilde" => {
+                Node::Accent(Box::new(self.argument(&command)?), Accent::Tilde)
+            }
+            "dot" => Node::Accent(Box::new(self.argument(&command)?), Accent::Dot),
+            "ddot" => Node::Accent(Box::new(self.argument(&command)?), Accent::Dots),
+            "vec" | "overrightarrow" => {
+                Node::Accent(Box::new(self.argument(&command)?), Accent::Vector)
+            }
+            "bar" | "overline" => {
+                Node::Accent(Box::new(self.argument(&command)?), Accent::Overline)
+            }
+            "underline" => Node::Accent(Box::new(self.argument(&command)?), Accent::Underline),
+            "," | "thinspace" => Node::Space(0.17),
+            ":" | ">" | "medspace" => Node::Space(0.22),
+            ";" | "thickspace" => Node::Space(0.28),
+            " " => Node::Space(0.33),
+            "enspace" => Node::Space(0.5),
+            "quad" => Node::Space(1.0),
+            "qquad" => Node::Space(2.0),
+            "!" => Node::Space(-0.17),
+            "{" | "}" | "$" | "%" | "&" | "#" | "_" => {
+                Node::Symbol(name, Style::Upright, Class::Ordinary)
+            }
+            "|" => Node::Symbol("‖".into(), Style::Upright, Class::Ordinary),
+            "displaystyle" | "textstyle" | "scriptstyle" | "limits" | "nolimits" => {
+                Node::Row(vec![])
+            }
+            "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "sinh" | "cosh" | "tanh" | "coth"
+            | "arcsin" | "arccos" | "arctan" | "log" | "ln" | "lg" | "exp" | "det" | "dim"
+            | "ker" | "arg" | "deg" | "gcd" | "hom" | "Pr" => {
+                Node::Symbol(name, Style::Upright, Class::Function)
+            }
+            "lim" | "limsup" | "liminf" | "max" | "min" | "sup" | "inf" | "argmax" | "argmin" => {
+                Node::Symbol(
+                    match name.as_str() {
+                        "limsup" => "lim sup".into(),
+                        "liminf" => "lim inf".into(),
+                        "argmax" => "arg max".into(),
+                        "argmin" => "arg min".into(),
+                        _ => name,
+                    },
+                    Style::Upright,
+                    Class::Limits,
+                )
+            }
+            _ => {
+                let (text, class) = symbol(&name).ok_or(format!("Unknown command {}", command))?;
+                let style = match text.chars().next() {
+                    Some('α'..='ω' | 'ϑ' | 'ϕ' | 'ϱ' | 'ϵ' | 'ϖ') => Style::Italic,
+                    _ => Style::Upright,
+                };
+                Node::Symbol(text.into(), style, class)
+            }
+        })
+    }
+
+    /// The delimiter after `\left` or `\right`, empty for `.`.
+    fn delimiter(&mut self, after: &str) -> Result<String, String> {
+        self.skip_spaces();
+        let missing = || format!("Missing delimiter after {}", after);
+        let c = self.peek().ok_or_else(missing)?;
+        if c != '\\' {
+            self.position += c.len_utf8();
+            return match c {
+                '.' => Ok(String::new()),
+                '(' | ')' | '[' | ']' | '|' | '/' | '<' | '>' => Ok(match c {
+                    '<' => "⟨".into(),
+                    '>' => "⟩".into(),
+                    _ => c.to_string(),
+                }),
+                _ => Err(missing()),
+            };
+        }
+        Ok(match self.command_name().as_str() {
+            "{" | "lbrace" => "{",
+            "}" | "rbrace" => "}",
+            "langle" => "⟨",
+            "rangle" => "⟩",
+            "vert" | "lvert" | "rvert" => "|",
+            "|" | "Vert" | "lVert" | "rVert" => "‖",
+            "lfloor" => "⌊",
+            "rfloor" => "⌋",
+            "lceil" => "⌈",
+            "rceil" => "⌉",
+            _ => return Err(missing()),
+        }
+        .into())
+    }
+
+    /// A matrix or a set of cases, after `\begin`.
+    fn environment(&mut self) -> Result<Node, String> {
+        let name = self.group_text()?;
+        let (left, right, columns) = match name.as_str() {
+            "matrix" | "smallmatrix" => ("", "", Columns::Center),
+            "pmatrix" => ("(", ")", Columns::Center),
+            "bmatrix" => ("[", "]", Columns::Center),
+            "Bmatrix" => ("{", "}", Columns::Center),
+            "vmatrix" => ("|", "|", Columns::Center),
+            "Vmatrix" => ("‖", "‖", Columns::Center),
+            "cases" => ("{", "", Columns::Left),
+            "array" => {
+                // The alignment of the columns isn't kept.
+                self.group_text()?;
+                ("", "", Columns::Center)
+            }
+            "aligned" | "align" | "align*" | "split" | "gathered" | "gather" | "gather*" => {
+                ("", "", Columns::Aligned)
+            }
+            _ => return Err(format!("Unknown environment {}", name)),
+        };
+
+        let mut rows = vec![];
+        let mut row = vec![];
+        loop {
+            let (cell, stop) = self.row()?;
+            row.push(cell);
+            match stop {
+                Stop::Cell => {}
+                Stop::Line => rows.push(std::mem::take(&mut row)),
+                Stop::Environment(end) if end == name => break,
+                Stop::Environment(end) => {
+                    return Err(format!("\\begin{{{}}} ended by \\end{{{}}}", name, end))
+                }
+                Stop::End => return Err(format!("\\begin{{{}}} without \\end", name)),
+                Stop::Brace => return Err("Unexpected }".into()),
+                Stop::Right => return Err("\\right without \\left".into()),
+            }
+        }
+        // A `\\` after the last row doesn't start another one.
+        if !matches!(&row[..], [Node::Row(nodes)] if nodes.is_empty()) {
+            rows.push(row);
+        }
+        Ok(Node::Matrix(rows, left.into(), right.into(), columns))
+    }
+}
+
+/// Gives `base` a subscript, or a superscript.
+fn attach(base: Node, subscript: bool, script: Node) -> Result<Node, String> {
+    let (base, mut sub, mut sup) = match base {
+        Node::Scripts(base, sub, sup) => (base, sub, sup),
+        base => (Box::new(base), None, None),
+    };
+    let slot = if subscript { &mut sub } else { &mut sup };
+    if slot.is_some() {
+        return Err(if subscript {
+            "Double subscript".into()
+        } else {
+            "Double superscript".into()
+        });
+    }
+    *slot = Some(Box::new(script));
+    Ok(Node::Scripts(base, sub, sup))
+}
+
+/// The text and the class of a symbol command.
+fn symbol(name: &str) -> Option<(&'static str, Class)> {
+    use Class::*;
+
+    Some(match name {
+        "alpha" => ("α", Ordinary),
+        "beta" => ("β", Ordinary),
+        "gamma" => ("γ", Ordinary),
+        "delta" => ("δ", Ordinary),
+        "epsilon" => ("ϵ", Ordinary),
+        "varepsilon" => ("ε", Ordinary),
+        "zeta" => ("ζ", Ordinary),
+        "eta" => ("η", Ordinary),
+        "theta" => ("θ", Ordinary),
+        "vartheta" => ("ϑ", Ordinary),
+        "iota" => ("ι", Ordinary),
+        "kappa" => ("κ", Ordinary),
+        "lambda" => ("λ", Ordinary),
+        "mu" => ("μ", Ordinary),
+        "nu" => ("ν", Ordinary),
+        "xi" => ("ξ", Ordinary),
+        "pi" => ("π", Ordinary),
+        "varpi" => ("ϖ", Ordinary),
+        "rho" => ("ρ", Ordinary),
+        "varrho" => ("ϱ", Ordinary),
+        "sigma" => ("σ", Ordinary),
+        "varsigma" => ("ς", Ordinary),
+        "tau" => ("τ", Ordinary),
+        "upsilon" => ("υ", Ordinary),
+        "phi" => ("ϕ", Ordinary),
+        "varphi" => ("φ", Ordinary),
+        "chi" => ("χ", Ordinary),
+        "psi" => ("ψ", Ordinary),
+        "omega" => ("ω", Ordinary),
+        "Gamma" => ("Γ", Ordinary),
+        "Delta" => ("Δ", Ordinary),
+        "Theta" => ("Θ", Ordinary),
+        "Lambda" => ("Λ", Ordinary),
+        "Xi" => ("Ξ", Ordinary),
+        "Pi" => ("Π", Ordinary),
+        "Sigma" => ("Σ", Ordinary),
+        "Upsilon" => ("Υ", Ordinary),
+        "Phi" => ("Φ", Ordinary),
+        "Psi" => ("Ψ", Ordinary),
+        "Omega" => ("Ω", Ordinary),
+        "pm" => ("±", Binary),
+        "mp" => ("∓", Binary),
+        "times" => ("×", Binary),
+        "div" => ("÷", Binary),
+        "cdot" => ("⋅", Binary),
+        "ast" => ("∗", Binary),
+        "star" => ("⋆", Binary),
+        "circ" => ("∘", Binary),
+        "bullet" => ("∙", Binary),
+        "oplus" => ("⊕", Binary),
+        "ominus" => ("⊖", Binary),
+        "otimes" => ("⊗", Binary),
+        "odot" => ("⊙", Binary),
+        "cup" => ("∪", Binary),
+        "cap" => ("∩", Binary),
+        "setminus" => ("∖", Binary),
+        "wedge" | "land" => ("∧", Binary),
+        "vee" | "lor" => ("∨", Binary),
+        "leq" | "le" => ("≤", Relation),
+        "geq" | "ge" => ("≥", Relation),
+        "neq" | "ne" => ("≠", Relation),
+        "approx" => ("≈", Relation),
+        "equiv" => ("≡", Relation),
+        "sim" => ("∼", Relation),
+        "simeq" => ("≃", Relation),
+        "cong" => ("≅", Relation),
+        "propto" => ("∝", Relation),
+        "ll" => ("≪", Relation),
+        "gg" => ("≫", Relation),
+        "in" => ("∈", Relation),
+        "notin" => ("∉", Relation),
+        "ni" => ("∋", Relation),
+        "subset" => ("⊂", Relation),
+        "supset" => ("⊃", Relation),
+        "subseteq" => ("⊆", Relation),
+        "supseteq" => ("⊇", Relation),
+        "to" | "rightarrow" => ("→", Relation),
+        "leftarrow" | "gets" => ("←", Relation),
+        "leftrightarrow" => ("↔", Relation),
+        "Rightarrow" => ("⇒", Relation),
+        "Leftarrow" => ("⇐", Relation),
+        "Leftrightarrow" => ("⇔", Relation),
+        "implies" | "Longrightarrow" => ("⟹", Relation),
+        "iff" | "Longleftrightarrow" => ("⟺", Relation),
+        "longrightarrow" => ("⟶", Relation),
+        "longleftarrow" => ("⟵", Relation),
+        "mapsto" => ("↦", Relation),
+        "uparrow" => ("↑", Relation),
+        "downarrow" => ("↓", Relation),
+        "perp" => ("⊥", Relation),
+        "parallel" => ("∥", Relation),
+        "mid" => ("∣", Relation),
+        "models" => ("⊨", Relation),
+        "vdash" => ("⊢", Relation),
+        "sum" => ("∑", Large),
+        "prod" => ("∏", Large),
+        "coprod" => ("∐", Large),
+        "bigcup" => ("⋃", Large),
+        "bigcap" => ("⋂", Large),
+        "bigoplus" => ("⨁", Large),
+        "bigotimes" => ("⨂", Large),
+        "int" => ("∫", Integral),
+        "iint" => ("∬", Integral),
+        "iiint" => ("∭", Integral),
+        "oint" => ("∮", Integral),
+        "infty" => ("∞", Ordinary),
+        "partial" => ("∂", Ordinary),
+        "nabla" => ("∇", Ordinary),
+        "forall" => ("∀", Ordinary),
+        "exists" => ("∃", Ordinary),
+        "nexists" => ("∄", Ordinary),
+        "emptyset" | "varnothing" => ("∅", Ordinary),
+        "neg" | "lnot" => ("¬", Ordinary),
+        "angle" => ("∠", Ordinary),
+        "triangle" => ("△", Ordinary),
+        "ell" => ("ℓ", Ordinary),
+        "hbar" => ("ℏ", Ordinary),
+        "Re" => ("ℜ", Ordinary),
+        "Im" => ("ℑ", Ordinary),
+        "aleph" => ("ℵ", Ordinary),
+        "prime" => ("′", Ordinary),
+        "ldots" | "dots" => ("…", Ordinary),
+        "cdots" => ("⋯", Ordinary),
+        "vdots" => ("⋮", Ordinary),
+        "ddots" => ("⋱", Ordinary),
+        "dagger" => ("†", Ordinary),
+        "top" => ("⊤", Ordinary),
+        "bot" => ("⊥", Ordinary),
+        "circledast" => ("⊛", Ordinary),
+        "checkmark" => ("✓", Ordinary),
+        "langle" => ("⟨", Ordinary),
+        "rangle" => ("⟩", Ordinary),
+        "lfloor" => ("⌊", Ordinary),
+        "rfloor" => ("⌋", Ordinary),
+        "lceil" => ("⌈", Ordinary),
+        "rceil" => ("⌉", Ordinary),
+        "backslash" => ("\\", Ordinary),
+        "colon" => (":", Punctuation),
+        _ => return None,
+    })
+}
+
+fn double_struck(c: char) -> char {
+    match c {
+        'C' => 'ℂ',
+        'H' => 'ℍ',
+        'N' => 'ℕ',
+        'P' => 'ℙ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'Z' => 'ℤ',
+        'A'..='Z' => char::from_u32(0x1D538 + (c as u32 - 'A' as u32)).unwrap_or(c),
+        '0'..='9' => char::from_u32(0x1D7D8 + (c as u32 - '0' as u32)).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn script(c: char) -> char {
+    match c {
+        'B' => 'ℬ',
+        'E' => 'ℰ',
+        'F' => 'ℱ',
+        'H' => 'ℋ',
+        'I' => 'ℐ',
+        'L' => 'ℒ',
+        'M' => 'ℳ',
+        'R' => 'ℛ',
+        'A'..='Z' => char::from_u32(0x1D49C + (c as u32 - 'A' as u32)).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// A formula laid out: what to draw, from the left of its baseline, and how far it goes.
+#[derive(Debug, Default)]
+struct Layout {
+    width: f32,
+    /// How far it goes above the baseline.
+    ascent: f32,
+    /// How far it goes under the baseline.
+    descent: f32,
+    items: Vec<Item>,
+}
+
+#[derive(Debug)]
+enum Item {
+    /// Text, from the top of its line.
+    Text {
+        position: iced::Point,
+        content: String,
+        size: f32,
+        font: iced::Font,
+    },
+    Rule(iced::Rectangle),
+}
+
+/// The line height of the text of formulas, relative to its size.
+const LINE_HEIGHT: f32 = 1.2;
+/// Where the baseline falls in a line of text, relative to its size.
+const BASELINE: f32 = 0.93;
+/// The height of the math axis above the baseline, where fraction bars and operators are
+/// centered, relative to the size.
+const AXIS: f32 = 0.25;
+
+impl Layout {
+    fn moved(mut self, x: f32, y: f32) -> Vec<Item> {
+        for item in &mut self.items {
+            match item {
+                Item::Text { position, .. } => {
+                    position.x += x;
+                    position.y += y;
+                }
+                Item::Rule(bounds) => {
+                    bounds.x += x;
+                    bounds.y += y;
+                }
+            }
+        }
+        self.items
+    }
+}
+
+/// Lays out `node` for text of `size`, measuring text with `measure`. In `display` math,
+/// big operators are bigger and have their limits above and below them.
+fn layout(
+    node: &Node,
+    size: f32,
+    display: bool,
+    measure: &dyn Fn(&str, f32, iced::Font) -> f32,
+) -> Layout {
+    let thickness = (size / 16.0).max(1.0);
+    match node {
+        Node::Symbol(text, style, class) => {
+            let font = iced::Font {
+                family: iced::font::Family::Serif,
+                weight: match style {
+                    Style::Bold => iced::font::Weight::Bold,
+                    _ => iced::font::Weight::Normal,
+                },
+                style: match style {
+                    Style::Italic => iced::font::Style::Italic,
+                    _ => iced::font::Style::Normal,
+                },
+                ..iced::Font::DEFAULT
+            };
+            let (glyph, baseline) = match class {
+                Class::Large | Class::Integral => {
+                    let glyph = size * if display { 1.6 } else { 1.2 };
+                    // Centered on the axis rather than on the baseline.
+                    (glyph, glyph * 0.25 - size * AXIS)
+                }
+                _ => (size, 0.0),
+            };
+            let (ascent, descent) = match class {
+                Class::Large | Class::Integral => {
+                    (glyph * 0.5 + size * AXIS, glyph * 0.5 - size * AXIS)
+                }
+                _ => (size * 0.75, size * 0.22),
+            };
+            Layout {
+                width: measure(text, glyph, font),
+                ascent,
+                descent,
+                items: vec![Item::Text {
+                    position: iced::Point::new(0.0, baseline - glyph * BASELINE),
+                    content: text.clone(),
+                    size: glyph,
+                    font,
+                }],
+            }
+        }
+        Node::Row(nodes) => {
+            let mut row = Layout::default();
+            let mut previous: Option<Class> = None;
+            for (i, node) in nodes.iter().enumerate() {
+                let class = class(node);
+                // A sign at the start, or after another operator, isn't spaced.
+                let class = match (class, previous) {
+                    (
+                        Some(Class::Binary),
+                        None
+                        | Some(Class::Binary | Class::Relation | Class::Punctuation | Class::Large),
+                    ) => Some(Class::Ordinary),
+                    _ => class,
+                };
+                let last = i + 1 == nodes.len();
+                let (before, after) = match class {
+                    Some(Class::Binary) if !last => (0.22, 0.22),
+                    Some(Class::Relation) => (0.28, if last { 0.0 } else { 0.28 }),
+                    Some(Class::Punctuation | Class::Function | Class::Limits) if !last => {
+                        (0.0, 0.17)
+                    }
+                    Some(Class::Large | Class::Integral) if !last => (0.0, 0.1),
+                    _ => (0.0, 0.0),
+                };
+                let (before, after) = if i == 0 {
+                    (0.0, after)
+                } else {
+                    (before, after)
+                };
+                let child = layout(node, size, display, measure);
+                let x = row.width + before * size;
+                row.width = x + child.width + after * size;
+                row.ascent = row.ascent.max(child.ascent);
+                row.descent = row.descent.max(child.descent);
+                row.items.extend(child.moved(x, 0.0));
+                previous = class.or(previous);
+            }
+            row
+        }
+        Node::Fraction(numerator, denominator, bar) => {
+            let inner = if display {
+                size
+            } else {
+                (size * 0.75).max(8.0)
+            };
+            let numerator = layout(numerator, inner, false, measure);
+            let denominator = layout(denominator, inner, false, measure);
+            let axis = size * AXIS;
+            let gap = size * if display { 0.18 } else { 0.12 };
+            let padding = size * 0.1;
+            let width = numerator.width.max(denominator.width) + padding * 2.0;
+
+            let up = axis + thickness / 2.0 + gap + numerator.descent;
+            let down = -axis + thickness / 2.0 + gap + denominator.ascent;
+            let mut fraction = Layout {
+                width,
+                ascent: up + numerator.ascent,
+                descent: down + denominator.descent,
+                items: vec![],
+            };
+            if *bar {
+                fraction.items.push(Item::Rule(iced::Rectangle::new(
+                    iced::Point::new(padding / 2.0, -axis - thickness / 2.0),
+                    iced::Size::new(width - padding, thickness),
+                )));
+            }
+            let (x_numerator, x_denominator) = (
+                (width - numerator.width) / 2.0,
+                (width - denominator.width) / 2.0,
+            );
+            fraction.items.extend(numerator.moved(x_numerator, -up));
+            fraction
+                .items
+                .extend(denominator.moved(x_denominator, down));
+            fraction
+        }
+        Node::Scripts(base, sub, sup) => {
+            let limits = display && matches!(class(base), Some(Class::Large | Class::Limits));
+            let base = layout(base, size, display, measure);
+            let small = (size * 0.7).max(7.0);
+            let sub = sub.as_ref().map(|sub| layout(sub, small, false, measure));
+            let sup = sup.as_ref().map(|sup| layout(sup, small, false, measure));
+
+            if limits {
+                let gap = size * 0.1;
+                let width = base
+                    .width
+                    .max(sub.as_ref().map_or(0.0, |sub| sub.width))
+                    .max(sup.as_ref().map_or(0.0, |sup| sup.width));
+                let mut stack = Layout {
+                    width,
+                    ascent: base.ascent,
+                    descent: base.descent,
+                    items: vec![],
+                };
+                if let Some(sup) = sup {
+                    let up = base.ascent + gap + sup.descent;
+                    stack.ascent = up + sup.ascent;
+                    let x = (width - sup.width) / 2.0;
+                    stack.items.extend(sup.moved(x, -up));
+                }
+                if let Some(sub) = sub {
+                    let down = base.descent + gap + sub.ascent;
+                    stack.descent = down + sub.descent;
+                    let x = (width - sub.width) / 2.0;
+                    stack.items.extend(sub.moved(x, down));
+                }
+                let x = (width - base.width) / 2.0;
+                stack.items.extend(base.moved(x, 0.0));
+                return stack;
+            }
+
+            let mut up = (base.ascent - size * 0.35).max(size * 0.4);
+            let mut down = (base.descent + size * 0.05).max(size * 0.2);
+            if let (Some(sub), Some(sup)) = (&sub, &sup) {
+                // Keeps a gap between the superscript and the subscript.
+                let gap = (down - sub.ascent) + (up - sup.descent);
+                if gap < size * 0.15 {
+                    down += (size * 0.15 - gap) / 2.0;
+                    up += (size * 0.15 - gap) / 2.0;
+                }
+            }
+            let x = base.width + size * 0.05;
+            let mut scripts = Layout {
+                width: x,
+                ascent: base.ascent,
+                descent: base.descent,
+                items: base.items,
+            };
+            if let Some(sup) = sup {
+                scripts.width = scripts.width.max(x + sup.width);
+                scripts.ascent = scripts.ascent.max(up + sup.ascent);
+                scripts.items.extend(sup.moved(x, -up));
+            }
+            if let Some(sub) = sub {
+                scripts.width = scripts.width.max(x + sub.width);
+                scripts.descent = scripts.descent.max(down + sub.descent);
+                scripts.items.extend(sub.moved(x, down));
+            }
+            scripts
+        }
+        Node::Root(degree, radicand) => {
+            let radicand = layout(radicand, size, display, measure);
+            let gap = size * 0.12;
+            // The sign goes from the top of the bar to the bottom of the radicand, and
+            // stands a fifth of its size under the baseline.
+            let top = radicand.ascent + gap + thickness;
+            let glyph = (top + radicand.descent).max(size);
+            let baseline = radicand.descent - glyph * 0.2;
+            let font = iced::Font {
+                family: iced::font::Family::Serif,
+                ..iced::Font::DEFAULT
+            };
+            let sign = measure("√", glyph, font);
+
+            let degree = degree
+                .as_ref()
+                .map(|degree| layout(degree, (size * 0.55).max(7.0), false, measure));
+            let x = degree
+                .as_ref()
+                .map_or(0.0, |degree| (degree.width - sign * 0.5).max(0.0));
+            let mut root = Layout {
+                width: x + sign + radicand.width + size * 0.1,
+                ascent: top,
+                descent: radicand.descent.max(baseline + glyph * 0.2),
+                items: vec![
+                    Item::Text {
+                        position: iced::Point::new(x, baseline - glyph * BASELINE),
+                        content: "√".into(),
+                        size: glyph,
+                        font,
+                    },
+                    Item::Rule(iced::Rectangle::new(
+                        iced::Point::new(x + sign * 0.95, -top),
+                        iced::Size::new(radicand.width + sign * 0.05 + size * 0.1, thickness),
+                    )),
+                ],
+            };
+            if let Some(degree) = degree {
+                let up = glyph * 0.35 - baseline + degree.descent;
+                root.ascent = root.ascent.max(up + degree.ascent);
+                let x = x + sign * 0.5 - degree.width;
+                root.items.extend(degree.moved(x, -up));
+            }
+            root.items.extend(radicand.moved(x + sign, 0.0));
+            root
+        }
+        Node::Delimited(left, inner, right) => {
+            let inner = layout(inner, size, display, measure);
+            delimit(left, inner, right, size, measure)
+        }
+        Node::Matrix(rows, left, right, columns) => {
+            let cells: Vec<Vec<Layout>> = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| layout(cell, size, false, measure))
+                        .collect()
+                })
+                .collect();
+            let count = cells.iter().map(Vec::len).max().unwrap_or(0);
+            let widths: Vec<f32> = (0..count)
+                .map(|column| {
+                    cells
+                        .iter()
+                        .filter_map(|row| row.get(column))
+                        .map(|cell| cell.width)
+                        .fold(0.0, f32::max)
+                })
+                .collect();
+            let gap = |column: usize| match columns {
+                Columns::Aligned if column % 2 == 1 => 0.0,
+                Columns::Aligned => size * 1.0,
+                Columns::Left => size * 1.0,
+                Columns::Center => size * 0.8,
+            };
+            let lines = size * 0.25;
+            let height: f32 = cells
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| cell.ascent)
+                        .fold(size * 0.75, f32::max)
+                        + row
+                            .iter()
+                            .map(|cell| cell.descent)
+                            .fold(size * 0.22, f32::max)
+                })
+                .sum::<f32>()
+                + lines * cells.len().saturating_sub(1) as f32;
+
+            let mut matrix = Layout {
+                width: widths.iter().sum::<f32>()
+                    + (1..count).map(|column| gap(column - 1)).sum::<f32>(),
+                ascent: height / 2.0 + size * AXIS,
+                descent: height / 2.0 - size * AXIS,
+                items: vec![],
+            };
+            let mut y = -matrix.ascent;
+            for row in cells {
+                let ascent = row
+                    .iter()
+                    .map(|cell| cell.ascent)
+                    .fold(size * 0.75, f32::max);
+                let descent = row
+                    .iter()
+                    .map(|cell| cell.descent)
+                    .fold(size * 0.22, f32::max);
+                let mut x = 0.0;
+                for (column, cell) in row.into_iter().enumerate() {
+                    let free = widths[column] - cell.width;
+                    let offset = match columns {
+                        Columns::Aligned if column % 2 == 0 => free,
+                        Columns::Aligned | Columns::Left => 0.0,
+                        Columns::Center => free / 2.0,
+                    };
+                    matrix.items.extend(cell.moved(x + offset, y + ascent));
+                    x += widths[column] + gap(column);
+                }
+                y += ascent + descent + lines;
+            }
+            delimit(left, matrix, right, size, measure)
+        }
+        Node::Accent(base, accent) => {
+            let base = layout(base, size, display, measure);
+            let gap = size * 0.08;
+            let mut accented = Layout {
+                width: base.width,
+                ascent: base.ascent,
+                descent: base.descent,
+                items: vec![],
+            };
+            match accent {
+                Accent::Overline => {
+                    accented.ascent = base.ascent + gap + thickness;
+                    accented.items.push(Item::Rule(iced::Rectangle::new(
+                        iced::Point::new(0.0, -accented.ascent),
+                        iced::Size::new(base.width, thickness),
+                    )));
+                }
+                Accent::Underline => {
+                    accented.descent = base.descent + gap + thickness;
+                    accented.items.push(Item::Rule(iced::Rectangle::new(
+                        iced::Point::new(0.0, base.descent + gap),
+                        iced::Size::new(base.width, thickness),
+                    )));
+                }
+                _ => {
+                    // The glyphs and how far their ink stands above their baseline.
+                    let (text, bottom, top) = match accent {
+                        Accent::Hat => ("ˆ", 0.56, 0.76),
+                        Accent::Tilde => ("˜", 0.58, 0.72),
+                        Accent::Dot => ("˙", 0.58, 0.72),
+                        Accent::Dots => ("¨", 0.58, 0.72),
+                        _ => ("→", 0.15, 0.45),
+                    };
+                    let glyph = size * 0.9;
+                    let font = iced::Font {
+                        family: iced::font::Family::Serif,
+                        ..iced::Font::DEFAULT
+                    };
+                    let width = measure(text, glyph, font);
+                    let baseline = -(base.ascent + gap) + glyph * bottom;
+                    accented.ascent = base.ascent + gap + glyph * (top - bottom);
+                    accented.width = base.width.max(width);
+                    accented.items.push(Item::Text {
+                        position: iced::Point::new(
+                            (base.width - width) / 2.0,
+                            baseline - glyph * BASELINE,
+                        ),
+                        content: text.into(),
+                        size: glyph,
+                        font,
+                    });
+                }
+            }
+            let x = (accented.width - base.width) / 2.0;
+            accented.items.extend(base.moved(x, 0.0));
+            accented
+        }
+        Node::Space(ems) => Layout {
+            width: ems * size,
+            ..Layout::default()
+        },
+    }
+}
+
+/// Puts `inner` between delimiters as tall as it is, centered on the axis.
+fn delimit(
+    left: &str,
+    inner: Layout,
+    right: &str,
+    size: f32,
+    measure: &dyn Fn(&str, f32, iced::Font) -> f32,
+) -> Layout {
+    let axis = size * AXIS;
+    let half = (inner.ascent - axis).max(inner.descent + axis) + size * 0.1;
+    let height = (half * 2.0).max(size);
+    let font = iced::Font {
+        family: iced::font::Family::Serif,
+        ..iced::Font::DEFAULT
+    };
+    let thickness = (size / 16.0).max(1.0);
+
+    // Straight delimiters are drawn as rules, so that they don't get wider as they grow.
+    let delimiter = |text: &str| -> Layout {
+        let top = -axis - height / 2.0;
+        let width = size * 0.3;
+        let rule = |x: f32, y: f32, width: f32, height: f32| {
+            Item::Rule(iced::Rectangle::new(
+                iced::Point::new(x, y),
+                iced::Size::new(width, height),
+            ))
+        };
+        let items = match text {
+            "" => vec![],
+            "|" => vec![rule(width / 2.0, top, thickness, height)],
+            "‖" => vec![
+                rule(width / 2.0 - thickness * 1.5, top, thickness, height),
+                rule(width / 2.0 + thickness * 1.5, top, thickness, height),
+            ],
+            "[" | "⌊" | "⌈" => vec![
+                rule(width * 0.35, top, thickness, height),
+                rule(width * 0.35, top, width * 0.5, thickness),
+                rule(
+                    width * 0.35,
+                    top + height - thickness,
+                    width * 0.5,
+                    thickness,
+                ),
+            ]
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| match text {
+                "⌊" => *i != 1,
+                "⌈" => *i != 2,
+                _ => true,
+            })
+            .map(|(_, item)| item)
+            .collect(),
+            "]" | "⌋" | "⌉" => vec![
+                rule(width * 0.65 - thickness, top, thickness, height),
+                rule(width * 0.15, top, width * 0.5, thickness),
+                rule(
+                    width * 0.15,
+                    top + height - thickness,
+                    width * 0.5,
+                    thickness,
+                ),
+            ]
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| match text {
+                "⌋" => *i != 1,
+                "⌉" => *i != 2,
+                _ => true,
+            })
+            .map(|(_, item)| item)
+            .collect(),
+            _ => {
+                // Glyphs are centered on the axis, their ink covering about their size.
+                let glyph = height;
+                let baseline = -axis + glyph * 0.25;
+                return Layout {
+                    width: measure(text, glyph, font),
+                    ascent: axis + glyph / 2.0,
+                    descent: glyph / 2.0 - axis,
+                    items: vec![Item::Text {
+                        position: iced::Point::new(0.0, baseline - glyph * BASELINE),
+                        content: text.into(),
+                        size: glyph,
+                        font,
+                    }],
+                };
+            }
+        };
+        Layout {
+            width: if items.is_empty() { 0.0 } else { width },
+            ascent: axis + height / 2.0,
+            descent: height / 2.0 - axis,
+            items,
+        }
+    };
+
+    let left = delimiter(left);
+    let right = delimiter(right);
+    let x = left.width;
+    let width = left.width + inner.width + right.width;
+    let ascent = inner.ascent.max(left.ascent).max(right.ascent);
+    let descent = inner.descent.max(left.descent).max(right.descent);
+    let inner_width = inner.width;
+    let mut items = left.items;
+    items.extend(inner.moved(x, 0.0));
+    items.extend(right.moved(x + inner_width, 0.0));
+    Layout {
+        width,
+        ascent,
+        descent,
+        items,
+    }
+}
+
+/// The class of a node, for the spacing around it: the class of its symbol or of the base of
+/// its scripts.
+fn class(node: &Node) -> Option<Class> {
+    match node {
+        Node::Symbol(_, _, class) => Some(*class),
+        Node::Scripts(base, _, _) => class(base),
+        _ => None,
+    }
+}
+
+/// How wide `content` is in `font` at `size`.
+fn measure(content: &str, size: f32, font: iced::Font) -> f32 {
+    use iced::advanced::text::Paragraph;
+
+    <iced::Renderer as iced::advanced::text::Renderer>::Paragraph::with_text(iced::advanced::Text {
+        content,
+        bounds: iced::Size::INFINITY,
+        size: size.into(),
+        line_height: iced::widget::text::LineHeight::Relative(LINE_HEIGHT),
+        font,
+        horizontal_alignment: iced::alignment::Horizontal::Left,
+        vertical_alignment: iced::alignment::Vertical::Top,
+        shaping: iced::widget::text::Shaping::Advanced,
+        wrapping: iced::widget::text::Wrapping::None,
+    })
+    .min_bounds()
+    .width
+}
+
+/// `node` as an SVG for text of `size`, in the color of the text around it. Inline formulas
+/// sit on the baseline of that text.
+pub fn svg(node: &Node, size: f32, display: bool) -> String {
+    let layout = layout(node, size, display, &measure);
+    let width = layout.width.max(0.0);
+    let height = layout.ascent + layout.descent;
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{:.1}" height="{:.1}" viewBox="0 {:.1} {:.1} {:.1}" fill="currentColor" style="vertical-align: {:.1}px">"#,
+        width, height, -layout.ascent, width, height, -layout.descent
+    );
+    for item in &layout.items {
+        match item {
+            Item::Text {
+                position,
+                content,
+                size,
+                font,
+            } => {
+                let family = match font.family {
+                    iced::font::Family::Name(name) => name,
+                    iced::font::Family::SansSerif => "sans-serif",
+                    iced::font::Family::Monospace => "monospace",
+                    _ => "serif",
+                };
+                svg.push_str(&format!(
+                    r#"<text x="{:.1}" y="{:.1}" font-family="{}" font-size="{:.1}"{}{}>{}</text>"#,
+                    position.x,
+                    position.y + size * BASELINE,
+                    family,
+                    size,
+                    if font.style == iced::font::Style::Italic {
+                        r#" font-style="italic""#
+                    } else {
+                        ""
+                    },
+                    if font.weight == iced::font::Weight::Bold {
+                        r#" font-weight="bold""#
+                    } else {
+                        ""
+                    },
+                    crate::util::escape(content)
+                ));
+            }
+            Item::Rule(rule) => svg.push_str(&format!(
+                r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}"/>"#,
+                rule.x, rule.y, rule.width, rule.height
+            )),
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// A formula of the preview, laid out once for its size.
+pub struct Formula<'a> {
+    node: &'a Node,
+    source: &'a str,
+    size: f32,
+    display: bool,
+}
+
+/// Shows `node`, read from `source`, for text of `size`. Inline formulas are padded so that
+/// their baseline lines up with the text around them when centered on its line.
+pub fn formula<'a>(node: &'a Node, source: &'a str, size: f32, display: bool) -> Formula<'a> {
+    Formula {
+        node,
+        source,
+        size,
+        display,
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// The source, the size and the kind of the formula laid out.
+    key: (String, u32, bool),
+    layout: Layout,
+}
+
+impl<Message> iced::advanced::Widget<Message, iced::Theme, iced::Renderer> for Formula<'_> {
+    fn tag(&self) -> iced::advanced::widget::tree::Tag {
+        iced::advanced::widget::tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> iced::advanced::widget::tree::State {
+        iced::advanced::widget::tree::State::new(State::default())
+    }
+
+    fn size(&self) -> iced::Size<iced::Length> {
+        iced::Size::new(iced::Length::Shrink, iced::Length::Shrink)
+    }
+
+    fn layout(
+        &self,
+        tree: &mut iced::advanced::widget::Tree,
+        _renderer: &iced::Renderer,
+        limits: &iced::advanced::layout::Limits,
+    ) -> iced::advanced::layout::Node {
+        let state = tree.state.downcast_mut::<State>();
+        let key = (self.source.to_string(), self.size.to_bits(), self.display);
+        if state.key != key {
+            let mut layout = layout(self.node, self.size, self.display, &measure);
+            if !self.display {
+                // Text is centered on its line with its baseline about a third of its size
+                // under the middle.
+                let middle = self.size * 0.35;
+                let up = layout.ascent.max(layout.descent + middle * 2.0);
+                let down = layout.descent.max(layout.ascent - middle * 2.0);
+                layout.ascent = up;
+                layout.descent = down;
+            }
+            *state = State { key, layout };
+        }
+        let size = iced::Size::new(
+            state.layout.width.max(0.0),
+            state.layout.ascent + state.layout.descent,
+        );
+        iced::advanced::layout::Node::new(limits.resolve(
+            iced::Length::Shrink,
+            iced::Length::Shrink,
+            size,
+        ))
+    }
+
+    fn draw(
+        &self,
+        tree: &iced::advanced::widget::Tree,
+        renderer: &mut iced::Renderer,
+        theme: &iced::Theme,
+        _style: &iced::advanced::renderer::Style,
+        layout: iced::advanced::Layout<'_>,
+        _cursor: iced::mouse::Cursor,
+        viewport: &iced::Rectangle,
+    ) {
+        use iced::advanced::text::Renderer as _;
+        use iced::advanced::Renderer as _;
+
+        let state = tree.state.downcast_ref::<State>();
+        let bounds = layout.bounds();
+        let origin = iced::Vector::new(bounds.x, bounds.y + state.layout.ascent);
+        let color = theme.palette().text;
+        for item in &state.layout.items {
+            match item {
+                Item::Text {
+                    position,
+                    content,
+                    size,
+                    font,
+                } => renderer.fill_text(
+                    iced::advanced::Text {
+                        content: content.clone(),
+                        bounds: iced::Size::INFINITY,
+                        size: (*size).into(),
+                        line_height: iced::widget::text::LineHeight::Relative(LINE_HEIGHT),
+                        font: *font,
+                        horizontal_alignment: iced::alignment::Horizontal::Left,
+                        vertical_alignment: iced::alignment::Vertical::Top,
+                        shaping: iced::widget::text::Shaping::Advanced,
+                        wrapping: iced::widget::text::Wrapping::None,
+                    },
+                    *position + origin,
+                    color,
+                    *viewport,
+                ),
+                Item::Rule(rule) => renderer.fill_quad(
+                    iced::advanced::renderer::Quad {
+                        bounds: *rule + origin,
+                        ..Default::default()
+                    },
+                    color,
+                ),
+            }
+        }
+    }
+}
+
+impl<'a, Message: 'a> From<Formula<'a>> for iced::Element<'a, Message> {
+    fn from(formula: Formula<'a>) -> Self {
+        iced::Element::new(formula)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The formulas `find` reads in `source`, as written.
+    fn found(source: &str) -> Vec<&str> {
+        find(source)
+            .into_iter()
+            .map(|(range, _)| &source[range])
+            .collect()
+    }
+
+    #[test]
+    fn inline_and_display_math() {
+        assert_eq!(find("a $x$ b $$\ny\n$$"), [(2..5, false), (8..15, true)]);
+        assert_eq!(found("$a\nb$ and $$ $$"), ["$a\nb$"]);
+        assert!(found("$a\n\nb$").is_empty());
+    }
+
+    #[test]
+    fn prices_and_escapes_are_not_math() {
+        assert!(found("costs $5 and $10").is_empty());
+        assert!(found("$ x$ and $x $ too").is_empty());
+        assert!(found(r"\$x$ and \\").is_empty());
+        assert_eq!(found(r"$a\$b$"), [r"$a\$b$"]);
+    }
+
+    #[test]
+    fn code_is_skipped() {
+        assert_eq!(found("`$a$` ``x ` $b$`` $c$"), ["$c$"]);
+        assert_eq!(found("```\n$a$\n```\n$b$"), ["$b$"]);
+        assert_eq!(found("text\n\n    $a$\n\t$b$\n\n$c$"), ["$c$"]);
+        // Indented lines continue paragraphs and list items.
+        assert_eq!(found("text\n    $a$"), ["$a$"]);
+        assert_eq!(found("- item\n\n    $a$"), ["$a$"]);
+    }
+
+    #[test]
+    fn links_are_skipped() {
+        assert_eq!(
+            found("[$a$](https://host/$b$/(c)) <https://host/$d$> $e$"),
+            ["$a$", "$e$"]
+        );
+        assert_eq!(found("[id]: https://host/$a$\n[^1]: $b$"), ["$b$"]);
+        assert_eq!(found("[a](b $c$"), ["$c$"]);
+    }
+
+    /// The nodes of the formula `source`.
+    fn nodes(source: &str) -> Vec<Node> {
+        match parse(source) {
+            Ok(Node::Row(nodes)) => nodes,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn formulas_are_parsed() {
+        assert!(matches!(
+            nodes(r"\frac{a}{b}")[..],
+            [Node::Fraction(_, _, true)]
+        ));
+        assert!(matches!(
+            nodes("x_1^2")[..],
+            [Node::Scripts(_, Some(_), Some(_))]
+        ));
+        assert!(matches!(
+            nodes(r"\sqrt[3]{x}")[..],
+            [Node::Root(Some(_), _)]
+        ));
+        assert!(matches!(
+            nodes(r"\alpha + 1")[..],
+            [
+                Node::Symbol(_, Style::Italic, Class::Ordinary),
+                Node::Symbol(_, _, Class::Binary),
+                Node::Symbol(_, Style::Upright, Class::Ordinary)
+            ]
+        ));
+    }
+
+    #[test]
+    fn errors_say_what_is_wrong() {
+        assert_eq!(parse("{x").unwrap_err(), "Missing }");
+        assert_eq!(parse("x}").unwrap_err(), "Unexpected }");
+        assert_eq!(parse(r"\nothing").unwrap_err(), r"Unknown command \nothing");
+        assert_eq!(
+            parse(r"\begin{matrix} a").unwrap_err(),
+            r"\begin{matrix} without \end"
+        );
+        assert_eq!(parse(r"\left( x").unwrap_err(), r"\left without \right");
+    }
+}
@@ -1,18 +1,91 @@
 pub struct NoteEditor {
     pub path: std::path::PathBuf,
     display_name: String,
+    config: crate::config::Configuration,
+    cursor: Option<(usize, usize)>,
+    /// The history to pick up once the note has loaded.
+    history: Option<crate::history::History>,
+    /// Where the note is folded once it has loaded, by line of the note.
+    folds: Vec<usize>,
+    /// Whether the outline is shown once the note has loaded.
+    outline: bool,
     state: crate::util::Query<InternalState, String>,
 }
 
 struct InternalState {
     view_mode: ViewMode,
     content: iced::widget::text_editor::Content,
-    preview: Vec<iced::widget::markdown::Item>,
+    preview: crate::preview::Preview,
+    panes: iced::widget::pane_grid::State<Pane>,
+    synced_line: Option<usize>,
+    /// Whether there are edits not saved to the file yet.
+    dirty: bool,
+    find: Option<FindBar>,
+    history: crate::history::History,
+    /// Whether the outline panel is shown.
+    outline: bool,
+    folds: crate::folding::Folds,
+    /// The size of the new table under the mouse, while the table menu is open.
+    table_menu: Option<(usize, usize)>,
+    /// The image shown over the note, clicked in the preview.
+    lightbox: Option<std::path::PathBuf>,
+    /// The notes embedded in the preview, and in those, as they load.
+    embeds: std::collections::HashMap<crate::embeds::Embed, crate::util::Query<Embedded, String>>,
+    /// The revision of the preview the embedded notes were last looked for in, if they're
+    /// still up to date.
+    embeds_read: Option<usize>,
 }
 
+/// The lines of a note with folds, read from the editor or from the folds hiding them.
+struct Document<'a> {
+    content: &'a iced::widget::text_editor::Content,
+    folds: &'a crate::folding::Folds,
+}
+
+impl crate::preview::Lines for Document<'_> {
+    fn line(&self, index: usize) -> Option<String> {
+        match self.folds.line(index) {
+            Ok(line) => self.content.line(line).map(|line| line.to_string()),
+            Err(hidden) => Some(hidden.to_string()),
+        }
+    }
+}
+
+/// A note embedded in the preview, or the section of it asked for.
+struct Embedded {
+    path: std::path::PathBuf,
+    preview: crate::preview::Preview,
+}
+
+/// What a note keeps while other notes are open.
+pub struct Kept {
+    history: crate::history::History,
+    /// The lines of the note where folds start.
+    folds: Vec<usize>,
+}
+
+/// The find bar of a note, with the replace field when replacing.
+struct FindBar {
+    query: crate::find::Query,
+    replace: Option<String>,
+    /// The matches, each with its line in the editor and its byte columns.
+    matches: Vec<(usize, std::ops::Range<usize>)>,
+    /// The match selected in the editor.
+    current: Option<usize>,
+    error: Option<String>,
+}
+
+#[derive(PartialEq)]
 enum ViewMode {
     Edit,
     Preview,
+    Split,
+}
+
+/// The panes shown side by side in [`ViewMode::Split`].
+enum Pane {
+    Editor,
+    Preview,
 }
 
 #[derive(Debug, Clone)]
@@ -21,91 +94,426 @@ pub enum Message {
     Edit(iced::widget::text_editor::Action),
     Loaded(Result<String, std::io::ErrorKind>),
     SwitchMode,
+    SwitchSplit,
+    ResizeSplit(iced::widget::pane_grid::ResizeEvent),
+    CopyCode(String),
+    ToggleTask(usize),
+    Save,
+    Saved(Result<(), std::io::ErrorKind>),
+    /// Opens the find bar, with the replace field if `true`.
+    OpenFind(bool),
+    CloseFind,
+    FindText(String),
+    ReplaceText(String),
+    SetCaseSensitive(bool),
+    SetWholeWord(bool),
+    SetRegex(bool),
+    FindNext,
+    FindPrevious,
+    ReplaceOne,
+    ReplaceAll,
+    Undo,
+    Redo,
+    HistoryLoaded(Option<crate::history::History>),
+    ToggleOutline,
+    /// Moves to a line picked in the outline.
+    GoToLine(usize),
+    /// Folds the section holding the cursor.
+    Fold,
+    /// Unfolds the section at the cursor.
+    Unfold,
+    FoldAll,
+    UnfoldAll,
+    /// Folds or unfolds the section at a line of the note.
+    ToggleFold(usize),
+    /// On Tab, moves to the next cell of a table or nests the list items of the selection.
+    Indent,
+    /// On Shift-Tab, moves to the previous cell of a table or takes the list items of the
+    /// selection out a level.
+    Outdent,
+    ToggleTableMenu,
+    /// The size of the new table under the mouse in the table menu, in rows and columns.
+    HoverTableSize(usize, usize),
+    InsertTable(usize, usize),
+    EditTable(crate::tables::Change),
+    /// On Ctrl-V, pastes the text on the clipboard, or the image on it as an attachment.
+    Paste,
+    ImagePasted(Option<Vec<u8>>),
+    FileDropped(std::path::PathBuf),
+    /// The path of an image stored in the attachments folder, relative to the note.
+    AttachmentSaved(Result<String, std::io::ErrorKind>),
+    /// Shows an image of the preview in the lightbox.
+    OpenImage(std::path::PathBuf),
+    CloseImage,
+    EmbedLoaded(
+        crate::embeds::Embed,
+        Result<(std::path::PathBuf, String), String>,
+    ),
+    /// Opens another note, embedded in this one.
+    OpenNote(std::path::PathBuf),
+    /// Exports the note to HTML.
+    Export,
 }
 
+const PREVIEW: &str = "note-preview";
+const FIND: &str = "note-find";
+const EDITOR: &str = "note-editor";
+/// The line of the gutter beside the line with the cursor.
+const CURSOR_LINE: &str = "note-cursor-line";
+/// The width of the gutter holding the fold markers.
+const GUTTER: f32 = 18.0;
+/// The height of a line of the editor, which the fold markers are placed by.
+const LINE_HEIGHT: f32 = 21.0;
+
 impl NoteEditor {
+    /// Opens the note at `path`, with what it `kept` when it was last left. Without it, the
+    /// history kept on disk is read if enabled.
     pub fn from_path(
         path: std::path::PathBuf,
         display_name: String,
+        config: &crate::config::Configuration,
+        kept: Option<Kept>,
     ) -> (Self, iced::Task<Message>) {
+        let (history, folds) = match kept {
+            Some(kept) => (Some(kept.history), kept.folds),
+            None => (None, vec![]),
+        };
+        let load_history = if history.is_none() && config.persist_undo {
+            iced::Task::perform(crate::history::load(path.clone()), Message::HistoryLoaded)
+        } else {
+            iced::Task::none()
+        };
         (
             Self {
                 path: path.clone(),
                 display_name: display_name.clone(),
+                config: config.clone(),
+                cursor: None,
+                history,
+                folds,
+                outline: false,
                 state: crate::util::Query::Pending,
             },
-            iced::Task::perform(crate::util::read_file(path), Message::Loaded),
+            iced::Task::batch([
+                iced::Task::perform(crate::util::read_file(path), Message::Loaded),
+                load_history,
+            ]),
         )
     }
 
-    pub fn view(self: &Self) -> iced::Element<'_, Message> {
+    /// Places the cursor at the given line and byte column once the note has loaded.
+    pub fn place_cursor(&mut self, line: usize, column: usize) {
+        self.cursor = Some((line, column));
+    }
+
+    pub fn outline_shown(&self) -> bool {
+        match &self.state {
+            crate::util::Query::Loaded(state) => state.outline,
+            _ => self.outline,
+        }
+    }
+
+    pub fn show_outline(&mut self, shown: bool) {
+        self.outline = shown;
+        if let crate::util::Query::Loaded(state) = &mut self.state {
+            state.outline = shown;
+            state.refresh_preview();
+        }
+    }
+
+    /// Reads the note again after it was changed on disk, keeping the cursor where it was.
+    /// A note with edits not saved yet is kept as is, since reading it would lose them.
+    pub fn reload(&mut self) -> iced::Task<Message> {
+        if let crate::util::Query::Loaded(state) = &mut self.state {
+            if state.dirty {
+                eprintln!("Not reloading {:?}, which has unsaved edits", self.path);
+                return iced::Task::none();
+            }
+            self.cursor = Some(state.cursor());
+            state.history.reload(snapshot(&state.content, &state.folds));
+            self.history = Some(std::mem::take(&mut state.history));
+            self.folds = state.folds.note_lines();
+        }
+        self.state = crate::util::Query::Pending;
+        iced::Task::perform(crate::util::read_file(self.path.clone()), Message::Loaded)
+    }
+
+    /// Drops the embedded notes read from `paths`, which changed on disk, and the ones that
+    /// couldn't be read, to read them again.
+    pub fn forget_embeds(&mut self, paths: &[&std::path::Path]) -> iced::Task<Message> {
+        let crate::util::Query::Loaded(state) = &mut self.state else {
+            return iced::Task::none();
+        };
+        let count = state.embeds.len();
+        state.embeds.retain(|_, embedded| match embedded {
+            crate::util::Query::Loaded(embedded) => !paths.contains(&embedded.path.as_path()),
+            crate::util::Query::Error(_) => false,
+            crate::util::Query::Pending => true,
+        });
+        if state.embeds.len() == count {
+            return iced::Task::none();
+        }
+        state.embeds_read = None;
+        state.load_embeds(&self.config)
+    }
+
+    /// Takes what the note keeps when leaving it, to give it back when it's opened again.
+    pub fn leave(&mut self) -> Option<Kept> {
+        let crate::util::Query::Loaded(state) = &mut self.state else {
+            return None;
+        };
+        let mut history = std::mem::take(&mut state.history);
+        history.leave(&state.file_text());
+        let folds = state.folds.note_lines();
+        (!history.is_empty() || !folds.is_empty()).then_some(Kept { history, folds })
+    }
+
+    /// Writes the note to its file if it was edited since the last save, along with its
+    /// history if it's kept on disk.
+    pub fn save(&mut self) -> iced::Task<Message> {
+        match &mut self.state {
+            crate::util::Query::Loaded(state) if state.dirty => {
+                state.dirty = false;
+                let text = state.file_text();
+                let history = if self.config.persist_undo {
+                    let mut history = state.history.clone();
+                    history.leave(&text);
+                    iced::Task::future(crate::history::store(self.path.clone(), history)).discard()
+                } else {
+                    iced::Task::none()
+                };
+                iced::Task::batch([
+                    iced::Task::perform(
+                        crate::util::write_file(self.path.clone(), text),
+                        Message::Saved,
+                    ),
+                    history,
+                ])
+            }
+            _ => iced::Task::none(),
+        }
+    }
+
+    pub fn view(&self) -> iced::Element<'_, Message> {
         match &self.state {
             crate::util::Query::Pending => iced::widget::Text::new("Loading...").into(),
             crate::util::Query::Loaded(InternalState {
                 content,
                 preview,
                 view_mode,
+                panes,
+                find,
+                outline,
+                folds,
+                table_menu,
+                lightbox,
+                embeds,
+                ..
             }) => {
+                let query = find.as_ref().map(|find| &find.query);
+                let editor = || editor(content, query, folds);
                 let main_body: iced::Element<'_, Message> = match view_mode {
-                    ViewMode::Edit => iced::widget::TextEditor::new(&content)
-                        .style(
-                            |theme: &iced::Theme, status| iced::widget::text_editor::Style {
-                                border: iced::Border {
-                                    width: 0.0,
-                                    ..Default::default()
-                                },
-                                ..iced::widget::text_editor::default(theme, status)
-                            },
-                        )
-                        .padding(0)
-                        .height(iced::Length::Fill)
-                        .on_action(Message::Edit)
-                        .into(),
-                    ViewMode::Preview => iced::widget::markdown::view(
-                        preview,
-                        iced::widget::markdown::Settings::default(),
-                        iced::widget::markdown::Style::from_palette(
-                            iced::Theme::TokyoNightStorm.palette(),
-                        ),
-                    )
-                    .map(Message::None)
+                    ViewMode::Edit => editor(),
+                    ViewMode::Preview => preview_view(preview, &self.path, &self.config, embeds),
+                    ViewMode::Split => iced::widget::pane_grid(panes, |_pane, kind, _maximized| {
+                        iced::widget::pane_grid::Content::new(match kind {
+                            Pane::Editor => editor(),
+                            Pane::Preview => {
+                                preview_view(preview, &self.path, &self.config, embeds)
+                            }
+                        })
+                    })
+                    .spacing(8)
+                    .on_resize(8, Message::ResizeSplit)
                     .into(),
                 };
 
-                iced::widget::column![
-                    iced::widget::text(self.display_name.clone()).size(24),
+                let tasks = preview.tasks();
+                let progress = (tasks.1 > 0).then(|| {
+                    iced::widget::text(format!("{} of {} done", tasks.0, tasks.1))
+                        .size(12)
+                        .style(|theme: &iced::Theme| iced::widget::text::Style {
+                            color: Some(iced::Color {
+                                a: 0.6,
+                                ..theme.palette().text
+                            }),
+                        })
+                });
+
+                let main_body: iced::Element<'_, Message> = if *outline {
+                    let headings = preview.outline();
+                    let line = folds.note_line(content.cursor_position().0);
+                    let current = headings.iter().rposition(|heading| heading.line <= line);
+                    let folded = headings
+                        .iter()
+                        .map(|heading| {
+                            folds
+                                .editor_line(heading.line)
+                                .is_some_and(|line| folds.is_folded(line))
+                        })
+                        .collect();
+                    iced::widget::row![outline_view(headings, folded, current), main_body]
+                        .spacing(8)
+                        .into()
+                } else {
+                    main_body
+                };
+
+                let note = iced::widget::column![
+                    iced::widget::row![
+                        iced::widget::text(self.display_name.clone()).size(24),
+                        iced::widget::horizontal_space(),
+                        iced::widget::button("Table")
+                            .style(if table_menu.is_some() {
+                                crate::util::button_no_bg_active
+                            } else {
+                                crate::util::button_secondary
+                            })
+                            .on_press(Message::ToggleTableMenu),
+                        iced::widget::button("Fold all")
+                            .style(crate::util::button_secondary)
+                            .on_press(Message::FoldAll),
+                        iced::widget::button("Unfold all")
+                            .style(crate::util::button_secondary)
+                            .on_press_maybe((!folds.is_empty()).then_some(Message::UnfoldAll)),
+                        iced::widget::button("Export")
+                            .style(crate::util::button_secondary)
+                            .on_press(Message::Export),
+                        iced::widget::button("Outline")
+                            .style(if *outline {
+                                crate::util::button_no_bg_active
+                            } else {
+                                crate::util::button_secondary
+                            })
+                            .on_press(Message::ToggleOutline),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                    iced::widget::Column::new()
+                        .push_maybe(progress)
+                        .push_maybe(find.as_ref().map(find_bar))
+                        .push_maybe(table_menu.map(|hovered| table_menu_view(content, hovered))),
                     iced::widget::container(main_body).height(iced::Length::Fill),
                     iced::widget::text(self.path.to_string_lossy())
                 ]
                 .spacing(4)
                 .padding(8)
-                .height(iced::Length::Fill)
-                .into()
+                .height(iced::Length::Fill);
+
+                match lightbox {
+                    Some(file) => iced::widget::stack![note, lightbox_view(file)].into(),
+                    None => note.into(),
+                }
             }
             crate::util::Query::Error(e) => iced::widget::Text::new(e.clone()).into(),
         }
     }
 
     pub fn update(&mut self, message: Message) -> iced::Task<Message> {
+        let cursor = match &self.state {
+            crate::util::Query::Loaded(state) => Some(state.content.cursor_position()),
+            _ => None,
+        };
+        let task = self.handle(message);
+        match &mut self.state {
+            crate::util::Query::Loaded(state) => {
+                // The editor grows with the note, so it's scrolled here to keep the cursor in view.
+                let follow = if cursor == Some(state.content.cursor_position()) {
+                    iced::Task::none()
+                } else {
+                    follow_cursor()
+                };
+                iced::Task::batch([task, follow, state.load_embeds(&self.config)])
+            }
+            _ => task,
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> iced::Task<Message> {
         match message {
             Message::Edit(action) => {
-                if let crate::util::Query::Loaded(InternalState {
-                    content, preview, ..
-                }) = &mut self.state
-                {
-                    content.perform(action);
-                    *preview = iced::widget::markdown::parse(&content.text()).collect();
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    if !action.is_edit() {
+                        state.history.seal();
+                        state.content.perform(action);
+                        return state.sync_preview();
+                    }
+
+                    let continued = matches!(
+                        action,
+                        iced::widget::text_editor::Action::Edit(
+                            iced::widget::text_editor::Edit::Enter
+                        )
+                    ) && state.continue_list();
+                    if !continued {
+                        state.edit(action);
+                    }
+                    state.sync_preview()
+                } else {
+                    iced::Task::none()
                 }
-                iced::Task::none()
             }
             Message::Loaded(Ok(contents)) => {
-                let content = iced::widget::text_editor::Content::with_text(&contents.clone());
-                let preview = iced::widget::markdown::parse(&content.text()).collect();
+                let mut content = iced::widget::text_editor::Content::with_text(&contents.clone());
+                if let Some((line, column)) = self.cursor.take() {
+                    crate::util::move_cursor_to(&mut content, line, column);
+                }
+                let history = self
+                    .history
+                    .take()
+                    .filter(|history| history.fits(&content.text()))
+                    .unwrap_or_default();
+                let panes = iced::widget::pane_grid::State::with_configuration(
+                    iced::widget::pane_grid::Configuration::Split {
+                        axis: match self.config.split_orientation {
+                            crate::config::SplitOrientation::Horizontal => {
+                                iced::widget::pane_grid::Axis::Vertical
+                            }
+                            crate::config::SplitOrientation::Vertical => {
+                                iced::widget::pane_grid::Axis::Horizontal
+                            }
+                        },
+                        ratio: 0.5,
+                        a: Box::new(iced::widget::pane_grid::Configuration::Pane(Pane::Editor)),
+                        b: Box::new(iced::widget::pane_grid::Configuration::Pane(Pane::Preview)),
+                    },
+                );
                 self.state = crate::util::Query::Loaded(InternalState {
                     content,
-                    preview,
+                    preview: crate::preview::Preview::new(&self.path),
                     view_mode: ViewMode::Edit,
+                    panes,
+                    synced_line: None,
+                    dirty: false,
+                    find: None,
+                    history,
+                    outline: self.outline,
+                    folds: crate::folding::Folds::default(),
+                    table_menu: None,
+                    lightbox: None,
+                    embeds: std::collections::HashMap::new(),
+                    embeds_read: None,
                 });
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.preview.read(&state.content);
+                    let folds = std::mem::take(&mut self.folds);
+                    if !folds.is_empty() {
+                        let line = state.content.cursor_position().0;
+                        state.refold(|state_folds, lines| {
+                            state_folds.restore(lines, &folds, line);
+                            true
+                        });
+                    }
+                    if self.outline {
+                        state.refresh_preview();
+                    }
+                }
+                iced::Task::none()
+            }
+            Message::Loaded(Err(std::io::ErrorKind::InvalidData)) => {
+                self.state = crate::util::Query::Error(
+                    "This file isn't text, so it can't be opened as a note.".to_string(),
+                );
                 iced::Task::none()
             }
             Message::Loaded(Err(e)) => {
@@ -113,25 +521,392 @@ impl NoteEditor {
                 iced::Task::none()
             }
             Message::SwitchMode => {
-                if let crate::util::Query::Loaded(InternalState { view_mode, .. }) = &mut self.state
-                {
-                    *view_mode = match view_mode {
-                        ViewMode::Edit => ViewMode::Preview,
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.view_mode = match state.view_mode {
+                        ViewMode::Edit | ViewMode::Split => ViewMode::Preview,
                         ViewMode::Preview => ViewMode::Edit,
                     };
+                    state.refresh_preview();
                 }
                 iced::Task::none()
             }
-            Message::None(url) => {
-                print!("{}", url);
+            Message::SwitchSplit => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.view_mode = if state.view_mode == ViewMode::Split {
+                        ViewMode::Edit
+                    } else {
+                        ViewMode::Split
+                    };
+                    state.refresh_preview();
+                    state.synced_line = None;
+                    state.sync_preview()
+                } else {
+                    iced::Task::none()
+                }
+            }
+            Message::ResizeSplit(iced::widget::pane_grid::ResizeEvent { split, ratio }) => {
+                if let crate::util::Query::Loaded(InternalState { panes, .. }) = &mut self.state {
+                    panes.resize(split, ratio.clamp(0.1, 0.9));
+                }
+                iced::Task::none()
+            }
+            Message::CopyCode(code) => iced::clipboard::write(code),
+            Message::ToggleTask(line) => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.toggle_task(line);
+                }
+                iced::Task::none()
+            }
+            Message::Save => self.save(),
+            // The note may embed sections of itself.
+            Message::Saved(Ok(())) => {
+                let path = self.path.clone();
+                self.forget_embeds(&[&path])
+            }
+            Message::Saved(Err(e)) => {
+                eprintln!("Failed to save {:?}: {:?}", self.path, e);
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.dirty = true;
+                }
+                iced::Task::none()
+            }
+            Message::OpenFind(replace) => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                if state.view_mode == ViewMode::Preview {
+                    state.view_mode = ViewMode::Edit;
+                }
+                // Matches in folded sections would be out of reach.
+                state.unfold_all();
+                let find = state.find.get_or_insert_with(|| FindBar {
+                    query: crate::find::Query::default(),
+                    replace: None,
+                    matches: vec![],
+                    current: None,
+                    error: None,
+                });
+                if let Some(selection) = state.content.selection().filter(|s| !s.contains('\n')) {
+                    find.query.text = selection;
+                }
+                if replace {
+                    find.replace.get_or_insert_with(String::new);
+                }
+                state.find_matches();
+                iced::widget::text_input::focus(iced::widget::text_input::Id::new(FIND))
+            }
+            Message::CloseFind => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.find = None;
+                }
+                iced::Task::none()
+            }
+            Message::FindText(_)
+            | Message::ReplaceText(_)
+            | Message::SetCaseSensitive(_)
+            | Message::SetWholeWord(_)
+            | Message::SetRegex(_) => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    if let Some(find) = &mut state.find {
+                        match message {
+                            Message::FindText(text) => find.query.text = text,
+                            Message::ReplaceText(text) => find.replace = Some(text),
+                            Message::SetCaseSensitive(value) => find.query.case_sensitive = value,
+                            Message::SetWholeWord(value) => find.query.whole_word = value,
+                            Message::SetRegex(value) => find.query.regex = value,
+                            _ => {}
+                        }
+                        state.find_matches();
+                    }
+                }
+                iced::Task::none()
+            }
+            Message::FindNext | Message::FindPrevious => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.select_next_match(matches!(message, Message::FindNext));
+                    return state.sync_preview();
+                }
+                iced::Task::none()
+            }
+            Message::ReplaceOne => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.replace_match();
+                    return state.sync_preview();
+                }
+                iced::Task::none()
+            }
+            Message::ReplaceAll => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.replace_all();
+                }
+                iced::Task::none()
+            }
+            Message::Undo | Message::Redo => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.step(matches!(message, Message::Undo));
+                    return state.sync_preview();
+                }
+                iced::Task::none()
+            }
+            Message::HistoryLoaded(history) => {
+                match &mut self.state {
+                    crate::util::Query::Pending => {
+                        self.history = self.history.take().or(history);
+                    }
+                    crate::util::Query::Loaded(state) if state.history.is_empty() => {
+                        if let Some(history) =
+                            history.filter(|history| history.fits(&state.file_text()))
+                        {
+                            state.history = history;
+                        }
+                    }
+                    _ => {}
+                }
+                iced::Task::none()
+            }
+            Message::ToggleOutline => {
+                let shown = !self.outline_shown();
+                self.show_outline(shown);
+                iced::Task::none()
+            }
+            Message::GoToLine(line) => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                state.history.seal();
+                if state.folds.editor_line(line).is_none() {
+                    state.unfold_all();
+                }
+                let editor_line = state.folds.editor_line(line).unwrap_or(line);
+                crate::util::move_cursor_to(&mut state.content, editor_line, 0);
+                if state.view_mode == ViewMode::Preview {
+                    state.scroll_preview(editor_line)
+                } else {
+                    state.sync_preview()
+                }
+            }
+            Message::Fold | Message::Unfold | Message::FoldAll | Message::UnfoldAll => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                let line = state.content.cursor_position().0;
+                state.refold(|folds, lines| match message {
+                    // The innermost section holding the cursor that isn't folded yet.
+                    Message::Fold => (0..=line).rev().any(|start| {
+                        !folds.is_folded(start)
+                            && crate::folding::range(lines, start, |line| folds.is_folded(line))
+                                .is_some_and(|end| end >= line)
+                            && folds.fold(lines, start)
+                    }),
+                    Message::Unfold => folds.unfold(lines, line),
+                    Message::FoldAll => {
+                        folds.fold_all(lines);
+                        true
+                    }
+                    _ => {
+                        folds.unfold_all(lines);
+                        true
+                    }
+                });
+                iced::Task::none()
+            }
+            Message::Indent | Message::Outdent => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                state.indent(matches!(message, Message::Outdent));
+                state.sync_preview()
+            }
+            Message::ToggleTableMenu => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.table_menu = match state.table_menu {
+                        Some(_) => None,
+                        None => Some((0, 0)),
+                    };
+                }
+                iced::Task::none()
+            }
+            Message::HoverTableSize(rows, columns) => {
+                if let crate::util::Query::Loaded(InternalState {
+                    table_menu: Some(size),
+                    ..
+                }) = &mut self.state
+                {
+                    *size = (rows, columns);
+                }
+                iced::Task::none()
+            }
+            Message::InsertTable(rows, columns) => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                state.table_menu = None;
+                state.insert_table(rows, columns);
+                state.sync_preview()
+            }
+            Message::EditTable(change) => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                state.change_table(change);
+                state.sync_preview()
+            }
+            Message::ToggleFold(line) => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                state.refold(|folds, lines| match folds.editor_line(line) {
+                    Some(line) if folds.is_folded(line) => folds.unfold(lines, line),
+                    Some(line) => folds.fold(lines, line),
+                    None => {
+                        folds.unfold_all(lines);
+                        folds.fold(lines, line);
+                        true
+                    }
+                });
+                iced::Task::none()
+            }
+            Message::Paste => iced::clipboard::read().then(|text| match text {
+                Some(text) => {
+                    iced::Task::done(Message::Edit(iced::widget::text_editor::Action::Edit(
+                        iced::widget::text_editor::Edit::Paste(std::sync::Arc::new(text)),
+                    )))
+                }
+                None => {
+                    iced::Task::perform(crate::attachments::clipboard_image(), Message::ImagePasted)
+                }
+            }),
+            Message::ImagePasted(Some(bytes)) => iced::Task::perform(
+                crate::attachments::store(
+                    self.path.clone(),
+                    self.config.attachments_path.clone(),
+                    "image".into(),
+                    "png".into(),
+                    bytes,
+                ),
+                Message::AttachmentSaved,
+            ),
+            Message::ImagePasted(None) => iced::Task::none(),
+            Message::FileDropped(path) => iced::Task::perform(
+                crate::attachments::copy(
+                    self.path.clone(),
+                    self.config.attachments_path.clone(),
+                    path,
+                ),
+                Message::AttachmentSaved,
+            ),
+            Message::AttachmentSaved(Ok(link)) => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                state.edit(iced::widget::text_editor::Action::Edit(
+                    iced::widget::text_editor::Edit::Paste(std::sync::Arc::new(
+                        crate::attachments::link(&link),
+                    )),
+                ));
+                state.sync_preview()
+            }
+            Message::AttachmentSaved(Err(e)) => {
+                eprintln!("Failed to store the image: {:?}", e);
                 iced::Task::none()
             }
+            Message::OpenImage(file) => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.lightbox = Some(file);
+                }
+                iced::Task::none()
+            }
+            Message::CloseImage => {
+                if let crate::util::Query::Loaded(state) = &mut self.state {
+                    state.lightbox = None;
+                }
+                iced::Task::none()
+            }
+            Message::EmbedLoaded(embed, loaded) => {
+                let crate::util::Query::Loaded(state) = &mut self.state else {
+                    return iced::Task::none();
+                };
+                let embedded = loaded.and_then(|(path, text)| {
+                    let text = match &embed.section {
+                        Some(section) => crate::embeds::section(&text, section)
+                            .ok_or_else(|| format!("No section {} in {}", section, embed.name))?,
+                        None => text,
+                    };
+                    let mut preview = crate::preview::Preview::new(&path);
+                    preview.rebuild(&content(&text));
+                    Ok(Embedded { path, preview })
+                });
+                state.embeds.insert(
+                    embed,
+                    match embedded {
+                        Ok(embedded) => crate::util::Query::Loaded(embedded),
+                        Err(error) => crate::util::Query::Error(error),
+                    },
+                );
+                // The note loaded may embed others in turn.
+                state.embeds_read = None;
+                iced::Task::none()
+            }
+            // Opened and exported by the list of notes, which knows the other notes.
+            Message::OpenNote(_) | Message::Export => iced::Task::none(),
+            Message::None(url) => {
+                let crate::util::Query::Loaded(state) = &self.state else {
+                    return iced::Task::none();
+                };
+                // Links within the note, to a heading or between a footnote and its reference.
+                let line = match url.scheme() {
+                    "anchor" => state
+                        .preview
+                        .outline()
+                        .into_iter()
+                        .find(|heading| {
+                            url::Url::parse(&format!("anchor:{}", heading.anchor))
+                                .is_ok_and(|anchor| anchor == url)
+                        })
+                        .map(|heading| heading.line),
+                    // The footnotes are at the end of the preview.
+                    "footnote" => {
+                        return iced::widget::scrollable::snap_to(
+                            iced::widget::scrollable::Id::new(PREVIEW),
+                            iced::widget::scrollable::RelativeOffset { x: 0.0, y: 1.0 },
+                        );
+                    }
+                    "footnote-ref" => url
+                        .path()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|number| {
+                            state
+                                .preview
+                                .footnotes()
+                                .get(number.checked_sub(1)?)
+                                .map(|footnote| footnote.id.clone())
+                        })
+                        .and_then(|id| state.preview.reference_line(&id)),
+                    _ => {
+                        print!("{}", url);
+                        None
+                    }
+                };
+                match line {
+                    Some(line) => iced::Task::done(Message::GoToLine(line)),
+                    None => iced::Task::none(),
+                }
+            }
         }
     }
-    pub fn subscription(self: &Self) -> iced::Subscription<Message> {
+    pub fn subscription(&self) -> iced::Subscription<Message> {
         match &self.state {
-            crate::util::Query::Loaded(InternalState { .. }) => {
-                iced::keyboard::on_key_press(|key, modifiers| {
+            crate::util::Query::Loaded(InternalState {
+                dirty, lightbox, ..
+            }) => {
+                let autosave = if *dirty {
+                    iced::time::every(std::time::Duration::from_secs(2)).map(|_| Message::Save)
+                } else {
+                    iced::Subscription::none()
+                };
+
+                let shortcuts = iced::keyboard::on_key_press(|key, modifiers| {
                     if modifiers.control() {
                         if key == iced::keyboard::Key::Character("b".into()) && modifiers.control()
                         {
@@ -167,19 +942,2093 @@ impl NoteEditor {
                         {
                             // TODO: Title 5
                             None
+                        } else if key == iced::keyboard::Key::Character("o".into())
+                            && modifiers.shift()
+                        {
+                            Some(Message::ToggleOutline)
+                        } else if key == iced::keyboard::Key::Character("p".into())
+                            && modifiers.control()
+                            && modifiers.shift()
+                        {
+                            Some(Message::SwitchSplit)
                         } else if key == iced::keyboard::Key::Character("p".into())
                             && modifiers.control()
                         {
                             Some(Message::SwitchMode)
+                        } else if key == iced::keyboard::Key::Character("f".into()) {
+                            Some(Message::OpenFind(false))
+                        } else if key == iced::keyboard::Key::Character("h".into()) {
+                            Some(Message::OpenFind(true))
+                        } else if key == iced::keyboard::Key::Character("z".into()) {
+                            Some(if modifiers.shift() {
+                                Message::Redo
+                            } else {
+                                Message::Undo
+                            })
+                        } else if key == iced::keyboard::Key::Character("y".into()) {
+                            Some(Message::Redo)
+                        } else if key == iced::keyboard::Key::Character("[".into()) {
+                            Some(if modifiers.alt() {
+                                Message::FoldAll
+                            } else {
+                                Message::Fold
+                            })
+                        } else if key == iced::keyboard::Key::Character("]".into()) {
+                            Some(if modifiers.alt() {
+                                Message::UnfoldAll
+                            } else {
+                                Message::Unfold
+                            })
                         } else {
                             None
                         }
+                    } else if key == iced::keyboard::Key::Named(iced::keyboard::key::Named::Escape)
+                    {
+                        Some(Message::CloseFind)
                     } else {
                         None
                     }
-                })
+                });
+
+                // The lightbox takes the keyboard until it's closed.
+                let shortcuts = if lightbox.is_some() {
+                    iced::keyboard::on_key_press(|key, _modifiers| {
+                        (key == iced::keyboard::Key::Named(iced::keyboard::key::Named::Escape))
+                            .then_some(Message::CloseImage)
+                    })
+                } else {
+                    shortcuts
+                };
+
+                let dropped = iced::event::listen_with(|event, _status, _window| match event {
+                    iced::Event::Window(iced::window::Event::FileDropped(path))
+                        if crate::attachments::is_image(&path) =>
+                    {
+                        Some(Message::FileDropped(path))
+                    }
+                    _ => None,
+                });
+
+                iced::Subscription::batch([shortcuts, autosave, dropped])
             }
             _ => iced::Subscription::none(),
         }
     }
 }
+
+impl InternalState {
+    /// Applies an editing action as typed by the user, recording it in the history.
+    fn edit(&mut self, action: iced::widget::text_editor::Action) {
+        self.record(crate::history::Kind::of(&action));
+        self.apply(action);
+        self.align_table();
+    }
+
+    fn record(&mut self, kind: crate::history::Kind) {
+        let (content, folds) = (&self.content, &self.folds);
+        self.history.record(kind, || snapshot(content, folds));
+    }
+
+    /// Applies an editing action, keeping the preview, the task count and the folds up to
+    /// date.
+    fn apply(&mut self, action: iced::widget::text_editor::Action) {
+        self.unfold_around(&action);
+        let (line_before, _) = self.content.cursor_position();
+        let lines_before = self.content.line_count();
+        let selected_lines = self
+            .content
+            .selection()
+            .map_or(0, |selection| selection.matches('\n').count());
+        self.content.perform(action);
+        let (line_after, _) = self.content.cursor_position();
+        let delta = self.content.line_count() as isize - lines_before as isize;
+        self.folds.shift(line_before + selected_lines, delta);
+
+        // The lines touched by the edit, as they are after it.
+        let first_line = line_before.min(line_after).saturating_sub(selected_lines);
+        let last_line = line_before.max(line_after) + selected_lines;
+        let edit = crate::preview::Edit {
+            first_line: self.folds.note_line(first_line),
+            last_line: self.folds.note_line(last_line),
+            delta,
+        };
+        let document = Document {
+            content: &self.content,
+            folds: &self.folds,
+        };
+        self.preview.edit(&document, edit);
+        if !self.preview_hidden() {
+            self.preview.refresh();
+        }
+        self.dirty = true;
+        self.find_matches_in(first_line, last_line, delta);
+    }
+
+    /// Continues the list or the quote of the cursor's line on Enter, or ends it on an empty
+    /// item. Returns `false` if Enter should just break the line.
+    fn continue_list(&mut self) -> bool {
+        use iced::widget::text_editor::{Action, Edit};
+
+        if self.content.selection().is_some() {
+            return false;
+        }
+        let (line, column) = self.content.cursor_position();
+        let lines = self.lines();
+        if crate::lists::in_code(&lines, line) {
+            return false;
+        }
+        let Some(enter) = crate::lists::enter(&lines, line, column) else {
+            return false;
+        };
+
+        self.record(crate::history::Kind::Other);
+        match enter {
+            crate::lists::Enter::Continue(prefix) => {
+                self.apply(Action::Edit(Edit::Paste(std::sync::Arc::new(format!(
+                    "\n{}",
+                    prefix
+                )))));
+                let cursor = self.content.cursor_position();
+                self.renumber(line + 1, false);
+                crate::util::move_cursor_to(&mut self.content, cursor.0, cursor.1);
+            }
+            crate::lists::Enter::End(rest) => {
+                let length = lines[line].len();
+                self.replace(line, 0..length, &rest);
+            }
+        }
+        true
+    }
+
+    /// Indents the list items of the lines of the selection, with the lines nested in them,
+    /// or outdents them. Elsewhere, Tab inserts spaces.
+    fn indent(&mut self, outdent: bool) {
+        use iced::widget::text_editor::{Action, Edit};
+
+        if self.next_cell(outdent) {
+            return;
+        }
+        let cursor = self.content.cursor_position();
+        let anchor = self
+            .anchor()
+            .and_then(|(line, column)| Some((self.folds.editor_line(line)?, column)));
+        let (first, last) = match anchor {
+            Some((line, _)) => (line.min(cursor.0), line.max(cursor.0)),
+            None => (cursor.0, cursor.0),
+        };
+        let lines = self.lines();
+        let nested = |line: usize| crate::folding::range(&lines, line, |_| false).unwrap_or(line);
+        // The lines of folded items move with them.
+        let end = nested(last).max(last);
+        if self
+            .folds
+            .lines()
+            .iter()
+            .any(|fold| (first..=end).contains(fold))
+        {
+            self.unfold_all();
+            return self.indent(outdent);
+        }
+
+        let mut moved = vec![];
+        let mut shifts: Vec<(usize, isize)> = vec![];
+        for line in first..=last {
+            if shifts.last().is_some_and(|(last, _)| *last >= line)
+                || crate::lists::in_code(&lines, line)
+            {
+                continue;
+            }
+            let shift = if outdent {
+                crate::lists::outdent(&lines, line).map(|width| -(width as isize))
+            } else {
+                crate::lists::indent(&lines, line).map(|width| width as isize)
+            };
+            let Some(shift) = shift else {
+                continue;
+            };
+            moved.push(line);
+            for (line, text) in lines.iter().enumerate().take(nested(line) + 1).skip(line) {
+                let indentation = text.len() - text.trim_start().len();
+                shifts.push((line, shift.max(-(indentation as isize))));
+            }
+        }
+        if shifts.is_empty() {
+            // The editor drops tabs typed on their own.
+            if !outdent && anchor.is_none() {
+                self.record(crate::history::Kind::Spacing);
+                self.apply(Action::Edit(Edit::Paste(std::sync::Arc::new(
+                    " ".repeat(4),
+                ))));
+            }
+            return;
+        }
+
+        self.record(crate::history::Kind::Other);
+        for (line, shift) in &shifts {
+            match usize::try_from(*shift) {
+                Ok(width) => self.replace(*line, 0..0, &" ".repeat(width)),
+                Err(_) => self.replace(*line, 0..shift.unsigned_abs(), ""),
+            }
+        }
+        // The lists the items leave and join are numbered again.
+        for line in moved {
+            let lines = self.lines();
+            let after = nested(line) + 1;
+            self.renumber(line, true);
+            if after < lines.len() {
+                self.renumber(after, true);
+            }
+        }
+
+        let shifted = |(line, column): (usize, usize)| {
+            let shift = shifts
+                .iter()
+                .find(|(other, _)| *other == line)
+                .map_or(0, |(_, shift)| *shift);
+            (line, column.saturating_add_signed(shift))
+        };
+        let (line, column) = shifted(anchor.unwrap_or(cursor));
+        crate::util::move_cursor_to(&mut self.content, line, column);
+        if anchor.is_some() {
+            let (line, column) = shifted(cursor);
+            crate::util::select_to(&mut self.content, line, column);
+        }
+    }
+
+    /// The table holding the cursor, with the lines of the editor and where the cursor is in
+    /// it.
+    fn table(&self) -> Option<(crate::tables::Table, Vec<String>, crate::tables::Position)> {
+        let (line, column) = self.content.cursor_position();
+        let lines = self.lines();
+        if crate::lists::in_code(&lines, line) {
+            return None;
+        }
+        let table = crate::tables::Table::at(&lines, line)?;
+        let position = table.position(&lines[line], line, column);
+        Some((table, lines, position))
+    }
+
+    /// Formats the table holding the cursor after an edit, keeping the cursor in its cell.
+    fn align_table(&mut self) {
+        if self.content.selection().is_some() {
+            return;
+        }
+        if let Some((table, lines, position)) = self.table() {
+            self.write_table(&lines, table.last(), &table, position, false);
+        }
+    }
+
+    /// Moves to the next cell of the table holding the cursor, or the previous one if `back`.
+    /// Returns `false` if the cursor isn't in a table.
+    fn next_cell(&mut self, back: bool) -> bool {
+        let Some((mut table, lines, position)) = self.table() else {
+            return false;
+        };
+        let last = table.last();
+        let position = table.next(position, back);
+        self.write_table(&lines, last, &table, position, true);
+        true
+    }
+
+    fn change_table(&mut self, change: crate::tables::Change) {
+        let Some((mut table, lines, position)) = self.table() else {
+            return;
+        };
+        let last = table.last();
+        let position = table.change(change, position);
+        self.write_table(&lines, last, &table, position, true);
+    }
+
+    /// Inserts an empty table of `rows` and `columns` after the cursor's line, or in its place
+    /// if it's blank.
+    fn insert_table(&mut self, rows: usize, columns: usize) {
+        use iced::widget::text_editor::{Action, Edit};
+
+        let (line, _) = self.content.cursor_position();
+        let lines = self.lines();
+        // Blank lines around it keep the lines next to it out of the table.
+        let mut text = String::new();
+        let first = if lines[line].trim().is_empty() {
+            crate::util::move_cursor_to(&mut self.content, line, 0);
+            crate::util::select_to(&mut self.content, line, usize::MAX);
+            let after_text = line > 0 && !lines[line - 1].trim().is_empty();
+            if after_text {
+                text.push('\n');
+            }
+            line + usize::from(after_text)
+        } else {
+            crate::util::move_cursor_to(&mut self.content, line, usize::MAX);
+            text.push_str("\n\n");
+            line + 2
+        };
+        let table = crate::tables::Table::new(first, rows, columns);
+        text.push_str(&table.lines().join("\n"));
+        if lines
+            .get(line + 1)
+            .is_some_and(|next| !next.trim().is_empty())
+        {
+            text.push('\n');
+        }
+
+        self.record(crate::history::Kind::Other);
+        self.apply(Action::Edit(Edit::Paste(std::sync::Arc::new(text))));
+        let (line, column) = table.cursor(crate::tables::Position {
+            line: first,
+            cell: 0,
+            offset: 0,
+        });
+        crate::util::move_cursor_to(&mut self.content, line, column);
+    }
+
+    /// Puts the formatted `table` in place of the lines it was read from, up to `last`, and
+    /// the cursor at `position`. A change starts an undo step if `record`.
+    fn write_table(
+        &mut self,
+        lines: &[String],
+        last: usize,
+        table: &crate::tables::Table,
+        position: crate::tables::Position,
+        record: bool,
+    ) {
+        use iced::widget::text_editor::{Action, Edit};
+
+        let formatted = table.lines();
+        if lines[table.first..=last] != formatted[..] {
+            if record {
+                self.record(crate::history::Kind::Other);
+            }
+            crate::util::move_cursor_to(&mut self.content, table.first, 0);
+            crate::util::select_to(&mut self.content, last, usize::MAX);
+            self.apply(Action::Edit(Edit::Paste(std::sync::Arc::new(
+                formatted.join("\n"),
+            ))));
+        }
+        let (line, column) = table.cursor(position);
+        crate::util::move_cursor_to(&mut self.content, line, column);
+    }
+
+    /// Numbers the ordered list holding `line` again, see [`crate::lists::renumber`].
+    fn renumber(&mut self, line: usize, restart: bool) {
+        for (line, range, number) in crate::lists::renumber(&self.lines(), line, restart) {
+            self.replace(line, range, &number);
+        }
+    }
+
+    /// Replaces the `range` of bytes of `line` with `text`, as part of the current step.
+    fn replace(&mut self, line: usize, range: std::ops::Range<usize>, text: &str) {
+        use iced::widget::text_editor::{Action, Edit};
+
+        crate::util::move_cursor_to(&mut self.content, line, range.start);
+        crate::util::select_to(&mut self.content, line, range.end);
+        self.apply(Action::Edit(Edit::Paste(std::sync::Arc::new(
+            text.to_string(),
+        ))));
+    }
+
+    /// The lines of the editor, without the folded ones.
+    fn lines(&self) -> Vec<String> {
+        self.content.lines().map(|line| line.to_string()).collect()
+    }
+
+    /// Unfolds the folds an edit would break: the ones it spans or joins lines into. Typing
+    /// on the line of a fold keeps it.
+    fn unfold_around(&mut self, action: &iced::widget::text_editor::Action) {
+        use iced::widget::text_editor::{Action, Edit};
+
+        let Action::Edit(edit) = action else {
+            return;
+        };
+        if self.folds.is_empty() {
+            return;
+        }
+        let (line, column) = self.content.cursor_position();
+        let selection = self.content.selection();
+        let length = self.content.line(line).map_or(0, |text| text.len());
+        let in_line = match edit {
+            Edit::Insert(c) => *c != '\n',
+            Edit::Paste(text) => !text.contains('\n'),
+            Edit::Backspace => selection.is_some() || column > 0,
+            Edit::Delete => selection.is_some() || column < length,
+            Edit::Enter => false,
+        } && selection
+            .as_ref()
+            .is_none_or(|selection| !selection.contains('\n'));
+        if in_line {
+            return;
+        }
+
+        // Unfolding a fold folds the ones inside it again, which may be in the way too.
+        loop {
+            let (line, _) = self.content.cursor_position();
+            let selected = self
+                .content
+                .selection()
+                .map_or(0, |selection| selection.matches('\n').count());
+            let first = if selection.is_none() && matches!(edit, Edit::Backspace) {
+                line.saturating_sub(1)
+            } else {
+                line.saturating_sub(selected)
+            };
+            let broken: Vec<usize> = self
+                .folds
+                .lines()
+                .into_iter()
+                .filter(|fold| (first..=line + selected).contains(fold))
+                .collect();
+            if broken.is_empty() {
+                return;
+            }
+            self.refold(|folds, lines| {
+                for line in broken.iter().rev() {
+                    folds.unfold(lines, *line);
+                }
+                true
+            });
+        }
+    }
+
+    /// Changes the folds with `change`, given the lines of the editor, and puts the lines
+    /// left back in the editor if it returns `true`. The cursor and the selection stay on the
+    /// same text, or move to the fold hiding them.
+    fn refold(
+        &mut self,
+        change: impl FnOnce(&mut crate::folding::Folds, &mut Vec<String>) -> bool,
+    ) {
+        let cursor = self.cursor();
+        let anchor = self.anchor();
+        let mut lines = self.lines();
+        if !change(&mut self.folds, &mut lines) {
+            return;
+        }
+
+        self.content = content(&lines.join("\n"));
+        let position = |folds: &crate::folding::Folds, (line, column): (usize, usize)| {
+            match folds.editor_line(line) {
+                Some(line) => (line, column),
+                // The end of the line hiding it.
+                None => (
+                    (0..line)
+                        .rev()
+                        .find_map(|line| folds.editor_line(line))
+                        .unwrap_or(0),
+                    usize::MAX,
+                ),
+            }
+        };
+        let (line, column) = position(&self.folds, anchor.unwrap_or(cursor));
+        crate::util::move_cursor_to(&mut self.content, line, column);
+        if anchor.is_some() {
+            let (line, column) = position(&self.folds, cursor);
+            crate::util::select_to(&mut self.content, line, column);
+        }
+        self.find_matches();
+    }
+
+    fn unfold_all(&mut self) {
+        if !self.folds.is_empty() {
+            self.refold(|folds, lines| {
+                folds.unfold_all(lines);
+                true
+            });
+        }
+    }
+
+    /// The text of the whole note, folded sections included.
+    fn text(&self) -> String {
+        if self.folds.is_empty() {
+            crate::find::text(&self.content)
+        } else {
+            self.folds.text(self.content.lines())
+        }
+    }
+
+    /// The text of the note as written to its file.
+    fn file_text(&self) -> String {
+        let mut text = self.text();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+
+    fn refresh_preview(&mut self) {
+        self.preview.refresh();
+    }
+
+    /// The line and column of the cursor in the whole note.
+    fn cursor(&self) -> (usize, usize) {
+        let (line, column) = self.content.cursor_position();
+        (self.folds.note_line(line), column)
+    }
+
+    /// The other end of the selection from the cursor in the whole note, if there's one.
+    fn anchor(&mut self) -> Option<(usize, usize)> {
+        use iced::widget::text_editor::{Action, Motion};
+
+        let selection = self.content.selection()?;
+        let text = crate::find::text(&self.content);
+        let (line, column) = self.content.cursor_position();
+        let cursor = crate::find::offset(&text, line, column);
+        let before = text[..cursor].ends_with(selection.as_str());
+        let after = text[cursor..].starts_with(selection.as_str());
+        // When the text on both sides matches, the selection grows if the cursor is at its
+        // start and moves left.
+        let backward = if before && after {
+            self.content.perform(Action::Select(Motion::Left));
+            let grew = self
+                .content
+                .selection()
+                .is_some_and(|moved| moved.len() > selection.len());
+            self.content.perform(Action::Select(Motion::Right));
+            grew
+        } else {
+            !before
+        };
+        let anchor = if backward {
+            cursor + selection.len()
+        } else {
+            cursor - selection.len()
+        };
+        let (line, column) = crate::find::position(&text, anchor.min(text.len()));
+        Some((self.folds.note_line(line), column))
+    }
+
+    /// Looks for the matches of the find bar, if it's open.
+    fn find_matches(&mut self) {
+        let Some(find) = &mut self.find else {
+            return;
+        };
+        find.current = None;
+        find.error = None;
+        find.matches = match find.query.regex() {
+            None => vec![],
+            Some(Err(e)) => {
+                find.error = Some(e.to_string());
+                vec![]
+            }
+            Some(Ok(regex)) => crate::find::matches(&regex, &crate::find::text(&self.content)),
+        };
+    }
+
+    /// Looks for the matches again in the lines from `first` to `last`, as they are after an
+    /// edit which added `delta` lines. Matches never span lines, so the others only move.
+    fn find_matches_in(&mut self, first: usize, last: usize, delta: isize) {
+        let Some(find) = &mut self.find else {
+            return;
+        };
+        let Some(Ok(regex)) = find.query.regex() else {
+            return;
+        };
+        find.current = None;
+        // The last edited line as it was before the edit.
+        let old_last = last as isize - delta;
+        let mut matches: Vec<_> = find
+            .matches
+            .drain(..)
+            .filter(|(line, _)| *line < first || *line as isize > old_last)
+            .map(|(line, found)| {
+                if line < first {
+                    (line, found)
+                } else {
+                    ((line as isize + delta) as usize, found)
+                }
+            })
+            .collect();
+        let last = last.min(self.content.line_count().saturating_sub(1));
+        let found: Vec<_> = (first..=last)
+            .filter_map(|i| {
+                let line = self.content.line(i)?;
+                Some(
+                    crate::find::line_matches(&regex, &line)
+                        .map(|found| (i, found))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .flatten()
+            .collect();
+        let at = matches.partition_point(|(line, _)| *line < first);
+        matches.splice(at..at, found);
+        find.matches = matches;
+    }
+
+    /// Selects the match after the cursor, or before it if not `forward`, wrapping around.
+    fn select_next_match(&mut self, forward: bool) {
+        let Some(find) = &self.find else {
+            return;
+        };
+        if find.matches.is_empty() {
+            return;
+        }
+        let count = find.matches.len();
+        let next = match find.current {
+            Some(current) if forward => (current + 1) % count,
+            Some(current) => (current + count - 1) % count,
+            None => {
+                let cursor = self.content.cursor_position();
+                if forward {
+                    find.matches
+                        .iter()
+                        .position(|(line, found)| (*line, found.start) >= cursor)
+                        .unwrap_or(0)
+                } else {
+                    find.matches
+                        .iter()
+                        .rposition(|(line, found)| (*line, found.end) <= cursor)
+                        .unwrap_or(count - 1)
+                }
+            }
+        };
+        self.select_match(next);
+    }
+
+    fn select_match(&mut self, index: usize) {
+        let Some(find) = &mut self.find else {
+            return;
+        };
+        let Some((line, found)) = find.matches.get(index).cloned() else {
+            return;
+        };
+        find.current = Some(index);
+
+        crate::util::move_cursor_to(&mut self.content, line, found.start);
+        // The editor moves by grapheme, so the selection is walked to where the match ends.
+        crate::util::select_to(&mut self.content, line, found.end);
+    }
+
+    /// Replaces the selected match and selects the next one, or just selects the next match
+    /// if none is selected.
+    fn replace_match(&mut self) {
+        use iced::widget::text_editor::{Action, Edit};
+
+        let Some(find) = &self.find else {
+            return;
+        };
+        let (Some(current), Some(replace), Some(Ok(regex))) =
+            (find.current, &find.replace, find.query.regex())
+        else {
+            return self.select_next_match(true);
+        };
+        let (line, found) = find.matches[current].clone();
+        let Some(text) = self.content.line(line).map(|line| line.to_string()) else {
+            return self.select_next_match(true);
+        };
+        let Some(captures) = regex
+            .captures_at(&text, found.start)
+            .filter(|captures| captures.get(0).is_some_and(|m| m.range() == found))
+        else {
+            return self.select_next_match(true);
+        };
+        if self.content.selection().as_deref() != text.get(found) {
+            return self.select_next_match(true);
+        }
+
+        let replacement = find.query.replacement(&captures, replace);
+        self.record(crate::history::Kind::Other);
+        self.apply(if replacement.is_empty() {
+            Action::Edit(Edit::Delete)
+        } else {
+            Action::Edit(Edit::Paste(std::sync::Arc::new(replacement)))
+        });
+        self.select_next_match(true);
+    }
+
+    /// Replaces every match in one edit.
+    fn replace_all(&mut self) {
+        use iced::widget::text_editor::{Action, Edit};
+
+        self.unfold_all();
+        let Some(find) = &self.find else {
+            return;
+        };
+        let (Some(replace), Some(Ok(regex))) = (&find.replace, find.query.regex()) else {
+            return;
+        };
+        let text = crate::find::text(&self.content);
+        let replaced = find.query.replace_all(&regex, &text, replace);
+        if replaced == text {
+            return;
+        }
+
+        self.record(crate::history::Kind::Other);
+        let (line, column) = self.content.cursor_position();
+        self.content.perform(Action::SelectAll);
+        self.apply(Action::Edit(Edit::Paste(std::sync::Arc::new(replaced))));
+        crate::util::move_cursor_to(&mut self.content, line, column);
+    }
+
+    /// Ticks or unticks the task at `line`, along with its subtasks and parents.
+    fn toggle_task(&mut self, line: usize) {
+        use iced::widget::text_editor::{Action, Edit, Motion};
+
+        // The preview counts the lines of the whole note.
+        self.unfold_all();
+        let Some((start, list)) = self.preview.task_list_at(line) else {
+            return;
+        };
+        let changes = crate::tasks::toggle(list, line - start);
+
+        self.record(crate::history::Kind::Other);
+        let (cursor_line, cursor_column) = self.content.cursor_position();
+        for (offset, checked) in changes {
+            let Some(mark) = self
+                .content
+                .line(start + offset)
+                .and_then(|text| crate::tasks::list_item(&text))
+                .map(|item| item.mark)
+            else {
+                continue;
+            };
+            crate::util::move_cursor_to(&mut self.content, start + offset, mark);
+            self.content.perform(Action::Select(Motion::Right));
+            self.apply(Action::Edit(Edit::Insert(if checked { 'x' } else { ' ' })));
+        }
+        crate::util::move_cursor_to(&mut self.content, cursor_line, cursor_column);
+    }
+
+    /// Undoes the last step, or redoes it if not `back`.
+    fn step(&mut self, back: bool) {
+        let current = snapshot(&self.content, &self.folds);
+        let snapshot = if back {
+            self.history.undo(current)
+        } else {
+            self.history.redo(current)
+        };
+        let Some(snapshot) = snapshot else {
+            return;
+        };
+
+        self.folds = crate::folding::Folds::default();
+        self.content = content(&snapshot.text);
+        let (line, column) = snapshot.cursor;
+        crate::util::move_cursor_to(&mut self.content, line, column);
+        self.preview.read(&self.content);
+        if !self.preview_hidden() {
+            self.preview.refresh();
+        }
+        self.dirty = true;
+        self.find_matches();
+    }
+
+    /// Whether nothing shows the preview, which then leaves its blocks to render when it's
+    /// shown. The outline is read from it.
+    fn preview_hidden(&self) -> bool {
+        self.view_mode == ViewMode::Edit && !self.outline
+    }
+
+    /// Keeps the preview of the split view scrolled to the part of the note being edited.
+    fn sync_preview(&mut self) -> iced::Task<Message> {
+        let line = self.content.cursor_position().0;
+        if self.view_mode != ViewMode::Split || self.synced_line == Some(line) {
+            return iced::Task::none();
+        }
+        self.synced_line = Some(line);
+        self.scroll_preview(line)
+    }
+
+    /// Starts loading the notes embedded in the preview, and in those, that aren't loaded yet,
+    /// once the preview or the embedded notes changed.
+    fn load_embeds(&mut self, config: &crate::config::Configuration) -> iced::Task<Message> {
+        if self.preview_hidden() || self.embeds_read == Some(self.preview.revision()) {
+            return iced::Task::none();
+        }
+        self.embeds_read = Some(self.preview.revision());
+        let missing: Vec<crate::embeds::Embed> = self
+            .preview
+            .embeds()
+            .into_iter()
+            .chain(
+                self.embeds
+                    .values()
+                    .flat_map(|embedded| match embedded {
+                        crate::util::Query::Loaded(embedded) => Some(embedded.preview.embeds()),
+                        _ => None,
+                    })
+                    .flatten(),
+            )
+            .filter(|embed| !self.embeds.contains_key(embed))
+            .cloned()
+            .collect();
+        iced::Task::batch(missing.into_iter().map(|embed| {
+            self.embeds
+                .insert(embed.clone(), crate::util::Query::Pending);
+            iced::Task::perform(
+                crate::embeds::load(
+                    config.notes_path.clone(),
+                    embed.name.clone(),
+                    config.default_extension.clone(),
+                ),
+                move |loaded| Message::EmbedLoaded(embed.clone(), loaded),
+            )
+        }))
+    }
+
+    /// Scrolls the preview about to where `line` of the editor is shown.
+    fn scroll_preview(&self, line: usize) -> iced::Task<Message> {
+        let line = self.folds.note_line(line);
+        let last_line = (self.content.line_count() + self.folds.hidden())
+            .saturating_sub(1)
+            .max(1);
+        iced::widget::scrollable::snap_to(
+            iced::widget::scrollable::Id::new(PREVIEW),
+            iced::widget::scrollable::RelativeOffset {
+                x: 0.0,
+                y: line as f32 / last_line as f32,
+            },
+        )
+    }
+}
+
+/// The editor content holding `text`, which may end with an empty line.
+fn content(text: &str) -> iced::widget::text_editor::Content {
+    // The editor drops the newline at the end of its text.
+    iced::widget::text_editor::Content::with_text(&format!("{}\n", text))
+}
+
+/// The text of the whole note and its cursor, for the history.
+fn snapshot(
+    content: &iced::widget::text_editor::Content,
+    folds: &crate::folding::Folds,
+) -> crate::history::Snapshot {
+    let (line, column) = content.cursor_position();
+    crate::history::Snapshot {
+        text: if folds.is_empty() {
+            crate::find::text(content)
+        } else {
+            folds.text(content.lines())
+        },
+        cursor: (folds.note_line(line), column),
+    }
+}
+
+/// The headings of the note, with the section holding the cursor highlighted and whether
+/// each is `folded`.
+fn outline_view<'a>(
+    headings: Vec<crate::preview::Heading>,
+    folded: Vec<bool>,
+    current: Option<usize>,
+) -> iced::Element<'a, Message> {
+    let body: iced::Element<'a, Message> = if headings.is_empty() {
+        iced::widget::text("No headings")
+            .size(12)
+            .style(|theme: &iced::Theme| iced::widget::text::Style {
+                color: Some(iced::Color {
+                    a: 0.6,
+                    ..theme.palette().text
+                }),
+            })
+            .into()
+    } else {
+        iced::widget::scrollable(iced::widget::Column::with_children(
+            headings
+                .into_iter()
+                .zip(folded)
+                .enumerate()
+                .map(|(i, (heading, folded))| {
+                    iced::widget::row![
+                        iced::widget::button(crate::util::icon(if folded {
+                            crate::util::ICON_RIGHT_SMALL
+                        } else {
+                            crate::util::ICON_DOWN_SMALL
+                        }))
+                        .style(crate::util::button_no_bg)
+                        .padding(2)
+                        .on_press(Message::ToggleFold(heading.line)),
+                        iced::widget::button(iced::widget::text(heading.text).size(14))
+                            .style(if current == Some(i) {
+                                crate::util::button_no_bg_active
+                            } else {
+                                crate::util::button_no_bg
+                            })
+                            .width(iced::Length::Fill)
+                            .padding([2, 4])
+                            .on_press(Message::GoToLine(heading.line)),
+                    ]
+                    .padding(iced::padding::left(12 * (heading.level - 1) as u16))
+                    .align_y(iced::Alignment::Center)
+                    .into()
+                }),
+        ))
+        .height(iced::Length::Fill)
+        .into()
+    };
+    iced::widget::container(body).width(200).into()
+}
+
+/// The editor, with a gutter holding a fold marker beside each line starting a section.
+fn editor<'a>(
+    content: &'a iced::widget::text_editor::Content,
+    query: Option<&crate::find::Query>,
+    folds: &crate::folding::Folds,
+) -> iced::Element<'a, Message> {
+    let settings = crate::highlighter::Settings {
+        query: query.cloned().unwrap_or_default(),
+        folded: folds.lines(),
+    };
+    let lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
+    let starts = crate::folding::starts(&lines, |line| folds.is_folded(line));
+    let cursor = content.cursor_position().0;
+
+    // Markers are placed a line height apart, so a line which wraps pushes the ones below it
+    // down from their lines.
+    let mut gutter = iced::widget::Column::new().width(GUTTER);
+    let mut top = 0;
+    for (i, _) in starts.iter().enumerate().filter(|(_, start)| **start) {
+        let marker = iced::widget::button(crate::util::icon(if folds.is_folded(i) {
+            crate::util::ICON_RIGHT_SMALL
+        } else {
+            crate::util::ICON_DOWN_SMALL
+        }))
+        .style(crate::util::button_no_bg)
+        .padding(0)
+        .on_press(Message::ToggleFold(folds.note_line(i)));
+        gutter = gutter
+            .push(iced::widget::vertical_space().height(LINE_HEIGHT * (i - top) as f32))
+            .push(
+                iced::widget::container(marker)
+                    .height(LINE_HEIGHT)
+                    .align_y(iced::Alignment::Center),
+            );
+        top = i + 1;
+    }
+    let cursor_line = iced::widget::column![
+        iced::widget::vertical_space().height(LINE_HEIGHT * cursor as f32),
+        iced::widget::container(iced::widget::Space::new(GUTTER, LINE_HEIGHT))
+            .id(iced::widget::container::Id::new(CURSOR_LINE)),
+    ];
+
+    let editor = iced::widget::TextEditor::new(content)
+        .style(
+            |theme: &iced::Theme, status| iced::widget::text_editor::Style {
+                border: iced::Border {
+                    width: 0.0,
+                    ..Default::default()
+                },
+                ..iced::widget::text_editor::default(theme, status)
+            },
+        )
+        .padding(0)
+        .line_height(iced::Pixels(LINE_HEIGHT))
+        .on_action(Message::Edit)
+        .key_binding(|key_press| {
+            use iced::keyboard::{key::Named, Key};
+            use iced::widget::text_editor::{Binding, Status};
+
+            match key_press.key {
+                Key::Named(Named::Tab) if key_press.status == Status::Focused => {
+                    Some(Binding::Custom(if key_press.modifiers.shift() {
+                        Message::Outdent
+                    } else {
+                        Message::Indent
+                    }))
+                }
+                Key::Character(ref c)
+                    if c.as_str() == "v"
+                        && key_press.modifiers.command()
+                        && !key_press.modifiers.alt()
+                        && key_press.status == Status::Focused =>
+                {
+                    Some(Binding::Custom(Message::Paste))
+                }
+                _ => Binding::from_key_press(key_press),
+            }
+        })
+        .highlight_with::<crate::highlighter::Markdown>(settings, crate::highlighter::to_format);
+
+    // The editor grows with the note, so that the gutter scrolls along with it.
+    iced::widget::scrollable(iced::widget::stack![
+        iced::widget::row![iced::widget::horizontal_space().width(GUTTER), editor],
+        gutter,
+        cursor_line,
+    ])
+    .id(iced::widget::scrollable::Id::new(EDITOR))
+    .height(iced::Length::Fill)
+    .into()
+}
+
+/// Scrolls the editor so that the line with the cursor is in view.
+fn follow_cursor() -> iced::Task<Message> {
+    iced::advanced::widget::operate(FollowCursor::default()).then(|offset| match offset {
+        Some(offset) => {
+            iced::widget::scrollable::scroll_to(iced::widget::scrollable::Id::new(EDITOR), offset)
+        }
+        None => iced::Task::none(),
+    })
+}
+
+/// Finds how far the editor should scroll for its line with the cursor to be in view.
+#[derive(Default)]
+struct FollowCursor {
+    /// The bounds of the editor and how far it's scrolled.
+    editor: Option<(iced::Rectangle, iced::Vector)>,
+    line: Option<iced::Rectangle>,
+}
+
+impl iced::advanced::widget::Operation<Option<iced::widget::scrollable::AbsoluteOffset>>
+    for FollowCursor
+{
+    fn container(
+        &mut self,
+        id: Option<&iced::advanced::widget::Id>,
+        bounds: iced::Rectangle,
+        operate_on_children: &mut dyn FnMut(
+            &mut dyn iced::advanced::widget::Operation<
+                Option<iced::widget::scrollable::AbsoluteOffset>,
+            >,
+        ),
+    ) {
+        if id == Some(&iced::advanced::widget::Id::new(CURSOR_LINE)) {
+            self.line = Some(bounds);
+        }
+        operate_on_children(self);
+    }
+
+    fn scrollable(
+        &mut self,
+        _state: &mut dyn iced::advanced::widget::operation::Scrollable,
+        id: Option<&iced::advanced::widget::Id>,
+        bounds: iced::Rectangle,
+        _content_bounds: iced::Rectangle,
+        translation: iced::Vector,
+    ) {
+        if id == Some(&iced::advanced::widget::Id::new(EDITOR)) {
+            self.editor = Some((bounds, translation));
+        }
+    }
+
+    fn finish(
+        &self,
+    ) -> iced::advanced::widget::operation::Outcome<Option<iced::widget::scrollable::AbsoluteOffset>>
+    {
+        let (Some((editor, translation)), Some(line)) = (self.editor, self.line) else {
+            return iced::advanced::widget::operation::Outcome::Some(None);
+        };
+        // Lines are laid out from the top of the editor, whatever its scroll.
+        let top = line.y - editor.y;
+        let y = if top < translation.y {
+            top
+        } else if top + line.height > translation.y + editor.height {
+            top + line.height - editor.height
+        } else {
+            return iced::advanced::widget::operation::Outcome::Some(None);
+        };
+        iced::advanced::widget::operation::Outcome::Some(Some(
+            iced::widget::scrollable::AbsoluteOffset { x: 0.0, y },
+        ))
+    }
+}
+
+fn preview_view<'a>(
+    preview: &'a crate::preview::Preview,
+    path: &'a std::path::Path,
+    config: &crate::config::Configuration,
+    embeds: &'a std::collections::HashMap<
+        crate::embeds::Embed,
+        crate::util::Query<Embedded, String>,
+    >,
+) -> iced::Element<'a, Message> {
+    let blocks = blocks_view(preview, config, embeds, &mut vec![(path, None)]);
+
+    iced::widget::scrollable(blocks)
+        .id(iced::widget::scrollable::Id::new(PREVIEW))
+        .height(iced::Length::Fill)
+        .into()
+}
+
+/// The blocks of a preview, with the notes embedded in it. `shown` holds the notes being
+/// shown around it, with their section: the note itself, then the ones it's embedded in.
+/// Only the tasks of the note itself can be ticked.
+fn blocks_view<'a>(
+    preview: &'a crate::preview::Preview,
+    config: &crate::config::Configuration,
+    embeds: &'a std::collections::HashMap<
+        crate::embeds::Embed,
+        crate::util::Query<Embedded, String>,
+    >,
+    shown: &mut Vec<(&'a std::path::Path, Option<&'a str>)>,
+) -> iced::Element<'a, Message> {
+    let settings = iced::widget::markdown::Settings::default();
+    let style = iced::widget::markdown::Style::from_palette(crate::util::THEME.palette());
+    let footnotes = preview.footnotes();
+    let ids: Vec<&str> = footnotes
+        .iter()
+        .map(|footnote| footnote.id.as_str())
+        .collect();
+    let ids = &ids;
+
+    // Only the tasks of the note itself can be ticked.
+    let own = shown.len() == 1;
+    let mut elements = rendered_view(
+        preview
+            .blocks()
+            .map(|(start, block)| (own.then_some(start), block)),
+        ids,
+        config,
+        embeds,
+        shown,
+    );
+    if !footnotes.is_empty() {
+        elements.push(iced::widget::horizontal_rule(1).into());
+        elements.push(footnotes_view(preview, &footnotes, ids, settings, style));
+    }
+    iced::widget::Column::with_children(elements)
+        .spacing(settings.text_size)
+        .into()
+}
+
+/// The elements showing `blocks`, each with the line it starts at if its tasks can be ticked.
+/// `shown` holds the notes being shown around them, as for [`blocks_view`].
+fn rendered_view<'a>(
+    blocks: impl Iterator<Item = (Option<usize>, &'a crate::preview::Rendered)>,
+    ids: &[&str],
+    config: &crate::config::Configuration,
+    embeds: &'a std::collections::HashMap<
+        crate::embeds::Embed,
+        crate::util::Query<Embedded, String>,
+    >,
+    shown: &mut Vec<(&'a std::path::Path, Option<&'a str>)>,
+) -> Vec<iced::Element<'a, Message>> {
+    let settings = iced::widget::markdown::Settings::default();
+    let style = iced::widget::markdown::Style::from_palette(crate::util::THEME.palette());
+
+    // Runs of markdown blocks are rendered together, code blocks, rich text and images on
+    // their own.
+    let mut elements = vec![];
+    let mut items = vec![];
+    let flush = |items: &mut Vec<_>, elements: &mut Vec<_>| {
+        if !items.is_empty() {
+            elements.push(
+                iced::widget::markdown::view(std::mem::take(items), settings, style)
+                    .map(Message::None),
+            );
+        }
+    };
+    for (start, block) in blocks {
+        match block {
+            crate::preview::Rendered::Markdown(block_items) => items.extend(block_items),
+            crate::preview::Rendered::Code(code) => {
+                flush(&mut items, &mut elements);
+                elements.push(code_block(code, settings, config.code_line_numbers));
+            }
+            crate::preview::Rendered::Diagram(code, diagram) => {
+                flush(&mut items, &mut elements);
+                elements.push(diagram_view(code, diagram, settings, config));
+            }
+            crate::preview::Rendered::Tasks(list) => {
+                flush(&mut items, &mut elements);
+                elements.push(task_list(start, list, ids, settings, style));
+            }
+            crate::preview::Rendered::Rich(rich) => {
+                flush(&mut items, &mut elements);
+                elements.push(rich_view(rich, ids, settings, style));
+            }
+            crate::preview::Rendered::Callout(callout) => {
+                flush(&mut items, &mut elements);
+                elements.push(callout_view(callout, ids, config, embeds, shown));
+            }
+            crate::preview::Rendered::Definitions(definitions) => {
+                flush(&mut items, &mut elements);
+                elements.push(definitions_view(definitions, ids, settings, style));
+            }
+            // Footnotes are gathered at the end.
+            crate::preview::Rendered::Footnotes(_) => {}
+            crate::preview::Rendered::Segments(segments) => {
+                for segment in segments {
+                    match segment {
+                        crate::preview::Segment::Markdown(segment_items) => {
+                            items.extend(segment_items)
+                        }
+                        crate::preview::Segment::Rich(rich) => {
+                            flush(&mut items, &mut elements);
+                            elements.push(rich_view(rich, ids, settings, style));
+                        }
+                        crate::preview::Segment::Images(images) => {
+                            flush(&mut items, &mut elements);
+                            elements.push(image_row(images, settings));
+                        }
+                        crate::preview::Segment::Embed(embed) => {
+                            flush(&mut items, &mut elements);
+                            elements.push(embed_view(embed, config, embeds, shown, settings));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    flush(&mut items, &mut elements);
+    elements
+}
+
+/// A note embedded in the preview, framed, under its name which opens it. A note embedded in
+/// itself, or nested too deep, only shows its name.
+fn embed_view<'a>(
+    embed: &'a crate::embeds::Embed,
+    config: &crate::config::Configuration,
+    embeds: &'a std::collections::HashMap<
+        crate::embeds::Embed,
+        crate::util::Query<Embedded, String>,
+    >,
+    shown: &mut Vec<(&'a std::path::Path, Option<&'a str>)>,
+    settings: iced::widget::markdown::Settings,
+) -> iced::Element<'a, Message> {
+    let faint = |theme: &iced::Theme| iced::widget::text::Style {
+        color: Some(iced::Color {
+            a: 0.6,
+            ..theme.palette().text
+        }),
+    };
+    let (path, body): (Option<&std::path::Path>, iced::Element<'a, Message>) =
+        match embeds.get(embed) {
+            Some(crate::util::Query::Loaded(embedded)) => {
+                let note = (embedded.path.as_path(), embed.section.as_deref());
+                let body = if shown.contains(&note) {
+                    iced::widget::text("Embedded in itself")
+                        .size(settings.text_size)
+                        .style(faint)
+                        .into()
+                } else if shown.len() > crate::embeds::DEPTH {
+                    iced::widget::text("Nested too deep")
+                        .size(settings.text_size)
+                        .style(faint)
+                        .into()
+                } else {
+                    shown.push(note);
+                    let body = blocks_view(&embedded.preview, config, embeds, shown);
+                    shown.pop();
+                    body
+                };
+                (Some(&embedded.path), body)
+            }
+            Some(crate::util::Query::Error(error)) => (
+                None,
+                iced::widget::text(error)
+                    .size(settings.text_size)
+                    .style(iced::widget::text::danger)
+                    .into(),
+            ),
+            _ => (
+                None,
+                iced::widget::text("Loading...")
+                    .size(settings.text_size)
+                    .style(faint)
+                    .into(),
+            ),
+        };
+    let name = iced::widget::text(embed.to_string())
+        .size(settings.text_size * 0.85)
+        .style(|theme: &iced::Theme| iced::widget::text::Style {
+            color: Some(theme.palette().primary),
+        });
+    let name: iced::Element<'a, Message> = match path {
+        Some(path) => iced::widget::button(name)
+            .padding([2, 6])
+            .style(crate::util::button_no_bg)
+            .on_press(Message::OpenNote(path.to_path_buf()))
+            .into(),
+        None => iced::widget::container(name).padding([2, 6]).into(),
+    };
+    iced::widget::container(iced::widget::column![name, body].spacing(settings.text_size * 0.5))
+        .width(iced::Length::Fill)
+        .padding(settings.text_size.0 * 0.75)
+        .style(|theme: &iced::Theme| iced::widget::container::Style {
+            border: iced::Border {
+                color: theme.extended_palette().background.strong.color,
+                width: 1.0,
+                radius: 4.0.into(),
+            },
+            ..Default::default()
+        })
+        .into()
+}
+
+/// Markdown with formulas and extended syntax, laid out like the Markdown view does.
+/// Footnote references are numbered after their place in `footnotes`.
+fn rich_view<'a>(
+    rich: &'a crate::preview::Rich,
+    footnotes: &[&str],
+    settings: iced::widget::markdown::Settings,
+    style: iced::widget::markdown::Style,
+) -> iced::Element<'a, Message> {
+    rich_items(&rich.items, &rich.formulas, footnotes, settings, style).map(Message::None)
+}
+
+fn rich_items<'a>(
+    items: &'a [iced::widget::markdown::Item],
+    formulas: &'a [crate::preview::Math],
+    footnotes: &[&str],
+    settings: iced::widget::markdown::Settings,
+    style: iced::widget::markdown::Style,
+) -> iced::Element<'a, url::Url> {
+    use iced::widget::markdown::Item;
+
+    let spacing = settings.text_size * 0.625;
+    let list = |marker: String, items: &'a [Item]| {
+        iced::widget::row![
+            iced::widget::text(marker).size(settings.text_size),
+            rich_items(items, formulas, footnotes, settings, style)
+        ]
+        .spacing(spacing)
+        .into()
+    };
+    let mut blocks: Vec<iced::Element<'a, url::Url>> = vec![];
+    for (i, item) in items.iter().enumerate() {
+        blocks.push(match item {
+            Item::Heading(level, heading) => {
+                let size = match level {
+                    iced::widget::markdown::HeadingLevel::H1 => settings.h1_size,
+                    iced::widget::markdown::HeadingLevel::H2 => settings.h2_size,
+                    iced::widget::markdown::HeadingLevel::H3 => settings.h3_size,
+                    iced::widget::markdown::HeadingLevel::H4 => settings.h4_size,
+                    iced::widget::markdown::HeadingLevel::H5 => settings.h5_size,
+                    iced::widget::markdown::HeadingLevel::H6 => settings.h6_size,
+                };
+                let spans = spans(heading, footnotes, size.0, style);
+                iced::widget::container(rich_text(spans, formulas, size.0))
+                    .padding(iced::padding::top(if i > 0 {
+                        settings.text_size.0 / 2.0
+                    } else {
+                        0.0
+                    }))
+                    .into()
+            }
+            Item::Paragraph(paragraph) => {
+                let spans = spans(paragraph, footnotes, settings.text_size.0, style);
+                rich_text(spans, formulas, settings.text_size.0)
+            }
+            Item::List { start, items } => {
+                iced::widget::Column::with_children(items.iter().enumerate().map(|(i, items)| {
+                    let marker = match start {
+                        Some(start) => format!("{}.", i as u64 + start),
+                        None => "•".into(),
+                    };
+                    list(marker, items)
+                }))
+                .spacing(spacing)
+                .into()
+            }
+            // Formulas aren't read in code, so they're shown as they were written.
+            Item::CodeBlock(code) => {
+                let spans: Vec<_> = code
+                    .spans(style)
+                    .iter()
+                    .map(|span| {
+                        let mut span = span.clone();
+                        if span.text.contains(is_formula) {
+                            let mut text = String::new();
+                            for c in span.text.chars() {
+                                match crate::preview::formula(c) {
+                                    Some(index) => text.extend(formulas.get(index).map(delimited)),
+                                    None => text.push(c),
+                                }
+                            }
+                            span.text = text.into();
+                        }
+                        span
+                    })
+                    .collect();
+                iced::widget::container(
+                    iced::widget::rich_text(spans)
+                        .font(iced::Font::MONOSPACE)
+                        .size(settings.code_size),
+                )
+                .width(iced::Length::Fill)
+                .padding(spacing.0)
+                .style(iced::widget::container::dark)
+                .into()
+            }
+        });
+    }
+    iced::widget::Column::with_children(blocks)
+        .width(iced::Length::Fill)
+        .spacing(settings.text_size)
+        .into()
+}
+
+/// Whether `c` stands for a formula.
+fn is_formula(c: char) -> bool {
+    crate::preview::formula(c).is_some()
+}
+
+/// A formula as written in the note.
+fn delimited(formula: &crate::preview::Math) -> String {
+    let delimiter = if formula.display { "$$" } else { "$" };
+    format!("{}{}{}", delimiter, formula.source, delimiter)
+}
+
+/// The spans of `text` with its extended syntax applied: highlights, superscripts,
+/// subscripts and footnote references, numbered after their place in `footnotes`.
+fn spans(
+    text: &iced::widget::markdown::Text,
+    footnotes: &[&str],
+    size: f32,
+    style: iced::widget::markdown::Style,
+) -> Vec<iced::widget::text::Span<'static, url::Url>> {
+    use crate::extended::{FOOTNOTE_REFERENCE, MARK, SUBSCRIPT, SUPERSCRIPT};
+
+    let highlight = iced::advanced::text::Highlight {
+        background: crate::extended::highlight().into(),
+        border: iced::Border::default().rounded(2),
+    };
+    let mut spans = vec![];
+    let mut marked = false;
+    // The marker opening a superscript, subscript or footnote reference, and its text.
+    let mut opened: Option<char> = None;
+    let mut inner = String::new();
+    for span in text.spans(style).iter() {
+        let mut piece = String::new();
+        let push = |piece: &mut String, spans: &mut Vec<_>, marked: bool| {
+            if !piece.is_empty() {
+                let mut span = span.clone();
+                span.text = std::mem::take(piece).into();
+                if marked {
+                    span.highlight = Some(highlight);
+                }
+                spans.push(span);
+            }
+        };
+        for c in span.text.chars() {
+            match c {
+                c if c == MARK.0 || c == MARK.1 => {
+                    push(&mut piece, &mut spans, marked);
+                    marked = c == MARK.0;
+                }
+                c if [SUPERSCRIPT.0, SUBSCRIPT.0, FOOTNOTE_REFERENCE.0].contains(&c) => {
+                    push(&mut piece, &mut spans, marked);
+                    opened = Some(c);
+                }
+                c if [SUPERSCRIPT.1, SUBSCRIPT.1, FOOTNOTE_REFERENCE.1].contains(&c) => {
+                    let text = std::mem::take(&mut inner);
+                    let mut script = span.clone();
+                    if marked {
+                        script.highlight = Some(highlight);
+                    }
+                    let written = match opened.take() {
+                        Some(open) if open == FOOTNOTE_REFERENCE.0 => {
+                            match footnotes.iter().position(|id| *id == text) {
+                                Some(i) => {
+                                    let number = (i + 1).to_string();
+                                    script.link =
+                                        url::Url::parse(&format!("footnote:{}", number)).ok();
+                                    script.color = Some(style.link_color);
+                                    crate::extended::superscript(&number)
+                                }
+                                None => Some(format!("[^{}]", text)),
+                            }
+                        }
+                        Some(open) if open == SUPERSCRIPT.0 => crate::extended::superscript(&text),
+                        _ => crate::extended::subscript(&text),
+                    };
+                    // What has no such characters is made smaller instead.
+                    script.text = match written {
+                        Some(written) => written.into(),
+                        None => {
+                            script.size = Some((size * 0.7).into());
+                            text.into()
+                        }
+                    };
+                    spans.push(script);
+                }
+                c if opened.is_some() => inner.push(c),
+                c => piece.push(c),
+            }
+        }
+        push(&mut piece, &mut spans, marked);
+    }
+    spans
+}
+
+/// Text with formulas, wrapped a word at a time so that formulas flow with it. Display
+/// formulas are centered on their own line.
+fn rich_text<'a>(
+    spans: Vec<iced::widget::text::Span<'static, url::Url>>,
+    formulas: &'a [crate::preview::Math],
+    size: f32,
+) -> iced::Element<'a, url::Url> {
+    if !spans.iter().any(|span| span.text.contains(is_formula)) {
+        return iced::widget::rich_text(spans).size(size).into();
+    }
+
+    let mut lines: Vec<iced::Element<'a, url::Url>> = vec![];
+    let mut row: Vec<iced::Element<'a, url::Url>> = vec![];
+    let mut word = vec![];
+    let flush_word = |word: &mut Vec<iced::widget::text::Span<'static, url::Url>>,
+                      row: &mut Vec<iced::Element<'a, url::Url>>| {
+        if !word.is_empty() {
+            row.push(
+                iced::widget::rich_text(std::mem::take(word))
+                    .size(size)
+                    .into(),
+            );
+        }
+    };
+    let flush_row = |row: &mut Vec<_>, lines: &mut Vec<_>| {
+        if !row.is_empty() {
+            lines.push(
+                iced::widget::Row::with_children(std::mem::take(row))
+                    .align_y(iced::Alignment::Center)
+                    .wrap()
+                    .into(),
+            );
+        }
+    };
+
+    for span in &spans {
+        let mut start = 0;
+        for (i, c) in span.text.char_indices() {
+            let index = crate::preview::formula(c);
+            if index.is_none() && !c.is_whitespace() {
+                continue;
+            }
+            // Words keep the space after them, so that they're spaced as usual.
+            let end = if index.is_some() { i } else { i + c.len_utf8() };
+            if end > start {
+                let mut piece = span.clone();
+                piece.text = span.text[start..end].to_string().into();
+                word.push(piece);
+            }
+            start = i + c.len_utf8();
+            flush_word(&mut word, &mut row);
+            let Some(formula) = index.and_then(|index| formulas.get(index)) else {
+                continue;
+            };
+            let element = formula_view(formula, size);
+            if formula.display {
+                flush_row(&mut row, &mut lines);
+                lines.push(
+                    iced::widget::container(element)
+                        .center_x(iced::Length::Fill)
+                        .into(),
+                );
+            } else {
+                row.push(element);
+                // Spaced from the word after it.
+                if span.text[start..].starts_with(char::is_whitespace) {
+                    row.push(iced::widget::horizontal_space().width(size * 0.3).into());
+                }
+            }
+        }
+        if start < span.text.len() {
+            let mut piece = span.clone();
+            piece.text = span.text[start..].to_string().into();
+            word.push(piece);
+        }
+    }
+    flush_word(&mut word, &mut row);
+    flush_row(&mut row, &mut lines);
+    iced::widget::Column::with_children(lines)
+        .spacing(size * 0.5)
+        .into()
+}
+
+/// A callout, framed in the color of its kind, its body shown as blocks of a note are.
+fn callout_view<'a>(
+    callout: &'a crate::preview::Callout,
+    footnotes: &[&str],
+    config: &crate::config::Configuration,
+    embeds: &'a std::collections::HashMap<
+        crate::embeds::Embed,
+        crate::util::Query<Embedded, String>,
+    >,
+    shown: &mut Vec<(&'a std::path::Path, Option<&'a str>)>,
+) -> iced::Element<'a, Message> {
+    let settings = iced::widget::markdown::Settings::default();
+    let color = callout.kind.color();
+    let title = iced::widget::row![
+        iced::widget::text(callout.kind.icon())
+            .size(settings.text_size)
+            .color(color),
+        iced::widget::text(&callout.title)
+            .size(settings.text_size)
+            .font(iced::Font {
+                weight: iced::font::Weight::Bold,
+                ..iced::Font::DEFAULT
+            })
+            .color(color),
+    ]
+    .spacing(settings.text_size * 0.5);
+    let body = rendered_view(
+        callout.body.iter().map(|block| (None, block)),
+        footnotes,
+        config,
+        embeds,
+        shown,
+    );
+    iced::widget::container(
+        iced::widget::column![title]
+            .extend(body)
+            .spacing(settings.text_size * 0.625),
+    )
+    .width(iced::Length::Fill)
+    .padding(settings.text_size.0 * 0.75)
+    .style(move |_| iced::widget::container::Style {
+        background: Some(iced::Color { a: 0.1, ..color }.into()),
+        border: iced::Border {
+            color,
+            width: 1.0,
+            radius: 4.0.into(),
+        },
+        ..Default::default()
+    })
+    .into()
+}
+
+/// A definition list: terms in bold, with their definitions indented under them.
+fn definitions_view<'a>(
+    definitions: &'a [crate::preview::Definition],
+    footnotes: &[&str],
+    settings: iced::widget::markdown::Settings,
+    style: iced::widget::markdown::Style,
+) -> iced::Element<'a, Message> {
+    let size = settings.text_size.0;
+    iced::widget::Column::with_children(definitions.iter().map(|definition| {
+        let term = definition.term.items.iter().filter_map(|item| match item {
+            iced::widget::markdown::Item::Paragraph(text) => {
+                let spans = spans(text, footnotes, size, style)
+                    .into_iter()
+                    .map(|span| {
+                        let font = span.font.unwrap_or_default();
+                        span.font(iced::Font {
+                            weight: iced::font::Weight::Bold,
+                            ..font
+                        })
+                    })
+                    .collect();
+                Some(rich_text(spans, &definition.term.formulas, size).map(Message::None))
+            }
+            _ => None,
+        });
+        let details = definition
+            .details
+            .iter()
+            .map(|detail| rich_view(detail, footnotes, settings, style));
+        iced::widget::column![
+            iced::widget::Column::with_children(term),
+            iced::widget::Column::with_children(details)
+                .spacing(size * 0.5)
+                .padding(iced::padding::left(size * 1.5)),
+        ]
+        .spacing(size * 0.25)
+        .into()
+    }))
+    .spacing(settings.text_size)
+    .into()
+}
+
+/// The footnotes of the note, numbered, each linking back to where it's first referenced.
+fn footnotes_view<'a>(
+    preview: &crate::preview::Preview,
+    footnotes: &[&'a crate::preview::Footnote],
+    ids: &[&str],
+    settings: iced::widget::markdown::Settings,
+    style: iced::widget::markdown::Style,
+) -> iced::Element<'a, Message> {
+    iced::widget::Column::with_children(footnotes.iter().enumerate().map(|(i, footnote)| {
+        let number = i + 1;
+        let back = preview.is_referenced(&footnote.id).then(|| {
+            let back: iced::Element<'a, url::Url> =
+                iced::widget::rich_text([iced::widget::span("↩")
+                    .color(style.link_color)
+                    .link_maybe(url::Url::parse(&format!("footnote-ref:{}", number)).ok())])
+                .size(settings.text_size)
+                .into();
+            back.map(Message::None)
+        });
+        iced::widget::row![
+            iced::widget::text(format!("{}.", number)).size(settings.text_size),
+            rich_view(&footnote.text, ids, settings, style),
+        ]
+        .push_maybe(back)
+        .spacing(settings.text_size * 0.625)
+        .into()
+    }))
+    .spacing(settings.text_size * 0.625)
+    .into()
+}
+
+/// A typeset formula, or its source with the error in a tooltip when it can't be read.
+fn formula_view<'a>(formula: &'a crate::preview::Math, size: f32) -> iced::Element<'a, url::Url> {
+    match &formula.node {
+        Ok(node) => crate::math::formula(node, &formula.source, size, formula.display).into(),
+        Err(error) => iced::widget::tooltip(
+            iced::widget::text(delimited(formula))
+                .font(iced::Font::MONOSPACE)
+                .size(size * 0.9)
+                .style(iced::widget::text::danger),
+            iced::widget::container(iced::widget::text(error.clone()).size(size * 0.8))
+                .padding(6)
+                .style(iced::widget::container::rounded_box),
+            iced::widget::tooltip::Position::Top,
+        )
+        .into(),
+    }
+}
+
+/// A row of images of the preview, opened in the lightbox when clicked. Images that can't be
+/// found show a placeholder with their path.
+fn image_row<'a>(
+    images: &'a [crate::preview::Image],
+    settings: iced::widget::markdown::Settings,
+) -> iced::Element<'a, Message> {
+    iced::widget::Row::with_children(images.iter().map(|image| {
+        match &image.file {
+            Some(file) => iced::widget::mouse_area(picture(
+                file,
+                image
+                    .width
+                    .map_or(iced::Length::Shrink, iced::Length::Fixed),
+                image
+                    .height
+                    .map_or(iced::Length::Shrink, iced::Length::Fixed),
+            ))
+            .on_press(Message::OpenImage(file.clone()))
+            .interaction(iced::mouse::Interaction::Pointer)
+            .into(),
+            None => iced::widget::container(
+                iced::widget::column![
+                    iced::widget::text(if image.alt.is_empty() {
+                        "Image not found"
+                    } else {
+                        &image.alt
+                    })
+                    .size(settings.text_size),
+                    iced::widget::text(&image.source)
+                        .font(iced::Font::MONOSPACE)
+                        .size(settings.code_size)
+                        .style(|theme: &iced::Theme| iced::widget::text::Style {
+                            color: Some(iced::Color {
+                                a: 0.6,
+                                ..theme.palette().text
+                            }),
+                        }),
+                ]
+                .spacing(4),
+            )
+            .padding(8)
+            .style(|theme: &iced::Theme| iced::widget::container::Style {
+                border: iced::Border {
+                    color: theme.palette().danger,
+                    width: 1.0,
+                    radius: 4.0.into(),
+                },
+                ..Default::default()
+            })
+            .into(),
+        }
+    }))
+    .spacing(8)
+    .wrap()
+    .into()
+}
+
+/// The image in `file`, drawn as a vector if it's an SVG.
+fn picture<'a>(
+    file: &std::path::Path,
+    width: iced::Length,
+    height: iced::Length,
+) -> iced::Element<'a, Message> {
+    let svg = file
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+    if svg {
+        iced::widget::svg(iced::widget::svg::Handle::from_path(file))
+            .width(width)
+            .height(height)
+            .into()
+    } else {
+        iced::widget::image(iced::widget::image::Handle::from_path(file))
+            .width(width)
+            .height(height)
+            .into()
+    }
+}
+
+/// An image of the preview shown over the whole note, closed by a click or Escape.
+fn lightbox_view(file: &std::path::Path) -> iced::Element<'_, Message> {
+    iced::widget::opaque(
+        iced::widget::mouse_area(
+            iced::widget::container(picture(file, iced::Length::Fill, iced::Length::Fill))
+                .padding(32)
+                .center(iced::Length::Fill)
+                .style(|_theme: &iced::Theme| iced::widget::container::Style {
+                    background: Some(
+                        iced::Color {
+                            a: 0.85,
+                            ..iced::Color::BLACK
+                        }
+                        .into(),
+                    ),
+                    ..Default::default()
+                }),
+        )
+        .on_press(Message::CloseImage),
+    )
+}
+
+/// A diagram of the preview, or its code with the error when it can't be drawn.
+fn diagram_view<'a>(
+    code: &'a crate::preview::CodeBlock,
+    diagram: &Result<iced::widget::svg::Handle, String>,
+    settings: iced::widget::markdown::Settings,
+    config: &crate::config::Configuration,
+) -> iced::Element<'a, Message> {
+    match diagram {
+        Ok(handle) => iced::widget::svg(handle.clone())
+            .width(iced::Length::Shrink)
+            .height(iced::Length::Shrink)
+            .into(),
+        Err(error) => iced::widget::column![
+            code_block(code, settings, config.code_line_numbers),
+            iced::widget::text(error.clone())
+                .size(settings.code_size)
+                .style(iced::widget::text::danger),
+        ]
+        .spacing(4)
+        .into(),
+    }
+}
+
+/// A fenced code block of the preview, with its language, a copy button and optionally line
+/// numbers.
+fn code_block(
+    code: &crate::preview::CodeBlock,
+    settings: iced::widget::markdown::Settings,
+    line_numbers: bool,
+) -> iced::Element<'_, Message> {
+    let lines = iced::widget::Column::with_children(code.code.lines().zip(&code.lines).map(
+        |(line, tokens)| {
+            let mut spans: Vec<_> = tokens
+                .iter()
+                .map(|(range, token)| {
+                    iced::widget::span(&line[range.clone()])
+                        .color_maybe(crate::highlighter::token_color(*token, &crate::util::THEME))
+                })
+                .collect();
+            // Keeps the height of empty lines.
+            if spans.is_empty() {
+                spans.push(iced::widget::span(" "));
+            }
+            iced::widget::rich_text(spans)
+                .font(iced::Font::MONOSPACE)
+                .size(settings.code_size)
+                .into()
+        },
+    ));
+
+    let body: iced::Element<'_, Message> = if line_numbers {
+        let numbers = (1..=code.lines.len()).map(|number| {
+            iced::widget::text(number)
+                .font(iced::Font::MONOSPACE)
+                .size(settings.code_size)
+                .style(|theme: &iced::Theme| iced::widget::text::Style {
+                    color: Some(iced::Color {
+                        a: 0.5,
+                        ..theme.palette().text
+                    }),
+                })
+                .into()
+        });
+        iced::widget::row![
+            iced::widget::Column::with_children(numbers)
+                .align_x(iced::alignment::Horizontal::Right),
+            lines
+        ]
+        .spacing(12)
+        .into()
+    } else {
+        lines.into()
+    };
+
+    iced::widget::container(
+        iced::widget::column![
+            iced::widget::row![
+                iced::widget::text(code.language.clone()).size(settings.code_size),
+                iced::widget::horizontal_space(),
+                iced::widget::button(iced::widget::text("Copy").size(settings.code_size))
+                    .style(crate::util::button_no_bg)
+                    .padding([2, 6])
+                    .on_press(Message::CopyCode(code.code.clone())),
+            ]
+            .align_y(iced::Alignment::Center),
+            iced::widget::scrollable(body).direction(
+                iced::widget::scrollable::Direction::Horizontal(
+                    iced::widget::scrollable::Scrollbar::default()
+                        .width(4)
+                        .scroller_width(4),
+                )
+            ),
+        ]
+        .spacing(4),
+    )
+    .width(iced::Length::Fill)
+    .padding(8)
+    .style(|theme: &iced::Theme| iced::widget::container::Style {
+        background: Some(theme.extended_palette().background.weak.color.into()),
+        border: iced::Border {
+            radius: 4.0.into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .into()
+}
+
+/// A list of the preview holding tasks, with a checkbox for each task.
+fn task_list<'a>(
+    start: Option<usize>,
+    list: &'a crate::tasks::TaskList,
+    footnotes: &[&str],
+    settings: iced::widget::markdown::Settings,
+    style: iced::widget::markdown::Style,
+) -> iced::Element<'a, Message> {
+    iced::widget::Column::with_children(list.items.iter().map(|item| {
+        let line = start.map(|start| start + item.line);
+        let marker: iced::Element<'a, Message> = match item.checked {
+            Some(checked) => iced::widget::checkbox("", checked)
+                .on_toggle_maybe(line.map(|line| move |_| Message::ToggleTask(line)))
+                .size(settings.text_size)
+                .spacing(0)
+                .into(),
+            None => iced::widget::text("•").size(settings.text_size).into(),
+        };
+        iced::widget::row![marker, rich_view(&item.text, footnotes, settings, style)]
+            .spacing(settings.text_size * 0.625)
+            .padding(iced::padding::left(
+                settings.text_size * 1.5 * item.depth as f32,
+            ))
+            .into()
+    }))
+    .spacing(settings.text_size * 0.625)
+    .into()
+}
+
+/// The changes to the table holding the cursor, or a grid to pick the size of a new table.
+fn table_menu_view<'a>(
+    content: &iced::widget::text_editor::Content,
+    hovered: (usize, usize),
+) -> iced::Element<'a, Message> {
+    use crate::tables::{Align, Change};
+
+    let lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
+    let (line, column) = content.cursor_position();
+    let table = (!crate::lists::in_code(&lines, line))
+        .then(|| crate::tables::Table::at(&lines, line))
+        .flatten();
+    if let Some(table) = table {
+        let cell = table.position(&lines[line], line, column).cell;
+        let button = |label: &'a str, change| {
+            iced::widget::button(label)
+                .style(crate::util::button_secondary)
+                .on_press(Message::EditTable(change))
+        };
+        return iced::widget::row![
+            button("Row above", Change::RowAbove),
+            button("Row below", Change::RowBelow),
+            button("Delete row", Change::DeleteRow),
+            button("Column left", Change::ColumnLeft),
+            button("Column right", Change::ColumnRight),
+            button("Delete column", Change::DeleteColumn),
+            button(
+                match table.align(cell) {
+                    Align::None => "Align: default",
+                    Align::Left => "Align: left",
+                    Align::Center => "Align: center",
+                    Align::Right => "Align: right",
+                },
+                Change::Align
+            ),
+        ]
+        .spacing(8)
+        .padding([4, 0])
+        .into();
+    }
+
+    const ROWS: usize = 8;
+    const COLUMNS: usize = 8;
+    let grid = iced::widget::Column::with_children((1..=ROWS).map(|rows| {
+        iced::widget::Row::with_children((1..=COLUMNS).map(|columns| {
+            iced::widget::mouse_area(
+                iced::widget::button(iced::widget::text(""))
+                    .width(16)
+                    .height(16)
+                    .padding(0)
+                    .style(if rows <= hovered.0 && columns <= hovered.1 {
+                        crate::util::button_no_bg_active
+                    } else {
+                        crate::util::button_secondary
+                    })
+                    .on_press(Message::InsertTable(rows, columns)),
+            )
+            .on_enter(Message::HoverTableSize(rows, columns))
+            .into()
+        }))
+        .spacing(2)
+        .into()
+    }))
+    .spacing(2);
+    let label = match hovered {
+        (0, _) => "Insert a table".to_string(),
+        (rows, columns) => format!("{} × {} table", rows, columns),
+    };
+    iced::widget::column![iced::widget::text(label).size(12), grid]
+        .spacing(4)
+        .padding([4, 0])
+        .into()
+}
+
+fn find_bar(find: &FindBar) -> iced::Element<'_, Message> {
+    let status = match (&find.error, find.current) {
+        (Some(e), _) => e.lines().last().unwrap_or_default().to_string(),
+        (None, _) if find.query.text.is_empty() => String::new(),
+        (None, _) if find.matches.is_empty() => "No matches".to_string(),
+        (None, Some(current)) => format!("{} of {}", current + 1, find.matches.len()),
+        (None, None) => format!("{} matches", find.matches.len()),
+    };
+
+    let find_row = iced::widget::row![
+        iced::widget::text_input("Find", &find.query.text)
+            .id(iced::widget::text_input::Id::new(FIND))
+            .on_input(Message::FindText)
+            .on_submit(Message::FindNext)
+            .width(240),
+        iced::widget::button("Previous")
+            .style(crate::util::button_secondary)
+            .on_press(Message::FindPrevious),
+        iced::widget::button("Next")
+            .style(crate::util::button_secondary)
+            .on_press(Message::FindNext),
+        iced::widget::checkbox("Match case", find.query.case_sensitive)
+            .on_toggle(Message::SetCaseSensitive),
+        iced::widget::checkbox("Whole word", find.query.whole_word)
+            .on_toggle(Message::SetWholeWord),
+        iced::widget::checkbox("Regex", find.query.regex).on_toggle(Message::SetRegex),
+        iced::widget::text(status).size(12),
+        iced::widget::horizontal_space(),
+        iced::widget::button(crate::util::icon(crate::util::ICON_DELETE))
+            .style(crate::util::button_no_bg)
+            .on_press(Message::CloseFind),
+    ]
+    .spacing(8)
+    .align_y(iced::Alignment::Center);
+
+    let replace_row = find.replace.as_ref().map(|replace| {
+        iced::widget::row![
+            iced::widget::text_input("Replace", replace)
+                .on_input(Message::ReplaceText)
+                .on_submit(Message::ReplaceOne)
+                .width(240),
+            iced::widget::button("Replace")
+                .style(crate::util::button_secondary)
+                .on_press(Message::ReplaceOne),
+            iced::widget::button("Replace all")
+                .style(crate::util::button_secondary)
+                .on_press(Message::ReplaceAll),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center)
+    });
+
+    iced::widget::column![find_row]
+        .push_maybe(replace_row)
+        .spacing(4)
+        .padding([4, 0])
+        .into()
+}
@@ -1,26 +1,152 @@
 use std::io;
 
-use iced::Theme;
-
 pub struct Notes {
+    config: crate::config::Configuration,
     notes: crate::util::Query<Directory, io::ErrorKind>,
     current: Option<crate::note_editor::NoteEditor>,
+    image: Option<File>,
+    selected: Option<std::path::PathBuf>,
+    new_note: Option<NewNote>,
+    dragging: Option<std::path::PathBuf>,
+    drop_target: Option<std::path::PathBuf>,
+    agenda: Option<crate::agenda::Agenda>,
+    /// Kept when hidden so that its last replace can still be undone.
+    vault_search: crate::vault_search::VaultSearch,
+    show_search: bool,
+    /// What the notes opened before keep, by path.
+    kept: std::collections::HashMap<std::path::PathBuf, crate::note_editor::Kept>,
+}
+
+/// The inline prompt shown in place of a freshly created note until it gets a title.
+struct NewNote {
+    file: File,
+    title: String,
+    templates: Vec<crate::templates::Template>,
+    template: Option<crate::templates::Template>,
+    error: Option<io::ErrorKind>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Create,
+    LoadedTemplates(Result<Vec<crate::templates::Template>, std::io::ErrorKind>),
+    NewNoteTitle(String),
+    NewNoteTemplate(Option<crate::templates::Template>),
+    ConfirmCreate,
+    CancelCreate,
+    Created(Result<File, std::io::ErrorKind>),
+    Named(Result<NamedNote, std::io::ErrorKind>),
     Delete(std::path::PathBuf),
+    Open(File),
     SetCurrent(std::path::PathBuf, String),
-    NoteEditorMessage(crate::note_editor::Message),
+    Editor(crate::note_editor::Message),
+    /// A note was saved, which may no longer be the open one.
+    NoteSaved(std::path::PathBuf, Result<(), std::io::ErrorKind>),
     LoadFiles(Result<Directory, std::io::ErrorKind>),
     Expand(std::path::PathBuf, bool),
+    SetSort(std::path::PathBuf, crate::sorting::SortMode),
+    SetFoldersFirst(std::path::PathBuf, bool),
+    StartDrag(std::path::PathBuf),
+    DragOver(std::path::PathBuf),
+    EndDrag,
+    SavedOrder(Result<(), std::io::ErrorKind>),
+    ToggleAgenda,
+    Agenda(crate::agenda::Message),
+    ToggleSearch,
+    Search(crate::vault_search::Message),
+    ExportFolder(std::path::PathBuf),
+    Exported(Result<std::path::PathBuf, String>),
 }
 
 #[derive(Debug, Clone)]
-struct File {
+pub struct File {
     display_name: String,
     path: std::path::PathBuf,
+    kind: FileKind,
+    modified: Option<std::time::SystemTime>,
+    created: Option<std::time::SystemTime>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Note,
+    Image,
+    Attachment,
+}
+
+/// Decides which files show up in the note tree and what kind of file each one is.
+#[derive(Debug, Clone)]
+struct FileRules {
+    root: std::path::PathBuf,
+    include: Option<globset::GlobSet>,
+    exclude: globset::GlobSet,
+    note_extension: String,
+}
+
+impl FileRules {
+    fn new(config: &crate::config::Configuration) -> Self {
+        let build = |globs: &[String]| {
+            let mut builder = globset::GlobSetBuilder::new();
+            for glob in globs {
+                match globset::Glob::new(glob) {
+                    Ok(glob) => {
+                        builder.add(glob);
+                    }
+                    Err(e) => eprintln!("Ignoring invalid glob {:?}: {}", glob, e),
+                }
+            }
+            builder
+                .build()
+                .unwrap_or_else(|_| globset::GlobSet::empty())
+        };
+
+        Self {
+            root: config.notes_path.clone(),
+            include: if config.include.is_empty() {
+                None
+            } else {
+                Some(build(&config.include))
+            },
+            exclude: build(&config.exclude),
+            note_extension: config.default_extension.to_lowercase(),
+        }
+    }
+
+    /// Globs match either the file name or the path relative to the notes folder. Include
+    /// globs only apply to files, so folders are always walked unless excluded.
+    fn is_visible(&self, path: &std::path::Path, is_dir: bool) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let name = path.file_name().map(std::path::Path::new).unwrap_or(path);
+        let matches = |set: &globset::GlobSet| set.is_match(name) || set.is_match(relative);
+
+        if matches(&self.exclude) {
+            return false;
+        }
+        match &self.include {
+            Some(include) if !is_dir => matches(include),
+            _ => true,
+        }
+    }
+
+    fn kind(&self, path: &std::path::Path) -> FileKind {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            None | Some("md" | "markdown" | "mdown" | "txt") => FileKind::Note,
+            Some(extension) if extension == self.note_extension => FileKind::Note,
+            _ if crate::attachments::is_image(path) => FileKind::Image,
+            Some(_) => FileKind::Attachment,
+        }
+    }
+}
+
+/// A new note after it got its title, along with the path it was created under.
+#[derive(Debug, Clone)]
+pub struct NamedNote {
+    old_path: std::path::PathBuf,
+    file: File,
+    cursor: Option<(usize, usize)>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,24 +156,97 @@ pub struct Directory {
     expanded: bool,
     files: Vec<File>,
     directories: Vec<Directory>,
+    order: crate::sorting::DirectoryOrder,
+    modified: Option<std::time::SystemTime>,
+    created: Option<std::time::SystemTime>,
+}
+
+/// An entry of a directory, as displayed in the tree.
+enum Entry<'a> {
+    Directory(&'a Directory),
+    File(&'a File),
+}
+
+impl Entry<'_> {
+    fn sort_key(&self) -> crate::sorting::SortKey<'_> {
+        match self {
+            Entry::Directory(directory) => crate::sorting::SortKey {
+                name: &directory.display_name,
+                is_dir: true,
+                modified: directory.modified,
+                created: directory.created,
+            },
+            Entry::File(file) => crate::sorting::SortKey {
+                name: &file.display_name,
+                is_dir: false,
+                modified: file.modified,
+                created: file.created,
+            },
+        }
+    }
+}
+
+impl Directory {
+    /// The directories and files of this directory, in the order chosen for it.
+    fn entries(&self) -> Vec<Entry<'_>> {
+        let mut entries: Vec<Entry<'_>> = self
+            .directories
+            .iter()
+            .map(Entry::Directory)
+            .chain(self.files.iter().map(Entry::File))
+            .collect();
+        entries.sort_by(|a, b| self.order.compare(&a.sort_key(), &b.sort_key()));
+        entries
+    }
+
+    /// The notes and folders of this directory, in the order of the tree, for a site.
+    fn site(&self) -> crate::site::Folder {
+        crate::site::Folder {
+            name: self.display_name.clone(),
+            path: self.path.clone(),
+            items: self
+                .entries()
+                .into_iter()
+                .filter_map(|entry| match entry {
+                    Entry::Directory(directory) => {
+                        Some(crate::site::Item::Folder(directory.site()))
+                    }
+                    Entry::File(file) => (file.kind == FileKind::Note)
+                        .then(|| crate::site::Item::Note(file.path.clone())),
+                })
+                .collect(),
+        }
+    }
 }
 
 impl Notes {
-    pub fn new(location: std::path::PathBuf) -> (Self, iced::Task<Message>) {
+    pub fn new(config: crate::config::Configuration) -> (Self, iced::Task<Message>) {
+        let location = config.notes_path.clone();
+        let rules = FileRules::new(&config);
         (
             Self {
+                config,
                 notes: crate::util::Query::Pending,
                 current: None,
+                image: None,
+                selected: None,
+                new_note: None,
+                dragging: None,
+                drop_target: None,
+                agenda: None,
+                vault_search: crate::vault_search::VaultSearch::new(),
+                show_search: false,
+                kept: std::collections::HashMap::new(),
             },
-            iced::Task::perform(load_files(location), Message::LoadFiles),
+            iced::Task::perform(load_files(location, rules), Message::LoadFiles),
         )
     }
-    pub fn view<'a>(self: &Self) -> iced::Element<'_, Message> {
+    pub fn view(&self) -> iced::Element<'_, Message> {
         let note_list: iced::Element<'_, Message> = match &self.notes {
             crate::util::Query::Pending => iced::widget::text("Loading...").into(),
             crate::util::Query::Error(e) => iced::widget::text(format!("Error: {:?}", e)).into(),
             crate::util::Query::Loaded(directory) => {
-                iced::widget::scrollable(self.dir_tree(&directory))
+                iced::widget::scrollable(self.dir_tree(directory))
                     .height(iced::Length::Fill)
                     .into()
             }
@@ -56,15 +255,33 @@ impl Notes {
         let sidebar = iced::widget::container(
             iced::widget::column![
                 iced::widget::container(
-                    iced::widget::button(crate::util::icon(crate::util::ICON_EDIT))
-                        .style(crate::util::button_secondary)
-                        .on_press(Message::Create)
+                    iced::widget::row![
+                        iced::widget::button(crate::util::icon(crate::util::ICON_EDIT))
+                            .style(crate::util::button_secondary)
+                            .on_press(Message::Create),
+                        iced::widget::button("Agenda")
+                            .style(if self.agenda.is_some() {
+                                crate::util::button_no_bg_active
+                            } else {
+                                crate::util::button_secondary
+                            })
+                            .on_press(Message::ToggleAgenda),
+                        iced::widget::button("Replace")
+                            .style(if self.show_search {
+                                crate::util::button_no_bg_active
+                            } else {
+                                crate::util::button_secondary
+                            })
+                            .on_press(Message::ToggleSearch),
+                    ]
+                    .spacing(8)
                 )
                 .center_x(iced::Length::Fill)
                 .width(iced::Length::Fill),
                 iced::widget::horizontal_rule(1),
-                note_list,
             ]
+            .push_maybe(self.sort_controls())
+            .push(note_list)
             .spacing(8),
         )
         .style(|theme| iced::widget::container::Style {
@@ -74,8 +291,14 @@ impl Notes {
         .width(280)
         .padding(8);
 
-        let main_view: iced::Element<'_, Message> = if let Some(current_note) = &self.current {
-            crate::note_editor::NoteEditor::view(current_note).map(Message::NoteEditorMessage)
+        let main_view: iced::Element<'_, Message> = if let Some(agenda) = &self.agenda {
+            agenda.view().map(Message::Agenda)
+        } else if self.show_search {
+            self.vault_search.view().map(Message::Search)
+        } else if let Some(image) = &self.image {
+            image_viewer(image)
+        } else if let Some(current_note) = &self.current {
+            crate::note_editor::NoteEditor::view(current_note).map(Message::Editor)
         } else {
             iced::widget::container(iced::widget::text("No note selected"))
                 .padding(8)
@@ -85,53 +308,286 @@ impl Notes {
         iced::widget::row![sidebar, main_view].into()
     }
 
-    pub fn update(self: &mut Self, message: Message) -> iced::Task<Message> {
+    pub fn update(&mut self, message: Message) -> iced::Task<Message> {
         match message {
+            // A note waiting for its title is finished first, rather than leaving an empty
+            // "Untitled" note behind.
+            Message::Create if self.new_note.is_some() => {
+                iced::widget::text_input::focus(NEW_NOTE_TITLE)
+            }
             Message::Create => {
-                if let crate::util::Query::Loaded(directory) = &mut self.notes {
-                    let mut display_name = "Untitled".to_string();
-                    let mut path = std::path::PathBuf::new()
-                        .join(&directory.path)
-                        .join(&display_name);
-                    let mut i = 1;
-                    while path.exists() {
-                        display_name = format!("Untitled {}", i);
-                        path = std::path::PathBuf::new()
-                            .join(&directory.path)
-                            .join(&display_name);
-                        i = i + 1;
-                    }
-                    std::fs::File::create(&path).unwrap();
-                    directory.files.push(File {
-                        path: path.clone(),
-                        display_name: display_name.clone(),
-                    });
-                    iced::Task::done(Message::SetCurrent(path, display_name))
+                if let crate::util::Query::Loaded(directory) = &self.notes {
+                    iced::Task::perform(
+                        create_untitled(
+                            self.target_directory(directory),
+                            self.config.default_extension.clone(),
+                        ),
+                        Message::Created,
+                    )
                 } else {
                     iced::Task::none()
                 }
             }
+            Message::Created(Ok(file)) => {
+                if let crate::util::Query::Loaded(directory) = &mut self.notes {
+                    if let Some(parent) = file.path.parent() {
+                        expand(directory, parent.to_path_buf(), true);
+                    }
+                    insert_file(directory, file.clone());
+                }
+                self.selected = Some(file.path.clone());
+                self.new_note = Some(NewNote {
+                    file,
+                    title: String::new(),
+                    templates: vec![],
+                    template: None,
+                    error: None,
+                });
+                iced::Task::batch(vec![
+                    iced::Task::perform(
+                        crate::templates::list_templates(
+                            self.config.notes_path.join(&self.config.templates_path),
+                        ),
+                        Message::LoadedTemplates,
+                    ),
+                    iced::widget::text_input::focus(NEW_NOTE_TITLE),
+                ])
+            }
+            Message::Created(Err(e)) => {
+                eprintln!("Failed to create note: {:?}", e);
+                iced::Task::none()
+            }
+            Message::LoadedTemplates(templates) => {
+                if let Some(new_note) = &mut self.new_note {
+                    // Without templates the picker is simply empty, so a broken templates
+                    // folder shouldn't keep the note from being created.
+                    new_note.templates = templates.unwrap_or_default();
+                }
+                iced::Task::none()
+            }
+            Message::NewNoteTitle(title) => {
+                if let Some(new_note) = &mut self.new_note {
+                    new_note.title = title;
+                }
+                iced::Task::none()
+            }
+            Message::NewNoteTemplate(template) => {
+                if let Some(new_note) = &mut self.new_note {
+                    new_note.template = template;
+                }
+                iced::Task::none()
+            }
+            Message::ConfirmCreate => match &self.new_note {
+                Some(new_note) => iced::Task::perform(
+                    name_note(
+                        new_note.file.clone(),
+                        new_note.title.clone(),
+                        new_note.template.clone(),
+                        self.config.default_extension.clone(),
+                    ),
+                    Message::Named,
+                ),
+                None => iced::Task::none(),
+            },
+            Message::CancelCreate => match self.new_note.take() {
+                Some(new_note) => iced::Task::done(Message::SetCurrent(
+                    new_note.file.path,
+                    new_note.file.display_name,
+                )),
+                None => iced::Task::none(),
+            },
+            Message::Named(Ok(NamedNote {
+                old_path,
+                file,
+                cursor,
+            })) => {
+                self.new_note = None;
+                if let crate::util::Query::Loaded(directory) = &mut self.notes {
+                    remove_file(directory, &old_path);
+                    insert_file(directory, file.clone());
+                }
+                self.open_note(file.path, file.display_name, cursor)
+            }
+            Message::Named(Err(e)) => {
+                if let Some(new_note) = &mut self.new_note {
+                    new_note.error = Some(e);
+                }
+                iced::Task::none()
+            }
             Message::Delete(path) => {
                 if let crate::util::Query::Loaded(directory) = &mut self.notes {
-                    directory.files.retain(|f| f.path != path);
+                    remove_file(directory, &path);
                     std::fs::remove_file(&path).unwrap();
                 }
+                self.kept.remove(&path);
+                // Otherwise autosave would bring the note back.
+                if self
+                    .current
+                    .as_ref()
+                    .is_some_and(|current| current.path == path)
+                {
+                    self.current = None;
+                }
                 iced::Task::none()
             }
-            Message::SetCurrent(path, display_name) => {
-                let (state, next_task) =
-                    crate::note_editor::NoteEditor::from_path(path, display_name);
-                self.current = Some(state);
-                next_task.map(Message::NoteEditorMessage)
+            Message::Open(file) => match file.kind {
+                FileKind::Note => {
+                    iced::Task::done(Message::SetCurrent(file.path, file.display_name))
+                }
+                FileKind::Image => {
+                    self.selected = Some(file.path.clone());
+                    self.agenda = None;
+                    self.show_search = false;
+                    self.image = Some(file);
+                    iced::Task::none()
+                }
+                FileKind::Attachment => {
+                    self.selected = Some(file.path.clone());
+                    if let Err(e) = open::that_detached(&file.path) {
+                        eprintln!("Failed to open {:?}: {}", file.path, e);
+                    }
+                    iced::Task::none()
+                }
+            },
+            Message::SetCurrent(path, display_name) => self.open_note(path, display_name, None),
+            Message::Editor(crate::note_editor::Message::OpenNote(path)) => {
+                let display_name = path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().to_string());
+                self.open_note(path, display_name, None)
+            }
+            Message::Editor(crate::note_editor::Message::Export) => {
+                let (crate::util::Query::Loaded(directory), Some(current)) =
+                    (&self.notes, &self.current)
+                else {
+                    return iced::Task::none();
+                };
+                let mut notes = vec![];
+                note_files(directory, &mut notes);
+                let export = iced::Task::perform(
+                    crate::export::export_note(notes, current.path.clone(), self.config.clone()),
+                    Message::Exported,
+                );
+                self.save_current().chain(export)
             }
-            Message::NoteEditorMessage(message) => {
+            Message::Editor(message) => {
                 if let Some(current_note) = &mut self.current {
-                    crate::note_editor::NoteEditor::update(current_note, message)
-                        .map(Message::NoteEditorMessage)
+                    let path = current_note.path.clone();
+                    editor_task(
+                        path,
+                        crate::note_editor::NoteEditor::update(current_note, message),
+                    )
                 } else {
                     iced::Task::none()
                 }
             }
+            Message::NoteSaved(path, result) => match &mut self.current {
+                Some(current) if current.path == path => current
+                    .update(crate::note_editor::Message::Saved(result))
+                    .map(Message::Editor),
+                current => match (result, current) {
+                    (Err(e), _) => {
+                        eprintln!("Failed to save {:?}: {:?}", path, e);
+                        iced::Task::none()
+                    }
+                    // The open note may embed the one saved.
+                    (Ok(()), Some(current)) => current.forget_embeds(&[&path]).map(Message::Editor),
+                    (Ok(()), None) => iced::Task::none(),
+                },
+            },
+            Message::ToggleAgenda => {
+                if self.agenda.take().is_some() {
+                    iced::Task::none()
+                } else {
+                    self.show_agenda()
+                }
+            }
+            Message::Agenda(crate::agenda::Message::Refresh) => self.show_agenda(),
+            Message::Agenda(crate::agenda::Message::Open(path, display_name, line)) => {
+                self.open_note(path, display_name, Some((line, 0)))
+            }
+            // As for vault search, pending edits are saved before the note is written to.
+            Message::Agenda(message @ crate::agenda::Message::Tick(_)) => {
+                let tick = match &mut self.agenda {
+                    Some(agenda) => agenda.update(message).map(Message::Agenda),
+                    None => iced::Task::none(),
+                };
+                self.save_current().chain(tick)
+            }
+            Message::Agenda(message) => {
+                let reload = match &message {
+                    crate::agenda::Message::Ticked(Ok(item)) => self.reload(&[&item.path]),
+                    _ => iced::Task::none(),
+                };
+                let update = match &mut self.agenda {
+                    Some(agenda) => agenda.update(message).map(Message::Agenda),
+                    None => iced::Task::none(),
+                };
+                iced::Task::batch([reload, update])
+            }
+            Message::ToggleSearch => {
+                self.show_search = !self.show_search;
+                if self.show_search {
+                    self.agenda = None;
+                }
+                iced::Task::none()
+            }
+            Message::Search(crate::vault_search::Message::Search) => {
+                let crate::util::Query::Loaded(directory) = &self.notes else {
+                    return iced::Task::none();
+                };
+                let mut notes = vec![];
+                note_files(directory, &mut notes);
+                let search = self.vault_search.search(notes).map(Message::Search);
+                self.save_current().chain(search)
+            }
+            Message::Search(crate::vault_search::Message::Open(path, display_name, line)) => {
+                self.open_note(path, display_name, Some((line, 0)))
+            }
+            // Pending edits are saved first, so that a note changed since the search is left
+            // alone rather than losing them on reload.
+            Message::Search(
+                message
+                @ (crate::vault_search::Message::Apply | crate::vault_search::Message::Undo),
+            ) => {
+                let write = self.vault_search.update(message).map(Message::Search);
+                self.save_current().chain(write)
+            }
+            Message::Search(message) => {
+                let reload = match &message {
+                    crate::vault_search::Message::Applied(Ok(written))
+                    | crate::vault_search::Message::Undone(Ok(written)) => {
+                        let paths: Vec<_> =
+                            written.iter().map(|file| file.path.as_path()).collect();
+                        self.reload(&paths)
+                    }
+                    _ => iced::Task::none(),
+                };
+                let update = self.vault_search.update(message).map(Message::Search);
+                iced::Task::batch([reload, update])
+            }
+            Message::ExportFolder(folder) => {
+                let crate::util::Query::Loaded(directory) = &self.notes else {
+                    return iced::Task::none();
+                };
+                let mut notes = vec![];
+                note_files(directory, &mut notes);
+                let export = iced::Task::perform(
+                    crate::export::export_folder(notes, folder, self.config.clone()),
+                    Message::Exported,
+                );
+                self.save_current().chain(export)
+            }
+            Message::Exported(Ok(page)) => {
+                if let Err(e) = open::that_detached(&page) {
+                    eprintln!("Failed to open {:?}: {}", page, e);
+                }
+                iced::Task::none()
+            }
+            Message::Exported(Err(e)) => {
+                eprintln!("Failed to export: {}", e);
+                iced::Task::none()
+            }
             Message::LoadFiles(Ok(directory)) => {
                 self.notes = crate::util::Query::Loaded(directory);
                 iced::Task::none()
@@ -142,15 +598,70 @@ impl Notes {
             }
             Message::Expand(path, open) => {
                 if let crate::util::Query::Loaded(directory) = &mut self.notes {
-                    expand(directory, path, open);
+                    expand(directory, path.clone(), open);
                 }
+                self.selected = Some(path);
+                iced::Task::none()
+            }
+            Message::SetSort(path, sort) => {
+                if let Some(directory) = self.directory_mut(&path) {
+                    if sort == crate::sorting::SortMode::Manual && directory.order.manual.is_empty()
+                    {
+                        // Start from what is on screen so switching doesn't shuffle the folder.
+                        directory.order.manual = entry_names(directory);
+                    }
+                    directory.order.sort = sort;
+                    save_order(directory)
+                } else {
+                    iced::Task::none()
+                }
+            }
+            Message::SetFoldersFirst(path, folders_first) => {
+                if let Some(directory) = self.directory_mut(&path) {
+                    directory.order.folders_first = folders_first;
+                    save_order(directory)
+                } else {
+                    iced::Task::none()
+                }
+            }
+            Message::StartDrag(path) => {
+                self.dragging = Some(path);
+                self.drop_target = None;
+                iced::Task::none()
+            }
+            Message::DragOver(path) => {
+                if self.dragging.is_some() {
+                    self.drop_target = Some(path);
+                }
+                iced::Task::none()
+            }
+            Message::EndDrag => match (self.dragging.take(), self.drop_target.take()) {
+                (Some(source), Some(target))
+                    if source != target && source.parent() == target.parent() =>
+                {
+                    match source
+                        .parent()
+                        .and_then(|parent| self.directory_mut(parent))
+                    {
+                        Some(directory) => {
+                            move_entry(directory, &source, &target);
+                            save_order(directory)
+                        }
+                        None => iced::Task::none(),
+                    }
+                }
+                _ => iced::Task::none(),
+            },
+            Message::SavedOrder(Ok(())) => iced::Task::none(),
+            Message::SavedOrder(Err(e)) => {
+                eprintln!("Failed to save the folder order: {:?}", e);
                 iced::Task::none()
             }
         }
     }
-    pub fn subscription(self: &Self) -> iced::Subscription<Message> {
+    pub fn subscription(&self) -> iced::Subscription<Message> {
         let current_note_sub = if let Some(current) = &self.current {
-            crate::note_editor::NoteEditor::subscription(current).map(Message::NoteEditorMessage)
+            crate::note_editor::NoteEditor::subscription(current).map(Message::Editor)
         } else {
             iced::Subscription::none()
         };
@@ -158,79 +669,437 @@ impl Notes {
         let notes_panel_subscription = iced::keyboard::on_key_press(|key, modifiers| {
             if key == iced::keyboard::Key::Character("n".into()) && modifiers.control() {
                 Some(Message::Create)
+            } else if key == iced::keyboard::Key::Named(iced::keyboard::key::Named::Escape) {
+                Some(Message::CancelCreate)
             } else {
                 None
             }
         });
 
-        iced::Subscription::batch(vec![current_note_sub, notes_panel_subscription])
+        let drag_subscription = if self.dragging.is_some() {
+            iced::event::listen_with(|event, _status, _window| match event {
+                iced::Event::Mouse(iced::mouse::Event::ButtonReleased(
+                    iced::mouse::Button::Left,
+                )) => Some(Message::EndDrag),
+                _ => None,
+            })
+        } else {
+            iced::Subscription::none()
+        };
+
+        iced::Subscription::batch(vec![
+            current_note_sub,
+            notes_panel_subscription,
+            drag_subscription,
+        ])
     }
 
-    fn dir_tree(self: &Self, directory: &Directory) -> iced::Element<'_, Message> {
-        let mut note_list = iced::widget::Column::new();
-        for child in &directory.directories {
-            note_list = note_list.push(iced::widget::row![
-                iced::widget::button(iced::widget::row![
-                    crate::util::icon(if child.expanded {
-                        crate::util::ICON_DOWN_SMALL
-                    } else {
-                        crate::util::ICON_RIGHT_SMALL
-                    }),
-                    iced::widget::container(iced::widget::text(child.display_name.clone()).style(
-                        |theme: &iced::Theme| iced::widget::text::Style {
-                            color: Some(theme.extended_palette().background.strong.text.into()),
-                            ..Default::default()
-                        }
-                    ))
-                    .width(iced::Length::Fill)
-                    .padding(iced::padding::left(15))
-                ])
-                .style(crate::util::button_no_bg)
-                .on_press(Message::Expand(child.path.clone(), !child.expanded)),
-                iced::widget::button(crate::util::icon(crate::util::ICON_DELETE))
-                    .style(crate::util::button_no_bg)
-                    .on_press(Message::Delete(child.path.clone()))
-            ]);
-            if child.expanded {
-                note_list = note_list.push(iced::widget::stack![
-                    iced::widget::container(self.dir_tree(&child)).padding(iced::padding::left(20)),
-                    iced::widget::container(iced::widget::vertical_rule(2))
-                        .padding(iced::padding::left(10))
-                ]);
+    /// Opens the note at `path` in the editor, saving the note that was open.
+    fn open_note(
+        &mut self,
+        path: std::path::PathBuf,
+        display_name: String,
+        cursor: Option<(usize, usize)>,
+    ) -> iced::Task<Message> {
+        self.selected = Some(path.clone());
+        self.image = None;
+        self.agenda = None;
+        self.show_search = false;
+        let save = self.save_current();
+        let mut outline = false;
+        if let Some(current) = &mut self.current {
+            if let Some(kept) = current.leave() {
+                self.kept.insert(current.path.clone(), kept);
             }
+            outline = current.outline_shown();
         }
-        for file in &directory.files {
-            note_list = note_list.push(
-                iced::widget::row![
-                    iced::widget::button(
-                        iced::widget::text(file.display_name.clone()).width(iced::Length::Fill)
-                    )
-                    .style(
-                        if self
-                            .current
-                            .as_ref()
-                            .map_or(false, |v| v.path == file.path.clone())
-                        {
-                            crate::util::button_no_bg_active
-                        } else {
-                            crate::util::button_no_bg
-                        }
-                    )
-                    .on_press(Message::SetCurrent(
-                        file.path.clone(),
-                        file.display_name.clone()
-                    )),
-                    iced::widget::button(crate::util::icon(crate::util::ICON_DELETE))
-                        .style(crate::util::button_no_bg)
-                        .on_press(Message::Delete(file.path.clone())),
-                ]
-                .padding(iced::padding::left(15)),
-            );
+        let kept = self.kept.remove(&path);
+        let (mut state, next_task) =
+            crate::note_editor::NoteEditor::from_path(path, display_name, &self.config, kept);
+        if let Some((line, column)) = cursor {
+            state.place_cursor(line, column);
+        }
+        state.show_outline(outline);
+        self.current = Some(state);
+        save.chain(next_task.map(Message::Editor))
+    }
+
+    fn save_current(&mut self) -> iced::Task<Message> {
+        match &mut self.current {
+            Some(current) => editor_task(current.path.clone(), current.save()),
+            None => iced::Task::none(),
+        }
+    }
+
+    /// Reloads the open note if it's one of `paths`, which were changed on disk. Otherwise
+    /// its autosave could undo the change. The notes it embeds are read again.
+    fn reload(&mut self, paths: &[&std::path::Path]) -> iced::Task<Message> {
+        match &mut self.current {
+            Some(current) if paths.contains(&current.path.as_path()) => {
+                current.reload().map(Message::Editor)
+            }
+            Some(current) => current.forget_embeds(paths).map(Message::Editor),
+            None => iced::Task::none(),
+        }
+    }
+
+    /// Shows the agenda, collecting tasks once the open note is saved.
+    fn show_agenda(&mut self) -> iced::Task<Message> {
+        let crate::util::Query::Loaded(directory) = &self.notes else {
+            return iced::Task::none();
+        };
+        let mut notes = vec![];
+        note_files(directory, &mut notes);
+
+        let (agenda, load) = crate::agenda::Agenda::new(notes);
+        self.agenda = Some(agenda);
+        self.show_search = false;
+        self.save_current().chain(load.map(Message::Agenda))
+    }
+
+    fn directory_mut(&mut self, path: &std::path::Path) -> Option<&mut Directory> {
+        match &mut self.notes {
+            crate::util::Query::Loaded(directory) => find_directory(directory, path),
+            _ => None,
+        }
+    }
+
+    /// Sort settings of the folder new notes would go into.
+    fn sort_controls(&self) -> Option<iced::Element<'_, Message>> {
+        let crate::util::Query::Loaded(root) = &self.notes else {
+            return None;
+        };
+        let path = self.target_directory(root);
+        let directory = find_directory_ref(root, &path)?;
+        let order = &directory.order;
+
+        Some(
+            iced::widget::row![
+                iced::widget::pick_list(crate::sorting::SortMode::ALL, Some(order.sort), {
+                    let path = path.clone();
+                    move |sort| Message::SetSort(path.clone(), sort)
+                })
+                .width(iced::Length::Fill),
+                iced::widget::checkbox("Folders first", order.folders_first).on_toggle(
+                    move |folders_first| Message::SetFoldersFirst(path.clone(), folders_first)
+                ),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into(),
+        )
+    }
+
+    /// Wraps a row of the tree so it can be dragged around when its folder is sorted
+    /// manually.
+    fn draggable<'a>(
+        &self,
+        directory: &Directory,
+        path: &std::path::Path,
+        row: iced::widget::Row<'a, Message>,
+    ) -> iced::Element<'a, Message> {
+        let row = if directory.order.sort == crate::sorting::SortMode::Manual {
+            let handle = iced::widget::mouse_area(
+                iced::widget::container(iced::widget::text("≡")).padding([4, 2]),
+            )
+            .on_press(Message::StartDrag(path.to_path_buf()))
+            .interaction(iced::mouse::Interaction::Grab);
+            iced::widget::Row::new().push(handle).push(row)
+        } else {
+            row
+        };
+        let is_target = self.dragging.is_some() && self.drop_target.as_deref() == Some(path);
+
+        iced::widget::mouse_area(
+            iced::widget::column![]
+                .push_maybe(is_target.then(|| iced::widget::horizontal_rule(2)))
+                .push(row),
+        )
+        .on_enter(Message::DragOver(path.to_path_buf()))
+        .into()
+    }
+
+    fn is_open(&self, path: &std::path::Path) -> bool {
+        self.current.as_ref().is_some_and(|v| v.path == path)
+            || self.image.as_ref().is_some_and(|v| v.path == path)
+    }
+
+    /// The directory new notes go into: the selected folder, or the folder of the selected
+    /// note, falling back to the root of the notes.
+    fn target_directory(&self, root: &Directory) -> std::path::PathBuf {
+        match &self.selected {
+            Some(path) if path.is_dir() => path.clone(),
+            Some(path) => path
+                .parent()
+                .map_or(root.path.clone(), std::path::Path::to_path_buf),
+            None => root.path.clone(),
+        }
+    }
+
+    fn dir_tree(&self, directory: &Directory) -> iced::Element<'_, Message> {
+        let mut note_list = iced::widget::Column::new();
+        for entry in directory.entries() {
+            match entry {
+                Entry::Directory(child) => {
+                    note_list = note_list.push(self.draggable(
+                        directory,
+                        &child.path,
+                        iced::widget::row![
+                            iced::widget::button(iced::widget::row![
+                                crate::util::icon(if child.expanded {
+                                    crate::util::ICON_DOWN_SMALL
+                                } else {
+                                    crate::util::ICON_RIGHT_SMALL
+                                }),
+                                iced::widget::container(
+                                    iced::widget::text(child.display_name.clone()).style(
+                                        |theme: &iced::Theme| iced::widget::text::Style {
+                                            color: Some(
+                                                theme.extended_palette().background.strong.text
+                                            ),
+                                        }
+                                    )
+                                )
+                                .width(iced::Length::Fill)
+                                .padding(iced::padding::left(15))
+                            ])
+                            .style(if self.selected.as_ref() == Some(&child.path) {
+                                crate::util::button_no_bg_active
+                            } else {
+                                crate::util::button_no_bg
+                            })
+                            .on_press(Message::Expand(child.path.clone(), !child.expanded)),
+                            iced::widget::button(iced::widget::text("Export").size(12))
+                                .style(crate::util::button_no_bg)
+                                .on_press(Message::ExportFolder(child.path.clone())),
+                            iced::widget::button(crate::util::icon(crate::util::ICON_DELETE))
+                                .style(crate::util::button_no_bg)
+                                .on_press(Message::Delete(child.path.clone()))
+                        ],
+                    ));
+                    if child.expanded {
+                        note_list = note_list.push(iced::widget::stack![
+                            iced::widget::container(self.dir_tree(child))
+                                .padding(iced::padding::left(20)),
+                            iced::widget::container(iced::widget::vertical_rule(2))
+                                .padding(iced::padding::left(10))
+                        ]);
+                    }
+                }
+                Entry::File(file) => {
+                    if let Some(new_note) = self
+                        .new_note
+                        .as_ref()
+                        .filter(|new_note| new_note.file.path == file.path)
+                    {
+                        note_list = note_list.push(
+                            iced::widget::container(new_note_prompt(new_note))
+                                .padding(iced::padding::left(15)),
+                        );
+                        continue;
+                    }
+                    note_list = note_list.push(
+                        self.draggable(
+                            directory,
+                            &file.path,
+                            iced::widget::row![
+                                iced::widget::button(
+                                    iced::widget::text(file.display_name.clone())
+                                        .width(iced::Length::Fill)
+                                        .style(match file.kind {
+                                            FileKind::Note => iced::widget::text::default,
+                                            FileKind::Image | FileKind::Attachment => {
+                                                attachment_text
+                                            }
+                                        })
+                                )
+                                .style(if self.is_open(&file.path) {
+                                    crate::util::button_no_bg_active
+                                } else {
+                                    crate::util::button_no_bg
+                                })
+                                .on_press(Message::Open(file.clone())),
+                                iced::widget::button(crate::util::icon(crate::util::ICON_DELETE))
+                                    .style(crate::util::button_no_bg)
+                                    .on_press(Message::Delete(file.path.clone())),
+                            ]
+                            .padding(iced::padding::left(15)),
+                        ),
+                    );
+                }
+            }
         }
         note_list.into()
     }
 }
 
+/// Maps a task of the editor of the note at `path`, reporting its saves with the note they
+/// were for, since another note may be open by the time they finish.
+fn editor_task(
+    path: std::path::PathBuf,
+    task: iced::Task<crate::note_editor::Message>,
+) -> iced::Task<Message> {
+    task.map(move |message| match message {
+        crate::note_editor::Message::Saved(result) => Message::NoteSaved(path.clone(), result),
+        message => Message::Editor(message),
+    })
+}
+
+/// Collects the paths and names of all the notes under `directory`.
+fn note_files(directory: &Directory, notes: &mut Vec<(std::path::PathBuf, String)>) {
+    for file in &directory.files {
+        if file.kind == FileKind::Note {
+            notes.push((file.path.clone(), file.display_name.clone()));
+        }
+    }
+    for child in &directory.directories {
+        note_files(child, notes);
+    }
+}
+
+fn entry_names(directory: &Directory) -> Vec<String> {
+    directory
+        .entries()
+        .iter()
+        .map(|entry| entry.sort_key().name.to_string())
+        .collect()
+}
+
+/// Moves `source` to where `target` is in the manual order of `directory`.
+fn move_entry(directory: &mut Directory, source: &std::path::Path, target: &std::path::Path) {
+    let mut names = entry_names(directory);
+    let name = |path: &std::path::Path| path.file_name().unwrap().to_string_lossy().to_string();
+    if let (Some(from), Some(to)) = (
+        names.iter().position(|n| *n == name(source)),
+        names.iter().position(|n| *n == name(target)),
+    ) {
+        let moved = names.remove(from);
+        names.insert(to, moved);
+        directory.order.sort = crate::sorting::SortMode::Manual;
+        directory.order.manual = names;
+    }
+}
+
+fn save_order(directory: &Directory) -> iced::Task<Message> {
+    iced::Task::perform(
+        crate::sorting::save_order(directory.path.clone(), directory.order.clone()),
+        Message::SavedOrder,
+    )
+}
+
+fn attachment_text(theme: &iced::Theme) -> iced::widget::text::Style {
+    iced::widget::text::Style {
+        color: Some(
+            theme
+                .extended_palette()
+                .background
+                .base
+                .text
+                .scale_alpha(0.6),
+        ),
+    }
+}
+
+fn image_viewer(file: &File) -> iced::Element<'_, Message> {
+    iced::widget::column![
+        iced::widget::text(file.display_name.clone()).size(24),
+        iced::widget::container(
+            iced::widget::image(iced::widget::image::Handle::from_path(&file.path))
+                .content_fit(iced::ContentFit::Contain)
+        )
+        .center(iced::Length::Fill),
+        iced::widget::text(file.path.to_string_lossy())
+    ]
+    .spacing(4)
+    .padding(8)
+    .height(iced::Length::Fill)
+    .into()
+}
+
+const NEW_NOTE_TITLE: &str = "new-note-title";
+
+fn new_note_prompt(new_note: &NewNote) -> iced::Element<'_, Message> {
+    let mut template_picker = iced::widget::row![iced::widget::pick_list(
+        new_note.templates.as_slice(),
+        new_note.template.as_ref(),
+        |template| Message::NewNoteTemplate(Some(template))
+    )
+    .placeholder("Empty note")
+    .width(iced::Length::Fill)]
+    .spacing(4);
+    if new_note.template.is_some() {
+        template_picker = template_picker.push(
+            iced::widget::button(crate::util::icon(crate::util::ICON_DELETE))
+                .style(crate::util::button_no_bg)
+                .on_press(Message::NewNoteTemplate(None)),
+        );
+    }
+
+    iced::widget::column![
+        iced::widget::text_input("Title", &new_note.title)
+            .id(NEW_NOTE_TITLE)
+            .on_input(Message::NewNoteTitle)
+            .on_submit(Message::ConfirmCreate),
+        template_picker,
+    ]
+    .push_maybe(
+        new_note
+            .error
+            .map(|e| iced::widget::text(format!("Error: {:?}", e))),
+    )
+    .spacing(4)
+    .into()
+}
+
+/// Finds the directory at `path` in the tree rooted at `directory`.
+fn find_directory<'a>(
+    directory: &'a mut Directory,
+    path: &std::path::Path,
+) -> Option<&'a mut Directory> {
+    if directory.path == path {
+        return Some(directory);
+    }
+    directory
+        .directories
+        .iter_mut()
+        .find(|dir| path.starts_with(&dir.path))
+        .and_then(|dir| find_directory(dir, path))
+}
+
+fn find_directory_ref<'a>(
+    directory: &'a Directory,
+    path: &std::path::Path,
+) -> Option<&'a Directory> {
+    if directory.path == path {
+        return Some(directory);
+    }
+    directory
+        .directories
+        .iter()
+        .find(|dir| path.starts_with(&dir.path))
+        .and_then(|dir| find_directory_ref(dir, path))
+}
+
+/// Adds `file` to the directory it lives in.
+fn insert_file(directory: &mut Directory, file: File) {
+    if let Some(parent) = file
+        .path
+        .parent()
+        .and_then(|parent| find_directory(directory, parent))
+    {
+        parent.files.push(file);
+    }
+}
+
+fn remove_file(directory: &mut Directory, path: &std::path::Path) {
+    if let Some(parent) = path
+        .parent()
+        .and_then(|parent| find_directory(directory, parent))
+    {
+        parent.files.retain(|f| f.path != path);
+    }
+}
+
 fn expand(directory: &mut Directory, path: std::path::PathBuf, open: bool) {
     for dir in &mut directory.directories {
         if dir.path == path {
@@ -240,8 +1109,116 @@ fn expand(directory: &mut Directory, path: std::path::PathBuf, open: bool) {
     }
 }
 
-async fn load_files(path: std::path::PathBuf) -> Result<Directory, std::io::ErrorKind> {
-    println!("Loading files from {:?}", path);
+/// Picks a file name in `directory` for `stem` that isn't taken yet, appending a number if
+/// needed. `own_path` is considered free, so a note can keep its current name.
+fn unique_path(
+    directory: &std::path::Path,
+    stem: &str,
+    extension: &str,
+    own_path: Option<&std::path::Path>,
+) -> (String, std::path::PathBuf) {
+    let file_name = |stem: &str| {
+        if extension.is_empty() {
+            stem.to_string()
+        } else {
+            format!("{}.{}", stem, extension)
+        }
+    };
+    let mut display_name = file_name(stem);
+    let mut path = directory.join(&display_name);
+    let mut i = 1;
+    while path.exists() && Some(path.as_path()) != own_path {
+        display_name = file_name(&format!("{} {}", stem, i));
+        path = directory.join(&display_name);
+        i += 1;
+    }
+    (display_name, path)
+}
+
+/// Creates an empty "Untitled" note in `directory`.
+async fn create_untitled(
+    directory: std::path::PathBuf,
+    extension: String,
+) -> Result<File, std::io::ErrorKind> {
+    let (display_name, path) = unique_path(&directory, "Untitled", &extension, None);
+    tokio::fs::write(&path, "").await.map_err(|e| e.kind())?;
+    Ok(File {
+        display_name,
+        path,
+        kind: FileKind::Note,
+        modified: Some(std::time::SystemTime::now()),
+        created: Some(std::time::SystemTime::now()),
+    })
+}
+
+/// Renames a freshly created note after `title` and fills it in from `template`.
+async fn name_note(
+    file: File,
+    title: String,
+    template: Option<crate::templates::Template>,
+    extension: String,
+) -> Result<NamedNote, std::io::ErrorKind> {
+    let directory = file.path.parent().unwrap().to_path_buf();
+
+    let title = title.trim().replace(['/', '\\'], "-");
+    let title = title
+        .strip_suffix(&format!(".{}", extension))
+        .unwrap_or(&title)
+        .to_string();
+    let title = if title.is_empty() {
+        file.path.file_stem().unwrap().to_string_lossy().to_string()
+    } else {
+        title
+    };
+
+    // The note is filled in before it's renamed, so that on failure it's still where the
+    // prompt thinks it is.
+    let mut cursor = None;
+    if let Some(template) = template {
+        let template = crate::util::read_file(template.path).await?;
+        let expanded = crate::templates::expand(&template, &title, chrono::Local::now());
+        tokio::fs::write(&file.path, expanded.text)
+            .await
+            .map_err(|e| e.kind())?;
+        cursor = expanded.cursor;
+    }
+
+    let (display_name, path) = unique_path(&directory, &title, &extension, Some(&file.path));
+    if path != file.path {
+        tokio::fs::rename(&file.path, &path)
+            .await
+            .map_err(|e| e.kind())?;
+    }
+
+    Ok(NamedNote {
+        old_path: file.path,
+        file: File {
+            display_name,
+            path,
+            kind: FileKind::Note,
+            modified: file.modified,
+            created: file.created,
+        },
+        cursor,
+    })
+}
+
+/// Loads `folder` as it shows in the tree, for a site.
+pub async fn site_tree(
+    folder: std::path::PathBuf,
+    config: crate::config::Configuration,
+) -> Result<crate::site::Folder, std::io::ErrorKind> {
+    let rules = FileRules::new(&config);
+    load_files(folder, rules)
+        .await
+        .map(|directory| directory.site())
+}
+
+/// Reads the files under `path` as the tree shows them.
+async fn load_files(
+    path: std::path::PathBuf,
+    rules: FileRules,
+) -> Result<Directory, std::io::ErrorKind> {
     let mut files = vec![];
     let mut directories = vec![];
 
@@ -257,8 +1234,16 @@ async fn load_files(path: std::path::PathBuf) -> Result<Directory, std::io::Erro
             .to_string_lossy()
             .to_string();
 
-        if child_path.is_dir() {
-            let contents = Box::pin(load_files(child_path.clone())).await?;
+        let is_dir = child_path.is_dir();
+        if display_name == crate::sorting::ORDER_FILE || !rules.is_visible(&child_path, is_dir) {
+            continue;
+        }
+        let metadata = entry.metadata().await.ok();
+        let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+        let created = metadata.as_ref().and_then(|m| m.created().ok());
+
+        if is_dir {
+            let contents = Box::pin(load_files(child_path.clone(), rules.clone())).await?;
 
             directories.push(Directory {
                 display_name,
@@ -266,20 +1251,29 @@ async fn load_files(path: std::path::PathBuf) -> Result<Directory, std::io::Erro
                 path: child_path,
                 files: contents.files,
                 directories: contents.directories,
+                order: contents.order,
+                modified,
+                created,
             });
         } else {
             files.push(File {
                 display_name,
+                kind: rules.kind(&child_path),
                 path: child_path,
+                modified,
+                created,
             });
         }
     }
 
     Ok(Directory {
         display_name: path.file_name().unwrap().to_string_lossy().to_string(),
+        order: crate::sorting::read_order(path.clone()).await,
         path,
         expanded: true,
         files,
         directories,
+        modified: None,
+        created: None,
     })
 }
@@ -0,0 +1,1133 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// An image, either a Markdown link with an optional `{width=300}` after it, or an HTML
+/// `<img>` tag.
+static IMAGE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r#"!\[([^\]]*)\]\(\s*(?:<([^>]*)>|([^\s)]+))(?:\s+"[^"]*")?\s*\)(?:\{([^}]*)\})?|(?i)<img\s([^>]*?)/?>"#,
+    )
+    .unwrap()
+});
+/// An attribute of an `<img>` tag or of the braces after an image.
+static ATTRIBUTE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r#"([\w-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>}]+))"#).unwrap()
+});
+
+/// The rendered side of a note, kept as a list of blocks that are parsed independently so
+/// that an edit only re-reads the blocks around it. Blocks are rendered once the preview is
+/// shown, with [`Preview::refresh`].
+pub struct Preview {
+    blocks: Vec<Block>,
+    /// The ids of the footnotes referenced in the note, in the order they're first referenced.
+    references: Vec<String>,
+    /// The link reference definitions of the note, added to every block so that links find
+    /// them wherever they're written.
+    definitions: String,
+    /// How many tasks of the note are done, and how many there are.
+    tasks: (usize, usize),
+    /// The note, against which the images are found.
+    note: std::path::PathBuf,
+    /// Counts the changes to the blocks, to tell when what they show needs reading again.
+    revision: usize,
+}
+
+/// The lines of a note, as the preview reads them.
+pub trait Lines {
+    fn line(&self, index: usize) -> Option<String>;
+}
+
+impl Lines for iced::widget::text_editor::Content {
+    fn line(&self, index: usize) -> Option<String> {
+        self.line(index).map(|line| line.to_string())
+    }
+}
+
+/// A run of lines separated from its neighbours by blank lines. Blocks tile the whole note:
+/// trailing blank lines belong to the block before them.
+struct Block {
+    start: usize,
+    lines: usize,
+    source: String,
+    /// `None` until the block is rendered.
+    rendered: Option<Rendered>,
+    /// The link reference definitions in the block, one per line.
+    definitions: String,
+    /// The ids of the footnotes referenced in the block, in order.
+    references: Vec<String>,
+    /// How many of its tasks are done, and how many there are.
+    tasks: (usize, usize),
+}
+
+impl Block {
+    fn new(start: usize, lines: usize, source: String) -> Self {
+        let mut definitions = String::new();
+        let mut fences = crate::folding::Fences::default();
+        for line in source.lines() {
+            if !fences.read(line) && crate::extended::is_link_definition(line) {
+                definitions.push_str(line);
+                definitions.push('\n');
+            }
+        }
+        let references = if source.contains("[^") {
+            crate::extended::references(&source)
+                .map(str::to_string)
+                .collect()
+        } else {
+            vec![]
+        };
+        Self {
+            start,
+            lines,
+            tasks: crate::tasks::count(source.lines()),
+            source,
+            rendered: None,
+            definitions,
+            references,
+        }
+    }
+}
+
+/// How a block is shown.
+pub enum Rendered {
+    Markdown(Vec<iced::widget::markdown::Item>),
+    Code(CodeBlock),
+    Tasks(crate::tasks::TaskList),
+    /// A block with images or embedded notes, which the Markdown view doesn't show, cut into
+    /// runs of text, rows of images and embeds.
+    Segments(Vec<Segment>),
+    /// A block with `$` math or extended syntax, which the Markdown view shows as written.
+    Rich(Rich),
+    /// A quote starting with `[!NOTE]` or another kind of callout.
+    Callout(Callout),
+    Definitions(Vec<Definition>),
+    /// Footnotes, which are shown at the end of the preview rather than where they are.
+    Footnotes(Vec<Footnote>),
+    /// A `mermaid` or `dot` code block, drawn, or the reason it can't be.
+    Diagram(CodeBlock, Result<iced::widget::svg::Handle, String>),
+}
+
+pub enum Segment {
+    Markdown(Vec<iced::widget::markdown::Item>),
+    Rich(Rich),
+    Images(Vec<Image>),
+    /// Another note, or a section of it, shown in this one.
+    Embed(crate::embeds::Embed),
+}
+
+/// The first of the characters standing for formulas in the text of a [`Rich`], in a private
+/// use plane. Each formula has its own, so that it's found wherever Markdown puts its text.
+const FORMULA: u32 = 0x10_0000;
+
+/// Markdown with formulas in it, each left out of its text for its [`placeholder`], and the
+/// extended syntax rewritten for the markers of [`crate::extended`].
+pub struct Rich {
+    pub items: Vec<iced::widget::markdown::Item>,
+    /// The formulas, by the index of their placeholders.
+    pub formulas: Vec<Math>,
+}
+
+pub struct Math {
+    pub source: String,
+    /// Whether it's display math, on its own line, rather than inline.
+    pub display: bool,
+    pub node: Result<crate::math::Node, String>,
+}
+
+pub struct Callout {
+    pub kind: crate::extended::Kind,
+    pub title: String,
+    pub body: Vec<Rendered>,
+}
+
+/// A term of a definition list, with its definitions.
+pub struct Definition {
+    pub term: Rich,
+    pub details: Vec<Rich>,
+}
+
+pub struct Footnote {
+    pub id: String,
+    pub text: Rich,
+}
+
+pub struct Image {
+    pub alt: String,
+    /// The URL of the image, or its path relative to the note.
+    pub source: String,
+    /// The size asked for, in pixels.
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    /// The file the image is read from, if it's found next to the note. It's looked for once,
+    /// when its block is read.
+    pub file: Option<std::path::PathBuf>,
+}
+
+/// A fenced code block, highlighted for the language given after the opening fence.
+pub struct CodeBlock {
+    pub language: String,
+    pub code: String,
+    pub lines: Vec<Vec<(Range<usize>, crate::highlighter::Token)>>,
+}
+
+/// A heading of the note, for its outline.
+pub struct Heading {
+    pub level: usize,
+    pub text: String,
+    pub line: usize,
+    /// The id given after it, `{#id}`, or the one made of its text, for links to it.
+    pub anchor: String,
+}
+
+/// The lines touched by an edit, in the coordinates of the edited note, and how many lines
+/// were added (or removed, when negative).
+pub struct Edit {
+    pub first_line: usize,
+    pub last_line: usize,
+    pub delta: isize,
+}
+
+impl Preview {
+    /// The preview of the note at `note`.
+    pub fn new(note: &std::path::Path) -> Self {
+        Self {
+            blocks: vec![],
+            references: vec![],
+            definitions: String::new(),
+            tasks: (0, 0),
+            note: note.to_path_buf(),
+            revision: 0,
+        }
+    }
+
+    /// The rendered blocks of the note, in order, with the line each starts at.
+    pub fn blocks(&self) -> impl Iterator<Item = (usize, &Rendered)> {
+        self.blocks
+            .iter()
+            .filter_map(|block| Some((block.start, block.rendered.as_ref()?)))
+    }
+
+    /// The blocks of the note with their source, in order, with the lines each spans.
+    pub fn sources(&self) -> impl Iterator<Item = (Range<usize>, &str, &Rendered)> {
+        self.blocks.iter().filter_map(|block| {
+            Some((
+                block.start..block.start + block.lines,
+                block.source.as_str(),
+                block.rendered.as_ref()?,
+            ))
+        })
+    }
+
+    /// The task list holding `line`, with the line it starts at.
+    pub fn task_list_at(&self, line: usize) -> Option<(usize, &crate::tasks::TaskList)> {
+        self.blocks
+            .iter()
+            .find(|block| block.start <= line && line < block.start + block.lines)
+            .and_then(|block| match &block.rendered {
+                Some(Rendered::Tasks(list)) => Some((block.start, list)),
+                _ => None,
+            })
+    }
+
+    /// The headings of the note, in order.
+    pub fn outline(&self) -> Vec<Heading> {
+        let mut outline = vec![];
+        let mut anchors: HashMap<String, usize> = HashMap::new();
+        for block in &self.blocks {
+            if !matches!(
+                block.rendered,
+                Some(Rendered::Markdown(_) | Rendered::Rich(_) | Rendered::Segments(_))
+            ) {
+                continue;
+            }
+            for (line, level, text) in headings(&block.source) {
+                let anchor = match block
+                    .source
+                    .lines()
+                    .nth(line)
+                    .and_then(crate::extended::heading_id)
+                {
+                    Some((_, id)) => id.to_string(),
+                    None => crate::extended::slug(&text),
+                };
+                // Anchors made twice are numbered, like GitHub does.
+                let count = anchors.entry(anchor.clone()).or_insert(0);
+                let anchor = match *count {
+                    0 => anchor,
+                    count => format!("{}-{}", anchor, count),
+                };
+                *count += 1;
+                outline.push(Heading {
+                    level,
+                    text,
+                    line: block.start + line,
+                    anchor,
+                });
+            }
+        }
+        outline
+    }
+
+    /// The footnotes of the note in the order they're numbered: the referenced ones as
+    /// they're first referenced, then the others.
+    pub fn footnotes(&self) -> Vec<&Footnote> {
+        let mut footnotes: Vec<&Footnote> = vec![];
+        for block in &self.blocks {
+            if let Some(Rendered::Footnotes(list)) = &block.rendered {
+                for footnote in list {
+                    if footnotes.iter().all(|known| known.id != footnote.id) {
+                        footnotes.push(footnote);
+                    }
+                }
+            }
+        }
+        let rank = |id: &str| {
+            self.references
+                .iter()
+                .position(|reference| reference == id)
+                .unwrap_or(self.references.len())
+        };
+        footnotes.sort_by_key(|footnote| rank(&footnote.id));
+        footnotes
+    }
+
+    /// The notes embedded in the note, callouts included.
+    pub fn embeds(&self) -> Vec<&crate::embeds::Embed> {
+        let mut embeds = vec![];
+        for rendered in self
+            .blocks
+            .iter()
+            .filter_map(|block| block.rendered.as_ref())
+        {
+            rendered_embeds(rendered, &mut embeds);
+        }
+        embeds
+    }
+
+    /// Changes whenever the blocks do.
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// How many tasks of the note are done, and how many there are, outside of code.
+    pub fn tasks(&self) -> (usize, usize) {
+        self.tasks
+    }
+
+    /// Whether the footnote with `id` is referenced.
+    pub fn is_referenced(&self, id: &str) -> bool {
+        self.references.iter().any(|reference| reference == id)
+    }
+
+    /// The line of the first reference to the footnote with `id`.
+    pub fn reference_line(&self, id: &str) -> Option<usize> {
+        self.blocks.iter().find_map(|block| {
+            block
+                .source
+                .lines()
+                .position(|line| crate::extended::references(line).any(|reference| reference == id))
+                .map(|line| block.start + line)
+        })
+    }
+
+    fn number_footnotes(&mut self) {
+        self.references.clear();
+        for block in &self.blocks {
+            for id in &block.references {
+                if !self.references.contains(id) {
+                    self.references.push(id.clone());
+                }
+            }
+        }
+    }
+
+    /// The link reference definitions of the note, one per line.
+    pub fn definitions(&self) -> &str {
+        &self.definitions
+    }
+
+    /// Reads the whole note and renders it.
+    pub fn rebuild(&mut self, lines: &impl Lines) {
+        self.read(lines);
+        self.refresh();
+    }
+
+    /// Reads the whole note, keeping the rendering of the blocks that didn't change for
+    /// [`Preview::refresh`].
+    pub fn read(&mut self, lines: &impl Lines) {
+        let old = std::mem::take(&mut self.blocks);
+        self.blocks = split(lines, 0, |_| false);
+        self.tasks = total(&self.blocks);
+        let definitions: String = self.blocks.iter().map(|b| b.definitions.as_str()).collect();
+        // Blocks are rendered with the definitions of the whole note, so all of them change
+        // with the definitions.
+        if definitions != self.definitions {
+            self.definitions = definitions;
+        } else {
+            reuse(&mut self.blocks, old);
+        }
+        self.number_footnotes();
+        self.revision += 1;
+    }
+
+    /// Renders the blocks read since the preview was last shown.
+    pub fn refresh(&mut self) {
+        let mut rendered = false;
+        for block in &mut self.blocks {
+            if block.rendered.is_none() {
+                block.rendered = Some(render(&block.source, &self.definitions, &self.note));
+                rendered = true;
+            }
+        }
+        if rendered {
+            self.revision += 1;
+        }
+    }
+
+    /// Updates the preview after `edit`, re-splitting from the block before the edit until
+    /// the blocks line up with the old ones again. What the blocks hold is read from the new
+    /// blocks only; they're rendered by [`Preview::refresh`].
+    pub fn edit(&mut self, lines: &impl Lines, edit: Edit) {
+        self.revision += 1;
+
+        // The block before the edit is included since the edit may merge into it.
+        let first = self
+            .blocks
+            .iter()
+            .position(|block| block.start + block.lines > edit.first_line)
+            .unwrap_or(self.blocks.len())
+            .saturating_sub(1);
+        let start = self.blocks.get(first).map_or(0, |block| block.start);
+
+        // Old block starts after the edit, keyed by where they start in the edited note.
+        let resume: HashMap<usize, usize> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .skip(first + 1)
+            .filter_map(|(i, block)| {
+                let start = block.start.checked_add_signed(edit.delta)?;
+                (start > edit.last_line).then_some((start, i))
+            })
+            .collect();
+
+        let mut resumed_at = None;
+        let mut blocks = split(lines, start, |line| match resume.get(&line) {
+            Some(i) => {
+                resumed_at = Some(*i);
+                true
+            }
+            None => false,
+        });
+
+        let mut old = self.blocks.split_off(first);
+        let kept = resumed_at.map(|i| old.split_off(i - first));
+        let (removed, added) = (total(&old), total(&blocks));
+        self.tasks = (
+            self.tasks.0 + added.0 - removed.0,
+            self.tasks.1 + added.1 - removed.1,
+        );
+        let definitions = |blocks: &[Block]| -> String {
+            blocks.iter().map(|b| b.definitions.as_str()).collect()
+        };
+        let references = |blocks: &[Block]| -> Vec<String> {
+            blocks.iter().flat_map(|b| b.references.clone()).collect()
+        };
+        let definitions_changed = definitions(&old) != definitions(&blocks);
+        let references_changed = references(&old) != references(&blocks);
+        reuse(&mut blocks, old);
+
+        self.blocks.append(&mut blocks);
+        if let Some(kept) = kept {
+            self.blocks.extend(kept.into_iter().map(|block| Block {
+                start: block.start.saturating_add_signed(edit.delta),
+                ..block
+            }));
+        }
+        if definitions_changed {
+            self.definitions = definitions(&self.blocks);
+            for block in &mut self.blocks {
+                block.rendered = None;
+            }
+        }
+        if references_changed {
+            self.number_footnotes();
+        }
+    }
+}
+
+/// Splits `lines` from `start` into blocks, stopping early when a block would start at a
+/// line for which `stop` returns `true`.
+fn split(lines: &impl Lines, start: usize, mut stop: impl FnMut(usize) -> bool) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut block_start = start;
+    let mut source = String::new();
+    let mut fences = crate::folding::Fences::default();
+    let mut front_matter = false;
+    // The marker of the list the block is in, whose items stay together when separated by
+    // blank lines, so that a loose list stays one list.
+    let mut list: Option<u8> = None;
+    let mut after_blank = false;
+
+    let mut index = start;
+    while let Some(line) = lines.line(index) {
+        let blank = line.trim().is_empty();
+        let indented = line.starts_with(' ') || line.starts_with('\t');
+        let marker = crate::tasks::list_item(&line)
+            .and_then(|_| line.bytes().find(|byte| !byte.is_ascii_digit()))
+            .filter(|_| !indented);
+
+        let open = fences.is_open() || front_matter;
+        if index > block_start
+            && !open
+            && after_blank
+            && !blank
+            && !indented
+            && (marker.is_none() || marker != list)
+        {
+            blocks.push(Block::new(
+                block_start,
+                index - block_start,
+                std::mem::take(&mut source),
+            ));
+            block_start = index;
+            list = None;
+            if stop(index) {
+                return blocks;
+            }
+        }
+
+        if index > block_start {
+            source.push('\n');
+        }
+        source.push_str(&line);
+
+        // Front matter is kept in one block, like a fence.
+        if front_matter {
+            front_matter = !matches!(line.trim_end(), "---" | "...");
+        } else if index == 0 && line.trim_end() == "---" {
+            front_matter = true;
+        } else if !fences.read(&line) && marker.is_some() {
+            list = marker;
+        }
+        after_blank = blank && !fences.is_open() && !front_matter;
+        index += 1;
+    }
+
+    if index > block_start {
+        blocks.push(Block::new(block_start, index - block_start, source));
+    }
+    blocks
+}
+
+/// Gives `blocks` the rendering of the `old` blocks with the same source.
+fn reuse(blocks: &mut [Block], old: Vec<Block>) {
+    let mut cache: HashMap<String, Rendered> = old
+        .into_iter()
+        .filter_map(|block| Some((block.source, block.rendered?)))
+        .collect();
+    for block in blocks {
+        block.rendered = cache.remove(&block.source);
+    }
+}
+
+/// How many tasks of `blocks` are done, and how many there are.
+fn total(blocks: &[Block]) -> (usize, usize) {
+    blocks.iter().fold((0, 0), |(done, all), block| {
+        (done + block.tasks.0, all + block.tasks.1)
+    })
+}
+
+/// How a block of the note at `note` holding `source` is shown.
+fn render(source: &str, definitions: &str, note: &std::path::Path) -> Rendered {
+    if let Some(code) = code_block(source) {
+        if crate::diagrams::is_diagram(&code.language) {
+            let diagram = crate::diagrams::render(&code.language, &code.code);
+            Rendered::Diagram(code, diagram)
+        } else {
+            Rendered::Code(code)
+        }
+    } else if let Some(list) = crate::tasks::task_list(source, definitions) {
+        Rendered::Tasks(list)
+    } else if let Some(footnotes) = crate::extended::footnotes(source) {
+        Rendered::Footnotes(
+            footnotes
+                .into_iter()
+                .map(|footnote| Footnote {
+                    text: text(&footnote.text, definitions),
+                    id: footnote.id,
+                })
+                .collect(),
+        )
+    } else if let Some(callout) = crate::extended::callout(source) {
+        Rendered::Callout(Callout {
+            kind: callout.kind,
+            title: callout.title,
+            body: callout_body(&callout.body, definitions, note),
+        })
+    } else if let Some(list) = crate::extended::definitions(source) {
+        Rendered::Definitions(
+            list.into_iter()
+                .map(|definition| Definition {
+                    term: text(&definition.term, definitions),
+                    details: definition
+                        .details
+                        .iter()
+                        .map(|detail| text(detail, definitions))
+                        .collect(),
+                })
+                .collect(),
+        )
+    } else if let Some(segments) = segments(source, definitions, note) {
+        Rendered::Segments(segments)
+    } else if let Some(rich) = rich(source, definitions) {
+        Rendered::Rich(rich)
+    } else {
+        Rendered::Markdown(items(source, definitions))
+    }
+}
+
+/// Adds the notes embedded in `rendered` to `embeds`.
+fn rendered_embeds<'a>(rendered: &'a Rendered, embeds: &mut Vec<&'a crate::embeds::Embed>) {
+    match rendered {
+        Rendered::Segments(segments) => {
+            embeds.extend(segments.iter().filter_map(|segment| match segment {
+                Segment::Embed(embed) => Some(embed),
+                _ => None,
+            }))
+        }
+        Rendered::Callout(callout) => {
+            for block in &callout.body {
+                rendered_embeds(block, embeds);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The blocks of the body of a callout, as the blocks of a note are read.
+fn callout_body(source: &str, definitions: &str, note: &std::path::Path) -> Vec<Rendered> {
+    let content = iced::widget::text_editor::Content::with_text(source);
+    split(&content, 0, |_| false)
+        .into_iter()
+        .filter(|block| !block.source.trim().is_empty())
+        .map(|block| render(&block.source, definitions, note))
+        .collect()
+}
+
+/// The headings of `source`, with the line each starts at, its level and its text, as the
+/// Markdown parser reads them.
+fn headings(source: &str) -> Vec<(usize, usize, String)> {
+    use pulldown_cmark::{Event, Tag};
+
+    let options = pulldown_cmark::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | pulldown_cmark::Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS
+        | pulldown_cmark::Options::ENABLE_TABLES
+        | pulldown_cmark::Options::ENABLE_STRIKETHROUGH;
+    pulldown_cmark::Parser::new_ext(source, options)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            let Event::Start(Tag::Heading { level, .. }) = event else {
+                return None;
+            };
+            let written = source[range.clone()].trim_end();
+            let first = written.lines().next().unwrap_or("");
+            // The text of a `#` heading is on its line, without its closing `#`s, and the
+            // text of an underlined one on the lines above its underline.
+            let text = if crate::folding::heading_level(first).is_some() {
+                let text = crate::extended::heading_id(first).map_or(first, |(text, _)| text);
+                let text = text.trim().trim_start_matches('#').trim_end();
+                let closing = text.trim_end_matches('#');
+                if closing.is_empty() || closing.ends_with([' ', '\t']) {
+                    closing.to_string()
+                } else {
+                    text.to_string()
+                }
+            } else {
+                let lines: Vec<&str> = written.lines().collect();
+                lines[..lines.len().saturating_sub(1)].join("\n")
+            };
+            Some((
+                source[..range.start].matches('\n').count(),
+                level as usize,
+                heading_text(&text),
+            ))
+        })
+        .collect()
+}
+
+/// The text of a heading written as `source`, as the outline shows it: formulas are given by
+/// their source, and extended syntax and footnote references are left out.
+fn heading_text(source: &str) -> String {
+    use pulldown_cmark::Event;
+
+    let (text, formulas) = take_formulas(source);
+    let text = crate::extended::inline(&text).unwrap_or(text);
+
+    let mut heading = String::new();
+    let mut reference = false;
+    for event in pulldown_cmark::Parser::new(&text) {
+        let (Event::Text(text) | Event::Code(text)) = event else {
+            if matches!(event, Event::SoftBreak | Event::HardBreak) {
+                heading.push(' ');
+            }
+            continue;
+        };
+        for c in text.chars() {
+            if let Some(index) = formula(c) {
+                heading.extend(formulas.get(index).map(|(formula, _)| *formula));
+                continue;
+            }
+            match c {
+                c if c == crate::extended::FOOTNOTE_REFERENCE.0 => reference = true,
+                c if c == crate::extended::FOOTNOTE_REFERENCE.1 => reference = false,
+                c if [
+                    crate::extended::MARK,
+                    crate::extended::SUPERSCRIPT,
+                    crate::extended::SUBSCRIPT,
+                ]
+                .iter()
+                .any(|(open, close)| c == *open || c == *close) => {}
+                _ if reference => {}
+                c => heading.push(c),
+            }
+        }
+    }
+    heading.trim().to_string()
+}
+
+/// Reads `source` as a fenced code block, if that's all it holds.
+fn code_block(source: &str) -> Option<CodeBlock> {
+    let mut lines: Vec<&str> = source
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let (opening, lines) = lines.split_first()?;
+    let (closing, lines) = lines.split_last()?;
+
+    let trimmed = opening.trim_start();
+    if opening.len() - trimmed.len() >= 4 {
+        return None;
+    }
+    let fence = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let marker = trimmed.chars().take_while(|c| *c == fence).count();
+    let closing = closing.trim();
+    if marker < 3 || closing.len() < marker || closing.chars().any(|c| c != fence) {
+        return None;
+    }
+
+    let language = trimmed[marker..]
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .next()
+        .unwrap_or("")
+        .to_string();
+    let code = lines.join("\n");
+    Some(CodeBlock {
+        lines: crate::highlighter::highlight_code(&language, &code),
+        language,
+        code,
+    })
+}
+
+/// Reads the images and embeds out of `source`, if it has some outside code. The lines
+/// holding images are cut out of the text around them, their images following their text,
+/// found next to the `note`.
+fn segments(source: &str, definitions: &str, note: &std::path::Path) -> Option<Vec<Segment>> {
+    if !IMAGE.is_match(source) && !source.contains("![[") {
+        return None;
+    }
+    let mut segments = vec![];
+    let mut text = String::new();
+    let mut blocks = crate::folding::Code::default();
+    for line in source.lines() {
+        let code = blocks.read(line);
+        if let Some(embed) = crate::embeds::embed(line).filter(|_| !code) {
+            if !text.trim().is_empty() {
+                segments.push(segment(&text, definitions));
+            }
+            text.clear();
+            segments.push(Segment::Embed(embed));
+            continue;
+        }
+        let images: Vec<Image> = if code {
+            vec![]
+        } else {
+            images(line)
+                .map(|(_, image)| Image {
+                    file: crate::attachments::resolve(note, &image.source)
+                        .filter(|file| file.is_file()),
+                    ..image
+                })
+                .collect()
+        };
+        if images.is_empty() {
+            text.push_str(line);
+            text.push('\n');
+            continue;
+        }
+
+        let rest = IMAGE.replace_all(line, "");
+        if !rest.trim().is_empty() {
+            text.push_str(&rest);
+        }
+        if !text.trim().is_empty() {
+            segments.push(segment(&text, definitions));
+        }
+        text.clear();
+        match segments.last_mut() {
+            Some(Segment::Images(row)) => row.extend(images),
+            _ => segments.push(Segment::Images(images)),
+        }
+    }
+    if !text.trim().is_empty() {
+        segments.push(segment(&text, definitions));
+    }
+    segments
+        .iter()
+        .any(|segment| matches!(segment, Segment::Images(_) | Segment::Embed(_)))
+        .then_some(segments)
+}
+
+/// A run of text between images or embeds.
+fn segment(text: &str, definitions: &str) -> Segment {
+    match rich(text, definitions) {
+        Some(rich) => Segment::Rich(rich),
+        None => Segment::Markdown(items(text, definitions)),
+    }
+}
+
+/// Reads `source` as Markdown, with its formulas and extended syntax.
+pub fn text(source: &str, definitions: &str) -> Rich {
+    rich(source, definitions).unwrap_or_else(|| Rich {
+        items: items(source, definitions),
+        formulas: vec![],
+    })
+}
+
+/// Reads `source` as Markdown, with the link reference `definitions` of its note.
+pub fn items(source: &str, definitions: &str) -> Vec<iced::widget::markdown::Item> {
+    if definitions.is_empty() {
+        iced::widget::markdown::parse(source).collect()
+    } else {
+        iced::widget::markdown::parse(&format!("{}\n\n{}", source, definitions)).collect()
+    }
+}
+
+/// Reads the formulas out of `source` and rewrites its extended syntax, if it has some,
+/// before reading it as Markdown.
+fn rich(source: &str, definitions: &str) -> Option<Rich> {
+    let (text, formulas) = take_formulas(source);
+    let formulas: Vec<Math> = formulas
+        .into_iter()
+        .map(|(formula, display)| Math {
+            source: formula.to_string(),
+            display,
+            node: crate::math::parse(formula),
+        })
+        .collect();
+    let text = match crate::extended::inline(&text) {
+        Some(rewritten) => rewritten,
+        None if formulas.is_empty() => return None,
+        None => text,
+    };
+    Some(Rich {
+        items: items(&text, definitions),
+        formulas,
+    })
+}
+
+/// `source` with its formulas left out for their placeholders, and the formulas without
+/// their delimiters, with whether each is display math.
+pub fn take_formulas(source: &str) -> (String, Vec<(&str, bool)>) {
+    let mut text = String::new();
+    let mut formulas = vec![];
+    let mut end = 0;
+    for (range, display) in crate::math::find(source) {
+        text.push_str(&source[end..range.start]);
+        text.push(placeholder(formulas.len()));
+        let delimiter = if display { 2 } else { 1 };
+        formulas.push((
+            source[range.start + delimiter..range.end - delimiter].trim(),
+            display,
+        ));
+        end = range.end;
+    }
+    text.push_str(&source[end..]);
+    (text, formulas)
+}
+
+/// The character standing for the formula at `index`.
+pub fn placeholder(index: usize) -> char {
+    u32::try_from(index)
+        .ok()
+        .and_then(|index| FORMULA.checked_add(index))
+        .and_then(char::from_u32)
+        .unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// The index of the formula `c` stands for, if it's a placeholder.
+pub fn formula(c: char) -> Option<usize> {
+    (c as u32).checked_sub(FORMULA).map(|index| index as usize)
+}
+
+/// The images on `line`, with where each is written.
+pub fn images(line: &str) -> impl Iterator<Item = (Range<usize>, Image)> + '_ {
+    IMAGE.captures_iter(line).map(|captures| {
+        let range = captures.get(0).map_or(0..0, |all| all.range());
+        (range, image(captures))
+    })
+}
+
+fn image(captures: regex::Captures) -> Image {
+    let attributes: HashMap<String, String> = captures
+        .get(4)
+        .or(captures.get(5))
+        .map(|attributes| {
+            ATTRIBUTE
+                .captures_iter(attributes.as_str())
+                .map(|attribute| {
+                    let value = (2..=4)
+                        .find_map(|i| attribute.get(i))
+                        .map_or("", |value| value.as_str());
+                    (attribute[1].to_lowercase(), value.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    // Sizes are in pixels, with or without `px`.
+    let size = |name: &str| {
+        attributes
+            .get(name)
+            .and_then(|value| value.trim_end_matches("px").parse::<f32>().ok())
+            .filter(|size| *size > 0.0)
+    };
+    let (alt, source) = match captures.get(5) {
+        Some(_) => (
+            attributes.get("alt").cloned().unwrap_or_default(),
+            attributes.get("src").cloned().unwrap_or_default(),
+        ),
+        None => (
+            captures[1].to_string(),
+            captures
+                .get(2)
+                .or(captures.get(3))
+                .map_or("", |source| source.as_str())
+                .to_string(),
+        ),
+    };
+    Image {
+        alt,
+        source,
+        width: size("width"),
+        height: size("height"),
+        file: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(text: &str) -> Vec<String> {
+        let content = iced::widget::text_editor::Content::with_text(text);
+        split(&content, 0, |_| false)
+            .into_iter()
+            .map(|block| block.source)
+            .collect()
+    }
+
+    #[test]
+    fn fence_closes_on_its_own_marker() {
+        assert_eq!(
+            blocks("````\n```\n\ninside\n```\n````\n\nafter"),
+            ["````\n```\n\ninside\n```\n````\n", "after"]
+        );
+        assert_eq!(
+            blocks("~~~\n```\n\ninside\n~~~\n\nafter"),
+            ["~~~\n```\n\ninside\n~~~\n", "after"]
+        );
+    }
+
+    #[test]
+    fn loose_list_stays_together() {
+        assert_eq!(
+            blocks("- one\n\n- two\n\n1. three\n\ntext"),
+            ["- one\n\n- two\n", "1. three\n", "text"]
+        );
+    }
+
+    #[test]
+    fn front_matter_is_one_block() {
+        assert_eq!(
+            blocks("---\ntitle: a\n\ntags: b\n---\n\ntext"),
+            ["---\ntitle: a\n\ntags: b\n---\n", "text"]
+        );
+    }
+
+    fn outline(text: &str) -> Vec<(usize, usize, String, String)> {
+        let mut preview = Preview::new(std::path::Path::new("note.md"));
+        preview.rebuild(&iced::widget::text_editor::Content::with_text(text));
+        preview
+            .outline()
+            .into_iter()
+            .map(|heading| (heading.line, heading.level, heading.text, heading.anchor))
+            .collect()
+    }
+
+    #[test]
+    fn outline_takes_headings_from_the_parser() {
+        assert_eq!(
+            outline(
+                "---\ntitle: a\n---\n\n# One #\n\nText\n---\n\n- item\n-\n\n<div>\n# html\n</div>"
+            ),
+            [
+                (4, 1, "One".into(), "one".into()),
+                (6, 2, "Text".into(), "text".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn outline_texts_and_anchors() {
+        assert_eq!(
+            outline("## Area $x^2$ ==now==[^1] {#area}\n\n# A *b* C#\n\n# A *b* C#"),
+            [
+                (0, 2, "Area x^2 now".into(), "area".into()),
+                (2, 1, "A b C#".into(), "a-b-c".into()),
+                (4, 1, "A b C#".into(), "a-b-c-1".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn link_definitions_outside_code() {
+        let mut preview = Preview::new(std::path::Path::new("note.md"));
+        preview.read(&iced::widget::text_editor::Content::with_text(
+            "[link][id]\n\n[id]: https://example.com\n[^1]: note\n```\n[code]: x\n```",
+        ));
+        assert_eq!(preview.definitions(), "[id]: https://example.com\n");
+    }
+
+    #[test]
+    fn edits_keep_the_note_in_sync() {
+        let content = |text: &str| iced::widget::text_editor::Content::with_text(text);
+        let mut preview = Preview::new(std::path::Path::new("note.md"));
+        preview.read(&content("- [ ] one\n\ntext[^b]\n\n[^a]: a\n[^b]: b"));
+        let after = content(
+            "- [ ] one\n\n- [x] two[^a]\n[id]: https://example.com\ntext[^b]\n\n[^a]: a\n[^b]: b",
+        );
+        preview.edit(
+            &after,
+            Edit {
+                first_line: 2,
+                last_line: 4,
+                delta: 2,
+            },
+        );
+        let mut read = Preview::new(std::path::Path::new("note.md"));
+        read.read(&after);
+        assert_eq!(preview.tasks(), (1, 2));
+        assert_eq!(preview.tasks(), read.tasks());
+        assert_eq!(preview.definitions(), "[id]: https://example.com\n");
+        assert_eq!(preview.definitions(), read.definitions());
+        assert_eq!(preview.references, ["a", "b"]);
+        assert_eq!(preview.references, read.references);
+    }
+
+    #[test]
+    fn images_are_found_next_to_the_note() {
+        let directory =
+            std::env::temp_dir().join(format!("darkstone-images-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("found.png"), b"").unwrap();
+
+        let mut preview = Preview::new(&directory.join("note.md"));
+        preview.rebuild(&iced::widget::text_editor::Content::with_text(
+            "![a](found.png) ![b](missing.png)\n\n    ![c](found.png)",
+        ));
+        let files: Vec<Option<std::path::PathBuf>> = preview
+            .blocks()
+            .flat_map(|(_, rendered)| match rendered {
+                Rendered::Segments(segments) => segments.iter().collect(),
+                _ => vec![],
+            })
+            .flat_map(|segment| match segment {
+                Segment::Images(images) => images.iter().map(|image| image.file.clone()).collect(),
+                _ => vec![],
+            })
+            .collect();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(files, [Some(directory.join("found.png")), None]);
+    }
+
+    #[test]
+    fn formulas_have_their_own_placeholders() {
+        let (text, formulas) = take_formulas("$a$ `$b$` $$c$$");
+        assert_eq!(text, format!("{} `$b$` {}", placeholder(0), placeholder(1)));
+        assert_eq!(formulas, [("a", false), ("c", true)]);
+        assert_eq!(formula(placeholder(1)), Some(1));
+        assert_eq!(formula('a'), None);
+        assert_eq!(formula(crate::extended::MARK.0), None);
+    }
+
+    #[test]
+    fn task_lists_read_math_and_extended_syntax() {
+        let list = crate::tasks::task_list("- [ ] $x^2$ is ==due==\n- [x] plain", "").unwrap();
+        let first = &list.items[0].text;
+        assert_eq!(first.formulas.len(), 1);
+        assert_eq!(first.formulas[0].source, "x^2");
+        let iced::widget::markdown::Item::Paragraph(paragraph) = &first.items[0] else {
+            panic!("not a paragraph");
+        };
+        let style = iced::widget::markdown::Style::from_palette(iced::Theme::Dark.palette());
+        let text: String = paragraph
+            .spans(style)
+            .iter()
+            .map(|span| span.text.as_ref())
+            .collect();
+        assert_eq!(
+            text,
+            format!(
+                "{} is {}due{}",
+                placeholder(0),
+                crate::extended::MARK.0,
+                crate::extended::MARK.1
+            )
+        );
+        assert!(list.items[1].text.formulas.is_empty());
+    }
+
+    #[test]
+    fn callouts_hold_blocks() {
+        let mut preview = Preview::new(std::path::Path::new("note.md"));
+        preview.rebuild(&iced::widget::text_editor::Content::with_text(
+            "> [!todo] Today\n> - [ ] a task[^1]\n>\n> ```\n> code\n> ```",
+        ));
+        let Some((_, Rendered::Callout(callout))) = preview.blocks().next() else {
+            panic!("not a callout");
+        };
+        assert_eq!(callout.title, "Today");
+        assert!(matches!(
+            callout.body.as_slice(),
+            [Rendered::Tasks(_), Rendered::Code(_)]
+        ));
+    }
+
+    #[test]
+    fn embeds_are_found_in_callouts() {
+        let mut preview = Preview::new(std::path::Path::new("note.md"));
+        preview.rebuild(&iced::widget::text_editor::Content::with_text(
+            "![[One]]\n\n> [!note]\n> ![[Two#Part]]",
+        ));
+        let names: Vec<&str> = preview
+            .embeds()
+            .into_iter()
+            .map(|embed| embed.name.as_str())
+            .collect();
+        assert_eq!(names, ["One", "Two"]);
+    }
+}
@@ -0,0 +1,614 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Filters the sidebar's search box with `search.json`, which needs the site to be served
+/// rather than opened from files.
+const SEARCH_SCRIPT: &str = r#"<script>
+(function () {
+  var input = document.getElementById("search");
+  var results = document.getElementById("results");
+  var root = input.dataset.root;
+  var index = null;
+  input.addEventListener("input", function () {
+    var words = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    if (!words.length) {
+      results.innerHTML = "";
+      return;
+    }
+    var load = index ? Promise.resolve(index) : fetch(root + "search.json")
+      .then(function (response) { return response.json(); })
+      .then(function (loaded) { index = loaded; return loaded; });
+    load.then(function (pages) {
+      results.innerHTML = "";
+      pages.filter(function (page) {
+        var text = (page.title + " " + page.tags.join(" ") + " " + page.text).toLowerCase();
+        return words.every(function (word) { return text.indexOf(word) >= 0; });
+      }).slice(0, 20).forEach(function (page) {
+        var item = document.createElement("li");
+        var link = document.createElement("a");
+        link.href = root + page.url;
+        link.textContent = page.title;
+        item.appendChild(link);
+        results.appendChild(item);
+      });
+    });
+  });
+})();
+</script>
+"#;
+
+/// A folder of the notes, with its notes and folders in the order of the tree.
+#[derive(Debug, Clone)]
+pub struct Folder {
+    pub name: String,
+    pub path: PathBuf,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Folder(Folder),
+    Note(PathBuf),
+}
+
+/// A note to publish.
+struct Page {
+    path: PathBuf,
+    text: String,
+    title: String,
+    tags: Vec<String>,
+}
+
+/// Writes the notes of `folder` with `publish: true` in their front matter as a website in
+/// `output`: a page per note, a page per tag, an index and `search.json`. Returns how many
+/// notes were published.
+pub async fn publish(
+    folder: Folder,
+    output: PathBuf,
+    config: crate::config::Configuration,
+) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || Site::new(folder, output, &config)?.write())
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+struct Site {
+    folder: Folder,
+    pages: Vec<Page>,
+    exporter: crate::export::Exporter,
+    /// The names of the pages of the tags, without their extension.
+    tag_files: BTreeMap<String, String>,
+    /// The index of the site and the folder of the pages of the tags, named so that no note's
+    /// page is written over.
+    index: PathBuf,
+    tags_folder: PathBuf,
+}
+
+impl Site {
+    fn new(
+        folder: Folder,
+        output: PathBuf,
+        config: &crate::config::Configuration,
+    ) -> Result<Self, String> {
+        let mut paths = vec![];
+        notes(&folder, &mut paths);
+        let mut pages = vec![];
+        for path in paths {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Can't read {:?}: {:?}", path, e.kind()))?;
+            let fields = front_matter(&text);
+            let field = |key: &str| {
+                fields
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .map(|(_, value)| unquote(value))
+            };
+            if !field("publish").is_some_and(|value| matches!(value, "true" | "yes")) {
+                continue;
+            }
+            let title = field("title")
+                .filter(|title| !title.is_empty())
+                .unwrap_or(crate::export::title(&path))
+                .to_string();
+            let tags = field("tags")
+                .map(|tags| {
+                    tags.trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(|tag| unquote(tag).trim_start_matches('#').to_string())
+                        .filter(|tag| !tag.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            pages.push(Page {
+                path,
+                text,
+                title,
+                tags,
+            });
+        }
+
+        // Only published notes can be linked to, so the others don't show up anywhere.
+        let notes = pages
+            .iter()
+            .map(|page| (page.path.clone(), page.title.clone()))
+            .collect();
+        let mut exporter = crate::export::Exporter::new(notes, config);
+        exporter.root = folder.path.clone();
+        exporter.export_path = output;
+        exporter.file_links = false;
+        let tag_files = tag_files(pages.iter().flat_map(|page| &page.tags));
+
+        let written: Vec<PathBuf> = pages.iter().map(|page| exporter.page(&page.path)).collect();
+        let root = &exporter.export_path;
+        let mut index = root.join("index.html");
+        let mut n = 1;
+        while written.contains(&index) {
+            n += 1;
+            index = root.join(format!("index-{}.html", n));
+        }
+        let mut tags_folder = root.join("tags");
+        let mut n = 1;
+        while written.iter().any(|page| page.starts_with(&tags_folder)) {
+            n += 1;
+            tags_folder = root.join(format!("tags-{}", n));
+        }
+        Ok(Self {
+            folder,
+            pages,
+            exporter,
+            tag_files,
+            index,
+            tags_folder,
+        })
+    }
+
+    fn write(&self) -> Result<usize, String> {
+        let bodies: Vec<_> = self
+            .pages
+            .iter()
+            .map(|page| self.exporter.body(&page.path, &page.text))
+            .collect();
+        let mut backlinks: Vec<Vec<usize>> = vec![vec![]; self.pages.len()];
+        for (from, (_, links)) in bodies.iter().enumerate() {
+            for link in links {
+                let Some(to) = self.pages.iter().position(|page| page.path == *link) else {
+                    continue;
+                };
+                if to != from && !backlinks[to].contains(&from) {
+                    backlinks[to].push(from);
+                }
+            }
+        }
+        let mut tags: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, page) in self.pages.iter().enumerate() {
+            for tag in &page.tags {
+                tags.entry(tag).or_default().push(i);
+            }
+        }
+
+        for ((page, (body, _)), backlinks) in self.pages.iter().zip(&bodies).zip(&backlinks) {
+            let file = self.exporter.page(&page.path);
+            let folder = file.parent().unwrap_or(&self.exporter.export_path);
+            // Notes usually start with their own title.
+            let mut html = if body.contains("<h1") {
+                String::new()
+            } else {
+                format!("<h1>{}</h1>\n", crate::util::escape(&page.title))
+            };
+            if !page.tags.is_empty() {
+                html.push_str("<p class=\"tags\">");
+                for tag in &page.tags {
+                    html.push_str(&self.tag_link(folder, tag));
+                }
+                html.push_str("</p>\n");
+            }
+            html.push_str(body);
+            if !backlinks.is_empty() {
+                html.push_str("<section class=\"backlinks\">\n<h2>Linked from</h2>\n");
+                html.push_str(&self.list(folder, backlinks));
+                html.push_str("</section>\n");
+            }
+            let aside = self.sidebar(folder, Some(&page.path));
+            crate::export::write(&file, &page.title, &aside, &html)?;
+        }
+
+        let tags_folder = &self.tags_folder;
+        for (tag, pages) in &tags {
+            let html = format!(
+                "<h1>#{}</h1>\n{}",
+                crate::util::escape(tag),
+                self.list(tags_folder, pages)
+            );
+            crate::export::write(
+                &tags_folder.join(format!("{}.html", self.tag_files[*tag])),
+                &format!("#{}", tag),
+                &self.sidebar(tags_folder, None),
+                &html,
+            )?;
+        }
+
+        let root = &self.exporter.export_path;
+        let all: Vec<usize> = (0..self.pages.len()).collect();
+        let mut html = format!(
+            "<h1>{}</h1>\n{}",
+            crate::util::escape(&self.folder.name),
+            self.list(root, &all)
+        );
+        if !tags.is_empty() {
+            html.push_str("<h2>Tags</h2>\n<p class=\"tags\">");
+            for tag in tags.keys() {
+                html.push_str(&self.tag_link(root, tag));
+            }
+            html.push_str("</p>\n");
+        }
+        crate::export::write(
+            &self.index,
+            &self.folder.name,
+            &self.sidebar(root, None),
+            &html,
+        )?;
+
+        let index: Vec<String> = self
+            .pages
+            .iter()
+            .map(|page| {
+                let tags: Vec<String> = page.tags.iter().map(|tag| json(tag)).collect();
+                format!(
+                    "{{\"title\":{},\"url\":{},\"tags\":[{}],\"text\":{}}}",
+                    json(&page.title),
+                    json(&crate::export::relative(
+                        root,
+                        &self.exporter.page(&page.path)
+                    )),
+                    tags.join(","),
+                    json(&plain_text(&page.text))
+                )
+            })
+            .collect();
+        std::fs::write(
+            root.join("search.json"),
+            format!("[\n{}\n]\n", index.join(",\n")),
+        )
+        .map_err(|e| format!("{:?}", e.kind()))?;
+        Ok(self.pages.len())
+    }
+
+    /// The sidebar of a page in `folder`: the search box and the tree of published notes, with
+    /// the one at `current` marked.
+    fn sidebar(&self, folder: &Path, current: Option<&Path>) -> String {
+        let root = match crate::export::relative(folder, &self.exporter.export_path) {
+            root if root.is_empty() => root,
+            root => format!("{}/", root),
+        };
+        format!(
+            "<p><a href=\"{}\">{}</a></p>\n<input type=\"search\" id=\"search\" placeholder=\"Search\" data-root=\"{}\">\n<ul id=\"results\"></ul>\n<nav>\n{}</nav>\n{}",
+            crate::export::relative(folder, &self.index),
+            crate::util::escape(&self.folder.name),
+            root,
+            self.tree(&self.folder, folder, current),
+            SEARCH_SCRIPT
+        )
+    }
+
+    /// The published notes under `tree`, linked from a page in `folder`. Folders without any
+    /// are left out.
+    fn tree(&self, tree: &Folder, folder: &Path, current: Option<&Path>) -> String {
+        let mut html = String::new();
+        for item in &tree.items {
+            match item {
+                Item::Folder(child) => {
+                    let notes = self.tree(child, folder, current);
+                    if !notes.is_empty() {
+                        html.push_str(&format!(
+                            "<li><span class=\"folder\">{}</span>\n{}</li>\n",
+                            crate::util::escape(&child.name),
+                            notes
+                        ));
+                    }
+                }
+                Item::Note(path) => {
+                    if let Some(page) = self.pages.iter().find(|page| page.path == *path) {
+                        let class = if current == Some(path.as_path()) {
+                            " class=\"current\""
+                        } else {
+                            ""
+                        };
+                        html.push_str(&format!(
+                            "<li><a href=\"{}\"{}>{}</a></li>\n",
+                            crate::export::relative(folder, &self.exporter.page(path)),
+                            class,
+                            crate::util::escape(&page.title)
+                        ));
+                    }
+                }
+            }
+        }
+        if html.is_empty() {
+            html
+        } else {
+            format!("<ul>\n{}</ul>\n", html)
+        }
+    }
+
+    /// A list of links to `pages` from a page in `folder`.
+    fn list(&self, folder: &Path, pages: &[usize]) -> String {
+        let items: String = pages
+            .iter()
+            .map(|&i| {
+                format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    crate::export::relative(folder, &self.exporter.page(&self.pages[i].path)),
+                    crate::util::escape(&self.pages[i].title)
+                )
+            })
+            .collect();
+        format!("<ul>\n{}</ul>\n", items)
+    }
+
+    fn tag_link(&self, folder: &Path, tag: &str) -> String {
+        let page = self
+            .tags_folder
+            .join(format!("{}.html", self.tag_files[tag]));
+        format!(
+            "<a href=\"{}\">#{}</a>",
+            crate::export::relative(folder, &page),
+            crate::util::escape(tag)
+        )
+    }
+}
+
+/// The notes under `folder`, in the order of the tree.
+fn notes(folder: &Folder, paths: &mut Vec<PathBuf>) {
+    for item in &folder.items {
+        match item {
+            Item::Folder(child) => notes(child, paths),
+            Item::Note(path) => paths.push(path.clone()),
+        }
+    }
+}
+
+/// The `key: value` fields of the front matter at the start of `text`. The items of a list
+/// under a key are joined with commas.
+fn front_matter(text: &str) -> Vec<(String, String)> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim_end) != Some("---") {
+        return vec![];
+    }
+    let mut fields: Vec<(String, String)> = vec![];
+    for line in lines {
+        if matches!(line.trim_end(), "---" | "...") {
+            return fields;
+        }
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            if let Some((_, value)) = fields.last_mut() {
+                if !value.is_empty() {
+                    value.push_str(", ");
+                }
+                value.push_str(item.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            if !line.starts_with(char::is_whitespace) {
+                fields.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    // Without its end, it's not front matter.
+    vec![]
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .or_else(|| {
+            value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+        })
+        .unwrap_or(value)
+}
+
+/// The names of the pages of `tags`, without their extension, by tag. Tags whose slugs are
+/// the same are told apart by a number.
+fn tag_files<'a>(tags: impl IntoIterator<Item = &'a String>) -> BTreeMap<String, String> {
+    let tags: std::collections::BTreeSet<&String> = tags.into_iter().collect();
+    let mut taken = std::collections::HashSet::new();
+    let mut files = BTreeMap::new();
+    for tag in tags {
+        let slug = match crate::extended::slug(tag) {
+            slug if slug.is_empty() => "tag".to_string(),
+            slug => slug,
+        };
+        let mut file = slug.clone();
+        let mut n = 1;
+        while !taken.insert(file.clone()) {
+            n += 1;
+            file = format!("{}-{}", slug, n);
+        }
+        files.insert(tag.clone(), file);
+    }
+    files
+}
+
+/// The words of the Markdown `text`, without its syntax or front matter, for the search.
+/// Formulas and the extended syntax are read as on the pages, formulas keeping their source
+/// and footnote references left out.
+fn plain_text(text: &str) -> String {
+    use pulldown_cmark::{Event, Tag, TagEnd};
+
+    let (text, formulas) = crate::preview::take_formulas(text);
+    let text = crate::extended::inline(&text).unwrap_or(text);
+    let mut words = String::new();
+    let mut front_matter = false;
+    for event in pulldown_cmark::Parser::new_ext(
+        &text,
+        pulldown_cmark::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+            | pulldown_cmark::Options::ENABLE_FOOTNOTES,
+    ) {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => front_matter = true,
+            Event::End(TagEnd::MetadataBlock(_)) => front_matter = false,
+            Event::Text(text) if !front_matter => {
+                let mut reference = false;
+                for c in text.chars() {
+                    use crate::extended::{FOOTNOTE_REFERENCE, MARK, SUBSCRIPT, SUPERSCRIPT};
+                    match c {
+                        c if c == FOOTNOTE_REFERENCE.0 => reference = true,
+                        c if c == FOOTNOTE_REFERENCE.1 => reference = false,
+                        _ if reference => {}
+                        c if [MARK, SUPERSCRIPT, SUBSCRIPT]
+                            .iter()
+                            .any(|(open, close)| c == *open || c == *close) => {}
+                        c => match crate::preview::formula(c) {
+                            Some(index) => {
+                                let source = formulas.get(index).map_or("", |(source, _)| source);
+                                words.push_str(source);
+                            }
+                            None => words.push(c),
+                        },
+                    }
+                }
+            }
+            Event::Code(text) if !front_matter => words.push_str(&text),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => words.push(' '),
+            _ => {}
+        }
+    }
+    // Wiki links are searched by their text.
+    let words = words.replace("![[", "").replace("[[", "").replace("]]", "");
+    words.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// `text` as a JSON string.
+fn json(text: &str) -> String {
+    let mut json = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(text: &str) -> Vec<String> {
+        front_matter(text)
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect()
+    }
+
+    /// Publishes the notes `files`, named by their path from the notes folder, and returns
+    /// the folder of the site.
+    fn publish_notes(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("darkstone-{}-{}", name, std::process::id()));
+        let (notes, output) = (root.join("notes"), root.join("site"));
+        let mut folder = Folder {
+            name: "Notes".to_string(),
+            path: notes.clone(),
+            items: vec![],
+        };
+        for (file, text) in files {
+            let path = notes.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, text).unwrap();
+            folder.items.push(Item::Note(path));
+        }
+        let config = crate::config::Configuration {
+            notes_path: notes,
+            ..Default::default()
+        };
+        let published = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(publish(folder, output.clone(), config));
+        assert_eq!(published, Ok(files.len()));
+        root
+    }
+
+    #[test]
+    fn index_keeps_clear_of_notes() {
+        let root = publish_notes(
+            "site-index",
+            &[("index.md", "---\npublish: true\n---\n# Welcome")],
+        );
+        let note = std::fs::read_to_string(root.join("site/index.html"));
+        let index = std::fs::read_to_string(root.join("site/index-2.html"));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(note.unwrap().contains("Welcome"));
+        let index = index.unwrap();
+        assert!(index.contains("<h1>Notes</h1>"), "{index}");
+        assert!(
+            index.contains(r#"<a href="index-2.html">Notes</a>"#),
+            "{index}"
+        );
+    }
+
+    #[test]
+    fn tag_pages_keep_clear_of_notes() {
+        let root = publish_notes(
+            "site-tags",
+            &[(
+                "tags/rust.md",
+                "---\npublish: true\ntags: [rust]\n---\n# About Rust",
+            )],
+        );
+        let note = std::fs::read_to_string(root.join("site/tags/rust.html"));
+        let tag = std::fs::read_to_string(root.join("site/tags-2/rust.html"));
+        std::fs::remove_dir_all(&root).unwrap();
+        let note = note.unwrap();
+        assert!(note.contains("About Rust"));
+        assert!(
+            note.contains(r#"href="../tags-2/rust.html">#rust</a>"#),
+            "{note}"
+        );
+        assert!(tag.unwrap().contains("<h1>#rust</h1>"));
+    }
+
+    #[test]
+    fn front_matter_fields_and_lists() {
+        assert_eq!(
+            fields("---\ntitle: \"A: B\"\npublish: true\ntags:\n  - one\n  - two\n---\ntext"),
+            ["title=\"A: B\"", "publish=true", "tags=one, two"]
+        );
+        assert_eq!(fields("---\ntitle: a\n...\n"), ["title=a"]);
+        assert_eq!(unquote(" 'quoted' "), "quoted");
+    }
+
+    #[test]
+    fn front_matter_needs_both_ends() {
+        assert!(front_matter("---\ntitle: a\n").is_empty());
+        assert!(front_matter("title: a\n---\n").is_empty());
+        assert!(front_matter("text\n---\ntitle: a\n---").is_empty());
+    }
+
+    #[test]
+    fn tag_files_are_told_apart() {
+        let tags = ["C++", "c", "rust", "c", "!!!"].map(String::from);
+        let files = tag_files(&tags);
+        assert_eq!(files["C++"], "c");
+        assert_eq!(files["c"], "c-2");
+        assert_eq!(files["rust"], "rust");
+        assert_eq!(files["!!!"], "tag");
+    }
+
+    #[test]
+    fn plain_text_reads_like_the_pages() {
+        assert_eq!(
+            plain_text(
+                "---\ntitle: Hidden\n---\n# Area\n\n$x^2$ is ==big==[^1], see [[Other]]\n\n[^1]: A note."
+            ),
+            "Area x^2 is big, see Other A note."
+        );
+    }
+}
@@ -0,0 +1,303 @@
+/// Per-folder file remembering how the folder is sorted, and its manual order.
+pub const ORDER_FILE: &str = ".darkstone-order";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    Name,
+    Modified,
+    Created,
+    Manual,
+}
+
+impl SortMode {
+    pub const ALL: [SortMode; 4] = [
+        SortMode::Name,
+        SortMode::Modified,
+        SortMode::Created,
+        SortMode::Manual,
+    ];
+}
+
+impl std::fmt::Display for SortMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SortMode::Name => "Name",
+                SortMode::Modified => "Modified",
+                SortMode::Created => "Created",
+                SortMode::Manual => "Manual",
+            }
+        )
+    }
+}
+
+/// What an entry of the tree gets sorted by.
+pub struct SortKey<'a> {
+    pub name: &'a str,
+    pub is_dir: bool,
+    pub modified: Option<std::time::SystemTime>,
+    pub created: Option<std::time::SystemTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectoryOrder {
+    pub sort: SortMode,
+    pub folders_first: bool,
+    /// Entry names in the order they were dragged into, used by [`SortMode::Manual`].
+    pub manual: Vec<String>,
+}
+
+impl Default for DirectoryOrder {
+    fn default() -> Self {
+        Self {
+            sort: SortMode::Name,
+            folders_first: true,
+            manual: vec![],
+        }
+    }
+}
+
+impl DirectoryOrder {
+    /// Parses the `key: value` lines of an order file, with one `item:` line per entry of
+    /// the manual order.
+    pub fn parse(contents: &str) -> Self {
+        let mut order = Self::default();
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "sort" => {
+                    order.sort = match value {
+                        "modified" => SortMode::Modified,
+                        "created" => SortMode::Created,
+                        "manual" => SortMode::Manual,
+                        _ => SortMode::Name,
+                    }
+                }
+                "folders_first" => order.folders_first = value != "false",
+                "item" => order.manual.push(value.to_string()),
+                _ => {}
+            }
+        }
+        order
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut contents = format!(
+            "sort: {}\nfolders_first: {}\n",
+            self.sort.to_string().to_lowercase(),
+            self.folders_first
+        );
+        for name in &self.manual {
+            contents.push_str(&format!("item: {}\n", name));
+        }
+        contents
+    }
+
+    pub fn compare(&self, a: &SortKey, b: &SortKey) -> std::cmp::Ordering {
+        if self.folders_first && a.is_dir != b.is_dir {
+            return b.is_dir.cmp(&a.is_dir);
+        }
+        let by_name = || natural_cmp(a.name, b.name);
+        match self.sort {
+            SortMode::Name => by_name(),
+            // Most recent first.
+            SortMode::Modified => b.modified.cmp(&a.modified).then_with(by_name),
+            SortMode::Created => b.created.cmp(&a.created).then_with(by_name),
+            SortMode::Manual => {
+                // Entries that were never dragged go after the ordered ones.
+                let position = |name| {
+                    self.manual
+                        .iter()
+                        .position(|item| item == name)
+                        .unwrap_or(usize::MAX)
+                };
+                position(a.name).cmp(&position(b.name)).then_with(by_name)
+            }
+        }
+    }
+}
+
+/// Compares names case-insensitively, with runs of digits compared by their value so that
+/// "Note 2" comes before "Note 10".
+pub fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits.trim_start_matches('0').to_string()
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(&y));
+                if ordering != std::cmp::Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != std::cmp::Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Reads the order file of `directory`, falling back to the default order.
+pub async fn read_order(directory: std::path::PathBuf) -> DirectoryOrder {
+    match crate::util::read_file(directory.join(ORDER_FILE)).await {
+        Ok(contents) => DirectoryOrder::parse(&contents),
+        Err(_) => DirectoryOrder::default(),
+    }
+}
+
+pub async fn save_order(
+    directory: std::path::PathBuf,
+    order: DirectoryOrder,
+) -> Result<(), std::io::ErrorKind> {
+    tokio::fs::write(directory.join(ORDER_FILE), order.serialize())
+        .await
+        .map_err(|e| e.kind())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    fn key(name: &str, is_dir: bool) -> SortKey<'_> {
+        SortKey {
+            name,
+            is_dir,
+            modified: None,
+            created: None,
+        }
+    }
+
+    fn sorted<'a>(order: &DirectoryOrder, mut keys: Vec<SortKey<'a>>) -> Vec<&'a str> {
+        keys.sort_by(|a, b| order.compare(a, b));
+        keys.into_iter().map(|key| key.name).collect()
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("Note 2", "Note 10"), Ordering::Less);
+        assert_eq!(natural_cmp("Note 007", "Note 7"), Ordering::Equal);
+        assert_eq!(natural_cmp("Note 010", "Note 9"), Ordering::Greater);
+        assert_eq!(natural_cmp("v1.2", "v1.10"), Ordering::Less);
+        assert_eq!(natural_cmp("0", "00"), Ordering::Equal);
+        assert_eq!(natural_cmp("Note", "Note 1"), Ordering::Less);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(natural_cmp("apple", "Banana"), Ordering::Less);
+        assert_eq!(natural_cmp("README", "readme"), Ordering::Equal);
+        assert_eq!(natural_cmp("Zeta", "alpha"), Ordering::Greater);
+    }
+
+    #[test]
+    fn order_round_trips() {
+        let order = DirectoryOrder {
+            sort: SortMode::Manual,
+            folders_first: false,
+            manual: vec!["b.md".into(), "a: colon.md".into()],
+        };
+        let parsed = DirectoryOrder::parse(&order.serialize());
+        assert_eq!(parsed.sort, SortMode::Manual);
+        assert!(!parsed.folders_first);
+        assert_eq!(parsed.manual, order.manual);
+
+        let parsed = DirectoryOrder::parse("sort: sideways\nunknown: 1");
+        assert_eq!(parsed.sort, SortMode::Name);
+        assert!(parsed.folders_first);
+    }
+
+    #[test]
+    fn folders_come_first() {
+        let keys = || {
+            vec![
+                key("b.md", false),
+                key("z", true),
+                key("a.md", false),
+                key("c", true),
+            ]
+        };
+        let order = DirectoryOrder::default();
+        assert_eq!(sorted(&order, keys()), ["c", "z", "a.md", "b.md"]);
+        let order = DirectoryOrder {
+            folders_first: false,
+            ..DirectoryOrder::default()
+        };
+        assert_eq!(sorted(&order, keys()), ["a.md", "b.md", "c", "z"]);
+    }
+
+    #[test]
+    fn manual_order_skips_missing_entries() {
+        let order = DirectoryOrder {
+            sort: SortMode::Manual,
+            folders_first: false,
+            manual: vec![
+                "gone.md".into(),
+                "c.md".into(),
+                "deleted".into(),
+                "a.md".into(),
+            ],
+        };
+        let keys = vec![
+            key("b.md", false),
+            key("a.md", false),
+            key("new 10.md", false),
+            key("c.md", false),
+            key("new 9.md", false),
+        ];
+        // Entries never dragged go last, by name.
+        assert_eq!(
+            sorted(&order, keys),
+            ["c.md", "a.md", "b.md", "new 9.md", "new 10.md"]
+        );
+    }
+
+    #[test]
+    fn recent_first_then_by_name() {
+        let at = |seconds| Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds));
+        let order = DirectoryOrder {
+            sort: SortMode::Modified,
+            ..DirectoryOrder::default()
+        };
+        let keys = vec![
+            SortKey {
+                modified: at(1),
+                ..key("old.md", false)
+            },
+            SortKey {
+                modified: at(5),
+                ..key("b.md", false)
+            },
+            SortKey {
+                modified: at(5),
+                ..key("a.md", false)
+            },
+            key("unknown.md", false),
+        ];
+        assert_eq!(
+            sorted(&order, keys),
+            ["a.md", "b.md", "old.md", "unknown.md"]
+        );
+    }
+}
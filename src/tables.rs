@@ -0,0 +1,455 @@
+/// A pipe table of a note, read from its lines to edit and format it.
+pub struct Table {
+    /// The line of the header row, the delimiter row following it.
+    pub first: usize,
+    /// The cells of the header row and of the other rows, trimmed, with as many cells in each.
+    rows: Vec<Vec<String>>,
+    aligns: Vec<Align>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// A change picked in the table menu.
+#[derive(Debug, Clone, Copy)]
+pub enum Change {
+    RowAbove,
+    RowBelow,
+    DeleteRow,
+    ColumnLeft,
+    ColumnRight,
+    DeleteColumn,
+    /// Switches the current column to the next alignment.
+    Align,
+}
+
+/// Where the cursor is in a table: a line, a cell and a byte offset in its text.
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub cell: usize,
+    pub offset: usize,
+}
+
+/// The smallest width of a column, for the delimiter row.
+const MIN_WIDTH: usize = 3;
+
+impl Table {
+    /// The table holding `line`, if there's one: lines with pipes, the second of which is the
+    /// delimiter row.
+    pub fn at(lines: &[String], line: usize) -> Option<Self> {
+        let row = |i: &usize| lines[*i].contains('|') && !lines[*i].trim().is_empty();
+        if !row(&line) {
+            return None;
+        }
+        let first = (0..line).rev().take_while(row).last().unwrap_or(line);
+        let last = (line..lines.len()).take_while(row).last().unwrap_or(line);
+        if last == first {
+            return None;
+        }
+
+        let aligns = split(&lines[first + 1])
+            .into_iter()
+            .map(|range| align(lines[first + 1][range].trim()))
+            .collect::<Option<Vec<_>>>()?;
+        if aligns.len() != split(&lines[first]).len() {
+            return None;
+        }
+        let mut rows: Vec<Vec<String>> = std::iter::once(first)
+            .chain(first + 2..=last)
+            .map(|i| {
+                split(&lines[i])
+                    .into_iter()
+                    .map(|range| lines[i][range].trim().to_string())
+                    .collect()
+            })
+            .collect();
+        let columns = rows
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(aligns.len());
+        for row in &mut rows {
+            row.resize(columns, String::new());
+        }
+        let mut table = Self {
+            first,
+            rows,
+            aligns,
+        };
+        table.aligns.resize(columns, Align::None);
+        Some(table)
+    }
+
+    /// An empty table starting at `first`, with the header among its `rows`.
+    pub fn new(first: usize, rows: usize, columns: usize) -> Self {
+        Self {
+            first,
+            rows: vec![vec![String::new(); columns.max(1)]; rows.max(1)],
+            aligns: vec![Align::None; columns.max(1)],
+        }
+    }
+
+    /// The line of the last row.
+    pub fn last(&self) -> usize {
+        self.first + self.rows.len()
+    }
+
+    pub fn align(&self, cell: usize) -> Align {
+        self.aligns.get(cell).copied().unwrap_or(Align::None)
+    }
+
+    /// Where `column` of `line`, whose text is `text`, is in the table.
+    pub fn position(&self, text: &str, line: usize, column: usize) -> Position {
+        let cells = split(text);
+        let cell = cells
+            .iter()
+            .position(|range| column <= range.end)
+            .unwrap_or(cells.len().saturating_sub(1));
+        let offset = cells.get(cell).map_or(0, |range| {
+            let raw = &text[range.clone()];
+            let start = range.start + raw.len() - raw.trim_start().len();
+            // Spaces typed after the text are kept, as far as the padding goes.
+            column.saturating_sub(start).min(raw.trim().len() + 1)
+        });
+        Position {
+            line,
+            cell: cell.min(self.aligns.len() - 1),
+            offset,
+        }
+    }
+
+    /// The lines of the table, formatted.
+    pub fn lines(&self) -> Vec<String> {
+        self.layout().into_iter().map(|(line, _)| line).collect()
+    }
+
+    /// The line and byte column of `position` in the formatted table.
+    pub fn cursor(&self, position: Position) -> (usize, usize) {
+        let layout = self.layout();
+        let (_, cells) = &layout[position
+            .line
+            .saturating_sub(self.first)
+            .min(layout.len() - 1)];
+        let (start, length) = cells[position.cell.min(cells.len() - 1)];
+        (position.line, start + position.offset.min(length + 1))
+    }
+
+    /// The first cell after `position`, or before it if `back`, at the end of its text. A row
+    /// is added after the last one.
+    pub fn next(&mut self, position: Position, back: bool) -> Position {
+        let columns = self.aligns.len();
+        let row = self.row(position.line);
+        let index = row * columns + position.cell;
+        let index = if back {
+            index.saturating_sub(1)
+        } else {
+            index + 1
+        };
+        if index / columns >= self.rows.len() {
+            self.rows.push(vec![String::new(); columns]);
+        }
+        let (row, cell) = (index / columns, index % columns);
+        Position {
+            line: self.line(row),
+            cell,
+            offset: self.rows[row][cell].len(),
+        }
+    }
+
+    /// Applies `change` at `position`, returning where the cursor goes.
+    pub fn change(&mut self, change: Change, position: Position) -> Position {
+        let columns = self.aligns.len();
+        let row = self.row(position.line);
+        let (row, cell) = match change {
+            Change::RowAbove | Change::RowBelow => {
+                // Nothing goes above the header.
+                let at = match change {
+                    Change::RowAbove => row.max(1),
+                    _ => row + 1,
+                };
+                self.rows.insert(at, vec![String::new(); columns]);
+                (at, position.cell)
+            }
+            Change::DeleteRow => {
+                if self.rows.len() > 1 {
+                    self.rows.remove(row);
+                }
+                (row.min(self.rows.len() - 1), position.cell)
+            }
+            Change::ColumnLeft | Change::ColumnRight => {
+                let at = match change {
+                    Change::ColumnLeft => position.cell,
+                    _ => position.cell + 1,
+                };
+                for row in &mut self.rows {
+                    row.insert(at, String::new());
+                }
+                self.aligns.insert(at, Align::None);
+                (row, at)
+            }
+            Change::DeleteColumn => {
+                if columns > 1 {
+                    for row in &mut self.rows {
+                        row.remove(position.cell);
+                    }
+                    self.aligns.remove(position.cell);
+                }
+                (row, position.cell.min(self.aligns.len() - 1))
+            }
+            Change::Align => {
+                self.aligns[position.cell] = match self.aligns[position.cell] {
+                    Align::None => Align::Left,
+                    Align::Left => Align::Center,
+                    Align::Center => Align::Right,
+                    Align::Right => Align::None,
+                };
+                return position;
+            }
+        };
+        Position {
+            line: self.line(row),
+            cell,
+            offset: 0,
+        }
+    }
+
+    /// The row at `line`, the header for the delimiter row.
+    fn row(&self, line: usize) -> usize {
+        (line - self.first).saturating_sub(1)
+    }
+
+    fn line(&self, row: usize) -> usize {
+        match row {
+            0 => self.first,
+            _ => self.first + row + 1,
+        }
+    }
+
+    /// The formatted lines, with the byte column and length of the text of each cell.
+    fn layout(&self) -> Vec<(String, Vec<(usize, usize)>)> {
+        let widths: Vec<usize> = (0..self.aligns.len())
+            .map(|cell| {
+                self.rows
+                    .iter()
+                    .map(|row| row[cell].chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(MIN_WIDTH)
+            })
+            .collect();
+        // Each cell as its text, with the padding before and after it.
+        let format = |cells: Vec<(usize, String, usize)>| {
+            let mut line = String::from("|");
+            let mut starts = vec![];
+            for (before, text, after) in cells {
+                line.push(' ');
+                line.push_str(&" ".repeat(before));
+                starts.push((line.len(), text.len()));
+                line.push_str(&text);
+                line.push_str(&" ".repeat(after));
+                line.push_str(" |");
+            }
+            (line, starts)
+        };
+
+        let row = |cells: &Vec<String>| {
+            format(
+                cells
+                    .iter()
+                    .zip(&widths)
+                    .zip(&self.aligns)
+                    .map(|((text, width), align)| {
+                        let padding = width - text.chars().count();
+                        let before = match align {
+                            Align::Right => padding,
+                            Align::Center => padding / 2,
+                            _ => 0,
+                        };
+                        (before, text.clone(), padding - before)
+                    })
+                    .collect(),
+            )
+        };
+        let delimiter = format(
+            widths
+                .iter()
+                .zip(&self.aligns)
+                .map(|(width, align)| {
+                    let (left, right) = match align {
+                        Align::None => ("", ""),
+                        Align::Left => (":", ""),
+                        Align::Center => (":", ":"),
+                        Align::Right => ("", ":"),
+                    };
+                    let dashes = "-".repeat(width - left.len() - right.len());
+                    (0, format!("{}{}{}", left, dashes, right), 0)
+                })
+                .collect(),
+        );
+
+        let mut layout = vec![row(&self.rows[0]), delimiter];
+        layout.extend(self.rows[1..].iter().map(row));
+        layout
+    }
+}
+
+/// The byte ranges of the cells of a table row, between its pipes. Escaped pipes don't split
+/// cells.
+fn split(line: &str) -> Vec<std::ops::Range<usize>> {
+    let mut cells = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '|' => {
+                cells.push(start..i);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    cells.push(start..line.len());
+    // The pipes at the start and at the end are optional.
+    if cells.len() > 1 && line[cells[0].clone()].trim().is_empty() {
+        cells.remove(0);
+    }
+    if cells.len() > 1 && line[cells[cells.len() - 1].clone()].trim().is_empty() {
+        cells.pop();
+    }
+    cells
+}
+
+/// The alignment a cell of the delimiter row sets, if it's one.
+fn align(cell: &str) -> Option<Align> {
+    let left = cell.starts_with(':');
+    let right = cell.ends_with(':') && cell.len() > 1;
+    let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+    if dashes.is_empty() || !dashes.bytes().all(|b| b == b'-') {
+        return None;
+    }
+    Some(match (left, right) {
+        (true, true) => Align::Center,
+        (true, false) => Align::Left,
+        (false, true) => Align::Right,
+        (false, false) => Align::None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    const TABLE: &str = "text\n| a | b |c\n|:-|-:|:-:\n| long cell | x\n|y\\|z|\nafter";
+
+    #[test]
+    fn tables_are_read_and_formatted() {
+        let lines = note(TABLE);
+        assert!(Table::at(&lines, 0).is_none());
+        assert!(Table::at(&lines, 5).is_none());
+        let table = Table::at(&lines, 3).unwrap();
+        assert_eq!((table.first, table.last()), (1, 4));
+        assert_eq!(table.align(1), Align::Right);
+        assert_eq!(table.align(5), Align::None);
+        assert_eq!(
+            table.lines(),
+            [
+                "| a         |   b |  c  |",
+                "| :-------- | --: | :-: |",
+                "| long cell |   x |     |",
+                "| y\\|z      |     |     |",
+            ]
+        );
+    }
+
+    #[test]
+    fn tables_need_a_delimiter_row() {
+        assert!(Table::at(&note("| a | b |\n| c | d |"), 0).is_none());
+        assert!(Table::at(&note("| a | b |\n| - |"), 0).is_none());
+        assert!(Table::at(&note("| a |"), 0).is_none());
+    }
+
+    #[test]
+    fn cursor_follows_its_cell() {
+        let lines = note("| a | bc |\n|---|---|\n|d|e|");
+        let table = Table::at(&lines, 0).unwrap();
+        let position = table.position(&lines[2], 2, 3);
+        assert_eq!((position.cell, position.offset), (1, 0));
+        assert_eq!(table.cursor(position), (2, 8));
+        let position = table.position(&lines[0], 0, 8);
+        assert_eq!((position.cell, position.offset), (1, 2));
+        assert_eq!(table.cursor(position), (0, 10));
+    }
+
+    #[test]
+    fn next_moves_between_cells_and_adds_rows() {
+        let lines = note("| a | b |\n|---|---|\n| c | d |");
+        let mut table = Table::at(&lines, 0).unwrap();
+        let position = table.position(&lines[0], 0, 7);
+        let position = table.next(position, false);
+        assert_eq!((position.line, position.cell, position.offset), (2, 0, 1));
+        let position = table.next(position, true);
+        assert_eq!((position.line, position.cell), (0, 1));
+
+        let position = table.position(&lines[2], 2, 7);
+        let position = table.next(position, false);
+        assert_eq!((position.line, position.cell, position.offset), (3, 0, 0));
+        assert_eq!(table.last(), 3);
+    }
+
+    #[test]
+    fn changes() {
+        let lines = note("| a | b |\n|---|---|\n| c | d |");
+        let mut table = Table::at(&lines, 0).unwrap();
+        let position = table.position(&lines[0], 0, 2);
+
+        let below = table.change(Change::RowAbove, position);
+        assert_eq!((below.line, below.cell), (2, 0));
+        let right = table.change(Change::ColumnRight, below);
+        assert_eq!((right.line, right.cell), (2, 1));
+        table.change(Change::Align, right);
+        table.change(Change::Align, right);
+        assert_eq!(
+            table.lines(),
+            [
+                "| a   |     | b   |",
+                "| --- | :-: | --- |",
+                "|     |     |     |",
+                "| c   |     | d   |",
+            ]
+        );
+
+        table.change(Change::DeleteColumn, right);
+        let deleted = table.change(Change::DeleteRow, below);
+        assert_eq!(deleted.line, 2);
+        assert_eq!(
+            table.lines(),
+            ["| a   | b   |", "| --- | --- |", "| c   | d   |"]
+        );
+    }
+
+    #[test]
+    fn the_last_cell_and_row_stay() {
+        let mut table = Table::new(0, 1, 1);
+        let position = Position {
+            line: 0,
+            cell: 0,
+            offset: 0,
+        };
+        table.change(Change::DeleteRow, position);
+        table.change(Change::DeleteColumn, position);
+        assert_eq!(table.lines(), ["|     |", "| --- |"]);
+    }
+}
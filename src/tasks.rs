@@ -0,0 +1,247 @@
+/// A line starting a list item, which may be a task.
+pub struct ListItem {
+    /// Width of the indentation, with tabs counting as four spaces.
+    pub indent: usize,
+    /// Whether the item is a `[ ]` or `[x]` task, and if it's done.
+    pub checked: Option<bool>,
+    /// Byte column of the character between the brackets of a task.
+    pub mark: usize,
+    /// Byte column where the text of the item starts.
+    pub text: usize,
+}
+
+/// A list holding tasks, as shown in the preview.
+pub struct TaskList {
+    pub items: Vec<Item>,
+}
+
+pub struct Item {
+    /// Line of the item, relative to the start of the list.
+    pub line: usize,
+    pub depth: usize,
+    pub checked: Option<bool>,
+    pub text: crate::preview::Rich,
+}
+
+pub fn list_item(line: &str) -> Option<ListItem> {
+    let trimmed = line.trim_start();
+    let start = line.len() - trimmed.len();
+    let indent = line[..start]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+
+    let bytes = trimmed.as_bytes();
+    let marker = match bytes.first()? {
+        b'-' | b'*' | b'+' => 1,
+        b'0'..=b'9' => {
+            let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+            match bytes.get(digits) {
+                Some(b'.' | b')') if digits <= 9 => digits + 1,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    match bytes.get(marker) {
+        None => {
+            return Some(ListItem {
+                indent,
+                checked: None,
+                mark: 0,
+                text: line.len(),
+            })
+        }
+        Some(b' ' | b'\t') => {}
+        Some(_) => return None,
+    }
+
+    let rest = &trimmed[marker..];
+    let text = rest.trim_start();
+    let text_start = start + marker + rest.len() - text.len();
+    let checked = match text.as_bytes() {
+        [b'[', b' ', b']', ..] => Some(false),
+        [b'[', b'x' | b'X', b']', ..] => Some(true),
+        _ => None,
+    }
+    .filter(|_| matches!(text.as_bytes().get(3), None | Some(b' ' | b'\t')));
+
+    Some(match checked {
+        Some(_) => ListItem {
+            indent,
+            checked,
+            mark: text_start + 1,
+            text: text_start + 3 + text[3..].len() - text[3..].trim_start().len(),
+        },
+        None => ListItem {
+            indent,
+            checked,
+            mark: 0,
+            text: text_start,
+        },
+    })
+}
+
+/// Reads `source` as a list with at least one task, if that's all it holds. Links in it may
+/// use the reference `definitions` of its note.
+pub fn task_list(source: &str, definitions: &str) -> Option<TaskList> {
+    let mut items = vec![];
+    let mut texts: Vec<String> = vec![];
+    let mut indents: Vec<usize> = vec![];
+
+    for (line, text) in source.lines().enumerate() {
+        if text.trim().is_empty() {
+            continue;
+        }
+        match list_item(text) {
+            Some(item) => {
+                while indents.last().is_some_and(|indent| *indent >= item.indent) {
+                    indents.pop();
+                }
+                items.push((line, indents.len(), item.checked));
+                indents.push(item.indent);
+                texts.push(text[item.text..].to_string());
+            }
+            // Lazy continuation lines of the previous item.
+            None if text.starts_with([' ', '\t']) => {
+                let previous = texts.last_mut()?;
+                previous.push('\n');
+                previous.push_str(text.trim_start());
+            }
+            None => return None,
+        }
+    }
+
+    if !items.iter().any(|(_, _, checked)| checked.is_some()) {
+        return None;
+    }
+
+    Some(TaskList {
+        items: items
+            .into_iter()
+            .zip(texts)
+            .map(|((line, depth, checked), text)| Item {
+                line,
+                depth,
+                checked,
+                text: crate::preview::text(&text, definitions),
+            })
+            .collect(),
+    })
+}
+
+/// The tasks to tick or untick when the task at `line` of `list` is toggled: its subtasks
+/// follow it, and a parent is done when all its subtasks are.
+pub fn toggle(list: &TaskList, line: usize) -> Vec<(usize, bool)> {
+    let Some(index) = list.items.iter().position(|item| item.line == line) else {
+        return vec![];
+    };
+    let Some(checked) = list.items[index].checked else {
+        return vec![];
+    };
+
+    let mut states: Vec<Option<bool>> = list.items.iter().map(|item| item.checked).collect();
+    let end = subtree_end(list, index);
+    for state in states[index..end].iter_mut().flatten() {
+        *state = !checked;
+    }
+
+    let mut child = index;
+    while let Some(parent) = (0..child)
+        .rev()
+        .find(|i| list.items[*i].depth < list.items[child].depth)
+    {
+        if states[parent].is_some() {
+            let done = states[parent + 1..subtree_end(list, parent)]
+                .iter()
+                .flatten()
+                .all(|state| *state);
+            states[parent] = Some(done);
+        }
+        child = parent;
+    }
+
+    list.items
+        .iter()
+        .zip(states)
+        .filter_map(|(item, state)| match (item.checked, state) {
+            (Some(old), Some(new)) if old != new => Some((item.line, new)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The index after the last subtask of the item at `index`.
+fn subtree_end(list: &TaskList, index: usize) -> usize {
+    let depth = list.items[index].depth;
+    list.items[index + 1..]
+        .iter()
+        .position(|item| item.depth <= depth)
+        .map_or(list.items.len(), |offset| index + 1 + offset)
+}
+
+/// Counts the tasks that are done and all the tasks in `lines`, outside of fenced blocks.
+pub fn count(lines: impl Iterator<Item = impl std::ops::Deref<Target = str>>) -> (usize, usize) {
+    let mut fences = crate::folding::Fences::default();
+    lines
+        .filter_map(|line| {
+            let fenced = fences.read(&line);
+            list_item(&line)?.checked.filter(|_| !fenced)
+        })
+        .fold((0, 0), |(done, total), checked| {
+            (done + usize::from(checked), total + 1)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_items() {
+        let item = list_item("  - [x] done").unwrap();
+        assert_eq!(
+            (item.indent, item.checked, item.mark, item.text),
+            (2, Some(true), 5, 8)
+        );
+        let item = list_item("\t12) [ ]").unwrap();
+        assert_eq!((item.indent, item.checked, item.text), (4, Some(false), 8));
+        let item = list_item("* [y] not a task").unwrap();
+        assert_eq!((item.checked, item.text), (None, 2));
+        assert_eq!(list_item("-").map(|item| item.text), Some(1));
+        assert!(list_item("---").is_none());
+        assert!(list_item("1234567890. too long").is_none());
+        assert!(list_item("-[ ] no space").is_none());
+    }
+
+    #[test]
+    fn toggle_ticks_subtasks_and_parents() {
+        let list = task_list("- [ ] a\n  - [ ] b\n  - [x] c\n- [ ] d", "").unwrap();
+        assert_eq!(toggle(&list, 0), [(0, true), (1, true)]);
+        assert_eq!(toggle(&list, 1), [(0, true), (1, true)]);
+        assert_eq!(toggle(&list, 2), [(2, false)]);
+        assert!(toggle(&list, 4).is_empty());
+    }
+
+    #[test]
+    fn toggle_skips_plain_items() {
+        let list = task_list("- [x] a\n  - b\n  - [x] c", "").unwrap();
+        assert_eq!(toggle(&list, 2), [(0, false), (2, false)]);
+        assert!(toggle(&list, 1).is_empty());
+    }
+
+    #[test]
+    fn task_lists_hold_only_lists() {
+        assert!(task_list("- a\n- b", "").is_none());
+        assert!(task_list("- [ ] a\ntext", "").is_none());
+        let list = task_list("- [ ] a\n  more\n\n- [x] b", "").unwrap();
+        let lines: Vec<usize> = list.items.iter().map(|item| item.line).collect();
+        assert_eq!(lines, [0, 3]);
+    }
+
+    #[test]
+    fn count_skips_code() {
+        let text = "- [x] a\n````\n- [ ] b\n```\n- [ ] c\n````\n- [ ] d";
+        assert_eq!(count(text.lines()), (1, 2));
+    }
+}
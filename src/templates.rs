@@ -0,0 +1,117 @@
+/// A note template, read from the templates folder of the vault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    pub display_name: String,
+    pub path: std::path::PathBuf,
+}
+
+impl std::fmt::Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_name)
+    }
+}
+
+/// A template after variable expansion, with the position requested by `{{cursor}}`.
+pub struct Expanded {
+    pub text: String,
+    pub cursor: Option<(usize, usize)>,
+}
+
+/// Lists the templates in `path`. A missing folder just means there are no templates.
+pub async fn list_templates(path: std::path::PathBuf) -> Result<Vec<Template>, std::io::ErrorKind> {
+    let mut templates = vec![];
+
+    let mut entries = match tokio::fs::read_dir(path).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(templates),
+        Err(e) => return Err(e.kind()),
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.is_file() {
+            templates.push(Template {
+                display_name: path.file_stem().unwrap().to_string_lossy().to_string(),
+                path,
+            });
+        }
+    }
+    templates.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+
+    Ok(templates)
+}
+
+/// Replaces `{{date}}`, `{{time}}` and `{{title}}` in `template`, and removes the first
+/// `{{cursor}}`, remembering its line and byte column.
+pub fn expand(template: &str, title: &str, now: chrono::DateTime<chrono::Local>) -> Expanded {
+    let text = template
+        .replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{title}}", title);
+
+    match text.find("{{cursor}}") {
+        Some(offset) => {
+            let before = &text[..offset];
+            let line = before.matches('\n').count();
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1);
+            Expanded {
+                text: text.replacen("{{cursor}}", "", 1),
+                cursor: Some((line, column)),
+            }
+        }
+        None => Expanded { text, cursor: None },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> chrono::DateTime<chrono::Local> {
+        use chrono::TimeZone;
+        chrono::Local.with_ymd_and_hms(2024, 3, 9, 7, 5, 0).unwrap()
+    }
+
+    #[test]
+    fn expand_fills_in_variables() {
+        let expanded = expand("# {{title}}\n{{date}} {{time}} {{title}}", "Plan", now());
+        assert_eq!(expanded.text, "# Plan\n2024-03-09 07:05 Plan");
+        assert_eq!(expanded.cursor, None);
+    }
+
+    #[test]
+    fn expand_places_the_cursor() {
+        let expanded = expand("# {{title}}\n\n- {{cursor}}\n{{cursor}}", "Été", now());
+        assert_eq!(expanded.text, "# Été\n\n- \n{{cursor}}");
+        assert_eq!(expanded.cursor, Some((2, 2)));
+
+        // Columns are counted in bytes, after the variables before them are filled in.
+        let expanded = expand("{{title}} {{cursor}}", "Été", now());
+        assert_eq!(expanded.cursor, Some((0, 6)));
+        assert_eq!(expand("{{cursor}}", "", now()).cursor, Some((0, 0)));
+    }
+
+    #[test]
+    fn templates_are_listed_by_name() {
+        let folder =
+            std::env::temp_dir().join(format!("darkstone-templates-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("folder")).unwrap();
+        std::fs::write(folder.join("meeting.md"), "").unwrap();
+        std::fs::write(folder.join("daily.md"), "").unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let templates = runtime.block_on(list_templates(folder.clone()));
+        let missing = runtime.block_on(list_templates(folder.join("missing")));
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        let names: Vec<String> = templates
+            .unwrap()
+            .into_iter()
+            .map(|template| template.display_name)
+            .collect();
+        assert_eq!(names, ["daily", "meeting"]);
+        assert_eq!(missing, Ok(vec![]));
+    }
+}
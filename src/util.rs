@@ -12,12 +12,99 @@ pub async fn read_file(
         .map_err(|e| e.kind())
 }
 
+pub async fn write_file(
+    pathname: std::path::PathBuf,
+    contents: String,
+) -> std::result::Result<(), std::io::ErrorKind> {
+    tokio::fs::write(pathname, contents)
+        .await
+        .map_err(|e| e.kind())
+}
+
+/// Escapes `text` for HTML and XML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Moves the cursor of `content` to the given line and byte column, clearing any selection.
+///
+/// The editor only exposes relative motions, so this walks down from the start of the
+/// document one visual line at a time and then right until the column is reached.
+pub fn move_cursor_to(
+    content: &mut iced::widget::text_editor::Content,
+    line: usize,
+    column: usize,
+) {
+    use iced::widget::text_editor::{Action, Motion};
+
+    content.perform(Action::Move(Motion::DocumentStart));
+    let line = line.min(content.line_count().saturating_sub(1));
+    let mut position = content.cursor_position();
+    while position.0 < line {
+        content.perform(Action::Move(Motion::Down));
+        if content.cursor_position() == position {
+            break;
+        }
+        position = content.cursor_position();
+    }
+    let column = content.line(line).map_or(0, |text| column.min(text.len()));
+    while position.0 == line && position.1 < column {
+        content.perform(Action::Move(Motion::Right));
+        if content.cursor_position() == position {
+            break;
+        }
+        position = content.cursor_position();
+    }
+}
+
+/// Extends the selection of `content` from where its cursor is to the given line and byte
+/// column, walking like [`move_cursor_to`].
+pub fn select_to(content: &mut iced::widget::text_editor::Content, line: usize, column: usize) {
+    use iced::widget::text_editor::{Action, Motion};
+
+    let line = line.min(content.line_count().saturating_sub(1));
+    let column = content.line(line).map_or(0, |text| column.min(text.len()));
+    let mut position = content.cursor_position();
+    while position.0 != line {
+        content.perform(Action::Select(if position.0 < line {
+            Motion::Down
+        } else {
+            Motion::Up
+        }));
+        if content.cursor_position() == position {
+            break;
+        }
+        position = content.cursor_position();
+    }
+    let right = position.1 < column;
+    while position.0 == line && position.1 != column && (position.1 < column) == right {
+        content.perform(Action::Select(if right {
+            Motion::Right
+        } else {
+            Motion::Left
+        }));
+        if content.cursor_position() == position {
+            break;
+        }
+        position = content.cursor_position();
+    }
+}
+
+/// The theme of the whole app, also used to style the preview.
+pub const THEME: iced::Theme = iced::Theme::TokyoNightStorm;
+
 pub const ICON_DELETE: char = '\u{e801}';
+#[allow(dead_code)]
 pub const ICON_NEW_FOLDER: char = '\u{e802}';
 pub const ICON_EDIT: char = '\u{e803}';
 pub const ICON_DOWN_SMALL: char = '\u{e800}';
 pub const ICON_RIGHT_SMALL: char = '\u{e804}';
+#[allow(dead_code)]
 pub const ICON_DOWN: char = '\u{e805}';
+#[allow(dead_code)]
 pub const ICON_RIGHT: char = '\u{e807}';
 
 pub fn icon<'a, Message>(code_point: char) -> iced::Element<'a, Message> {
@@ -32,30 +119,30 @@ pub fn button_no_bg_active(
     match status {
         iced::widget::button::Status::Active => iced::widget::button::Style {
             background: Some(theme.extended_palette().background.strong.color.into()),
-            text_color: theme.extended_palette().background.base.text.into(),
+            text_color: theme.extended_palette().background.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().background.base.color.into(),
+                color: theme.extended_palette().background.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
         },
         iced::widget::button::Status::Hovered => iced::widget::button::Style {
             background: Some(theme.extended_palette().background.strong.color.into()),
-            text_color: theme.extended_palette().background.base.text.into(),
+            text_color: theme.extended_palette().background.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().background.base.color.into(),
+                color: theme.extended_palette().background.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
         },
         iced::widget::button::Status::Pressed => iced::widget::button::Style {
             background: Some(theme.palette().background.into()),
-            text_color: theme.extended_palette().background.base.text.into(),
+            text_color: theme.extended_palette().background.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().background.base.color.into(),
+                color: theme.extended_palette().background.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
@@ -70,30 +157,30 @@ pub fn button_no_bg(
     match status {
         iced::widget::button::Status::Active => iced::widget::button::Style {
             background: None,
-            text_color: theme.extended_palette().background.base.text.into(),
+            text_color: theme.extended_palette().background.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().background.base.color.into(),
+                color: theme.extended_palette().background.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
         },
         iced::widget::button::Status::Hovered => iced::widget::button::Style {
             background: Some(theme.extended_palette().background.strong.color.into()),
-            text_color: theme.extended_palette().background.base.text.into(),
+            text_color: theme.extended_palette().background.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().background.base.color.into(),
+                color: theme.extended_palette().background.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
         },
         iced::widget::button::Status::Pressed => iced::widget::button::Style {
             background: Some(theme.palette().background.into()),
-            text_color: theme.extended_palette().background.base.text.into(),
+            text_color: theme.extended_palette().background.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().background.base.color.into(),
+                color: theme.extended_palette().background.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
@@ -109,30 +196,30 @@ pub fn button_secondary(
     match status {
         iced::widget::button::Status::Active => iced::widget::button::Style {
             background: Some(theme.extended_palette().secondary.strong.color.into()),
-            text_color: theme.extended_palette().secondary.strong.text.into(),
+            text_color: theme.extended_palette().secondary.strong.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().secondary.base.color.into(),
+                color: theme.extended_palette().secondary.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
         },
         iced::widget::button::Status::Hovered => iced::widget::button::Style {
             background: Some(theme.extended_palette().primary.base.text.into()),
-            text_color: theme.extended_palette().secondary.base.text.into(),
+            text_color: theme.extended_palette().secondary.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().secondary.base.color.into(),
+                color: theme.extended_palette().secondary.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
         },
         iced::widget::button::Status::Pressed => iced::widget::button::Style {
             background: Some(theme.palette().background.into()),
-            text_color: theme.extended_palette().background.base.text.into(),
+            text_color: theme.extended_palette().background.base.text,
             border: iced::Border {
                 width: 0.0,
-                color: theme.extended_palette().secondary.base.color.into(),
+                color: theme.extended_palette().secondary.base.color,
                 radius: 4.0.into(),
             },
             ..Default::default()
@@ -0,0 +1,478 @@
+/// Search and replace across every note of the vault.
+pub struct VaultSearch {
+    query: crate::find::Query,
+    replace: String,
+    results: Option<crate::util::Query<Vec<FileResult>, std::io::ErrorKind>>,
+    error: Option<String>,
+    /// The files touched by the last replace, to undo it.
+    undo: Option<Vec<Written>>,
+    status: Option<String>,
+}
+
+/// The matches found in a note.
+#[derive(Debug, Clone)]
+pub struct FileResult {
+    path: std::path::PathBuf,
+    note: String,
+    /// The note as it was searched, to check it didn't change before replacing.
+    contents: String,
+    matches: Vec<MatchResult>,
+}
+
+#[derive(Debug, Clone)]
+struct MatchResult {
+    range: std::ops::Range<usize>,
+    replacement: String,
+    line: usize,
+    before: String,
+    after: String,
+    included: bool,
+}
+
+/// A file written by a replace, with its contents before and after.
+#[derive(Debug, Clone)]
+pub struct Written {
+    pub path: std::path::PathBuf,
+    original: String,
+    replaced: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    QueryText(String),
+    ReplaceText(String),
+    SetCaseSensitive(bool),
+    SetWholeWord(bool),
+    SetRegex(bool),
+    /// Runs the search, handled by the notes which know the vault.
+    Search,
+    Searched(Result<Vec<FileResult>, std::io::ErrorKind>),
+    IncludeFile(usize, bool),
+    IncludeMatch(usize, usize, bool),
+    Apply,
+    Applied(Result<Vec<Written>, String>),
+    Undo,
+    Undone(Result<Vec<Written>, String>),
+    /// Opens a note at a line.
+    Open(std::path::PathBuf, String, usize),
+}
+
+impl VaultSearch {
+    pub fn new() -> Self {
+        Self {
+            query: crate::find::Query::default(),
+            replace: String::new(),
+            results: None,
+            error: None,
+            undo: None,
+            status: None,
+        }
+    }
+
+    /// Searches `notes`, given as paths and display names.
+    pub fn search(&mut self, notes: Vec<(std::path::PathBuf, String)>) -> iced::Task<Message> {
+        self.error = None;
+        self.status = None;
+        match self.query.regex() {
+            None => {
+                self.results = None;
+                iced::Task::none()
+            }
+            Some(Err(e)) => {
+                self.error = Some(e.to_string());
+                iced::Task::none()
+            }
+            Some(Ok(regex)) => {
+                self.results = Some(crate::util::Query::Pending);
+                iced::Task::perform(
+                    search(notes, regex, self.query.clone(), self.replace.clone()),
+                    Message::Searched,
+                )
+            }
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> iced::Task<Message> {
+        match message {
+            Message::QueryText(text) => self.query.text = text,
+            Message::ReplaceText(text) => self.replace = text,
+            Message::SetCaseSensitive(value) => self.query.case_sensitive = value,
+            Message::SetWholeWord(value) => self.query.whole_word = value,
+            Message::SetRegex(value) => self.query.regex = value,
+            Message::Search | Message::Open(..) => {}
+            Message::Searched(result) => {
+                self.results = Some(match result {
+                    Ok(results) => crate::util::Query::Loaded(results),
+                    Err(e) => crate::util::Query::Error(e),
+                });
+            }
+            Message::IncludeFile(file, included) => {
+                if let Some(file) = self.files_mut().and_then(|files| files.get_mut(file)) {
+                    for found in &mut file.matches {
+                        found.included = included;
+                    }
+                }
+            }
+            Message::IncludeMatch(file, index, included) => {
+                if let Some(found) = self
+                    .files_mut()
+                    .and_then(|files| files.get_mut(file))
+                    .and_then(|file| file.matches.get_mut(index))
+                {
+                    found.included = included;
+                }
+            }
+            Message::Apply => {
+                let Some(crate::util::Query::Loaded(files)) = &self.results else {
+                    return iced::Task::none();
+                };
+                let writes: Vec<Written> = files
+                    .iter()
+                    .filter(|file| file.matches.iter().any(|found| found.included))
+                    .map(|file| Written {
+                        path: file.path.clone(),
+                        original: file.contents.clone(),
+                        replaced: replace(file),
+                    })
+                    .collect();
+                if !writes.is_empty() {
+                    return iced::Task::perform(write_all(writes), Message::Applied);
+                }
+            }
+            Message::Applied(Ok(written)) => {
+                self.status = Some(format!("Replaced in {} notes", written.len()));
+                self.results = None;
+                self.undo = Some(written);
+            }
+            Message::Undo => {
+                if let Some(written) = self.undo.take() {
+                    let writes = written
+                        .into_iter()
+                        .map(|file| Written {
+                            path: file.path,
+                            original: file.replaced,
+                            replaced: file.original,
+                        })
+                        .collect();
+                    return iced::Task::perform(write_all(writes), Message::Undone);
+                }
+            }
+            Message::Undone(Ok(written)) => {
+                self.status = Some(format!("Restored {} notes", written.len()));
+            }
+            Message::Applied(Err(e)) | Message::Undone(Err(e)) => self.error = Some(e),
+        }
+        iced::Task::none()
+    }
+
+    fn files_mut(&mut self) -> Option<&mut Vec<FileResult>> {
+        match &mut self.results {
+            Some(crate::util::Query::Loaded(files)) => Some(files),
+            _ => None,
+        }
+    }
+
+    pub fn view(&self) -> iced::Element<'_, Message> {
+        let inputs = iced::widget::column![
+            iced::widget::row![
+                iced::widget::text_input("Find in all notes", &self.query.text)
+                    .on_input(Message::QueryText)
+                    .on_submit(Message::Search),
+                iced::widget::button("Search")
+                    .style(crate::util::button_secondary)
+                    .on_press(Message::Search),
+            ]
+            .spacing(8),
+            iced::widget::row![
+                iced::widget::text_input("Replace with", &self.replace)
+                    .on_input(Message::ReplaceText)
+                    .on_submit(Message::Search),
+                iced::widget::button("Replace selected")
+                    .style(crate::util::button_secondary)
+                    .on_press_maybe(
+                        matches!(self.results, Some(crate::util::Query::Loaded(_)))
+                            .then_some(Message::Apply)
+                    ),
+                iced::widget::button("Undo")
+                    .style(crate::util::button_secondary)
+                    .on_press_maybe(self.undo.is_some().then_some(Message::Undo)),
+            ]
+            .spacing(8),
+            iced::widget::row![
+                iced::widget::checkbox("Match case", self.query.case_sensitive)
+                    .on_toggle(Message::SetCaseSensitive),
+                iced::widget::checkbox("Whole word", self.query.whole_word)
+                    .on_toggle(Message::SetWholeWord),
+                iced::widget::checkbox("Regex", self.query.regex).on_toggle(Message::SetRegex),
+            ]
+            .spacing(8),
+        ]
+        .spacing(8);
+
+        let status = self
+            .error
+            .as_ref()
+            .map(|e| {
+                iced::widget::text(e.as_str()).style(|theme: &iced::Theme| {
+                    iced::widget::text::Style {
+                        color: Some(theme.palette().danger),
+                    }
+                })
+            })
+            .or_else(|| self.status.as_deref().map(iced::widget::text));
+
+        let results: iced::Element<'_, Message> = match &self.results {
+            None => iced::widget::Space::new(iced::Length::Fill, iced::Length::Fill).into(),
+            Some(crate::util::Query::Pending) => iced::widget::text("Searching...").into(),
+            Some(crate::util::Query::Error(e)) => {
+                iced::widget::text(format!("Failed to search: {:?}", e)).into()
+            }
+            Some(crate::util::Query::Loaded(files)) if files.is_empty() => {
+                iced::widget::text("No matches").into()
+            }
+            Some(crate::util::Query::Loaded(files)) => iced::widget::scrollable(
+                iced::widget::Column::with_children(
+                    files.iter().enumerate().map(|(i, file)| file_view(i, file)),
+                )
+                .spacing(16),
+            )
+            .height(iced::Length::Fill)
+            .into(),
+        };
+
+        iced::widget::column![iced::widget::text("Search and replace").size(24), inputs]
+            .push_maybe(status)
+            .push(results)
+            .spacing(8)
+            .padding(8)
+            .height(iced::Length::Fill)
+            .into()
+    }
+}
+
+fn file_view(index: usize, file: &FileResult) -> iced::Element<'_, Message> {
+    let included = file.matches.iter().all(|found| found.included);
+    let line = |prefix: &str, text: &str, color: fn(&iced::Theme) -> iced::Color| {
+        iced::widget::text(format!("{} {}", prefix, text))
+            .font(iced::Font::MONOSPACE)
+            .size(12)
+            .style(move |theme: &iced::Theme| iced::widget::text::Style {
+                color: Some(color(theme)),
+            })
+    };
+
+    iced::widget::column![iced::widget::row![
+        iced::widget::checkbox("", included)
+            .on_toggle(move |value| Message::IncludeFile(index, value)),
+        iced::widget::button(iced::widget::text(file.note.as_str()).size(16))
+            .style(crate::util::button_no_bg)
+            .on_press(Message::Open(file.path.clone(), file.note.clone(), 0)),
+        iced::widget::text(format!("{} matches", file.matches.len())).size(12),
+    ]
+    .spacing(8)
+    .align_y(iced::Alignment::Center)]
+    .extend(file.matches.iter().enumerate().map(|(i, found)| {
+        iced::widget::row![
+            iced::widget::checkbox("", found.included)
+                .on_toggle(move |value| Message::IncludeMatch(index, i, value)),
+            iced::widget::button(iced::widget::text(format!("{}", found.line + 1)).size(12))
+                .style(crate::util::button_no_bg)
+                .on_press(Message::Open(
+                    file.path.clone(),
+                    file.note.clone(),
+                    found.line
+                )),
+            iced::widget::column![
+                line("-", &found.before, |theme| theme.palette().danger),
+                line("+", &found.after, |theme| theme.palette().success),
+            ],
+        ]
+        .spacing(8)
+        .padding(iced::padding::left(24))
+        .into()
+    }))
+    .spacing(4)
+    .into()
+}
+
+/// Finds the matches of `regex` in `notes`, skipping the ones that can't be read as text.
+async fn search(
+    notes: Vec<(std::path::PathBuf, String)>,
+    regex: regex::Regex,
+    query: crate::find::Query,
+    replace: String,
+) -> Result<Vec<FileResult>, std::io::ErrorKind> {
+    let mut files = vec![];
+    for (path, note) in notes {
+        let contents = match crate::util::read_file(path.clone()).await {
+            Ok(contents) => contents,
+            Err(std::io::ErrorKind::InvalidData) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let matches: Vec<MatchResult> = crate::find::captures(&regex, &contents)
+            .filter_map(|(line_start, captures)| {
+                let found = captures.get(0)?;
+                let replacement = query.replacement(&captures, &replace);
+                let text = &contents[line_start..];
+                let line_text = &text[..text.find('\n').unwrap_or(text.len())];
+                Some(MatchResult {
+                    line: contents[..line_start].matches('\n').count(),
+                    before: line_text.to_string(),
+                    after: format!(
+                        "{}{}{}",
+                        &line_text[..found.start()],
+                        replacement,
+                        &line_text[found.end()..]
+                    ),
+                    range: line_start + found.start()..line_start + found.end(),
+                    replacement,
+                    included: true,
+                })
+            })
+            .collect();
+
+        if !matches.is_empty() {
+            files.push(FileResult {
+                path,
+                note,
+                contents,
+                matches,
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// The contents of `file` with its included matches replaced.
+fn replace(file: &FileResult) -> String {
+    let mut replaced = String::new();
+    let mut last = 0;
+    for found in file.matches.iter().filter(|found| found.included) {
+        replaced.push_str(&file.contents[last..found.range.start]);
+        replaced.push_str(&found.replacement);
+        last = found.range.end;
+    }
+    replaced.push_str(&file.contents[last..]);
+    replaced
+}
+
+/// Writes every file or none: nothing is written if a file changed since it was read, and
+/// the files already written are restored if one of them fails.
+async fn write_all(writes: Vec<Written>) -> Result<Vec<Written>, String> {
+    for file in &writes {
+        let current = crate::util::read_file(file.path.clone())
+            .await
+            .map_err(|e| format!("Failed to read {}: {:?}", file.path.display(), e))?;
+        if current != file.original {
+            return Err(format!(
+                "{} changed since the search, search again.",
+                file.path.display()
+            ));
+        }
+    }
+
+    // Everything is written next to the notes first, so that a failure leaves them as is.
+    let temporary = |path: &std::path::Path| {
+        path.with_file_name(format!(
+            ".{}.darkstone-replace",
+            path.file_name().unwrap().to_string_lossy()
+        ))
+    };
+    for (i, file) in writes.iter().enumerate() {
+        if let Err(e) = tokio::fs::write(temporary(&file.path), &file.replaced).await {
+            for file in &writes[..=i] {
+                let _ = tokio::fs::remove_file(temporary(&file.path)).await;
+            }
+            return Err(format!("Failed to write {}: {}", file.path.display(), e));
+        }
+    }
+
+    for (i, file) in writes.iter().enumerate() {
+        if let Err(e) = tokio::fs::rename(temporary(&file.path), &file.path).await {
+            for file in &writes[..i] {
+                let _ = tokio::fs::write(&file.path, &file.original).await;
+            }
+            for file in &writes[i..] {
+                let _ = tokio::fs::remove_file(temporary(&file.path)).await;
+            }
+            return Err(format!("Failed to replace {}: {}", file.path.display(), e));
+        }
+    }
+
+    Ok(writes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run<T>(future: impl std::future::Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn note(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "darkstone-search-{}-{}.md",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn search_in(path: &std::path::Path, query: &str, replace: &str) -> FileResult {
+        let query = crate::find::Query {
+            text: query.into(),
+            regex: true,
+            ..crate::find::Query::default()
+        };
+        let regex = query.regex().unwrap().unwrap();
+        run(search(
+            vec![(path.to_path_buf(), "note".into())],
+            regex,
+            query,
+            replace.into(),
+        ))
+        .unwrap()
+        .remove(0)
+    }
+
+    #[test]
+    fn replace_included_matches() {
+        let path = note("included", "a1 b2\nc3 é4");
+        let mut file = search_in(&path, r"(\w)(\d)", "$2$1");
+        let lines: Vec<usize> = file.matches.iter().map(|found| found.line).collect();
+        assert_eq!(lines, [0, 0, 1, 1]);
+        assert_eq!(file.matches[3].before, "c3 é4");
+        assert_eq!(file.matches[3].after, "c3 4é");
+        assert_eq!(replace(&file), "1a 2b\n3c 4é");
+
+        file.matches[1].included = false;
+        file.matches[2].included = false;
+        assert_eq!(replace(&file), "1a b2\nc3 4é");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn write_all_checks_files_first() {
+        let path = note("changed", "old");
+        let file = search_in(&path, "old", "new");
+        let written = Written {
+            path: path.clone(),
+            original: file.contents.clone(),
+            replaced: replace(&file),
+        };
+        std::fs::write(&path, "edited").unwrap();
+        assert!(run(write_all(vec![written.clone()])).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "edited");
+
+        std::fs::write(&path, "old").unwrap();
+        assert!(run(write_all(vec![written])).is_ok());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        std::fs::remove_file(path).unwrap();
+    }
+}